@@ -0,0 +1,138 @@
+//! Run with `cargo bench`. Covers the instruction loop, sprite drawing and
+//! whole ROMs, the parts that decide how fast the emulator runs
+
+use std::hint::black_box;
+
+use chip_8_rs::{Chip8, Quirks, asm, frontend::NullFrontend, jit::Jit};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+/// Arithmetic and jumps, no drawing or memory access
+const ALU_LOOP: &str = "
+: main
+	loop
+		v0 += 1
+		v1 := v0
+		v1 <<= v1
+		v2 ^= v1
+		v3 += v2
+		if v3 == 0 then v4 += 1
+	again
+";
+
+/// Draws the same sprite over and over, so it keeps flipping
+const LORES_SPRITES: &str = "
+: sprite
+	0xFF 0x81 0x81 0x81 0x81 0x81 0x81 0xFF
+	0xFF 0x81 0x81 0x81 0x81 0x81 0x81
+: main
+	v0 := 60
+	v1 := 20
+	i := sprite
+	loop
+		sprite v0 v1 15
+	again
+";
+
+const HIRES_SPRITES: &str = "
+: sprite
+	0xFF 0xFF 0x80 0x01 0x80 0x01 0x80 0x01 0x80 0x01 0x80 0x01 0x80 0x01 0x80 0x01
+	0x80 0x01 0x80 0x01 0x80 0x01 0x80 0x01 0x80 0x01 0x80 0x01 0x80 0x01 0xFF 0xFF
+: main
+	hires
+	v0 := 60
+	v1 := 20
+	i := sprite
+	loop
+		sprite v0 v1 0
+	again
+";
+
+fn machine(rom: &[u8], quirks: Quirks) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.set_quirks(quirks);
+    chip8.set_seed(0);
+    chip8.load(rom).unwrap();
+    chip8
+}
+
+fn cycle(c: &mut Criterion) {
+    let mut chip8 = machine(&asm::assemble(ALU_LOOP).unwrap(), Quirks::default());
+    let mut group = c.benchmark_group("cycle");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("alu", |b| b.iter(|| black_box(&mut chip8).cycle().unwrap()));
+    group.finish();
+}
+
+fn draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw");
+    // Each loop iteration is the draw and the jump back
+    group.throughput(Throughput::Elements(1));
+    for (name, source, quirks) in [
+        ("lores 8x15 wrapping", LORES_SPRITES, Quirks::default()),
+        ("lores 8x15 clipped", LORES_SPRITES, Quirks::CHIP8),
+        ("hires 16x16", HIRES_SPRITES, Quirks::SCHIP),
+    ] {
+        let mut chip8 = machine(&asm::assemble(source).unwrap(), quirks);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                chip8.cycle().unwrap();
+                chip8.cycle().unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+/// A second of the demo ROMs at a fast 1000 instructions per frame
+fn roms(c: &mut Criterion) {
+    const FRAMES: u64 = 60;
+    const INSTRUCTIONS_PER_FRAME: u32 = 1000;
+
+    let mut group = c.benchmark_group("rom");
+    group.throughput(Throughput::Elements(FRAMES * INSTRUCTIONS_PER_FRAME as u64));
+    for (name, rom) in [
+        ("maze", &include_bytes!("../roms/maze.ch8")[..]),
+        ("bounce", &include_bytes!("../roms/bounce.ch8")[..]),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || machine(rom, Quirks::default()),
+                |chip8| {
+                    for _ in 0..FRAMES {
+                        chip8
+                            .run_frame(
+                                INSTRUCTIONS_PER_FRAME,
+                                &mut NullFrontend,
+                                &mut NullFrontend,
+                                &mut NullFrontend,
+                            )
+                            .unwrap();
+                    }
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(format!("{} jit", name), |b| {
+            b.iter_batched_ref(
+                || (machine(rom, Quirks::default()), Jit::new()),
+                |(chip8, jit)| {
+                    for _ in 0..FRAMES {
+                        jit.run_frame(
+                            chip8,
+                            INSTRUCTIONS_PER_FRAME,
+                            &mut NullFrontend,
+                            &mut NullFrontend,
+                            &mut NullFrontend,
+                        )
+                        .unwrap();
+                    }
+                },
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cycle, draw, roms);
+criterion_main!(benches);
@@ -0,0 +1,87 @@
+//! Runs arbitrary ROMs with arbitrary quirks and key presses. Errors such as
+//! unknown opcodes are fine, only panics are bugs. Run from the repository
+//! root with `cargo +nightly fuzz run interpreter`
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chip_8_rs::{Chip8, Quirks, frontend::NullFrontend};
+use libfuzzer_sys::fuzz_target;
+
+/// Long enough for loops and timers, short enough to keep runs fast
+const MAX_FRAMES: usize = 64;
+const INSTRUCTIONS_PER_FRAME: u32 = 100;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    quirks: [bool; 10],
+    seed: u64,
+    /// Keys changed before each frame, one frame per entry
+    keys: Vec<KeyEvent>,
+    rom: Vec<u8>,
+}
+
+#[derive(Debug, Arbitrary)]
+struct KeyEvent {
+    /// Any byte, keys past F have to be ignored
+    key: u8,
+    down: bool,
+}
+
+fuzz_target!(|input: Input| {
+    let [
+        shift_vy,
+        load_store_increment_i,
+        load_store_increment_x,
+        jump_vx,
+        vf_reset,
+        clip_sprites,
+        megachip,
+        chip8x,
+        wait_for_release,
+        display_wait,
+    ] = input.quirks;
+    let mut chip8 = Chip8::new();
+    chip8.set_quirks(Quirks {
+        shift_vy,
+        load_store_increment_i,
+        load_store_increment_x,
+        jump_vx,
+        vf_reset,
+        clip_sprites,
+        megachip,
+        chip8x,
+        wait_for_release,
+        display_wait,
+    });
+    chip8.set_seed(input.seed);
+    if chip8.load(&input.rom).is_err() {
+        return;
+    }
+
+    for event in input.keys.iter().take(MAX_FRAMES) {
+        if event.down {
+            chip8.key_down(event.key);
+        } else {
+            chip8.key_up(event.key);
+        }
+        let result = chip8.run_frame(
+            INSTRUCTIONS_PER_FRAME,
+            &mut NullFrontend,
+            &mut NullFrontend,
+            &mut NullFrontend,
+        );
+        if result.is_err() {
+            break;
+        }
+        chip8.call_stack();
+    }
+
+    // Whatever state the ROM left behind has to survive a round trip
+    let state = chip8.save_state();
+    let mut restored = Chip8::new();
+    restored
+        .load_state(&state)
+        .expect("saved states load again");
+    assert_eq!(restored.save_state(), state);
+});
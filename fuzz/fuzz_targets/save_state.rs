@@ -0,0 +1,40 @@
+//! Loads arbitrary save states and runs them for a while. Save states come
+//! from disk and the network, so anything `load_state` accepts has to be safe
+//! to run. Run from the repository root with
+//! `cargo +nightly fuzz run save_state`
+
+#![no_main]
+
+use chip_8_rs::{Chip8, STATE_HEADER_SIZE, STATE_SIZE, frontend::NullFrontend};
+use libfuzzer_sys::fuzz_target;
+
+const FRAMES: usize = 16;
+const INSTRUCTIONS_PER_FRAME: u32 = 100;
+
+fuzz_target!(|data: &[u8]| {
+    let mut chip8 = Chip8::new();
+    chip8.set_seed(0);
+    // Random bytes rarely have the right size and header, so the input is
+    // made into the body of a state that has them
+    let _ = chip8.load_state(data);
+    let mut state = chip8.save_state();
+    state.truncate(STATE_HEADER_SIZE);
+    state.extend_from_slice(data);
+    state.resize(STATE_SIZE, 0);
+    if chip8.load_state(&state).is_err() {
+        return;
+    }
+
+    for _ in 0..FRAMES {
+        let result = chip8.run_frame(
+            INSTRUCTIONS_PER_FRAME,
+            &mut NullFrontend,
+            &mut NullFrontend,
+            &mut NullFrontend,
+        );
+        if result.is_err() {
+            break;
+        }
+    }
+    chip8.call_stack();
+});
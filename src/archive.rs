@@ -0,0 +1,84 @@
+//! Reading ROMs that are shipped compressed. With the `archives` feature a
+//! `.gz` file is decompressed and a `.zip` file gives its first ROM, so ROM
+//! packs don't have to be extracted first. The format is detected from the
+//! file's contents, not its name
+
+use std::{fs, path::Path};
+
+#[cfg(feature = "archives")]
+use std::io::Read;
+
+use crate::error::Chip8Error;
+#[cfg(feature = "archives")]
+use crate::megachip::MEGACHIP_MEMORY;
+
+/// Extensions of the files a zip archive is searched for
+pub const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+
+#[cfg(feature = "archives")]
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+#[cfg(feature = "archives")]
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+/// Largest ROM an archive can give, the size of the largest memory. A small
+/// archive can decompress to gigabytes, this stops it long before that
+#[cfg(feature = "archives")]
+const MAX_ROM_SIZE: usize = MEGACHIP_MEMORY;
+
+/// Reads the ROM at `path`, decompressing it if it's a zip or gzip file
+pub fn read_rom<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Chip8Error> {
+    decompress(fs::read(path)?)
+}
+
+/// Returns the ROM in `data`, which is decompressed first if it's a zip or
+/// gzip file. For ROMs that don't come from a file
+pub fn decompress(data: Vec<u8>) -> Result<Vec<u8>, Chip8Error> {
+    #[cfg(feature = "archives")]
+    if data.starts_with(ZIP_MAGIC) {
+        return unzip(&data).map_err(Chip8Error::InvalidArchive);
+    } else if data.starts_with(GZIP_MAGIC) {
+        return gunzip(&data).map_err(Chip8Error::InvalidArchive);
+    }
+    Ok(data)
+}
+
+/// Whether `name` has one of the `ROM_EXTENSIONS`
+pub fn is_rom_name(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ROM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// The first ROM in the archive, in the order the entries are stored
+#[cfg(feature = "archives")]
+fn unzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    use std::io::Cursor;
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(|e| e.to_string())?;
+        if entry.is_file() && entry.name().is_ok_and(|name| is_rom_name(&name)) {
+            return read_limited(entry);
+        }
+    }
+    Err(String::from("the archive contains no ROM"))
+}
+
+#[cfg(feature = "archives")]
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    read_limited(flate2::read::GzDecoder::new(data))
+}
+
+/// Decompresses no more than `MAX_ROM_SIZE` bytes, failing if there are more
+#[cfg(feature = "archives")]
+fn read_limited(decoder: impl Read) -> Result<Vec<u8>, String> {
+    let mut rom = Vec::new();
+    decoder
+        .take(MAX_ROM_SIZE as u64 + 1)
+        .read_to_end(&mut rom)
+        .map_err(|e| e.to_string())?;
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(format!("the ROM is larger than {} bytes", MAX_ROM_SIZE));
+    }
+    Ok(rom)
+}
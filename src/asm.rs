@@ -0,0 +1,645 @@
+//! An assembler for the core of the Octo language (`.8o`). Supported are
+//! labels, `:const`, `:alias`, `:call`, all register and `i` statements,
+//! `if ... then`, `if ... begin ... else ... end`, `loop ... while ... again`
+//! and raw bytes. Macros, `:org`, `:next` and the comparison pseudo-ops
+//! (`<`, `>`, `<=`, `>=`) aren't.
+//!
+//! ```
+//! let rom = chip_8_rs::asm::assemble(": main  v0 := 5  loop again").unwrap();
+//! assert_eq!(rom, [0x12, 0x02, 0x60, 0x05, 0x12, 0x04]);
+//! ```
+
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{error::Error, fmt, mem};
+
+use crate::{
+    chip8::{MEMORY_SIZE, PROGRAM_START},
+    symbols::Symbols,
+};
+
+/// The most a program can assemble to, what a 4KB machine has after
+/// `PROGRAM_START`
+const MAX_SIZE: usize = MEMORY_SIZE - PROGRAM_START as usize;
+
+/// Why a source file couldn't be assembled, with the line it happened on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// Assembles Octo source into a ROM to be loaded at `PROGRAM_START`. When a
+/// `main` label is defined the ROM starts with a jump to it
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    assemble_with_symbols(source).map(|(rom, _)| rom)
+}
+
+/// Assembles like `assemble`, and also returns the labels and the addresses
+/// they ended up at, for the debugger and disassembler to show
+pub fn assemble_with_symbols(source: &str) -> Result<(Vec<u8>, Symbols), AsmError> {
+    let mut assembler = Assembler::new(tokenize(source));
+    assembler.run()?;
+    let mut symbols = Symbols::new();
+    for (&name, &address) in &assembler.labels {
+        symbols.insert(name, address);
+    }
+    Ok((assembler.output, symbols))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+/// Splits the source on whitespace, dropping `#` comments
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let code = line.split('#').next().unwrap_or("");
+            code.split_whitespace().map(move |text| Token {
+                text,
+                line: index + 1,
+            })
+        })
+        .collect()
+}
+
+/// A jump whose target is filled in once a label or the end of a block is
+/// reached
+struct Fixup<'a> {
+    offset: usize,
+    label: Token<'a>,
+}
+
+/// Open control flow blocks, holding the offsets of jumps to patch when the
+/// block ends
+enum Block {
+    If {
+        jump: usize,
+        line: usize,
+    },
+    Else {
+        jump: usize,
+        line: usize,
+    },
+    Loop {
+        start: u16,
+        breaks: Vec<usize>,
+        line: usize,
+    },
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    output: Vec<u8>,
+    labels: BTreeMap<&'a str, u16>,
+    constants: BTreeMap<&'a str, i32>,
+    aliases: BTreeMap<&'a str, u16>,
+    fixups: Vec<Fixup<'a>>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(tokens: Vec<Token<'a>>) -> Self {
+        Self {
+            tokens,
+            position: 0,
+            output: Vec::new(),
+            labels: BTreeMap::new(),
+            constants: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) -> Result<(), AsmError> {
+        let main = self
+            .tokens
+            .windows(2)
+            .find(|pair| pair[0].text == ":" && pair[1].text == "main");
+        if let Some(&[_, label]) = main {
+            self.emit_jump(0x1000, label)?;
+        }
+
+        while let Some(&token) = self.tokens.get(self.position) {
+            self.statement()?;
+            if self.output.len() > MAX_SIZE {
+                return Err(error(
+                    token,
+                    format!("the program doesn't fit into {} bytes", MAX_SIZE),
+                ));
+            }
+        }
+
+        if let Some(block) = self.blocks.last() {
+            let (keyword, line) = match block {
+                Block::If { line, .. } | Block::Else { line, .. } => ("begin", *line),
+                Block::Loop { line, .. } => ("loop", *line),
+            };
+            return Err(AsmError {
+                line,
+                message: format!("`{}` is never closed", keyword),
+            });
+        }
+
+        for fixup in mem::take(&mut self.fixups) {
+            let Some(&address) = self.labels.get(fixup.label.text) else {
+                return Err(error(
+                    fixup.label,
+                    format!("undefined label `{}`", fixup.label.text),
+                ));
+            };
+            self.patch(fixup.offset, address, fixup.label)?;
+        }
+        Ok(())
+    }
+
+    fn address(&self) -> u16 {
+        PROGRAM_START + self.output.len() as u16
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.output.extend_from_slice(&opcode.to_be_bytes());
+    }
+
+    /// Sets the lowest 12 bits of the opcode at `offset`, `token` being what
+    /// the address is blamed on when it doesn't fit
+    fn patch(&mut self, offset: usize, address: u16, token: Token<'_>) -> Result<(), AsmError> {
+        let address = check_address(token, address)?;
+        self.output[offset] |= (address >> 8) as u8;
+        self.output[offset + 1] = address as u8;
+        Ok(())
+    }
+
+    /// Emits an `xNNN` opcode, where the address is a number, a constant or a
+    /// label that may not be defined yet
+    fn emit_jump(&mut self, opcode: u16, target: Token<'a>) -> Result<(), AsmError> {
+        if let Some(&address) = self.labels.get(target.text) {
+            self.emit(opcode | check_address(target, address)?);
+        } else if let Some(value) = self.number(target) {
+            self.emit(opcode | check_range(target, value?, 0, 0xFFF)? as u16);
+        } else {
+            self.fixups.push(Fixup {
+                offset: self.output.len(),
+                label: target,
+            });
+            self.emit(opcode);
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, AsmError> {
+        let token = self.tokens.get(self.position).copied().ok_or_else(|| {
+            let line = self.tokens.last().map_or(1, |token| token.line);
+            AsmError {
+                line,
+                message: "unexpected end of file".to_string(),
+            }
+        })?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).map(|token| token.text)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token = self.next()?;
+        if token.text == text {
+            Ok(())
+        } else {
+            Err(error(
+                token,
+                format!("expected `{}`, found `{}`", text, token.text),
+            ))
+        }
+    }
+
+    /// A number literal or constant, `None` when the token is neither
+    fn number(&self, token: Token<'a>) -> Option<Result<i32, AsmError>> {
+        if let Some(&value) = self.constants.get(token.text) {
+            return Some(Ok(value));
+        }
+
+        let (negative, digits) = match token.text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token.text),
+        };
+        let parsed = if let Some(hex) = digits.strip_prefix("0x") {
+            i32::from_str_radix(hex, 16)
+        } else if let Some(binary) = digits.strip_prefix("0b") {
+            i32::from_str_radix(binary, 2)
+        } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+            digits.parse()
+        } else {
+            return None;
+        };
+
+        Some(
+            parsed
+                .map(|value| if negative { -value } else { value })
+                .map_err(|_| error(token, format!("invalid number `{}`", token.text))),
+        )
+    }
+
+    fn expect_number(&self, token: Token<'a>) -> Result<i32, AsmError> {
+        self.number(token).unwrap_or_else(|| {
+            Err(error(
+                token,
+                format!("expected a number, found `{}`", token.text),
+            ))
+        })
+    }
+
+    /// A number that fits in a byte. Negative numbers are two's complement
+    fn byte(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        let value = self.expect_number(token)?;
+        Ok(check_range(token, value, -128, 0xFF)? as u8 as u16)
+    }
+
+    fn nibble(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        let value = self.expect_number(token)?;
+        Ok(check_range(token, value, 0, 0xF)? as u16)
+    }
+
+    /// The index of a `v0`-`vf` register or an alias of one
+    fn register_index(&self, token: Token<'a>) -> Option<u16> {
+        if let Some(&index) = self.aliases.get(token.text) {
+            return Some(index);
+        }
+
+        let digit = token.text.strip_prefix(['v', 'V'])?;
+        if digit.len() == 1 {
+            u16::from_str_radix(digit, 16).ok()
+        } else {
+            None
+        }
+    }
+
+    fn register(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        self.register_index(token).ok_or_else(|| {
+            error(
+                token,
+                format!("expected a register, found `{}`", token.text),
+            )
+        })
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.next()?;
+        match token.text {
+            ":" => {
+                let name = self.next()?;
+                if self.labels.insert(name.text, self.address()).is_some() {
+                    return Err(error(
+                        name,
+                        format!("label `{}` is already defined", name.text),
+                    ));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.next()?;
+                let value = self.expect_number(value)?;
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name.text, register);
+            }
+            ":call" => {
+                let target = self.next()?;
+                self.emit_jump(0x2000, target)?;
+            }
+            "clear" => self.emit(0x00E0),
+            "return" | ";" => self.emit(0x00EE),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(0x00C0 | n);
+            }
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-left" => self.emit(0x00FC),
+            "exit" => self.emit(0x00FD),
+            "lores" => self.emit(0x00FE),
+            "hires" => self.emit(0x00FF),
+            "jump" => {
+                let target = self.next()?;
+                self.emit_jump(0x1000, target)?;
+            }
+            "jump0" => {
+                let target = self.next()?;
+                self.emit_jump(0xB000, target)?;
+            }
+            "i" => self.index_statement()?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let low = match token.text {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.emit(0xF000 | x << 8 | low);
+            }
+            "audio" => self.emit(0xF002),
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(0xD000 | x << 8 | y << 4 | n);
+            }
+            "bcd" | "save" | "load" | "saveflags" | "loadflags" => {
+                let x = self.register()?;
+                let low = match token.text {
+                    "bcd" => 0x33,
+                    "save" => 0x55,
+                    "load" => 0x65,
+                    "saveflags" => 0x75,
+                    _ => 0x85,
+                };
+                self.emit(0xF000 | x << 8 | low);
+            }
+            "if" => self.if_statement()?,
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) => {
+                    let end = self.output.len();
+                    self.emit(0x1000);
+                    self.patch(jump, self.address(), token)?;
+                    self.blocks.push(Block::Else {
+                        jump: end,
+                        line: token.line,
+                    });
+                }
+                _ => return Err(error(token, "`else` without `begin`".to_string())),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. } | Block::Else { jump, .. }) => {
+                    self.patch(jump, self.address(), token)?;
+                }
+                _ => return Err(error(token, "`end` without `begin`".to_string())),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.address(),
+                breaks: Vec::new(),
+                line: token.line,
+            }),
+            "while" => {
+                let skip = self.condition()?;
+                let jump = self.output.len();
+                let Some(Block::Loop { breaks, .. }) = self.blocks.last_mut() else {
+                    return Err(error(token, "`while` outside of a loop".to_string()));
+                };
+                breaks.push(jump + 2);
+                // Leave the loop when the condition doesn't hold
+                self.emit(invert_skip(skip));
+                self.emit(0x1000);
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, breaks, .. }) => {
+                    self.emit(0x1000 | check_address(token, start)?);
+                    for jump in breaks {
+                        self.patch(jump, self.address(), token)?;
+                    }
+                }
+                _ => return Err(error(token, "`again` without `loop`".to_string())),
+            },
+            _ => {
+                if let Some(x) = self.register_index(token) {
+                    self.register_statement(x)?;
+                } else if let Some(value) = self.number(token) {
+                    let value = check_range(token, value?, -128, 0xFF)?;
+                    self.output.push(value as u8);
+                } else {
+                    // A bare label name calls it
+                    self.emit_jump(0x2000, token)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn index_statement(&mut self) -> Result<(), AsmError> {
+        let operator = self.next()?;
+        match operator.text {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.position += 1;
+                    let x = self.register()?;
+                    self.emit(0xF029 | x << 8);
+                }
+                Some("bighex") => {
+                    self.position += 1;
+                    let x = self.register()?;
+                    self.emit(0xF030 | x << 8);
+                }
+                _ => {
+                    let target = self.next()?;
+                    self.emit_jump(0xA000, target)?;
+                }
+            },
+            "+=" => {
+                let x = self.register()?;
+                self.emit(0xF01E | x << 8);
+            }
+            _ => {
+                return Err(error(
+                    operator,
+                    format!("unknown operator `{}` for i", operator.text),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u16) -> Result<(), AsmError> {
+        let operator = self.next()?;
+        let source = self.peek().and_then(|text| {
+            self.register_index(Token {
+                text,
+                line: operator.line,
+            })
+        });
+        if let Some(y) = source {
+            self.position += 1;
+            let low = match operator.text {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                _ => return Err(unknown_operator(operator)),
+            };
+            self.emit(0x8000 | x << 8 | y << 4 | low);
+            return Ok(());
+        }
+
+        match (operator.text, self.peek()) {
+            (":=", Some("random")) => {
+                self.position += 1;
+                let mask = self.byte()?;
+                self.emit(0xC000 | x << 8 | mask);
+            }
+            (":=", Some("delay")) => {
+                self.position += 1;
+                self.emit(0xF007 | x << 8);
+            }
+            (":=", Some("key")) => {
+                self.position += 1;
+                self.emit(0xF00A | x << 8);
+            }
+            (":=", _) => {
+                let value = self.byte()?;
+                self.emit(0x6000 | x << 8 | value);
+            }
+            ("+=", _) => {
+                let value = self.byte()?;
+                self.emit(0x7000 | x << 8 | value);
+            }
+            // Subtracting a constant is adding its negation
+            ("-=", _) => {
+                let value = self.byte()?;
+                self.emit(0x7000 | x << 8 | (value as u8).wrapping_neg() as u16);
+            }
+            _ => return Err(unknown_operator(operator)),
+        }
+        Ok(())
+    }
+
+    /// `if <condition> then` emits an instruction that skips the following
+    /// statement when the condition doesn't hold. `if <condition> begin`
+    /// jumps over the block instead
+    fn if_statement(&mut self) -> Result<(), AsmError> {
+        let skip = self.condition()?;
+        let keyword = self.next()?;
+        match keyword.text {
+            "then" => self.emit(skip),
+            "begin" => {
+                self.emit(invert_skip(skip));
+                self.blocks.push(Block::If {
+                    jump: self.output.len(),
+                    line: keyword.line,
+                });
+                self.emit(0x1000);
+            }
+            _ => {
+                return Err(error(
+                    keyword,
+                    format!("expected `then` or `begin`, found `{}`", keyword.text),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses `vx == n`, `vx != vy`, `vx key`, `vx -key` and so on, returning
+    /// the opcode that skips the next instruction when the condition is false
+    fn condition(&mut self) -> Result<u16, AsmError> {
+        let x = self.register()?;
+        let operator = self.next()?;
+        match operator.text {
+            "key" => return Ok(0xE0A1 | x << 8),
+            "-key" => return Ok(0xE09E | x << 8),
+            "==" | "!=" => {}
+            _ => {
+                return Err(error(
+                    operator,
+                    format!("unsupported condition `{}`", operator.text),
+                ));
+            }
+        }
+
+        let equal = operator.text == "==";
+        let source = self.peek().and_then(|text| {
+            self.register_index(Token {
+                text,
+                line: operator.line,
+            })
+        });
+        Ok(match source {
+            Some(y) => {
+                self.position += 1;
+                (if equal { 0x9000 } else { 0x5000 }) | x << 8 | y << 4
+            }
+            None => {
+                let value = self.byte()?;
+                (if equal { 0x4000 } else { 0x3000 }) | x << 8 | value
+            }
+        })
+    }
+}
+
+/// Turns a skip-if-equal into a skip-if-not-equal and the other way around
+fn invert_skip(opcode: u16) -> u16 {
+    match (opcode & 0xF000, opcode & 0x00FF) {
+        (0xE000, 0x9E) => opcode & 0xFF00 | 0xA1,
+        (0xE000, _) => opcode & 0xFF00 | 0x9E,
+        (0x3000, _) => opcode & 0x0FFF | 0x4000,
+        (0x4000, _) => opcode & 0x0FFF | 0x3000,
+        (0x5000, _) => opcode & 0x0FFF | 0x9000,
+        _ => opcode & 0x0FFF | 0x5000,
+    }
+}
+
+fn check_range(token: Token<'_>, value: i32, min: i32, max: i32) -> Result<i32, AsmError> {
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(error(
+            token,
+            format!("{} is out of range {}..={}", value, min, max),
+        ))
+    }
+}
+
+/// An address jumps, calls and `i :=` can reach with their 12 bits
+fn check_address(token: Token<'_>, address: u16) -> Result<u16, AsmError> {
+    if address <= 0xFFF {
+        Ok(address)
+    } else {
+        Err(error(
+            token,
+            format!(
+                "address 0x{:X} of `{}` doesn't fit in 12 bits",
+                address, token.text
+            ),
+        ))
+    }
+}
+
+fn unknown_operator(token: Token<'_>) -> AsmError {
+    error(token, format!("unknown operator `{}`", token.text))
+}
+
+fn error(token: Token<'_>, message: String) -> AsmError {
+    AsmError {
+        line: token.line,
+        message,
+    }
+}
@@ -0,0 +1,163 @@
+//! The beep played while the sound timer runs, or the XO-CHIP audio pattern
+//! instead once a ROM loaded one, as samples for whichever frontend outputs
+//! them
+
+// Only the SDL frontend plays sound
+#![cfg_attr(not(feature = "sdl"), allow(dead_code))]
+
+use std::f32::consts::TAU;
+
+use chip_8_rs::frontend::AudioPattern;
+use serde::Deserialize;
+
+/// Beeps outside this range are either inaudible or painful
+const MIN_FREQUENCY: f32 = 20.0;
+const MAX_FREQUENCY: f32 = 20_000.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Waveform {
+    /// The harsh buzz of the original hardware
+    #[default]
+    Square,
+    /// Softer than a square, still with some edge
+    Triangle,
+    /// A pure tone
+    Sine,
+    /// Random values held for half a period, the frequency sets how rough
+    /// it sounds
+    Noise,
+}
+
+/// What the beep sounds like
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tone {
+    pub waveform: Waveform,
+    /// In Hz
+    pub frequency: f32,
+    /// From 0 for silence to 1 for full scale
+    pub volume: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            volume: 0.15,
+        }
+    }
+}
+
+impl Tone {
+    /// Checks settings read from the config
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_FREQUENCY..=MAX_FREQUENCY).contains(&self.frequency) {
+            return Err(format!(
+                "beep frequency {} is outside {} to {} Hz",
+                self.frequency, MIN_FREQUENCY, MAX_FREQUENCY
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.volume) {
+            return Err(format!("beep volume {} is outside 0 to 1", self.volume));
+        }
+        Ok(())
+    }
+}
+
+/// Generates the samples of a `Tone` or an `AudioPattern` at a fixed sample
+/// rate
+pub struct Oscillator {
+    tone: Tone,
+    sample_rate: f32,
+    pattern: Option<AudioPattern>,
+    /// Position within the pattern in pattern samples, from 0 to 128
+    position: f32,
+    /// Position within the current period, from 0 to 1
+    phase: f32,
+    phase_inc: f32,
+    /// State of the noise generator, a 16-bit Galois LFSR
+    lfsr: u16,
+    /// The noise value held until the next half period
+    noise: f32,
+}
+
+impl Oscillator {
+    pub fn new(tone: Tone, sample_rate: u32) -> Self {
+        Self {
+            tone,
+            sample_rate: sample_rate as f32,
+            pattern: None,
+            position: 0.0,
+            phase: 0.0,
+            phase_inc: tone.frequency / sample_rate as f32,
+            lfsr: 0xACE1,
+            noise: 1.0,
+        }
+    }
+
+    /// Plays `pattern` instead of the tone, or the tone again for `None`
+    pub fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.pattern = pattern;
+    }
+
+    /// Overwrites `out` with the next samples
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            let value = match self.pattern {
+                Some(pattern) => self.next_pattern_sample(pattern),
+                None => self.next_sample(),
+            };
+            *sample = value * self.tone.volume;
+        }
+    }
+
+    /// The pattern averaged over the span one output sample covers, so bits
+    /// shorter than a sample at high pitches blend instead of aliasing
+    fn next_pattern_sample(&mut self, pattern: AudioPattern) -> f32 {
+        let step = pattern.rate() / self.sample_rate;
+        let end = self.position + step;
+        let mut sum = 0.0;
+        let mut position = self.position;
+        while position < end {
+            let boundary = (position.floor() + 1.0).min(end);
+            let level = if pattern.bit(position as usize) {
+                1.0
+            } else {
+                -1.0
+            };
+            sum += level * (boundary - position);
+            position = boundary;
+        }
+        self.position = end % 128.0;
+        sum / step
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        let phase = self.phase;
+        let next = phase + self.phase_inc;
+        // A new noise value every half period
+        if (next * 2.0).floor() != (phase * 2.0).floor() {
+            self.noise = self.next_noise();
+        }
+        self.phase = next % 1.0;
+
+        match self.tone.waveform {
+            Waveform::Square if phase < 0.5 => 1.0,
+            Waveform::Square => -1.0,
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Noise => self.noise,
+        }
+    }
+
+    fn next_noise(&mut self) -> f32 {
+        let bit = self.lfsr & 1;
+        self.lfsr >>= 1;
+        if bit != 0 {
+            self.lfsr ^= 0xB400;
+        }
+        self.lfsr as f32 / u16::MAX as f32 * 2.0 - 1.0
+    }
+}
@@ -0,0 +1,28 @@
+//! The state saved when the emulator quits, so the ROM can be resumed where
+//! it was left the next time it's run. It's kept as `autosave.state` in the
+//! ROM's data directory
+
+use std::{fs, io, path::PathBuf};
+
+use chip_8_rs::Chip8;
+
+use crate::config;
+
+/// The state saved for the ROM `chip8` runs, if there is one
+pub fn load(chip8: &Chip8) -> Option<Vec<u8>> {
+    fs::read(path(chip8)?).ok()
+}
+
+pub fn save(chip8: &Chip8) -> io::Result<()> {
+    let Some(path) = path(chip8) else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, chip8.save_state())
+}
+
+fn path(chip8: &Chip8) -> Option<PathBuf> {
+    Some(config::rom_data_dir(chip8.rom())?.join("autosave.state"))
+}
@@ -0,0 +1,34 @@
+//! Demo ROMs compiled into the binary, for trying the emulator without
+//! hunting for ROMs first: `chip8-rs run --builtin maze`. Their Octo sources
+//! are in `roms/`, assemble them again with `chip8-rs asm` after a change
+
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Builtin {
+    /// Fills the screen with a random maze, any key starts a new one
+    Maze,
+    /// A ball bouncing off the edges of the screen
+    Bounce,
+    /// Shows the last key pressed, for checking the keypad layout
+    Keys,
+}
+
+impl Builtin {
+    pub fn rom(self) -> &'static [u8] {
+        match self {
+            Builtin::Maze => include_bytes!("../roms/maze.ch8"),
+            Builtin::Bounce => include_bytes!("../roms/bounce.ch8"),
+            Builtin::Keys => include_bytes!("../roms/keys.ch8"),
+        }
+    }
+
+    /// Stands in for the ROM's path, save states and screenshots are named
+    /// after it in the working directory
+    pub fn path(self) -> PathBuf {
+        let name = self.to_possible_value().expect("no variant is skipped");
+        PathBuf::from(format!("{}.ch8", name.get_name()))
+    }
+}
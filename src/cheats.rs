@@ -0,0 +1,173 @@
+//! Memory patches like infinite lives, loaded from `<rom>.cht` next to the
+//! ROM. Each line is a cheat: a name, then the bytes it writes, and `freeze`
+//! to write them again after every frame instead of once when enabled:
+//!
+//! ```text
+//! # Lives are kept at 0x3F0
+//! Infinite lives = 0x3F0:03 freeze
+//! Start on level 5 = 0x3F1:05, 0x3F2:00
+//! ```
+//!
+//! All cheats start enabled. F4 turns them all off and on again, the
+//! debugger's `cheat` command toggles them one at a time
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chip_8_rs::Chip8;
+use log::info;
+
+use crate::cli::parse_address;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub name: String,
+    /// Addresses and the bytes written to them
+    pub patches: Vec<(u16, u8)>,
+    /// Written after every frame, so the game can't change the values
+    pub freeze: bool,
+    pub enabled: bool,
+    /// Whether a cheat that isn't frozen still has to be written
+    pending: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+    /// Turns every cheat off without forgetting which ones are enabled
+    active: bool,
+}
+
+impl Cheats {
+    /// Loads the cheat file of the ROM at `rom_path`. A ROM without one gets
+    /// no cheats
+    pub fn load(rom_path: &Path) -> io::Result<Self> {
+        let path = Self::path(rom_path);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+
+        let mut cheats = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cheat = parse_cheat(line).map_err(|message| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} line {}: {}", path.display(), number + 1, message),
+                )
+            })?;
+            cheats.push(cheat);
+        }
+
+        if !cheats.is_empty() {
+            info!("Loaded {} cheats from {}", cheats.len(), path.display());
+        }
+        Ok(Self {
+            cheats,
+            active: true,
+        })
+    }
+
+    fn path(rom_path: &Path) -> PathBuf {
+        let mut path = rom_path.to_path_buf().into_os_string();
+        path.push(".cht");
+        PathBuf::from(path)
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Turns all cheats off or back on, and returns whether they're on
+    pub fn toggle_all(&mut self) -> bool {
+        self.active = !self.active;
+        if self.active {
+            self.rearm();
+        }
+        self.active
+    }
+
+    /// Enables or disables the cheat at `index`. Returns false if there's no
+    /// such cheat
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        let Some(cheat) = self.cheats.get_mut(index) else {
+            return false;
+        };
+        cheat.enabled = enabled;
+        cheat.pending = enabled;
+        true
+    }
+
+    /// Writes the cheats that aren't frozen again on the next frame, after
+    /// the machine was reset or a state was loaded
+    pub fn rearm(&mut self) {
+        for cheat in &mut self.cheats {
+            cheat.pending = cheat.enabled;
+        }
+    }
+
+    /// Writes the enabled cheats into memory. Called after every frame
+    pub fn apply(&mut self, chip8: &mut Chip8) {
+        if !self.active {
+            return;
+        }
+        for cheat in &mut self.cheats {
+            if cheat.enabled && (cheat.freeze || cheat.pending) {
+                for &(address, value) in &cheat.patches {
+                    // Addresses were checked when parsing
+                    let _ = chip8.poke(address, value);
+                }
+                cheat.pending = false;
+            }
+        }
+    }
+}
+
+/// Parses `<name> = <address>:<byte>, ... [freeze]`
+fn parse_cheat(line: &str) -> Result<Cheat, String> {
+    let (name, rest) = line
+        .split_once('=')
+        .ok_or("expected <name> = <address>:<byte>")?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("the cheat has no name".to_string());
+    }
+
+    let (patches, freeze) = match rest.trim().strip_suffix("freeze") {
+        Some(patches) => (patches, true),
+        None => (rest, false),
+    };
+    let patches = patches
+        .split(',')
+        .map(|patch| {
+            let (address, value) = patch
+                .trim()
+                .split_once(':')
+                .ok_or_else(|| format!("expected <address>:<byte>, found {:?}", patch.trim()))?;
+            let address = parse_address(address.trim())?;
+            let value = value.trim();
+            let value = u8::from_str_radix(value.strip_prefix("0x").unwrap_or(value), 16)
+                .map_err(|_| format!("invalid byte {:?}", value))?;
+            Ok((address, value))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(Cheat {
+        name: name.to_string(),
+        patches,
+        freeze,
+        enabled: true,
+        pending: true,
+    })
+}
@@ -1,8 +1,31 @@
-use std::{fs::File, io::Read, path::Path};
+use alloc::{borrow::Cow, boxed::Box, collections::BTreeMap, format, vec, vec::Vec};
+use core::mem;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use std::path::Path;
 
-use rand::Rng;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use crate::archive;
 
-const FONTSET: [u8; 80] = [
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::{
+    chip8x::{CHIP8X_PROGRAM_START, Chip8X},
+    condition::Condition,
+    debug::{History, Stop, Target, Watchpoint},
+    decode::{Instruction, decode},
+    error::{Chip8Error, UnknownOpcodes},
+    frontend::{AudioPattern, AudioSink, DisplaySink, InputSource},
+    megachip::{MEGACHIP_HEIGHT, MEGACHIP_MEMORY, MEGACHIP_WIDTH, MegaChip, Sample},
+    profile::Profile,
+    quirks::Quirks,
+    romdb::{self, RomInfo},
+    system::{SystemCalls, SystemHook},
+};
+
+/// Size of a fontset, 5 bytes for each of the hex digits
+pub const FONT_SIZE: usize = 80;
+
+const FONTSET: [u8; FONT_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -21,50 +44,496 @@ const FONTSET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+/// SUPER-CHIP's 8x10 digits for FX30, loaded right after `FONTSET`. The
+/// original only had 0 to 9, A to F follow the same style
+const BIG_FONTSET: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// Memory of the original interpreters, the default
+pub const MEMORY_SIZE: usize = 4096;
+
+/// Memory of XO-CHIP, everything 16-bit addresses reach and the most
+/// `Chip8::with_sizes` allows
+pub const XO_CHIP_MEMORY: usize = 0x10000;
+
+/// Subroutine calls the original interpreters could nest, the default
+pub const STACK_DEPTH: usize = 16;
+
+/// Programs are loaded here, the first 512 bytes of memory were taken up by
+/// the interpreter on the original hardware. CHIP-8X programs start at
+/// `CHIP8X_PROGRAM_START` instead
+pub const PROGRAM_START: u16 = 0x200;
+
+/// XO-CHIP's pitch register starts at 4000 samples per second
+pub(crate) const DEFAULT_PITCH: u8 = 64;
+
+/// Warnings kept until `take_warnings`, later ones are dropped
+const MAX_WARNINGS: usize = 256;
+
+/// Width and height of the standard CHIP-8 display
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+
+/// Width and height of the SUPER-CHIP high resolution display
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+/// Hi-res CHIP-8 programs start by jumping over the interpreter patch that
+/// doubled the VIP's display to 64x64. The patch is VIP machine code, so
+/// the jump is redirected to where the program itself starts
+const TWO_PAGE_SIGNATURE: [u8; 2] = [0x12, 0x60];
+const TWO_PAGE_START: [u8; 2] = [0x12, 0xC0];
+
 pub struct Chip8 {
-    memory: [u8; 4096],
-    v: [u8; 16],
-    i: u16,
-    pc: u16,
-    stack: [u16; 16],
-    sp: u8,
-    display: [bool; 32 * 64],
-    delay_timer: u8,
-    sound_timer: u8,
-    keypad: [bool; 16],
+    pub(crate) memory: Vec<u8>,
+    pub(crate) v: [u8; 16],
+    /// 16 bits wide, or 24 with the `megachip` quirk
+    pub(crate) i: u32,
+    pub(crate) pc: u16,
+    pub(crate) stack: Vec<u16>,
+    pub(crate) sp: u8,
+    /// Subroutine entered by each call on the stack, for the debugger
+    pub(crate) subroutines: Vec<u16>,
+    /// Memory without the `megachip` quirk, which needs more
+    memory_size: usize,
+    pub(crate) display: [bool; HIRES_WIDTH * HIRES_HEIGHT],
+    pub(crate) hires: bool,
+    /// The 64x64 display of hi-res CHIP-8, detected when the ROM is loaded
+    pub(crate) two_page: bool,
+    /// Set while a MegaChip program is in MegaChip mode
+    pub(crate) megachip: Option<Box<MegaChip>>,
+    /// Set with the `chip8x` quirk
+    pub(crate) chip8x: Option<Box<Chip8X>>,
+    pub(crate) delay_timer: u8,
+    pub(crate) sound_timer: u8,
+    pub(crate) keypad: [bool; 16],
+    /// CHIP-8X's second keypad
+    pub(crate) second_keypad: [bool; 16],
+    /// Keys checked by EX9E, EXA1 and FX0A since `take_polled_keys`, a bit
+    /// per key
+    polled_keys: u16,
+    /// The key FX0A saw pressed and waits to be released, with the
+    /// `wait_for_release` quirk. Not part of save states, a wait starts
+    /// over when one is loaded
+    pub(crate) pressed_key: Option<u8>,
+    /// Set by DXYN with the `display_wait` quirk until the timers tick at
+    /// the end of the frame
+    pub(crate) vblank_wait: bool,
+    pub(crate) rpl: [u8; 16],
+    /// XO-CHIP's audio pattern, once F002 loaded one
+    pub(crate) audio_pattern: Option<[u8; 16]>,
+    pub(crate) audio_pitch: u8,
+    /// Addresses to stop at, when their condition holds if they have one
+    pub(crate) breakpoints: BTreeMap<u16, Option<Condition>>,
+    pub(crate) watchpoints: Vec<Watchpoint>,
+    /// Where `run_to`, `step_over` or `step_out` stop, until a stop happens
+    pub(crate) run_to: Option<Target>,
+    /// Why execution stopped, kept until the frontend asks
+    pub(crate) stop: Option<Stop>,
+    /// Lets the instruction that caused a stop run once execution resumes
+    pub(crate) resume: bool,
+    pub(crate) profile: Option<Box<Profile>>,
+    /// The last instructions executed, once `enable_history` was called
+    pub(crate) history: Option<History>,
+    /// Stands in for 0NNN routines
+    pub(crate) system_hook: Option<SystemHook>,
+    pub(crate) system_calls: SystemCalls,
+    unknown_opcodes: UnknownOpcodes,
+    /// Errors that were skipped instead of stopping execution
+    warnings: Vec<Chip8Error>,
+    pub(crate) rom: Vec<u8>,
+    /// Copied to the start of memory, `FONTSET` unless `set_font` replaced it
+    font: [u8; FONT_SIZE],
+    /// The loaded ROM, when it's in the built-in list
+    rom_info: Option<RomInfo>,
+    quirks: Quirks,
+    /// Whether loading a known ROM picks its quirks. Turned off once quirks
+    /// are set explicitly
+    detect_quirks: bool,
+    /// Seed of `rng`, kept so a reset replays the same numbers
+    seed: Option<u64>,
+    /// Generator for CXKK, created once instead of on every instruction
+    rng: StdRng,
+}
+
+/// The generator for `seed`. An unseeded machine gets its seed from the
+/// thread RNG, or seed 0 without std since there's no entropy source
+fn rng_for(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        #[cfg(feature = "std")]
+        None => StdRng::from_rng(&mut rand::rng()),
+        #[cfg(not(feature = "std"))]
+        None => StdRng::seed_from_u64(0),
+    }
+}
+
+/// Where programs start with `quirks`
+pub(crate) fn program_start(quirks: Quirks) -> u16 {
+    if quirks.chip8x {
+        CHIP8X_PROGRAM_START
+    } else {
+        PROGRAM_START
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chip8 {
+    /// A machine with the original interpreters' 4KB of memory and 16 stack
+    /// slots
     pub fn new() -> Self {
+        Self::with_sizes(MEMORY_SIZE, STACK_DEPTH)
+    }
+
+    /// A machine with `memory_size` bytes of memory and room for
+    /// `stack_depth` nested subroutine calls, e.g. `XO_CHIP_MEMORY` for
+    /// ROMs too large for 4KB. The `megachip` quirk still gets
+    /// `MEGACHIP_MEMORY`. Both are kept across resets.
+    ///
+    /// # Panics
+    ///
+    /// If `memory_size` isn't between `MEMORY_SIZE` and `XO_CHIP_MEMORY` or
+    /// `stack_depth` isn't between 1 and 255
+    pub fn with_sizes(memory_size: usize, stack_depth: usize) -> Self {
+        assert!(
+            (MEMORY_SIZE..=XO_CHIP_MEMORY).contains(&memory_size),
+            "memory size {} is outside {}..={}",
+            memory_size,
+            MEMORY_SIZE,
+            XO_CHIP_MEMORY
+        );
+        assert!(
+            (1..=u8::MAX as usize).contains(&stack_depth),
+            "stack depth {} is outside 1..=255",
+            stack_depth
+        );
         let mut state = Self {
-            memory: [0u8; 4096],
+            memory: vec![0; memory_size],
             v: [0; 16],
             i: 0,
-            pc: 0x200, // Leaving the first 512 bytes of memory
-            stack: [0; 16],
+            pc: PROGRAM_START,
+            stack: vec![0; stack_depth],
             sp: 0,
-            display: [false; 32 * 64],
+            subroutines: vec![0; stack_depth],
+            memory_size,
+            display: [false; HIRES_WIDTH * HIRES_HEIGHT],
+            hires: false,
+            two_page: false,
+            megachip: None,
+            chip8x: None,
             delay_timer: 0,
             sound_timer: 0,
             keypad: [false; 16],
+            second_keypad: [false; 16],
+            polled_keys: 0,
+            pressed_key: None,
+            vblank_wait: false,
+            rpl: [0; 16],
+            audio_pattern: None,
+            audio_pitch: DEFAULT_PITCH,
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            run_to: None,
+            stop: None,
+            resume: false,
+            profile: None,
+            history: None,
+            system_hook: None,
+            system_calls: SystemCalls::Ignore,
+            unknown_opcodes: UnknownOpcodes::Halt,
+            warnings: Vec::new(),
+            rom: Vec::new(),
+            font: FONTSET,
+            rom_info: None,
+            quirks: Quirks::default(),
+            detect_quirks: true,
+            seed: None,
+            rng: rng_for(None),
         };
 
         state.load_fontset();
-        return state;
+        state
     }
 
     fn load_fontset(&mut self) {
-        self.memory[0..FONTSET.len()].copy_from_slice(&FONTSET);
+        self.memory[0..FONT_SIZE].copy_from_slice(&self.font);
+        self.memory[FONT_SIZE..FONT_SIZE + BIG_FONTSET.len()].copy_from_slice(&BIG_FONTSET);
+    }
+
+    /// Replaces the hex digits FX29 points at with `font`, 5 rows for each
+    /// digit from 0 to F. It's written to memory right away and kept across
+    /// resets. Fails if `font` isn't `FONT_SIZE` bytes or a digit is blank,
+    /// which is more likely a wrong file than a theme
+    pub fn set_font(&mut self, font: &[u8]) -> Result<(), Chip8Error> {
+        let font: [u8; FONT_SIZE] = font.try_into().map_err(|_| {
+            Chip8Error::InvalidFont(format!("expected {} bytes, got {}", FONT_SIZE, font.len()))
+        })?;
+        if let Some(digit) = font
+            .chunks(5)
+            .position(|glyph| glyph.iter().all(|&row| row == 0))
+        {
+            return Err(Chip8Error::InvalidFont(format!(
+                "the digit {:X} is blank",
+                digit
+            )));
+        }
+        self.font = font;
+        self.load_fontset();
+        Ok(())
+    }
+
+    /// Reads the ROM at `path` and loads it into memory, decompressing it
+    /// first if it's a zip or gzip file. Not available on wasm or without
+    /// std, where the ROM bytes have to be passed to `load` instead
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    pub fn load_rom<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Chip8Error> {
+        let rom = archive::read_rom(path)?;
+        self.load(&rom)
+    }
+
+    /// Copies the program bytes into memory starting at `PROGRAM_START`. When
+    /// the ROM is a known one and no quirks were set with `set_quirks`, the
+    /// quirks of its platform are used. A ROM starting with 1260 is taken to
+    /// be written for hi-res CHIP-8 and gets a 64x64 display
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let rom_info = romdb::lookup(rom);
+        let quirks = match &rom_info {
+            Some(info) if self.detect_quirks => info.platform.quirks(),
+            _ => self.quirks,
+        };
+
+        let start = program_start(quirks) as usize;
+        let max = self.memory_size(quirks) - start;
+        if rom.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+
+        self.apply_quirks(quirks);
+        self.memory[start..start + rom.len()].copy_from_slice(rom);
+        self.pc = start as u16;
+        self.two_page = start == PROGRAM_START as usize
+            && !quirks.megachip
+            && rom.starts_with(&TWO_PAGE_SIGNATURE);
+        if self.two_page {
+            self.memory[start..start + 2].copy_from_slice(&TWO_PAGE_START);
+        }
+        self.rom = rom.to_vec();
+        self.rom_info = rom_info;
+        Ok(())
+    }
+
+    /// Restarts the loaded program: memory is restored from the ROM and all
+    /// registers, timers and the display are cleared. Quirks, breakpoints,
+    /// watchpoints, the profile, the history, the 0NNN hook and how unknown
+    /// opcodes are handled are kept, and so are the font and the sizes of
+    /// memory and the stack. A seeded RNG starts over from its seed
+    pub fn reset(&mut self) {
+        let rom = mem::take(&mut self.rom);
+        *self = Self {
+            quirks: self.quirks,
+            detect_quirks: self.detect_quirks,
+            breakpoints: mem::take(&mut self.breakpoints),
+            watchpoints: mem::take(&mut self.watchpoints),
+            profile: self.profile.take(),
+            history: self.history.take(),
+            system_hook: self.system_hook.take(),
+            system_calls: self.system_calls,
+            unknown_opcodes: self.unknown_opcodes,
+            font: self.font,
+            seed: self.seed,
+            rng: rng_for(self.seed),
+            ..Self::with_sizes(self.memory_size, self.stack.len())
+        };
+        self.load_fontset();
+        // The ROM fit into memory when it was first loaded
+        let _ = self.load(&rom);
+    }
+
+    /// The ROM passed to the last `load`
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// The XO-CHIP audio pattern and pitch, once the ROM loaded a pattern
+    pub fn audio_pattern(&self) -> Option<AudioPattern> {
+        self.audio_pattern.map(|bits| AudioPattern {
+            bits,
+            pitch: self.audio_pitch,
+        })
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Overrides the quirks, including ones picked for a known ROM
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.apply_quirks(quirks);
+        self.detect_quirks = false;
+    }
+
+    /// Memory needed for `quirks`
+    fn memory_size(&self, quirks: Quirks) -> usize {
+        if quirks.megachip {
+            MEGACHIP_MEMORY
+        } else {
+            self.memory_size
+        }
+    }
+
+    /// How many subroutine calls can be nested
+    pub fn stack_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Memory grows or shrinks with the `megachip` quirk, and the color
+    /// board comes and goes with the `chip8x` one
+    fn apply_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        self.memory.resize(self.memory_size(quirks), 0);
+        if !quirks.megachip {
+            self.megachip = None;
+            self.i &= 0xFFFF;
+        }
+        match (quirks.chip8x, &self.chip8x) {
+            (true, None) => self.chip8x = Some(Box::new(Chip8X::new())),
+            (false, Some(_)) => self.chip8x = None,
+            _ => {}
+        }
+    }
+
+    /// The loaded ROM, if it's in the built-in list
+    pub fn rom_info(&self) -> Option<&RomInfo> {
+        self.rom_info.as_ref()
+    }
+
+    /// Makes CXKK draw its numbers from a generator seeded with `seed`, so
+    /// runs with the same input are reproducible. The generator's position
+    /// isn't part of save states. Without std there's no thread RNG, and
+    /// numbers come from seed 0 until this is called
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.rng = rng_for(Some(seed));
+    }
+
+    /// Whether unknown opcodes stop execution, which they do by default
+    pub fn set_unknown_opcodes(&mut self, unknown_opcodes: UnknownOpcodes) {
+        self.unknown_opcodes = unknown_opcodes;
     }
 
-    pub fn load_rom<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
-        let mut file = File::open(path)?;
-        file.read(&mut self.memory[0x200..])?;
-        return Ok(());
+    pub fn unknown_opcodes(&self) -> UnknownOpcodes {
+        self.unknown_opcodes
     }
 
-    pub fn get_display(&self) -> &[bool; 32 * 64] {
-        &self.display
+    /// Unknown opcodes skipped with `UnknownOpcodes::Warn` since the last
+    /// call, oldest first. Only the first few hundred are kept when nobody
+    /// asks
+    pub fn take_warnings(&mut self) -> Vec<Chip8Error> {
+        mem::take(&mut self.warnings)
+    }
+
+    /// The seed passed to `set_seed`, if any
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Returns the pixels of the active display, row by row, with
+    /// `display_width() * display_height()` entries. In MegaChip mode these
+    /// are the pixels that aren't black
+    pub fn get_display(&self) -> &[bool] {
+        match &self.megachip {
+            Some(megachip) => &megachip.lit,
+            None => &self.display[..self.display_width() * self.display_height()],
+        }
+    }
+
+    /// The colors of the display as 0xAARRGGBB in MegaChip mode or on
+    /// CHIP-8X, laid out like `get_display`. `None` when the display is
+    /// monochrome
+    pub fn get_colors(&self) -> Option<Cow<'_, [u32]>> {
+        if let Some(megachip) = &self.megachip {
+            return Some(Cow::Borrowed(&megachip.screen));
+        }
+        let chip8x = self.chip8x.as_ref()?;
+        let (width, height) = (self.display_width(), self.display_height());
+        Some(Cow::Owned(chip8x.colorize(
+            self.get_display(),
+            width,
+            height,
+        )))
+    }
+
+    /// Hands the display to `display`, in color when there are colors
+    pub fn present(&self, display: &mut impl DisplaySink) {
+        let (width, height) = (self.display_width(), self.display_height());
+        match self.get_colors() {
+            Some(colors) => display.present_colors(&colors, width, height),
+            None => display.present(self.get_display(), width, height),
+        }
+    }
+
+    /// The sample a MegaChip program started with 060N, which frontends
+    /// don't play yet
+    pub fn megachip_sample(&self) -> Option<Sample> {
+        self.megachip.as_ref().and_then(|megachip| megachip.sample)
+    }
+
+    /// The pitch CHIP-8X's FXF8 set, which frontends don't play yet. The
+    /// tone is 27535 / (pitch + 1) Hz
+    pub fn chip8x_pitch(&self) -> Option<u8> {
+        self.chip8x.as_ref().map(|chip8x| chip8x.pitch)
+    }
+
+    pub fn display_width(&self) -> usize {
+        if self.megachip.is_some() {
+            MEGACHIP_WIDTH
+        } else if self.hires {
+            HIRES_WIDTH
+        } else {
+            LORES_WIDTH
+        }
+    }
+
+    pub fn display_height(&self) -> usize {
+        if self.megachip.is_some() {
+            MEGACHIP_HEIGHT
+        } else if self.hires || self.two_page {
+            HIRES_HEIGHT
+        } else {
+            LORES_HEIGHT
+        }
     }
 
     pub fn key_down(&mut self, key: u8) {
@@ -73,74 +542,96 @@ impl Chip8 {
         }
     }
 
+    /// Which keys are held, indexed by their hex value
+    pub fn keypad(&self) -> &[bool; 16] {
+        &self.keypad
+    }
+
     pub fn key_up(&mut self, key: u8) {
         if key < 16 {
             self.keypad[key as usize] = false;
         }
     }
 
-    pub fn cycle(&mut self) {
-        let opcode = self.fetch_opcode();
-        self.execute_opcode(opcode);
-        self.update_timers();
+    /// The keys the program checked since the last call, bit N being key N.
+    /// FX0A takes any key, so it counts as checking all of them
+    pub fn take_polled_keys(&mut self) -> u16 {
+        mem::take(&mut self.polled_keys)
     }
 
-    fn fetch_opcode(&self) -> u16 {
-        let hi_byte = self.memory[self.pc as usize] as u16;
-        let lo_byte = self.memory[self.pc as usize + 1] as u16;
-        (hi_byte << 8) | lo_byte
+    /// `key_down` for CHIP-8X's second keypad
+    pub fn second_key_down(&mut self, key: u8) {
+        if key < 16 {
+            self.second_keypad[key as usize] = true;
+        }
     }
 
-    fn execute_opcode(&mut self, opcode: u16) {
-        let nibbles = (
-            (opcode & 0xF000) >> 12 as u8,
-            (opcode & 0x0F00) >> 8 as u8,
-            (opcode & 0x00F0) >> 4 as u8,
-            (opcode & 0x000F) as u8,
-        );
+    /// `key_up` for CHIP-8X's second keypad
+    pub fn second_key_up(&mut self, key: u8) {
+        if key < 16 {
+            self.second_keypad[key as usize] = false;
+        }
+    }
 
-        // https://en.wikipedia.org/wiki/CHIP-8#Opcode_table
-        match nibbles {
-            (0x00, 0x00, 0x0e, 0x00) => self.op_00e0(),
-            (0x00, 0x00, 0x0e, 0x0e) => self.op_00ee(),
-            (0x01, _, _, _) => self.op_1nnn(opcode),
-            (0x02, _, _, _) => self.op_2nnn(opcode),
-            (0x03, _, _, _) => self.op_3xkk(opcode),
-            (0x04, _, _, _) => self.op_4xkk(opcode),
-            (0x05, _, _, 0x00) => self.op_5xy0(opcode),
-            (0x06, _, _, _) => self.op_6xkk(opcode),
-            (0x07, _, _, _) => self.op_7xkk(opcode),
-            (0x08, _, _, 0x00) => self.op_8xy0(opcode),
-            (0x08, _, _, 0x01) => self.op_8xy1(opcode),
-            (0x08, _, _, 0x02) => self.op_8xy2(opcode),
-            (0x08, _, _, 0x03) => self.op_8xy3(opcode),
-            (0x08, _, _, 0x04) => self.op_8xy4(opcode),
-            (0x08, _, _, 0x05) => self.op_8xy5(opcode),
-            (0x08, _, _, 0x06) => self.op_8xy6(opcode),
-            (0x08, _, _, 0x07) => self.op_8xy7(opcode),
-            (0x08, _, _, 0x0e) => self.op_8xye(opcode),
-            (0x09, _, _, 0x00) => self.op_9xy0(opcode),
-            (0x0a, _, _, _) => self.op_annn(opcode),
-            (0x0b, _, _, _) => self.op_bnnn(opcode),
-            (0x0c, _, _, _) => self.op_cxkk(opcode),
-            (0x0d, _, _, _) => self.op_dxyn(opcode),
-            (0x0e, _, 0x09, 0x0e) => self.op_ex9e(opcode),
-            (0x0e, _, 0x0a, 0x01) => self.op_exa1(opcode),
-            (0x0f, _, 0x00, 0x07) => self.op_fx07(opcode),
-            (0x0f, _, 0x00, 0x0a) => self.op_fx0a(opcode),
-            (0x0f, _, 0x01, 0x05) => self.op_fx15(opcode),
-            (0x0f, _, 0x01, 0x08) => self.op_fx18(opcode),
-            (0x0f, _, 0x01, 0x0e) => self.op_fx1e(opcode),
-            (0x0f, _, 0x02, 0x09) => self.op_fx29(opcode),
-            (0x0f, _, 0x03, 0x03) => self.op_fx33(opcode),
-            (0x0f, _, 0x05, 0x05) => self.op_fx55(opcode),
-            (0x0f, _, 0x06, 0x05) => self.op_fx65(opcode),
-            // If no match, move to next instruction
-            _ => self.pc += 2,
-        }
-    }
-
-    fn update_timers(&mut self) {
+    /// Runs a single 60Hz frame: polls input, executes `instructions` cycles,
+    /// ticks the timers and hands the results to the display and audio sinks
+    pub fn run_frame(
+        &mut self,
+        instructions: u32,
+        input: &mut impl InputSource,
+        display: &mut impl DisplaySink,
+        audio: &mut impl AudioSink,
+    ) -> Result<(), Chip8Error> {
+        input.poll(&mut self.keypad);
+        input.poll_second(&mut self.second_keypad);
+
+        for _ in 0..instructions {
+            self.cycle()?;
+            if self.stop.is_some() || self.vblank_wait {
+                break;
+            }
+        }
+        self.tick_timers();
+
+        self.present(display);
+        audio.set_pattern(self.audio_pattern());
+        audio.set_tone(self.sound_timer > 0);
+        Ok(())
+    }
+
+    /// Fetches and executes a single instruction. Timers are not touched
+    /// here; the caller is expected to call `tick_timers` at 60Hz. When the
+    /// instruction hits a breakpoint or watchpoint nothing is executed, the
+    /// next call runs it. Nothing is executed either while a DXYN waits for
+    /// `tick_timers` with the `display_wait` quirk
+    pub fn cycle(&mut self) -> Result<(), Chip8Error> {
+        if self.vblank_wait {
+            return Ok(());
+        }
+        let opcode = self.fetch_opcode()?;
+        if self.resume {
+            self.resume = false;
+        } else if let Some(stop) = self.check_stop(opcode) {
+            self.stop = Some(stop);
+            self.run_to = None;
+            self.resume = true;
+            return Ok(());
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.record(self.pc, opcode);
+        }
+        if let Some(history) = &mut self.history {
+            history.record(self.pc, opcode);
+        }
+
+        self.execute_opcode(opcode)
+    }
+
+    /// Decrements the delay and sound timers by one. Meant to be called at
+    /// a fixed 60Hz regardless of how many instructions are executed, it's
+    /// also the vertical blank DXYN waits for with the `display_wait` quirk
+    pub fn tick_timers(&mut self) {
+        self.vblank_wait = false;
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -150,326 +641,622 @@ impl Chip8 {
         }
     }
 
+    pub(crate) fn fetch_opcode(&self) -> Result<u16, Chip8Error> {
+        let hi_byte = self.read_memory(self.pc as usize)? as u16;
+        let lo_byte = self.read_memory(self.pc as usize + 1)? as u16;
+        Ok((hi_byte << 8) | lo_byte)
+    }
+
+    /// Moves PC `by` bytes forward. PC is 16 bits, so an instruction at the
+    /// very end of a 64KB memory can't be followed by another
+    pub(crate) fn advance(&mut self, by: u16) -> Result<(), Chip8Error> {
+        self.pc = self
+            .pc
+            .checked_add(by)
+            .ok_or(Chip8Error::MemoryOutOfBounds {
+                address: XO_CHIP_MEMORY,
+            })?;
+        Ok(())
+    }
+
+    /// Moves PC to the next instruction, or past it if `skip`
+    fn skip_if(&mut self, skip: bool) -> Result<(), Chip8Error> {
+        self.advance(if skip { 4 } else { 2 })
+    }
+
+    fn read_memory(&self, address: usize) -> Result<u8, Chip8Error> {
+        self.memory
+            .get(address)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds { address })
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Chip8Error> {
+        let byte = self
+            .memory
+            .get_mut(address)
+            .ok_or(Chip8Error::MemoryOutOfBounds { address })?;
+        *byte = value;
+        Ok(())
+    }
+
+    /// I wraps around at 16 bits, or 24 with the `megachip` quirk
+    pub(crate) fn index_mask(&self) -> u32 {
+        if self.quirks.megachip {
+            0xFF_FFFF
+        } else {
+            0xFFFF
+        }
+    }
+
+    /// Moves I after FX55/FX65 accessed V0 to Vx, depending on the quirks
+    fn increment_index(&mut self, x: usize) {
+        let step = if self.quirks.load_store_increment_i {
+            x as u32 + 1
+        } else if self.quirks.load_store_increment_x {
+            x as u32
+        } else {
+            0
+        };
+        self.i = self.i.wrapping_add(step) & self.index_mask();
+    }
+
+    fn execute_opcode(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        match decode(opcode) {
+            Some(instruction) => self.execute(instruction, opcode),
+            None => self.unknown_opcode(opcode),
+        }
+    }
+
+    /// Halts on, skips or warns about `opcode`, depending on
+    /// `unknown_opcodes`
+    pub(crate) fn unknown_opcode(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let error = Chip8Error::UnknownOpcode {
+            address: self.pc,
+            opcode,
+        };
+        match self.unknown_opcodes {
+            UnknownOpcodes::Halt => return Err(error),
+            UnknownOpcodes::Ignore => {}
+            UnknownOpcodes::Warn => {
+                if self.warnings.len() < MAX_WARNINGS {
+                    self.warnings.push(error);
+                }
+            }
+        }
+        self.advance(2)
+    }
+
+    /// Runs an instruction decoded from `opcode`, which is only needed for
+    /// the error when it isn't supported
+    pub(crate) fn execute(
+        &mut self,
+        instruction: Instruction,
+        opcode: u16,
+    ) -> Result<(), Chip8Error> {
+        // https://en.wikipedia.org/wiki/CHIP-8#Opcode_table
+        // SUPER-CHIP 1.1 extensions: http://devernay.free.fr/hacks/chip8/schip.txt
+        match instruction {
+            Instruction::LoadPalette { kk: 0xA0 } | Instruction::JumpOffset { .. }
+                if self.chip8x.is_some() =>
+            {
+                self.execute_chip8x(instruction, opcode)
+            }
+            // 0230, hi-res CHIP-8's clear, which has to clear both pages
+            Instruction::LoadPalette { kk: 0x30 } if self.two_page => self.op_00e0(),
+            Instruction::AddNibbles { .. }
+            | Instruction::SkipKey2 { .. }
+            | Instruction::SkipNotKey2 { .. }
+            | Instruction::SetPitch { .. }
+            | Instruction::ReadPort { .. } => self.execute_chip8x(instruction, opcode),
+            Instruction::MegaOff
+            | Instruction::MegaOn
+            | Instruction::ScrollUp { .. }
+            | Instruction::LoadIndexLong { .. }
+            | Instruction::LoadPalette { .. }
+            | Instruction::SpriteWidth { .. }
+            | Instruction::SpriteHeight { .. }
+            | Instruction::ScreenAlpha { .. }
+            | Instruction::PlaySample { .. }
+            | Instruction::StopSample
+            | Instruction::BlendMode { .. }
+            | Instruction::CollisionColor { .. } => self.execute_megachip(instruction, opcode),
+            Instruction::Clear
+            | Instruction::ScrollDown { .. }
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Draw { .. }
+                if self.megachip.is_some() =>
+            {
+                self.execute_megachip(instruction, opcode)
+            }
+            Instruction::ScrollDown { n } => self.op_00cn(n as usize),
+            Instruction::Clear => self.op_00e0(),
+            Instruction::Return => self.op_00ee(),
+            Instruction::ScrollRight => self.op_00fb(),
+            Instruction::ScrollLeft => self.op_00fc(),
+            Instruction::Lores => self.op_00fe(),
+            Instruction::Hires => self.op_00ff(),
+            Instruction::System { nnn } => self.system_call(nnn),
+            // EXIT isn't supported and is skipped
+            Instruction::Exit => self.advance(2),
+            Instruction::Jump { nnn } => self.op_1nnn(nnn),
+            Instruction::Call { nnn } => self.op_2nnn(nnn),
+            Instruction::SkipEqual { x, kk } => self.op_3xkk(x as usize, kk),
+            Instruction::SkipNotEqual { x, kk } => self.op_4xkk(x as usize, kk),
+            Instruction::SkipEqualRegister { x, y } => self.op_5xy0(x as usize, y as usize),
+            Instruction::Load { x, kk } => self.op_6xkk(x as usize, kk),
+            Instruction::Add { x, kk } => self.op_7xkk(x as usize, kk),
+            Instruction::Move { x, y } => self.op_8xy0(x as usize, y as usize),
+            Instruction::Or { x, y } => self.op_8xy1(x as usize, y as usize),
+            Instruction::And { x, y } => self.op_8xy2(x as usize, y as usize),
+            Instruction::Xor { x, y } => self.op_8xy3(x as usize, y as usize),
+            Instruction::AddRegister { x, y } => self.op_8xy4(x as usize, y as usize),
+            Instruction::Sub { x, y } => self.op_8xy5(x as usize, y as usize),
+            Instruction::ShiftRight { x, y } => self.op_8xy6(x as usize, y as usize),
+            Instruction::SubReverse { x, y } => self.op_8xy7(x as usize, y as usize),
+            Instruction::ShiftLeft { x, y } => self.op_8xye(x as usize, y as usize),
+            Instruction::SkipNotEqualRegister { x, y } => self.op_9xy0(x as usize, y as usize),
+            Instruction::LoadIndex { nnn } => self.op_annn(nnn),
+            Instruction::JumpOffset { x, nnn } => self.op_bnnn(x as usize, nnn),
+            Instruction::Random { x, kk } => self.op_cxkk(x as usize, kk),
+            Instruction::Draw { x, y, n: 0 } => self.op_dxy0(x as usize, y as usize),
+            Instruction::Draw { x, y, n } => self.op_dxyn(x as usize, y as usize, n as usize),
+            Instruction::SkipKey { x } => self.op_ex9e(x as usize),
+            Instruction::SkipNotKey { x } => self.op_exa1(x as usize),
+            Instruction::LoadPattern => self.op_f002(),
+            Instruction::LoadDelay { x } => self.op_fx07(x as usize),
+            Instruction::WaitKey { x } => self.op_fx0a(x as usize),
+            Instruction::SetDelay { x } => self.op_fx15(x as usize),
+            Instruction::SetSound { x } => self.op_fx18(x as usize),
+            Instruction::AddIndex { x } => self.op_fx1e(x as usize),
+            Instruction::Font { x } => self.op_fx29(x as usize),
+            Instruction::Bcd { x } => self.op_fx33(x as usize),
+            Instruction::SetAudioPitch { x } => self.op_fx3a(x as usize),
+            Instruction::Store { x } => self.op_fx55(x as usize),
+            Instruction::Restore { x } => self.op_fx65(x as usize),
+            Instruction::StoreFlags { x } => self.op_fx75(x as usize),
+            Instruction::RestoreFlags { x } => self.op_fx85(x as usize),
+            Instruction::BigFont { x } => self.op_fx30(x as usize),
+        }
+    }
+
+    /// Scrolls the display down by n pixels
+    fn op_00cn(&mut self, n: usize) -> Result<(), Chip8Error> {
+        let width = self.display_width();
+        let height = self.display_height();
+
+        for y in (0..height).rev() {
+            for x in 0..width {
+                self.display[y * width + x] = y >= n && self.display[(y - n) * width + x];
+            }
+        }
+        self.advance(2)
+    }
+
     /// Clears the display
-    fn op_00e0(&mut self) {
+    fn op_00e0(&mut self) -> Result<(), Chip8Error> {
         self.display.fill(false);
-        self.pc += 2;
+        self.advance(2)
     }
 
     /// Returns from subroutine
     /// Decrements the stack pointer and sets the program counter to the
     /// return address on the stack
-    fn op_00ee(&mut self) {
+    fn op_00ee(&mut self) -> Result<(), Chip8Error> {
+        if self.sp == 0 {
+            return Err(Chip8Error::StackUnderflow { address: self.pc });
+        }
         self.sp -= 1;
-        self.pc = self.stack[self.sp as usize] + 2;
+        // A loaded save state can hold any return address
+        self.pc = self.stack[self.sp as usize].wrapping_add(2);
+        self.subroutines[self.sp as usize] = 0;
+        Ok(())
+    }
+
+    /// Scrolls the display right by 4 pixels
+    fn op_00fb(&mut self) -> Result<(), Chip8Error> {
+        let width = self.display_width();
+        let height = self.display_height();
+
+        for y in 0..height {
+            for x in (0..width).rev() {
+                self.display[y * width + x] = x >= 4 && self.display[y * width + x - 4];
+            }
+        }
+        self.advance(2)
+    }
+
+    /// Scrolls the display left by 4 pixels
+    fn op_00fc(&mut self) -> Result<(), Chip8Error> {
+        let width = self.display_width();
+        let height = self.display_height();
+
+        for y in 0..height {
+            for x in 0..width {
+                self.display[y * width + x] = x + 4 < width && self.display[y * width + x + 4];
+            }
+        }
+        self.advance(2)
+    }
+
+    /// Switches to the low resolution (64x32) display mode
+    fn op_00fe(&mut self) -> Result<(), Chip8Error> {
+        self.set_hires(false);
+        self.advance(2)
+    }
+
+    /// Switches to the high resolution (128x64) display mode
+    fn op_00ff(&mut self) -> Result<(), Chip8Error> {
+        self.set_hires(true);
+        self.advance(2)
+    }
+
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.display.fill(false);
     }
 
     /// Jumps to nnn address
-    fn op_1nnn(&mut self, opcode: u16) {
-        self.pc = opcode & 0x0FFF;
+    fn op_1nnn(&mut self, nnn: u16) -> Result<(), Chip8Error> {
+        self.pc = nnn;
+        Ok(())
     }
 
     /// Calls the subroutine at nnn address
-    fn op_2nnn(&mut self, opcode: u16) {
+    fn op_2nnn(&mut self, nnn: u16) -> Result<(), Chip8Error> {
+        if self.sp as usize == self.stack.len() {
+            return Err(Chip8Error::StackOverflow { address: self.pc });
+        }
         self.stack[self.sp as usize] = self.pc;
+        self.subroutines[self.sp as usize] = nnn;
         self.sp += 1;
-        self.pc = opcode & 0x0FFF;
+        self.pc = nnn;
+        Ok(())
     }
 
     /// Skips the next instruction if the value of Vx == kk
-    fn op_3xkk(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let kk = (opcode & 0x00FF) as u8;
-        if self.v[x] == kk {
-            self.pc += 4;
-        } else {
-            self.pc += 2;
-        }
+    fn op_3xkk(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
+        self.skip_if(self.v[x] == kk)
     }
 
     /// Skips the next instruction if the value of Vx != kk
-    fn op_4xkk(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let kk = (opcode & 0x00FF) as u8;
-        if self.v[x] != kk {
-            self.pc += 4;
-        } else {
-            self.pc += 2;
-        }
+    fn op_4xkk(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
+        self.skip_if(self.v[x] != kk)
     }
 
     /// Skips the next instruction if Vx == Vy
-    fn op_5xy0(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
-        if self.v[x] == self.v[y] {
-            self.pc += 4;
-        } else {
-            self.pc += 2;
-        }
+    fn op_5xy0(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        self.skip_if(self.v[x] == self.v[y])
     }
 
     /// Sets Vx to kk
-    fn op_6xkk(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let kk = (opcode & 0x00FF) as u8;
+    fn op_6xkk(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
         self.v[x] = kk;
-        self.pc += 2;
+        self.advance(2)
     }
 
     /// Adds kk to Vx
-    fn op_7xkk(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let kk = (opcode & 0x00FF) as u8;
+    fn op_7xkk(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
         self.v[x] = self.v[x].wrapping_add(kk);
-        self.pc += 2;
+        self.advance(2)
     }
 
     /// Sets Vx to Vy
-    fn op_8xy0(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    fn op_8xy0(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         self.v[x] = self.v[y];
-        self.pc += 2;
+        self.advance(2)
     }
 
     /// Sets Vx to Vx | Vy
-    fn op_8xy1(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    fn op_8xy1(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         self.v[x] |= self.v[y];
-        self.pc += 2;
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+        self.advance(2)
     }
 
     /// Sets Vx to Vx & Vy
-    fn op_8xy2(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    fn op_8xy2(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         self.v[x] &= self.v[y];
-        self.pc += 2;
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+        self.advance(2)
     }
 
     /// Sets Vx to Vx ^ Vy
-    fn op_8xy3(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    fn op_8xy3(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         self.v[x] ^= self.v[y];
-        self.pc += 2;
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+        self.advance(2)
     }
 
     /// Adds Vy to Vx and sets Vf to 1 if an overflow occurs
-    fn op_8xy4(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    fn op_8xy4(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         let (sum, carry) = self.v[x].overflowing_add(self.v[y]);
         self.v[x] = sum;
         self.v[0xF] = if carry { 1 } else { 0 };
-        self.pc += 2;
+        self.advance(2)
     }
 
     /// Subtracts Vy from Vx and sets Vf to 0 if an underflow occurs, else 1
-    fn op_8xy5(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    fn op_8xy5(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         let (result, carry) = self.v[x].overflowing_sub(self.v[y]);
         self.v[x] = result;
         self.v[0xF] = if carry { 0 } else { 1 };
-        self.pc += 2;
+        self.advance(2)
     }
 
-    /// Shifts Vx to the right by 1, storing its least significant bit in
-    /// Vf before the shift
-    fn op_8xy6(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        self.v[0xF] = self.v[x] & 0x01;
-        self.v[x] >>= 1;
-        self.pc += 2;
+    /// Shifts Vx (or Vy with the shift quirk) to the right by 1, storing its
+    /// least significant bit in Vf before the shift
+    fn op_8xy6(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        let source = if self.quirks.shift_vy {
+            self.v[y]
+        } else {
+            self.v[x]
+        };
+        self.v[0xF] = source & 0x01;
+        self.v[x] = source >> 1;
+        self.advance(2)
     }
 
     /// Sets Vx = Vy - Vx and sets Vf to 0 if an underflow occurs, else 1
-    fn op_8xy7(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
+    fn op_8xy7(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
         let (result, carry) = self.v[y].overflowing_sub(self.v[x]);
         self.v[x] = result;
         self.v[0xF] = if carry { 0 } else { 1 };
-        self.pc += 2;
+        self.advance(2)
     }
 
-    /// Shifts Vx to the left by 1. If its most significant bit before the shift
-    /// was set, sets Vf to 1, else 0
-    fn op_8xye(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        self.v[0xF] = (self.v[x] & 0x80) >> 7;
-        self.v[x] <<= 1;
-        self.pc += 2;
+    /// Shifts Vx (or Vy with the shift quirk) to the left by 1. If its most
+    /// significant bit before the shift was set, sets Vf to 1, else 0
+    fn op_8xye(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        let source = if self.quirks.shift_vy {
+            self.v[y]
+        } else {
+            self.v[x]
+        };
+        self.v[0xF] = (source & 0x80) >> 7;
+        self.v[x] = source << 1;
+        self.advance(2)
     }
 
     /// Skips the next instruction if Vx != Vy
-    fn op_9xy0(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
-        if self.v[x] != self.v[y] {
-            self.pc += 4;
-        } else {
-            self.pc += 2;
-        }
+    fn op_9xy0(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        self.skip_if(self.v[x] != self.v[y])
     }
 
     /// Set I to the nnn address
-    fn op_annn(&mut self, opcode: u16) {
-        self.i = opcode & 0x0FFF;
-        self.pc += 2;
+    fn op_annn(&mut self, nnn: u16) -> Result<(), Chip8Error> {
+        self.i = nnn as u32;
+        self.advance(2)
     }
 
-    /// Jumps to the nnn address plus V0
-    fn op_bnnn(&mut self, opcode: u16) {
-        let nnn = opcode & 0x0FFF;
-        self.pc = nnn + self.v[0] as u16;
+    /// Jumps to the nnn address plus V0, or with the jump quirk to xnn plus Vx
+    fn op_bnnn(&mut self, x: usize, nnn: u16) -> Result<(), Chip8Error> {
+        let offset = if self.quirks.jump_vx {
+            self.v[x]
+        } else {
+            self.v[0]
+        };
+        self.pc = nnn + offset as u16;
+        Ok(())
     }
 
     /// Sets Vx to the result of kk & random number
-    fn op_cxkk(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let kk = (opcode & 0x00FF) as u8;
-        let r: u8 = rand::rng().random();
+    fn op_cxkk(&mut self, x: usize, kk: u8) -> Result<(), Chip8Error> {
+        let r: u8 = self.rng.random();
         self.v[x] = r & kk;
-        self.pc += 2;
+        self.advance(2)
     }
 
     /// Draws a sprite to the screen at (Vx, Vy), with a width of
     /// 8 pixels and a height of n pixels.
     /// Sets Vf to 1 when there is a collision with existing screen pixels, or
     /// it sets it to 0 if there isn't.
-    fn op_dxyn(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
-        let height = (opcode & 0x00F) as usize;
+    fn op_dxyn(&mut self, x: usize, y: usize, height: usize) -> Result<(), Chip8Error> {
+        // The starting position always wraps, the sprite itself may be clipped
+        let vx = self.v[x] as usize % self.display_width();
+        let vy = self.v[y] as usize % self.display_height();
+        self.v[0xF] = 0;
+
+        for row in 0..height {
+            let sprite = self.read_memory(self.i as usize + row)? as u16;
+            self.draw_sprite_row(vx, vy + row, sprite << 8, 8);
+        }
 
-        let vx = self.v[x] as usize;
-        let vy = self.v[y] as usize;
+        self.vblank_wait = self.quirks.display_wait;
+        self.advance(2)
+    }
 
+    /// Draws a 16x16 sprite to the screen at (Vx, Vy), reading two bytes per
+    /// row starting at the address of I.
+    /// Sets Vf the same way as DXYN
+    fn op_dxy0(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        // The starting position always wraps, the sprite itself may be clipped
+        let vx = self.v[x] as usize % self.display_width();
+        let vy = self.v[y] as usize % self.display_height();
         self.v[0xF] = 0;
 
-        for row in 0..height {
-            let sprite = self.memory[self.i as usize + row];
-            for col in 0..8 {
-                if (sprite & (0x80 >> col)) != 0 {
-                    let pixel_index = (vx + col + (vy + row) * 64) % (32 * 64);
-                    if self.display[pixel_index] {
-                        self.v[0xF] = 1;
-                    }
-                    self.display[pixel_index] ^= true;
+        for row in 0..16 {
+            let address = self.i as usize + row * 2;
+            let hi = self.read_memory(address)? as u16;
+            let lo = self.read_memory(address + 1)? as u16;
+            self.draw_sprite_row(vx, vy + row, (hi << 8) | lo, 16);
+        }
+
+        self.vblank_wait = self.quirks.display_wait;
+        self.advance(2)
+    }
+
+    /// XORs the `width` most significant bits of `bits` onto the display
+    /// starting at (x, y), wrapping around or clipping at the edges of the
+    /// screen depending on the clip quirk
+    fn draw_sprite_row(&mut self, x: usize, y: usize, bits: u16, width: usize) {
+        let display_width = self.display_width();
+        let display_height = self.display_height();
+
+        if self.quirks.clip_sprites && y >= display_height {
+            return;
+        }
+
+        for col in 0..width {
+            if self.quirks.clip_sprites && x + col >= display_width {
+                break;
+            }
+
+            if (bits & (0x8000 >> col)) != 0 {
+                let px = (x + col) % display_width;
+                let py = y % display_height;
+                let pixel_index = py * display_width + px;
+                if self.display[pixel_index] {
+                    self.v[0xF] = 1;
                 }
+                self.display[pixel_index] ^= true;
             }
         }
-
-        self.pc += 2;
     }
 
     /// Skips the next instruction if the key stored in Vx is pressed
-    fn op_ex9e(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let key = self.v[x] as usize;
-        if self.keypad[key] {
-            self.pc += 4;
-        } else {
-            self.pc += 2;
-        }
+    fn op_ex9e(&mut self, x: usize) -> Result<(), Chip8Error> {
+        let key = (self.v[x] & 0x0F) as usize;
+        self.polled_keys |= 1 << key;
+        self.skip_if(self.keypad[key])
     }
 
     /// Skips the next instruction if the key stored in Vx is not pressed
-    fn op_exa1(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let key = self.v[x] as usize;
-        if !self.keypad[key] {
-            self.pc += 4;
-        } else {
-            self.pc += 2;
+    fn op_exa1(&mut self, x: usize) -> Result<(), Chip8Error> {
+        let key = (self.v[x] & 0x0F) as usize;
+        self.polled_keys |= 1 << key;
+        self.skip_if(!self.keypad[key])
+    }
+
+    /// Loads the 16 bytes at I into the audio pattern
+    fn op_f002(&mut self) -> Result<(), Chip8Error> {
+        let mut pattern = [0; 16];
+        for (offset, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.read_memory(self.i as usize + offset)?;
         }
+        self.audio_pattern = Some(pattern);
+        self.advance(2)
     }
 
     /// Sets Vx to the value of delay timer
-    fn op_fx07(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    fn op_fx07(&mut self, x: usize) -> Result<(), Chip8Error> {
         self.v[x] = self.delay_timer;
-        self.pc += 2;
+        self.advance(2)
     }
 
-    /// Waits for a key press and stores the value in Vx
-    fn op_fx0a(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-
+    /// Waits for a key press, or a press and release with the quirk, and
+    /// stores the key in Vx
+    fn op_fx0a(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.polled_keys = u16::MAX;
         // Returns the index of the first key inside keypad that is pressed
-        let key_pressed = self.keypad.iter().position(|&k| k);
+        let key_pressed = self.keypad.iter().position(|&k| k).map(|key| key as u8);
 
-        match key_pressed {
-            Some(key) => {
-                self.v[x] = key as u8;
-                self.pc += 2;
+        // The PC is left untouched until the wait is over, so the same
+        // instruction runs again
+        let key = if self.quirks.wait_for_release {
+            match self.pressed_key {
+                Some(key) if !self.keypad[key as usize] => self.pressed_key.take(),
+                Some(_) => None,
+                None => {
+                    self.pressed_key = key_pressed;
+                    None
+                }
             }
-            None => self.pc -= 2, // Run the same instruction again until something is pressed
+        } else {
+            key_pressed
+        };
+        if let Some(key) = key {
+            self.v[x] = key;
+            self.advance(2)?;
         }
+        Ok(())
     }
 
     /// Sets the delay timer to the value of Vx
-    fn op_fx15(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    fn op_fx15(&mut self, x: usize) -> Result<(), Chip8Error> {
         self.delay_timer = self.v[x];
-        self.pc += 2;
+        self.advance(2)
     }
 
     /// Sets the sound timer to the value of Vx
-    fn op_fx18(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    fn op_fx18(&mut self, x: usize) -> Result<(), Chip8Error> {
         self.sound_timer = self.v[x];
-        self.pc += 2;
+        self.advance(2)
     }
 
     /// Adds Vx to I
-    fn op_fx1e(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        self.i = self.i.wrapping_add(self.v[x] as u16);
-        self.pc += 2;
+    fn op_fx1e(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.i = self.i.wrapping_add(self.v[x] as u32) & self.index_mask();
+        self.advance(2)
     }
 
     /// Sets I to the location of the sprite for the character in Vx
-    fn op_fx29(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        self.i += self.v[x] as u16 * 5;
-        self.pc += 2;
+    fn op_fx29(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.i = (self.v[x] & 0x0F) as u32 * 5;
+        self.advance(2)
     }
 
-    ///
-    fn op_fx33(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
+    /// Sets I to the location of the 8x10 sprite for the digit in Vx
+    fn op_fx30(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.i = (FONT_SIZE + (self.v[x] & 0x0F) as usize * 10) as u32;
+        self.advance(2)
+    }
+
+    /// Stores the binary-coded decimal representation of Vx at the addresses
+    /// I, I + 1 and I + 2
+    fn op_fx33(&mut self, x: usize) -> Result<(), Chip8Error> {
         let vx = self.v[x];
 
-        self.memory[self.i as usize] = vx / 100;
-        self.memory[(self.i + 1) as usize] = (vx % 100) / 10;
-        self.memory[(self.i + 2) as usize] = vx % 10;
-        self.pc += 2;
+        let i = self.i as usize;
+
+        self.write_memory(i, vx / 100)?;
+        self.write_memory(i + 1, (vx % 100) / 10)?;
+        self.write_memory(i + 2, vx % 10)?;
+        self.advance(2)
+    }
+
+    /// Sets the pitch of the audio pattern to Vx
+    fn op_fx3a(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.audio_pitch = self.v[x];
+        self.advance(2)
     }
 
     /// Stores all registers from 0 to x (inclusive) starting at the address
     /// of I
-    fn op_fx55(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-
+    fn op_fx55(&mut self, x: usize) -> Result<(), Chip8Error> {
         for index in 0..=x {
-            self.memory[self.i as usize + index] = self.v[index];
+            self.write_memory(self.i as usize + index, self.v[index])?;
         }
-        self.pc += 2;
+        self.increment_index(x);
+        self.advance(2)
     }
 
     /// Fills registers V0 to Vx (inclusive) from memory starting at the address
     /// of I
-    fn op_fx65(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-
+    fn op_fx65(&mut self, x: usize) -> Result<(), Chip8Error> {
         for index in 0..=x {
-            self.v[index] = self.memory[self.i as usize + index];
+            self.v[index] = self.read_memory(self.i as usize + index)?;
         }
-        self.pc += 2;
+        self.increment_index(x);
+        self.advance(2)
+    }
+
+    /// Stores registers V0 to Vx (inclusive) in the RPL user flags
+    fn op_fx75(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+        self.advance(2)
+    }
+
+    /// Fills registers V0 to Vx (inclusive) from the RPL user flags
+    fn op_fx85(&mut self, x: usize) -> Result<(), Chip8Error> {
+        self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+        self.advance(2)
     }
 }
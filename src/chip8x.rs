@@ -0,0 +1,171 @@
+//! CHIP-8X, RCA's interpreter for the VP-590 color board and the VP-595
+//! sound board. Programs start at 0x300 and the display stays 64x32 and one
+//! bit per pixel, but each 8x1 strip of it has its own foreground color and
+//! the background can be switched between four colors. It's only recognized
+//! with the `chip8x` quirk.
+//!
+//! 02A0 cycles the background color and BXYN colors an area, so BNNN jumps
+//! and MegaChip's LDPAL 0xA0 are lost. A second keypad is read by EXF2 and
+//! EXF5, and FXF8 sets the pitch of the tone, which frontends don't play yet.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    chip8::{Chip8, LORES_HEIGHT, LORES_WIDTH},
+    decode::Instruction,
+    error::Chip8Error,
+};
+
+/// Programs are loaded here, the interpreter itself took up the page before
+pub const CHIP8X_PROGRAM_START: u16 = 0x300;
+
+/// Foreground colors are set for strips this many pixels wide
+const ZONE_WIDTH: usize = LORES_WIDTH / 8;
+/// BXY0 colors zones this many pixels high
+const ZONE_HEIGHT: usize = 4;
+const COLUMNS: usize = LORES_WIDTH / ZONE_WIDTH;
+
+/// The VP-590's colors as 0xAARRGGBB, indexed by the three bits BXYN takes
+const FOREGROUND_COLORS: [u32; 8] = [
+    0xFF00_0000, // black
+    0xFFFF_0000, // red
+    0xFF00_00FF, // blue
+    0xFFFF_00FF, // violet
+    0xFF00_FF00, // green
+    0xFFFF_FF00, // yellow
+    0xFF00_FFFF, // aqua
+    0xFFFF_FFFF, // white
+];
+
+/// The background colors 02A0 cycles through
+const BACKGROUND_COLORS: [u32; 4] = [
+    0xFF00_0080, // blue
+    0xFF00_0000, // black
+    0xFF00_8000, // green
+    0xFF80_0000, // red
+];
+
+/// Every strip starts out red
+const DEFAULT_FOREGROUND: u8 = 1;
+
+/// The state of the color and sound boards
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Chip8X {
+    /// Index into `BACKGROUND_COLORS`
+    pub(crate) background: u8,
+    /// Foreground color of each 8x1 strip, row by row
+    pub(crate) zones: Vec<u8>,
+    /// Set by FXF8, the tone is 27535 / (pitch + 1) Hz
+    pub(crate) pitch: u8,
+}
+
+impl Chip8X {
+    pub(crate) const ZONES: usize = COLUMNS * LORES_HEIGHT;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            background: 0,
+            zones: vec![DEFAULT_FOREGROUND; Self::ZONES],
+            pitch: 0x80,
+        }
+    }
+
+    /// Whether a deserialized state has a color for every strip and a valid
+    /// background
+    #[cfg(feature = "serde")]
+    pub(crate) fn is_valid(&self) -> bool {
+        self.zones.len() == Self::ZONES && (self.background as usize) < BACKGROUND_COLORS.len()
+    }
+
+    /// The display in color. `pixels` can be either resolution, high
+    /// resolution pixels take the color of the strip they fall in
+    pub(crate) fn colorize(&self, pixels: &[bool], width: usize, height: usize) -> Vec<u32> {
+        let background = BACKGROUND_COLORS[self.background as usize % BACKGROUND_COLORS.len()];
+        pixels
+            .iter()
+            .enumerate()
+            .map(|(index, &on)| {
+                if !on {
+                    return background;
+                }
+                let column = index % width * LORES_WIDTH / width / ZONE_WIDTH;
+                let row = index / width * LORES_HEIGHT / height;
+                FOREGROUND_COLORS[self.zones[row * COLUMNS + column] as usize & 7]
+            })
+            .collect()
+    }
+
+    /// Colors the strips from `left` to `right` and `top` to `bottom`,
+    /// clamped to the screen
+    fn fill(&mut self, columns: (usize, usize), rows: (usize, usize), color: u8) {
+        for row in rows.0..=rows.1.min(LORES_HEIGHT - 1) {
+            for column in columns.0..=columns.1.min(COLUMNS - 1) {
+                self.zones[row * COLUMNS + column] = color & 7;
+            }
+        }
+    }
+}
+
+impl Chip8 {
+    /// Runs the instructions CHIP-8X adds or changes, which are unknown to
+    /// other interpreters
+    pub(crate) fn execute_chip8x(
+        &mut self,
+        instruction: Instruction,
+        opcode: u16,
+    ) -> Result<(), Chip8Error> {
+        let Some(chip8x) = &mut self.chip8x else {
+            return self.unknown_opcode(opcode);
+        };
+
+        match instruction {
+            // 02A0
+            Instruction::LoadPalette { .. } => {
+                chip8x.background = (chip8x.background + 1) % BACKGROUND_COLORS.len() as u8;
+            }
+            Instruction::AddNibbles { x, y } => {
+                let (vx, vy) = (self.v[x as usize], self.v[y as usize]);
+                let high = (vx & 0xF0).wrapping_add(vy & 0xF0) & 0x70;
+                let low = ((vx & 0x0F) + (vy & 0x0F)) & 0x07;
+                self.v[x as usize] = high | low;
+            }
+            // BXY0 colors zones of 8x4 pixels: VX holds the first column in
+            // its low nibble and how many more to color in its high one,
+            // VX+1 the same for rows. BXYN colors N rows of the strip at
+            // the pixel coordinates in VX and VX+1
+            Instruction::JumpOffset { x, nnn } => {
+                let (y, n) = ((nnn >> 4 & 0xF) as usize, (nnn & 0xF) as usize);
+                let horizontal = self.v[x as usize] as usize;
+                let vertical = self.v[(x as usize + 1) & 0xF] as usize;
+                let color = self.v[y];
+                if n == 0 {
+                    let (left, top) = (horizontal & 0xF, vertical & 0xF);
+                    chip8x.fill(
+                        (left, left + (horizontal >> 4)),
+                        (
+                            top * ZONE_HEIGHT,
+                            (top + (vertical >> 4) + 1) * ZONE_HEIGHT - 1,
+                        ),
+                        color,
+                    );
+                } else {
+                    let column = horizontal % LORES_WIDTH / ZONE_WIDTH;
+                    let top = vertical % LORES_HEIGHT;
+                    chip8x.fill((column, column), (top, top + n - 1), color);
+                }
+            }
+            Instruction::SkipKey2 { x } | Instruction::SkipNotKey2 { x } => {
+                let pressed = self.second_keypad[(self.v[x as usize] & 0x0F) as usize];
+                if pressed == matches!(instruction, Instruction::SkipKey2 { .. }) {
+                    self.advance(2)?;
+                }
+            }
+            Instruction::SetPitch { x } => chip8x.pitch = self.v[x as usize],
+            // Nothing is connected to the input port
+            Instruction::ReadPort { x } => self.v[x as usize] = 0,
+            _ => unreachable!("only called for CHIP-8X instructions"),
+        }
+        self.advance(2)
+    }
+}
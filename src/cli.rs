@@ -0,0 +1,570 @@
+use std::path::PathBuf;
+
+use chip_8_rs::{
+    Quirks, UnknownOpcodes,
+    debug::{Condition, WatchKind, WatchTarget, Watchpoint},
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+#[cfg(feature = "builtin-roms")]
+use crate::builtin::Builtin;
+use crate::{
+    INSTRUCTIONS_PER_FRAME,
+    palette::{Color, PaletteName, parse_hex_color},
+};
+
+#[derive(Parser)]
+#[command(name = "chip8-rs", version, about = "A CHIP-8 and SUPER-CHIP emulator")]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+impl Cli {
+    /// The subcommand to execute, `run` when none was given
+    pub fn into_command(self) -> Command {
+        self.command
+            .unwrap_or_else(|| Command::Run(Box::new(self.run)))
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Runs a ROM (the default when no subcommand is given)
+    Run(Box<RunArgs>),
+    /// Prints the instructions of a ROM
+    Disasm(DisasmArgs),
+    /// Assembles Octo source into a ROM
+    Asm(AsmArgs),
+    /// Prints the registers before every instruction, or compares them with
+    /// a trace from another emulator
+    Trace(TraceArgs),
+    /// Runs a ROM or a saved snapshot without a window and prints the state
+    /// as JSON
+    #[cfg(feature = "json")]
+    DumpState(DumpStateArgs),
+    /// Runs a ROM as fast as possible without a window and reports how many
+    /// instructions and frames it ran per second
+    Bench(BenchArgs),
+    /// Runs a ROM for a number of instructions without a window, then
+    /// prints the display as text or writes it as a PBM image
+    Screen(ScreenArgs),
+}
+
+#[derive(Args)]
+pub struct ScreenArgs {
+    /// Path to the ROM to run, or `-` to read it from stdin
+    pub rom: PathBuf,
+
+    /// Number of instructions to execute
+    #[arg(long, default_value_t = 1000)]
+    pub cycles: u64,
+
+    /// Instructions executed per 60Hz frame, which decides when the timers
+    /// count down and the input changes
+    #[arg(long, default_value_t = INSTRUCTIONS_PER_FRAME, value_parser = clap::value_parser!(u32).range(1..))]
+    pub ipf: u32,
+
+    /// A movie file with the keys to press, recorded with --record or
+    /// written by hand. Its seed is used for CXKK
+    #[arg(long, value_name = "FILE", conflicts_with = "seed")]
+    pub input: Option<PathBuf>,
+
+    /// Write the display to this file as a PBM image instead of printing it
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Interpreter quirks to emulate, detected for known ROMs by default
+    #[arg(long, visible_alias = "platform", value_enum)]
+    pub quirks: Option<QuirkPreset>,
+
+    /// Seed for the random numbers of CXKK
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Path to the ROM to run, or `-` to read it from stdin
+    pub rom: PathBuf,
+
+    /// How long to run for
+    #[arg(long, default_value_t = 5.0, value_parser = parse_seconds)]
+    pub seconds: f64,
+
+    /// Instructions executed per 60Hz frame
+    #[arg(long, default_value_t = INSTRUCTIONS_PER_FRAME, value_parser = clap::value_parser!(u32).range(1..))]
+    pub ipf: u32,
+
+    /// Interpreter quirks to emulate, detected for known ROMs by default
+    #[arg(long, visible_alias = "platform", value_enum)]
+    pub quirks: Option<QuirkPreset>,
+
+    /// Seed for the random numbers of CXKK, fixed so runs can be compared
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Run on the experimental block-compiling backend instead of the
+    /// interpreter
+    #[arg(long)]
+    pub jit: bool,
+}
+
+fn parse_seconds(value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|&seconds: &f64| seconds > 0.0 && seconds.is_finite())
+        .ok_or(format!("{} isn't a positive number of seconds", value))
+}
+
+#[cfg(feature = "json")]
+#[derive(Args)]
+pub struct DumpStateArgs {
+    /// Path to the ROM to run, or `-` to read it from stdin
+    #[arg(required_unless_present = "state")]
+    pub rom: Option<PathBuf>,
+
+    /// Continue from a snapshot printed by an earlier dump-state instead of
+    /// a ROM
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["rom", "quirks"])]
+    pub state: Option<PathBuf>,
+
+    /// Number of frames to run before dumping
+    #[arg(long, default_value_t = 0)]
+    pub frames: u64,
+
+    /// Instructions executed per 60Hz frame
+    #[arg(long, default_value_t = INSTRUCTIONS_PER_FRAME, value_parser = clap::value_parser!(u32).range(1..))]
+    pub ipf: u32,
+
+    /// Interpreter quirks to emulate, detected for known ROMs by default
+    #[arg(long, visible_alias = "platform", value_enum)]
+    pub quirks: Option<QuirkPreset>,
+
+    /// Seed for the random numbers of CXKK
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct TraceArgs {
+    /// Path to the ROM to trace, or `-` to read it from stdin
+    pub rom: PathBuf,
+
+    /// Number of instructions to trace
+    #[arg(long, default_value_t = 1000)]
+    pub instructions: u64,
+
+    /// A reference trace to compare with, one instruction per line. Reports
+    /// the first instruction where a register differs
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["instructions", "output"])]
+    pub compare: Option<PathBuf>,
+
+    /// Where to write the trace, defaults to stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Instructions executed per 60Hz frame, which decides when the timers
+    /// count down
+    #[arg(long, default_value_t = INSTRUCTIONS_PER_FRAME, value_parser = clap::value_parser!(u32).range(1..))]
+    pub ipf: u32,
+
+    /// Interpreter quirks to emulate, detected for known ROMs by default
+    #[arg(long, visible_alias = "platform", value_enum)]
+    pub quirks: Option<QuirkPreset>,
+
+    /// Seed for the random numbers of CXKK
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct AsmArgs {
+    /// Path to the `.8o` source file
+    pub source: PathBuf,
+
+    /// Where to write the ROM, defaults to the source path with a `.ch8`
+    /// extension
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Also write the labels' addresses to `<rom>.sym`, where the debugger
+    /// and disassembler look for them
+    #[arg(long)]
+    pub symbols: bool,
+}
+
+#[derive(Args)]
+pub struct DisasmArgs {
+    /// Path to the ROM to disassemble, or `-` to read it from stdin
+    pub rom: PathBuf,
+
+    /// Decode every two bytes as an instruction instead of following the
+    /// code from the start and showing the rest as data
+    #[arg(long)]
+    pub linear: bool,
+
+    /// Symbol file naming addresses of the ROM, `<rom>.sym` by default
+    #[arg(long, value_name = "FILE")]
+    pub symbols: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// Path to the ROM to run, or `-` to read it from stdin. Opens a ROM
+    /// picker when left out
+    pub rom: Option<PathBuf>,
+
+    /// Run one of the demo ROMs compiled into the emulator instead
+    #[cfg(feature = "builtin-roms")]
+    #[arg(long, value_name = "NAME", conflicts_with = "rom")]
+    pub builtin: Option<Builtin>,
+
+    /// Initial size of a CHIP-8 pixel in window pixels
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub scale: u32,
+
+    /// Only scale the display by whole numbers when the window is resized
+    #[arg(long)]
+    pub integer_scale: bool,
+
+    /// Fade pixels out over a few frames to reduce flicker
+    #[arg(long)]
+    pub ghosting: bool,
+
+    /// Draw scanlines and a vignette like an old CRT monitor
+    #[arg(long)]
+    pub crt: bool,
+
+    /// Wait for the monitor's refresh before showing a frame, which stops
+    /// tearing. Games still run at 60 frames per second on faster monitors
+    #[arg(long)]
+    pub vsync: bool,
+
+    /// Color preset used to draw the display
+    #[arg(long, value_enum)]
+    pub palette: Option<PaletteName>,
+
+    /// Foreground color as #rrggbb, overrides the palette
+    #[arg(long, value_parser = parse_hex_color)]
+    pub fg: Option<Color>,
+
+    /// Background color as #rrggbb, overrides the palette
+    #[arg(long, value_parser = parse_hex_color)]
+    pub bg: Option<Color>,
+
+    /// Instructions executed per 60Hz frame [default: the speed last picked
+    /// for the ROM with +/-, or 10]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub ipf: Option<u32>,
+
+    /// Instructions executed per second, overrides --ipf
+    #[arg(long, conflicts_with = "ipf", value_parser = clap::value_parser!(u32).range(1..))]
+    pub speed: Option<u32>,
+
+    /// Interpreter quirks to emulate. By default they're picked for ROMs in
+    /// the built-in list or the --database and left off for the rest
+    #[arg(long, visible_alias = "platform", value_enum)]
+    pub quirks: Option<QuirkPreset>,
+
+    /// Apply an IPS or BPS patch to the ROM when loading it, the file
+    /// itself isn't changed. Can be given multiple times
+    #[arg(long = "patch", value_name = "FILE")]
+    pub patches: Vec<PathBuf>,
+
+    /// Replace the built-in hex digits with an 80-byte fontset, 5 rows for
+    /// each digit from 0 to F
+    #[arg(long, value_name = "FILE")]
+    pub font: Option<PathBuf>,
+
+    /// The programs.json of the CHIP-8 community database, for showing the
+    /// title, authors and recommended settings of known ROMs
+    #[arg(long, value_name = "FILE")]
+    pub database: Option<PathBuf>,
+
+    /// Seed for the random numbers of CXKK, making runs reproducible
+    #[arg(long, conflicts_with = "play")]
+    pub seed: Option<u64>,
+
+    /// Record the keypad and RNG seed into a movie file, saved on exit
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["play", "headless"])]
+    pub record: Option<PathBuf>,
+
+    /// Replay the keypad from a movie file made with --record. Use the same
+    /// ROM and quirks it was recorded with
+    #[arg(long, value_name = "FILE")]
+    pub play: Option<PathBuf>,
+
+    /// Wait for a second player to connect to this address, e.g.
+    /// 0.0.0.0:7800, and play together. The ROM has to be the same on both
+    /// sides, the host's quirks, speed and seed are used
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["connect", "play", "record", "headless", "debug", "breakpoints", "watchpoints"])]
+    pub host: Option<String>,
+
+    /// Run a second ROM next to the first in the same window, e.g. another
+    /// version of the game. Both machines get the keypad, sound comes from
+    /// the left one
+    #[arg(long, value_name = "ROM", conflicts_with_all = ["play", "record", "host", "connect", "headless"])]
+    pub side_by_side: Option<PathBuf>,
+
+    /// Run the ROM a second time next to the first with these quirks, to see
+    /// which quirks it depends on. Both machines get the same keypad and
+    /// random numbers
+    #[arg(long, value_name = "PRESET", value_enum, conflicts_with_all = ["side_by_side", "play", "record", "host", "connect", "headless"])]
+    pub compare_quirks: Option<QuirkPreset>,
+
+    /// With --side-by-side, send the keypad to one machine at a time, the `
+    /// key switches between them
+    #[arg(long, requires = "side_by_side")]
+    pub independent_input: bool,
+
+    /// Join a game started with --host, e.g. 192.168.1.20:7800
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["play", "record", "headless", "debug", "breakpoints", "watchpoints"])]
+    pub connect: Option<String>,
+
+    /// Pause when the PC reaches this address, e.g. 0x230, optionally only
+    /// if a condition holds, e.g. "0x230 if v[3] == 0x10 && dt == 0". Can be
+    /// given multiple times
+    #[arg(long = "break", value_name = "ADDRESS", value_parser = parse_breakpoint)]
+    pub breakpoints: Vec<(u16, Option<Condition>)>,
+
+    /// Pause before a register or memory address is accessed, e.g. v3 or
+    /// 0x300. Add :r to watch reads, :rw for both, writes are watched by
+    /// default. Can be given multiple times
+    #[arg(long = "watch", value_name = "TARGET", value_parser = parse_watchpoint)]
+    pub watchpoints: Vec<Watchpoint>,
+
+    /// Load a plugin from a dynamic library built with
+    /// chip_8_rs::declare_plugin!. Can be given multiple times
+    #[cfg(feature = "plugins")]
+    #[arg(long = "plugin", value_name = "FILE")]
+    pub plugins: Vec<PathBuf>,
+
+    /// Count how often each instruction and address runs and print the
+    /// busiest ones on exit or when F8 is pressed
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Symbol file naming addresses of the ROM for the debugger,
+    /// `<rom>.sym` by default
+    #[arg(long, value_name = "FILE")]
+    pub symbols: Option<PathBuf>,
+
+    /// Start paused with a debugger prompt in the terminal
+    #[arg(long, conflicts_with = "headless")]
+    pub debug: bool,
+
+    /// Start paused and accept Debug Adapter Protocol connections from an
+    /// editor on this address, e.g. 127.0.0.1:4711
+    #[arg(long, value_name = "ADDRESS")]
+    pub dap: Option<String>,
+
+    /// Run without creating a window or audio device
+    #[arg(long, conflicts_with = "frontend")]
+    pub headless: bool,
+
+    /// Stop after running this many frames, headless only
+    #[arg(long, requires = "headless")]
+    pub frames: Option<u64>,
+
+    /// Accept WebSocket connections on this address, e.g. 127.0.0.1:7900,
+    /// for pausing, inspecting and pressing keys from a client
+    #[cfg(feature = "remote")]
+    #[arg(long, value_name = "ADDRESS", requires = "headless")]
+    pub remote: Option<String>,
+
+    /// Don't limit a headless run to 60 frames per second
+    #[arg(long, requires = "headless")]
+    pub unthrottled: bool,
+
+    /// What to do with opcodes that aren't part of the instruction set
+    #[arg(long, value_enum, default_value_t = UnknownOpcodeMode::Halt)]
+    pub unknown_opcodes: UnknownOpcodeMode,
+
+    /// Run a headless run on the experimental block-compiling backend
+    /// instead of the interpreter. Mostly useful together with --unthrottled
+    #[arg(long, requires = "headless")]
+    pub jit: bool,
+
+    /// Where to draw the display and read input from
+    #[arg(long, value_enum, default_value_t = Frontend::default())]
+    pub frontend: Frontend,
+
+    /// Two-player key profile from the config, instead of the one its
+    /// rom_profiles set for the ROM
+    #[arg(long, value_name = "PROFILE")]
+    pub keys: Option<String>,
+
+    /// Start with the sound off, M turns it back on. The sound timer keeps
+    /// running either way
+    #[arg(long)]
+    pub mute: bool,
+
+    /// Directory screenshots taken with F12 are saved to
+    #[arg(long, default_value = ".")]
+    pub screenshot_dir: PathBuf,
+
+    /// Size of a CHIP-8 pixel in screenshots
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub screenshot_scale: u32,
+
+    /// Path to the config file, defaults to chip8-rs/config.toml in the
+    /// user's config directory
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Which messages are shown, a level for all of them and levels for
+    /// modules, e.g. `warn,netplay=debug`. Levels are off, error, warn,
+    /// info, debug and trace
+    #[arg(long, value_name = "SPEC")]
+    pub log: Option<String>,
+
+    /// A file the messages are appended to as well
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+}
+
+impl RunArgs {
+    /// Whether a second machine runs next to the first
+    pub fn split_screen(&self) -> bool {
+        self.side_by_side.is_some() || self.compare_quirks.is_some()
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.speed_arg().unwrap_or(INSTRUCTIONS_PER_FRAME)
+    }
+
+    /// Instructions per frame from --ipf or --speed, when either was given
+    pub fn speed_arg(&self) -> Option<u32> {
+        match self.speed {
+            // Spread evenly across 60 frames
+            Some(hz) => Some((hz / 60).max(1)),
+            None => self.ipf,
+        }
+    }
+}
+
+/// The first windowed frontend compiled in is the default
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Frontend {
+    /// A window with sound
+    #[cfg(feature = "sdl")]
+    #[default]
+    Sdl,
+    /// A window drawn with wgpu, without sound
+    #[cfg(feature = "pixels")]
+    #[cfg_attr(not(feature = "sdl"), default)]
+    Pixels,
+    /// The terminal, using block characters
+    #[cfg_attr(not(any(feature = "sdl", feature = "pixels")), default)]
+    Tui,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum QuirkPreset {
+    /// No quirks, the emulator's historical behavior
+    Default,
+    /// The original COSMAC VIP CHIP-8 interpreter
+    Chip8,
+    /// CHIP-48 on the HP-48 calculators, which many classic games were
+    /// written for
+    Chip48,
+    /// SUPER-CHIP 1.1
+    Schip,
+    /// MegaChip8, with a 256x192 color display
+    Megachip,
+    /// CHIP-8X, with colors and a second keypad
+    Chip8x,
+}
+
+impl QuirkPreset {
+    /// The preset with exactly these quirks, if there is one
+    pub fn matching(quirks: Quirks) -> Option<Self> {
+        Self::value_variants()
+            .iter()
+            .copied()
+            .find(|&preset| Quirks::from(preset) == quirks)
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum UnknownOpcodeMode {
+    /// Stop emulation with an error
+    Halt,
+    /// Skip them silently
+    Ignore,
+    /// Skip them and print where they were
+    Warn,
+}
+
+impl From<UnknownOpcodeMode> for UnknownOpcodes {
+    fn from(mode: UnknownOpcodeMode) -> Self {
+        match mode {
+            UnknownOpcodeMode::Halt => UnknownOpcodes::Halt,
+            UnknownOpcodeMode::Ignore => UnknownOpcodes::Ignore,
+            UnknownOpcodeMode::Warn => UnknownOpcodes::Warn,
+        }
+    }
+}
+
+impl From<QuirkPreset> for Quirks {
+    fn from(preset: QuirkPreset) -> Self {
+        match preset {
+            QuirkPreset::Default => Quirks::default(),
+            QuirkPreset::Chip8 => Quirks::CHIP8,
+            QuirkPreset::Chip48 => Quirks::CHIP48,
+            QuirkPreset::Schip => Quirks::SCHIP,
+            QuirkPreset::Megachip => Quirks::MEGACHIP,
+            QuirkPreset::Chip8x => Quirks::CHIP8X,
+        }
+    }
+}
+
+/// Parses a hex address, with or without the leading `0x`
+pub fn parse_address(value: &str) -> Result<u16, String> {
+    let hex = value.strip_prefix("0x").unwrap_or(value);
+    match u16::from_str_radix(hex, 16) {
+        Ok(address) if address < 0x1000 => Ok(address),
+        _ => Err(format!("Invalid address {}, expected 0x000-0xFFF", value)),
+    }
+}
+
+/// Parses an address, optionally followed by `if` and a condition
+pub fn parse_breakpoint(value: &str) -> Result<(u16, Option<Condition>), String> {
+    let (address, condition) = match value.split_once(" if ") {
+        Some((address, condition)) => (address, Some(condition)),
+        None => (value, None),
+    };
+    let condition = condition
+        .map(str::parse)
+        .transpose()
+        .map_err(|e| format!("Invalid condition: {}", e))?;
+    Ok((parse_address(address.trim())?, condition))
+}
+
+/// Parses `v3`, `0x300`, `v3:r`, `0x300:rw` and so on
+pub fn parse_watchpoint(value: &str) -> Result<Watchpoint, String> {
+    let (target, kind) = match value.split_once(':') {
+        Some((target, "r")) => (target, WatchKind::Read),
+        Some((target, "w")) => (target, WatchKind::Write),
+        Some((target, "rw")) => (target, WatchKind::ReadWrite),
+        Some(_) => return Err(format!("Invalid access in {}, expected r, w or rw", value)),
+        None => (value, WatchKind::Write),
+    };
+
+    let register = target
+        .strip_prefix(['v', 'V'])
+        .filter(|digit| digit.len() == 1)
+        .and_then(|digit| u8::from_str_radix(digit, 16).ok());
+    let target = match register {
+        Some(index) => WatchTarget::Register(index),
+        None => WatchTarget::Memory(parse_address(target)?),
+    };
+
+    Ok(Watchpoint { target, kind })
+}
@@ -0,0 +1,400 @@
+//! Conditions of breakpoints, small expressions over the machine's state
+//! like `v[3] == 0x10 && dt == 0`. They're evaluated each time the PC
+//! reaches the breakpoint's address, which only stops when it holds.
+//!
+//! Values are `v[N]` or `vN` for the registers, `i`, `pc`, `dt`, `st`, `sp`
+//! for the stack depth, `m[ADDR]` for a byte of memory and `key[N]`, which is
+//! 1 while the key is held. Numbers are decimal, `0x` hex or `0b` binary.
+//! They're combined with `+`, `-` and `&`, then compared with `==`, `!=`,
+//! `<`, `<=`, `>` and `>=`, and the results joined with `!`, `&&` and `||`.
+//! Parentheses group. Any value that isn't 0 counts as true
+//!
+//! ```
+//! use chip_8_rs::debug::Condition;
+//!
+//! let condition: Condition = "v[3] == 0x10 && dt == 0".parse().unwrap();
+//! assert_eq!(condition.to_string(), "v[3] == 0x10 && dt == 0");
+//! ```
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{error::Error, fmt, str::FromStr};
+
+use crate::chip8::Chip8;
+
+/// Why a condition couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionError(pub String);
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for ConditionError {}
+
+/// A parsed condition, shown the way it was written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    /// Whether the condition holds for the machine's current state
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        self.expr.eval(chip8) != 0
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(source: &str) -> Result<Self, ConditionError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            depth: 0,
+            operators: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(ConditionError(format!("Unexpected {}", token)));
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    LogicalAnd,
+    LogicalOr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Number(i64),
+    Register(u8),
+    Index,
+    Pc,
+    DelayTimer,
+    SoundTimer,
+    StackDepth,
+    Memory(Box<Expr>),
+    Key(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, chip8: &Chip8) -> i64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Register(index) => chip8.registers()[*index as usize] as i64,
+            Expr::Index => chip8.index() as i64,
+            Expr::Pc => chip8.pc() as i64,
+            Expr::DelayTimer => chip8.delay_timer() as i64,
+            Expr::SoundTimer => chip8.sound_timer() as i64,
+            Expr::StackDepth => chip8.stack().len() as i64,
+            // Addresses outside memory read as 0
+            Expr::Memory(address) => usize::try_from(address.eval(chip8))
+                .ok()
+                .and_then(|address| chip8.memory().get(address))
+                .map_or(0, |&byte| byte as i64),
+            Expr::Key(key) => usize::try_from(key.eval(chip8))
+                .ok()
+                .and_then(|key| chip8.keypad().get(key))
+                .is_some_and(|&held| held) as i64,
+            Expr::Not(expr) => (expr.eval(chip8) == 0) as i64,
+            Expr::Binary(Op::LogicalAnd, left, right) => {
+                (left.eval(chip8) != 0 && right.eval(chip8) != 0) as i64
+            }
+            Expr::Binary(Op::LogicalOr, left, right) => {
+                (left.eval(chip8) != 0 || right.eval(chip8) != 0) as i64
+            }
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(chip8), right.eval(chip8));
+                match op {
+                    Op::Add => left.wrapping_add(right),
+                    Op::Sub => left.wrapping_sub(right),
+                    Op::And => left & right,
+                    Op::Eq => (left == right) as i64,
+                    Op::Ne => (left != right) as i64,
+                    Op::Lt => (left < right) as i64,
+                    Op::Le => (left <= right) as i64,
+                    Op::Gt => (left > right) as i64,
+                    Op::Ge => (left >= right) as i64,
+                    Op::LogicalAnd | Op::LogicalOr => unreachable!("short-circuited above"),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Name(name) => f.write_str(name),
+            Token::Symbol(symbol) => f.write_str(symbol),
+        }
+    }
+}
+
+/// Longest first, so `<=` isn't read as `<` and `=`
+const SYMBOLS: [&str; 16] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "+", "-", "&", "(", ")", "[", "]",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ConditionError> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_alphanumeric() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            tokens.push(match parse_number(word) {
+                Some(value) => Token::Number(value),
+                None if c.is_ascii_digit() => {
+                    return Err(ConditionError(format!("Invalid number {}", word)));
+                }
+                None => Token::Name(word.to_ascii_lowercase()),
+            });
+            len
+        } else {
+            let symbol = SYMBOLS
+                .into_iter()
+                .find(|symbol| rest.starts_with(symbol))
+                .ok_or_else(|| ConditionError(format!("Unexpected {}", c)))?;
+            tokens.push(Token::Symbol(symbol));
+            symbol.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    if let Some(hex) = text.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/// How deeply parentheses, brackets and `!` can nest. Conditions come from
+/// the network with the debug adapter, and each level is a few calls deeper
+/// on the stack
+const MAX_DEPTH: usize = 64;
+
+/// How many binary operators a condition can have. A chain like `1 + 1 + 1`
+/// nests to the left without any parentheses, and evaluating it recurses
+/// once per operator
+const MAX_OPERATORS: usize = 256;
+
+/// Recursive descent, one method per precedence level from the loosest
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// How many groups and `!` the parser is inside of
+    depth: usize,
+    /// How many binary operators were parsed so far
+    operators: usize,
+}
+
+impl Parser {
+    fn or(&mut self) -> Result<Expr, ConditionError> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            let right = self.and()?;
+            expr = self.binary(Op::LogicalOr, expr, right)?;
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ConditionError> {
+        let mut expr = self.comparison()?;
+        while self.eat("&&") {
+            let right = self.comparison()?;
+            expr = self.binary(Op::LogicalAnd, expr, right)?;
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ConditionError> {
+        let left = self.arithmetic()?;
+        let op = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ]
+        .into_iter()
+        .find_map(|(symbol, op)| self.eat(symbol).then_some(op));
+        match op {
+            Some(op) => {
+                let right = self.arithmetic()?;
+                self.binary(op, left, right)
+            }
+            None => Ok(left),
+        }
+    }
+
+    /// `+`, `-` and `&` are applied left to right
+    fn arithmetic(&mut self) -> Result<Expr, ConditionError> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = if self.eat("+") {
+                Op::Add
+            } else if self.eat("-") {
+                Op::Sub
+            } else if self.eat("&") {
+                Op::And
+            } else {
+                return Ok(expr);
+            };
+            let right = self.unary()?;
+            expr = self.binary(op, expr, right)?;
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ConditionError> {
+        if self.eat("!") {
+            return self.nested(|parser| Ok(Expr::Not(Box::new(parser.unary()?))));
+        }
+        if self.eat("(") {
+            let expr = self.nested(Self::or)?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        match token {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Name(name)) => self.value(&name),
+            Some(token) => Err(ConditionError(format!("Unexpected {}", token))),
+            None => Err(ConditionError("Unexpected end of condition".to_string())),
+        }
+    }
+
+    fn value(&mut self, name: &str) -> Result<Expr, ConditionError> {
+        match name {
+            "i" => return Ok(Expr::Index),
+            "pc" => return Ok(Expr::Pc),
+            "dt" => return Ok(Expr::DelayTimer),
+            "st" => return Ok(Expr::SoundTimer),
+            "sp" => return Ok(Expr::StackDepth),
+            "v" => {
+                let index = self.subscript()?;
+                return match index {
+                    Expr::Number(index @ 0..=15) => Ok(Expr::Register(index as u8)),
+                    _ => Err(ConditionError("Registers are v[0] to v[15]".to_string())),
+                };
+            }
+            "m" => return Ok(Expr::Memory(Box::new(self.subscript()?))),
+            "key" => return Ok(Expr::Key(Box::new(self.subscript()?))),
+            _ => {}
+        }
+        // v0 to vf
+        match name
+            .strip_prefix('v')
+            .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+        {
+            Some(index) if name.len() == 2 => Ok(Expr::Register(index)),
+            _ => Err(ConditionError(format!(
+                "Unknown value {}, expected v[N], i, pc, dt, st, sp, m[ADDR] or key[N]",
+                name
+            ))),
+        }
+    }
+
+    fn subscript(&mut self) -> Result<Expr, ConditionError> {
+        self.expect("[")?;
+        let expr = self.nested(Self::or)?;
+        self.expect("]")?;
+        Ok(expr)
+    }
+
+    /// Joins `left` and `right` with `op`, failing past `MAX_OPERATORS`
+    fn binary(&mut self, op: Op, left: Expr, right: Expr) -> Result<Expr, ConditionError> {
+        if self.operators == MAX_OPERATORS {
+            return Err(ConditionError(format!(
+                "More than {} operators",
+                MAX_OPERATORS
+            )));
+        }
+        self.operators += 1;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    /// Parses one level deeper, failing past `MAX_DEPTH`
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expr, ConditionError>,
+    ) -> Result<Expr, ConditionError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ConditionError(format!(
+                "Nested more than {} levels deep",
+                MAX_DEPTH
+            )));
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found =
+            matches!(self.tokens.get(self.position), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ConditionError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(ConditionError(format!("Expected {}", symbol)))
+        }
+    }
+}
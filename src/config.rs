@@ -0,0 +1,232 @@
+//! Settings loaded from a TOML file, e.g.
+//!
+//! ```toml
+//! # Where the ROM picker starts when no ROM is given on the command line,
+//! # and the directory tree its library (Tab) lists the ROMs of
+//! rom_dir = "/home/me/roms"
+//!
+//! # programs.json from the CHIP-8 community database
+//! database = "/home/me/chip-8-database/database/programs.json"
+//!
+//! # green, amber, lcd or white, optionally with custom colors on top
+//! palette = "amber"
+//! foreground = "#ffcc00"
+//! background = "#000000"
+//!
+//! # Fade pixels out over a few frames to reduce flicker
+//! ghosting = true
+//!
+//! # Scanlines and a vignette like an old CRT monitor
+//! crt = true
+//!
+//! # Wait for the monitor's refresh, which stops tearing
+//! vsync = true
+//!
+//! # Which messages are shown, like --log, and a file they go to as well
+//! log = "info,netplay=debug"
+//! log_file = "/home/me/chip8.log"
+//!
+//! # Where the keypad is on the keyboard: qwerty, azerty, qwertz, dvorak or
+//! # colemak. It takes the same physical keys on all of them
+//! layout = "azerty"
+//!
+//! # The beep: square, triangle, sine or noise, its frequency in Hz and
+//! # its volume from 0 to 1
+//! [audio]
+//! waveform = "triangle"
+//! frequency = 440.0
+//! volume = 0.15
+//!
+//! # CHIP-8 key (hex digit) = SDL key name, replacing the layout's key
+//! [keypad]
+//! 5 = "Up"
+//! 8 = "Down"
+//! 7 = "Left"
+//! 9 = "Right"
+//!
+//! # Two-player layouts, bound on top of the keypad above. Each player gets
+//! # a cluster of the keyboard, keys taken by a profile lose their keypad
+//! # binding
+//! [profiles.pong.player1]
+//! 1 = "W"
+//! 4 = "S"
+//!
+//! [profiles.pong.player2]
+//! C = "Up"
+//! D = "Down"
+//!
+//! # The profile for a ROM by its file name, --keys picks one for any ROM
+//! [rom_profiles]
+//! "pong2.ch8" = "pong"
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use chip_8_rs::romdb;
+use serde::Deserialize;
+
+use crate::{
+    audio::Tone,
+    cli::RunArgs,
+    keyboard::KeyboardLayout,
+    palette::{Palette, PaletteName, parse_hex_color},
+};
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Directory the ROM picker opens in and the library is scanned from,
+    /// the working directory by default
+    pub rom_dir: Option<PathBuf>,
+    /// Used when `--database` isn't given
+    pub database: Option<PathBuf>,
+    pub palette: Option<PaletteName>,
+    /// Foreground color as #rrggbb, overrides the palette
+    pub foreground: Option<String>,
+    /// Background color as #rrggbb, overrides the palette
+    pub background: Option<String>,
+    pub ghosting: bool,
+    pub crt: bool,
+    pub vsync: bool,
+    /// Used when `--log` isn't given
+    pub log: Option<String>,
+    /// Used when `--log-file` isn't given
+    pub log_file: Option<PathBuf>,
+    /// The keyboard layout the keypad's keys are picked for
+    pub layout: KeyboardLayout,
+    /// The beep, checked when the config is loaded
+    pub audio: Tone,
+    /// Overrides for the default keypad layout, from CHIP-8 key to SDL key
+    /// name
+    pub keypad: BTreeMap<String, String>,
+    /// Two-player layouts by name
+    pub profiles: BTreeMap<String, KeyProfile>,
+    /// Name of the profile used for a ROM, by the ROM's file name
+    pub rom_profiles: BTreeMap<String, String>,
+}
+
+/// Keys for games that split the keypad between two players, each a map
+/// from CHIP-8 key to key name like `keypad`
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyProfile {
+    pub player1: BTreeMap<String, String>,
+    pub player2: BTreeMap<String, String>,
+}
+
+impl KeyProfile {
+    /// Both players' keys as CHIP-8 key and key name
+    pub fn bindings(&self) -> Result<Vec<(u8, &str)>, String> {
+        self.player1
+            .iter()
+            .chain(&self.player2)
+            .map(|(key, name)| Ok((parse_chip8_key(key)?, name.as_str())))
+            .collect()
+    }
+}
+
+/// A CHIP-8 key written as a hex digit
+pub fn parse_chip8_key(key: &str) -> Result<u8, String> {
+    u8::from_str_radix(key, 16)
+        .ok()
+        .filter(|&key| key < 16)
+        .ok_or(format!("Invalid CHIP-8 key: {}", key))
+}
+
+impl Config {
+    /// Loads the config at `path`, or at the default location when no path is
+    /// given. A missing default config isn't an error
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|e| e.to_string())
+                .and_then(|config: Self| config.audio.validate().map(|()| config))
+                .map_err(|e| format!("Invalid config {}: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read config {}: {}", path.display(), e)),
+        }
+    }
+}
+
+impl Config {
+    /// Resolves the display colors, with command line options taking
+    /// precedence over the config file
+    pub fn palette(&self, args: &RunArgs) -> Result<Palette, String> {
+        let mut palette = args.palette.or(self.palette).unwrap_or_default().palette();
+
+        if let Some(foreground) = &self.foreground {
+            palette.foreground = parse_hex_color(foreground)?;
+        }
+        if let Some(background) = &self.background {
+            palette.background = parse_hex_color(background)?;
+        }
+        if let Some(foreground) = args.fg {
+            palette.foreground = foreground;
+        }
+        if let Some(background) = args.bg {
+            palette.background = background;
+        }
+
+        Ok(palette)
+    }
+
+    /// The two-player profile picked with `--keys`, or else the one set for
+    /// the ROM at `rom_path`. Fails for unknown profiles and invalid CHIP-8
+    /// keys
+    pub fn key_profile(
+        &self,
+        args: &RunArgs,
+        rom_path: &Path,
+    ) -> Result<Option<&KeyProfile>, String> {
+        let name = match &args.keys {
+            Some(name) => name,
+            None => {
+                let file_name = rom_path.file_name().and_then(|name| name.to_str());
+                match file_name.and_then(|name| self.rom_profiles.get(name)) {
+                    Some(name) => name,
+                    None => return Ok(None),
+                }
+            }
+        };
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or(format!("Unknown key profile: {}", name))?;
+        profile.bindings()?;
+        Ok(Some(profile))
+    }
+}
+
+/// `chip8-rs/config.toml` inside the platform's config directory
+fn default_path() -> Option<PathBuf> {
+    Some(app_dir()?.join("config.toml"))
+}
+
+/// `chip8-rs` inside the platform's config directory, which holds the config
+/// and what the emulator remembers between runs
+pub fn app_dir() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("chip8-rs"))
+}
+
+/// Where what's remembered about a single ROM is kept, `roms/<sha1>` in the
+/// app's directory so it follows the ROM when it's renamed or moved
+pub fn rom_data_dir(rom: &[u8]) -> Option<PathBuf> {
+    Some(app_dir()?.join("roms").join(romdb::sha1_hex(rom)))
+}
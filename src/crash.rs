@@ -0,0 +1,127 @@
+//! Crash dumps. When the emulator panics, what the machine was doing goes to
+//! `crashes/crash-<time>.txt` in the app's directory, with a save state of it
+//! next to it, so a bug report has what's needed to reproduce the crash
+
+use std::{
+    fmt::Write as _,
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chip_8_rs::{Chip8, disasm, romdb, trace};
+use log::error;
+
+use crate::config;
+
+/// How many of the last executed instructions a dump lists
+pub const HISTORY_LEN: usize = 64;
+
+/// The message of the panic being unwound, for the dump
+static PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+/// Keeps the message of a panic for the dump, then prints it like the
+/// default hook does
+pub fn install_hook() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Ok(mut message) = PANIC.lock() {
+            *message = Some(info.to_string());
+        }
+        default(info);
+    }));
+}
+
+/// Runs `f` with `chip8`, writing a dump of it if `f` panics
+pub fn guard<T>(chip8: &mut Chip8, f: impl FnOnce(&mut Chip8) -> T) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(|| f(chip8))) {
+        Ok(result) => result,
+        Err(payload) => {
+            write_dump(chip8);
+            panic::resume_unwind(payload)
+        }
+    }
+}
+
+/// Writes the dump of `chip8` and says where it went
+pub fn write_dump(chip8: &Chip8) {
+    match write(chip8) {
+        Ok(path) => error!(
+            "Wrote a crash dump to {}, please attach it and the .state file next to it to a bug report",
+            path.display()
+        ),
+        Err(e) => error!("Failed to write a crash dump: {}", e),
+    }
+}
+
+fn write(chip8: &Chip8) -> io::Result<PathBuf> {
+    // The working directory when there's no app directory
+    let dir = config::app_dir()
+        .map(|dir| dir.join("crashes"))
+        .unwrap_or_default();
+    if !dir.as_os_str().is_empty() {
+        fs::create_dir_all(&dir)?;
+    }
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let path = dir.join(format!("crash-{}.txt", time));
+    fs::write(path.with_extension("state"), chip8.save_state())?;
+    fs::write(&path, report(chip8))?;
+    Ok(path)
+}
+
+/// The panic, the instruction that was running, the registers and the
+/// instructions that led up to it
+fn report(chip8: &Chip8) -> String {
+    let message = PANIC
+        .lock()
+        .ok()
+        .and_then(|message| message.clone())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    let rom = chip8.rom();
+    let history: Vec<_> = chip8.history().collect();
+
+    let mut out = String::new();
+    let _ = writeln!(out, "chip-8-rs {} crashed", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(out, "{}", message);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "ROM {} ({} bytes)", romdb::sha1_hex(rom), rom.len());
+    let _ = writeln!(out, "{:?}", chip8.quirks());
+    match history.last() {
+        Some(last) => {
+            let _ = writeln!(
+                out,
+                "Running {:04X} {} at 0x{:03X}",
+                last.opcode,
+                disasm::disassemble(last.opcode),
+                last.address
+            );
+        }
+        None => {
+            let _ = writeln!(out, "No instruction history, was the JIT on?");
+        }
+    }
+    let _ = writeln!(out, "{}", trace::line(chip8));
+    let stack: Vec<String> = chip8
+        .stack()
+        .iter()
+        .map(|address| format!("0x{:03X}", address))
+        .collect();
+    let _ = writeln!(out, "Stack [{}]", stack.join(", "));
+
+    let _ = writeln!(out);
+    let _ = writeln!(out, "Last {} instructions, oldest first:", history.len());
+    for executed in &history {
+        let _ = writeln!(
+            out,
+            "0x{:03X}  {:04X}  {}",
+            executed.address,
+            executed.opcode,
+            disasm::disassemble(executed.opcode)
+        );
+    }
+    out
+}
@@ -0,0 +1,769 @@
+//! A Debug Adapter Protocol server, so editors like VS Code can debug the ROM
+//! that's running. It listens on the address given with `--dap` for the
+//! editor to connect to, e.g. with a launch configuration like
+//!
+//! ```json
+//! {"type": "chip8", "request": "attach", "debugServer": 4711}
+//! ```
+//!
+//! There's no source to map addresses to, so breakpoints are set on
+//! addresses in the disassembly view or on symbol names as function
+//! breakpoints. The registers are shown as variables and memory can be read.
+//! Messages are read on a separate thread and executed between frames, like
+//! the debugger's commands
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+};
+
+use chip_8_rs::{
+    Chip8,
+    debug::{Condition, ConditionError, Stop},
+    disasm,
+    symbols::Symbols,
+};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::debugger::Flow;
+
+/// The only thread, CHIP-8 has a single one
+const THREAD_ID: u64 = 1;
+/// Variables reference of the registers scope, 0 means no children
+const REGISTERS: u64 = 1;
+/// Longest message accepted, far more than any request needs
+const MAX_MESSAGE_LEN: usize = 4 * 1024 * 1024;
+/// Longest header line accepted
+const MAX_HEADER_LEN: u64 = 1024;
+
+enum Event {
+    Connected(usize, TcpStream),
+    Message(usize, String),
+    Closed(usize),
+}
+
+#[derive(Deserialize)]
+struct Request {
+    seq: u64,
+    command: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct LaunchArguments {
+    stop_on_entry: bool,
+}
+
+#[derive(Deserialize)]
+struct FunctionBreakpoint {
+    name: String,
+    condition: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstructionBreakpoint {
+    instruction_reference: String,
+    #[serde(default)]
+    offset: i64,
+    condition: Option<String>,
+}
+
+/// Where a requested breakpoint goes and when it stops, or why it can't be
+/// set
+type Resolved = Result<(u16, Option<Condition>), String>;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadMemoryArguments {
+    memory_reference: String,
+    #[serde(default)]
+    offset: i64,
+    count: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DisassembleArguments {
+    memory_reference: String,
+    #[serde(default)]
+    offset: i64,
+    #[serde(default)]
+    instruction_offset: i64,
+    instruction_count: u64,
+}
+
+/// The editor that's connected, and what it set up
+struct Client {
+    id: usize,
+    stream: TcpStream,
+    /// Sequence number of the next message sent
+    seq: u64,
+    /// Whether to stop on entry, once `launch` or `attach` came
+    launched: Option<bool>,
+    configured: bool,
+    function_breakpoints: Vec<u16>,
+    instruction_breakpoints: Vec<u16>,
+}
+
+pub struct Dap {
+    events: Receiver<Event>,
+    client: Option<Client>,
+    /// Whether the emulator was paused at the end of the last poll, to tell
+    /// the editor when something else paused or resumed it
+    was_paused: bool,
+}
+
+impl Dap {
+    /// Starts accepting connections on `address`
+    pub fn spawn(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        info!(
+            "Debug adapter listening on {}, waiting for the editor",
+            listener.local_addr()?
+        );
+
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let sender = sender.clone();
+                thread::spawn(move || serve(id, stream, sender));
+            }
+        });
+
+        Ok(Self {
+            events,
+            client: None,
+            was_paused: true,
+        })
+    }
+
+    /// Executes the requests received since the last call, and tells the
+    /// editor when execution stopped or continued without it asking. `stop`
+    /// is why the emulator paused since then, if a stop paused it
+    pub fn poll(
+        &mut self,
+        chip8: &mut Chip8,
+        paused: &mut bool,
+        stop: Option<Stop>,
+        symbols: &Symbols,
+    ) -> Flow {
+        if *paused != self.was_paused
+            && let Some(client) = &mut self.client
+        {
+            if *paused {
+                let reason = match stop {
+                    Some(Stop::Breakpoint { .. }) => "breakpoint",
+                    Some(Stop::Watchpoint { .. }) => "data breakpoint",
+                    // Steps over and out of calls end here
+                    Some(Stop::Reached { .. }) => "step",
+                    None => "pause",
+                };
+                client.stopped(reason);
+            } else {
+                client.event("continued", json!({ "threadId": THREAD_ID }));
+            }
+        }
+
+        let flow = loop {
+            match self.events.try_recv() {
+                Ok(Event::Connected(id, stream)) => {
+                    if self.client.is_some() {
+                        warn!("Another editor connected to the debug adapter, dropping the first");
+                    }
+                    self.client = Some(Client::new(id, stream));
+                }
+                // The program keeps running without the editor
+                Ok(Event::Closed(id)) => {
+                    if let Some(mut client) = self.client.take_if(|client| client.id == id) {
+                        client.clear_breakpoints(chip8);
+                        chip8.cancel_run_to();
+                        *paused = false;
+                    }
+                }
+                Ok(Event::Message(id, text)) => {
+                    let Some(client) = self.client.as_mut().filter(|client| client.id == id) else {
+                        continue;
+                    };
+                    match serde_json::from_str(&text) {
+                        Ok(request) => {
+                            if client.execute(request, chip8, paused, symbols) == Flow::Quit {
+                                break Flow::Quit;
+                            }
+                        }
+                        Err(e) => warn!("Invalid debug adapter message: {}", e),
+                    }
+                }
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => break Flow::Continue,
+            }
+        };
+        self.was_paused = *paused;
+        flow
+    }
+
+    /// Tells the editor the program ended
+    pub fn finish(&mut self) {
+        if let Some(client) = &mut self.client {
+            client.event("terminated", json!({}));
+        }
+    }
+}
+
+impl Client {
+    fn new(id: usize, stream: TcpStream) -> Self {
+        Self {
+            id,
+            stream,
+            seq: 1,
+            launched: None,
+            configured: false,
+            function_breakpoints: Vec::new(),
+            instruction_breakpoints: Vec::new(),
+        }
+    }
+
+    fn execute(
+        &mut self,
+        request: Request,
+        chip8: &mut Chip8,
+        paused: &mut bool,
+        symbols: &Symbols,
+    ) -> Flow {
+        let result = match request.command.as_str() {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsFunctionBreakpoints": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsReadMemoryRequest": true,
+                "supportsDisassembleRequest": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" | "attach" => {
+                let arguments: LaunchArguments =
+                    serde_json::from_value(request.arguments.clone()).unwrap_or_default();
+                self.launched = Some(arguments.stop_on_entry);
+                Ok(Value::Null)
+            }
+            "configurationDone" => {
+                self.configured = true;
+                Ok(Value::Null)
+            }
+            "setBreakpoints" => Ok(json!({
+                "breakpoints": request.arguments["breakpoints"]
+                    .as_array()
+                    .map_or(&[][..], Vec::as_slice)
+                    .iter()
+                    .map(|_| json!({
+                        "verified": false,
+                        "message": "ROMs have no source, set breakpoints in the disassembly",
+                    }))
+                    .collect::<Vec<_>>(),
+            })),
+            "setFunctionBreakpoints" => {
+                self.set_function_breakpoints(&request.arguments, chip8, symbols)
+            }
+            "setInstructionBreakpoints" => {
+                self.set_instruction_breakpoints(&request.arguments, chip8)
+            }
+            "threads" => Ok(json!({
+                "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }],
+            })),
+            "stackTrace" => Ok(stack_trace(chip8, symbols)),
+            "scopes" => Ok(json!({
+                "scopes": [{
+                    "name": "Registers",
+                    "presentationHint": "registers",
+                    "variablesReference": REGISTERS,
+                    "expensive": false,
+                }],
+            })),
+            "variables" => Ok(json!({
+                "variables": if request.arguments["variablesReference"] == REGISTERS {
+                    registers(chip8)
+                } else {
+                    Vec::new()
+                },
+            })),
+            "continue" => {
+                chip8.cancel_run_to();
+                *paused = false;
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            // Calls run until they return, the stop is reported by `poll`
+            "next" if chip8.step_over() => {
+                *paused = false;
+                Ok(Value::Null)
+            }
+            "stepOut" if chip8.step_out() => {
+                *paused = false;
+                Ok(Value::Null)
+            }
+            "stepOut" => Err("Not in a subroutine".to_string()),
+            "next" | "stepIn" => {
+                *paused = true;
+                step(chip8).map(|()| Value::Null)
+            }
+            "pause" => {
+                chip8.cancel_run_to();
+                *paused = true;
+                Ok(Value::Null)
+            }
+            "readMemory" => read_memory(&request.arguments, chip8),
+            "disassemble" => disassemble(&request.arguments, chip8, symbols),
+            // Detaching leaves the program running, ending the session quits
+            "disconnect" if request.arguments["terminateDebuggee"] != true => {
+                self.clear_breakpoints(chip8);
+                chip8.cancel_run_to();
+                *paused = false;
+                Ok(Value::Null)
+            }
+            "disconnect" | "terminate" => {
+                self.respond(&request, Ok(Value::Null));
+                return Flow::Quit;
+            }
+            command => Err(format!("{} isn't supported", command)),
+        };
+
+        let stopped = match request.command.as_str() {
+            "next" | "stepIn" if result.is_ok() && *paused => Some("step"),
+            "pause" => Some("pause"),
+            _ => None,
+        };
+        let start = matches!(
+            request.command.as_str(),
+            "launch" | "attach" | "configurationDone"
+        );
+        self.respond(&request, result);
+        // Breakpoints can be set from here on, until configurationDone
+        if request.command == "initialize" {
+            self.event("initialized", json!({}));
+        }
+        if let Some(reason) = stopped {
+            self.stopped(reason);
+        }
+        if start
+            && self.configured
+            && let Some(stop_on_entry) = self.launched
+        {
+            self.configured = false;
+            if stop_on_entry {
+                *paused = true;
+                self.stopped("entry");
+            } else {
+                *paused = false;
+            }
+        }
+        Flow::Continue
+    }
+
+    /// Replaces the breakpoints on symbol names or addresses
+    fn set_function_breakpoints(
+        &mut self,
+        arguments: &Value,
+        chip8: &mut Chip8,
+        symbols: &Symbols,
+    ) -> Result<Value, String> {
+        let requested: Vec<FunctionBreakpoint> =
+            serde_json::from_value(arguments["breakpoints"].clone()).map_err(|e| e.to_string())?;
+        let resolved: Vec<Resolved> = requested
+            .iter()
+            .map(|breakpoint| {
+                let address = symbols
+                    .address(&breakpoint.name)
+                    .or_else(|| parse_reference(&breakpoint.name));
+                resolve(address, breakpoint.condition.as_deref())
+            })
+            .collect();
+        self.function_breakpoints = replace_breakpoints(
+            chip8,
+            &self.function_breakpoints,
+            &self.instruction_breakpoints,
+            &resolved,
+        );
+        Ok(breakpoints(&resolved))
+    }
+
+    /// Replaces the breakpoints set in the disassembly view
+    fn set_instruction_breakpoints(
+        &mut self,
+        arguments: &Value,
+        chip8: &mut Chip8,
+    ) -> Result<Value, String> {
+        let requested: Vec<InstructionBreakpoint> =
+            serde_json::from_value(arguments["breakpoints"].clone()).map_err(|e| e.to_string())?;
+        let resolved: Vec<Resolved> = requested
+            .iter()
+            .map(|breakpoint| {
+                let address = parse_reference(&breakpoint.instruction_reference)
+                    .and_then(|address| u16::try_from(address as i64 + breakpoint.offset).ok());
+                resolve(address, breakpoint.condition.as_deref())
+            })
+            .collect();
+        self.instruction_breakpoints = replace_breakpoints(
+            chip8,
+            &self.instruction_breakpoints,
+            &self.function_breakpoints,
+            &resolved,
+        );
+        Ok(breakpoints(&resolved))
+    }
+
+    fn clear_breakpoints(&mut self, chip8: &mut Chip8) {
+        replace_breakpoints(chip8, &self.function_breakpoints, &[], &[]);
+        replace_breakpoints(chip8, &self.instruction_breakpoints, &[], &[]);
+        self.function_breakpoints.clear();
+        self.instruction_breakpoints.clear();
+    }
+
+    fn respond(&mut self, request: &Request, result: Result<Value, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "command": request.command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = Value::String(message),
+        }
+        self.send(response);
+    }
+
+    fn stopped(&mut self, reason: &str) {
+        self.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        );
+    }
+
+    fn event(&mut self, event: &str, body: Value) {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }));
+    }
+
+    fn send(&mut self, mut message: Value) {
+        message["seq"] = self.seq.into();
+        self.seq += 1;
+        let text = message.to_string();
+        // A client that went away is noticed by its reader thread
+        let _ = write!(
+            self.stream,
+            "Content-Length: {}\r\n\r\n{}",
+            text.len(),
+            text
+        );
+    }
+}
+
+/// Swaps the breakpoints in `old` for the ones in `new` that could be set,
+/// keeping the ones the other kind of breakpoint still needs. Returns the
+/// addresses of the new ones
+fn replace_breakpoints(
+    chip8: &mut Chip8,
+    old: &[u16],
+    other: &[u16],
+    new: &[Resolved],
+) -> Vec<u16> {
+    for address in old {
+        if !other.contains(address) {
+            chip8.remove_breakpoint(*address);
+        }
+    }
+    new.iter()
+        .flatten()
+        .map(|(address, condition)| {
+            match condition {
+                Some(condition) => chip8.add_conditional_breakpoint(*address, condition.clone()),
+                None => chip8.add_breakpoint(*address),
+            }
+            *address
+        })
+        .collect()
+}
+
+/// An address, with the condition parsed if there's one
+fn resolve(address: Option<u16>, condition: Option<&str>) -> Resolved {
+    let address = address.ok_or("No such symbol or address")?;
+    let condition = condition
+        .filter(|condition| !condition.trim().is_empty())
+        .map(str::parse)
+        .transpose()
+        .map_err(|e: ConditionError| format!("Invalid condition: {}", e))?;
+    Ok((address, condition))
+}
+
+/// The response to setting breakpoints, the ones that couldn't be set
+/// aren't verified
+fn breakpoints(resolved: &[Resolved]) -> Value {
+    json!({
+        "breakpoints": resolved
+            .iter()
+            .map(|breakpoint| match breakpoint {
+                Ok((address, _)) => json!({
+                    "verified": true,
+                    "instructionReference": reference(*address),
+                }),
+                Err(message) => json!({
+                    "verified": false,
+                    "message": message,
+                }),
+            })
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Runs the instruction at the PC, even when it has a breakpoint
+fn step(chip8: &mut Chip8) -> Result<(), String> {
+    chip8.cycle().map_err(|e| e.to_string())?;
+    if chip8.take_stop().is_some() {
+        chip8.cycle().map_err(|e| e.to_string())?;
+        chip8.take_stop();
+    }
+    Ok(())
+}
+
+/// The innermost subroutine first, `main` last
+fn stack_trace(chip8: &Chip8, symbols: &Symbols) -> Value {
+    let mut location = chip8.pc();
+    let mut frames = Vec::new();
+    let calls = chip8.call_stack();
+    for (depth, call) in calls.iter().rev().enumerate() {
+        frames.push(frame(
+            depth,
+            &symbols.subroutine_name(call.subroutine),
+            location,
+        ));
+        location = call.call_site;
+    }
+    frames.push(frame(calls.len(), "main", location));
+    json!({ "stackFrames": frames, "totalFrames": frames.len() })
+}
+
+fn frame(id: usize, name: &str, address: u16) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "line": 0,
+        "column": 0,
+        "instructionPointerReference": reference(address),
+    })
+}
+
+fn registers(chip8: &Chip8) -> Vec<Value> {
+    let variable = |name: String, value: String, memory: Option<u32>| {
+        let mut variable = json!({
+            "name": name,
+            "value": value,
+            "variablesReference": 0,
+        });
+        if let Some(address) = memory {
+            variable["memoryReference"] = format!("0x{:03X}", address).into();
+        }
+        variable
+    };
+
+    let mut variables: Vec<Value> = chip8
+        .registers()
+        .iter()
+        .enumerate()
+        .map(|(index, value)| variable(format!("V{:X}", index), format!("0x{:02X}", value), None))
+        .collect();
+    variables.extend([
+        variable(
+            "I".to_string(),
+            format!("0x{:03X}", chip8.index()),
+            Some(chip8.index()),
+        ),
+        variable(
+            "PC".to_string(),
+            reference(chip8.pc()),
+            Some(chip8.pc() as u32),
+        ),
+        variable(
+            "DT".to_string(),
+            format!("0x{:02X}", chip8.delay_timer()),
+            None,
+        ),
+        variable(
+            "ST".to_string(),
+            format!("0x{:02X}", chip8.sound_timer()),
+            None,
+        ),
+    ]);
+    variables
+}
+
+fn read_memory(arguments: &Value, chip8: &Chip8) -> Result<Value, String> {
+    let arguments: ReadMemoryArguments =
+        serde_json::from_value(arguments.clone()).map_err(|e| e.to_string())?;
+    let address = (parse_reference(&arguments.memory_reference)
+        .ok_or_else(|| format!("Invalid memory reference {}", arguments.memory_reference))?
+        as i64)
+        .checked_add(arguments.offset)
+        .ok_or_else(|| format!("Offset {} is out of range", arguments.offset))?;
+    let memory = chip8.memory();
+    // No more than all of memory is ever read
+    let count = arguments.count.min(memory.len() as u64) as usize;
+    let start = address.clamp(0, memory.len() as i64) as usize;
+    let end = (start + count).min(memory.len());
+    Ok(json!({
+        "address": format!("0x{:03X}", start),
+        "data": base64(&memory[start..end]),
+        "unreadableBytes": count - (end - start),
+    }))
+}
+
+fn disassemble(arguments: &Value, chip8: &Chip8, symbols: &Symbols) -> Result<Value, String> {
+    let arguments: DisassembleArguments =
+        serde_json::from_value(arguments.clone()).map_err(|e| e.to_string())?;
+    let start = (parse_reference(&arguments.memory_reference)
+        .ok_or_else(|| format!("Invalid memory reference {}", arguments.memory_reference))?
+        as i64)
+        .checked_add(arguments.offset)
+        .and_then(|start| start.checked_add(arguments.instruction_offset.checked_mul(2)?))
+        .ok_or_else(|| "Offset is out of range".to_string())?;
+    let memory = chip8.memory();
+    // Memory can't hold more instructions than it has bytes
+    let count = arguments.instruction_count.min(memory.len() as u64) as i64;
+
+    let instructions: Vec<Value> = (0..count)
+        .map(|index| {
+            let address = start.saturating_add(index * 2);
+            let bytes = usize::try_from(address)
+                .ok()
+                .and_then(|address| memory.get(address..address + 2));
+            match bytes {
+                Some(&[high, low]) => {
+                    let address = address as u16;
+                    let mut instruction = json!({
+                        "address": reference(address),
+                        "instructionBytes": format!("{:02X} {:02X}", high, low),
+                        "instruction": disasm::disassemble_with(
+                            u16::from_be_bytes([high, low]),
+                            symbols,
+                        ),
+                    });
+                    if let Some(name) = symbols.name(address) {
+                        instruction["symbol"] = name.into();
+                    }
+                    instruction
+                }
+                _ => json!({
+                    "address": format!("0x{:03X}", address.max(0)),
+                    "instruction": "",
+                    "presentationHint": "invalid",
+                }),
+            }
+        })
+        .collect();
+    Ok(json!({ "instructions": instructions }))
+}
+
+/// Addresses are passed around as `0x` and hex digits
+fn reference(address: u16) -> String {
+    format!("0x{:03X}", address)
+}
+
+fn parse_reference(text: &str) -> Option<u16> {
+    u16::from_str_radix(text.strip_prefix("0x")?, 16).ok()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (index, &byte)| {
+                group | (byte as u32) << (16 - index * 8)
+            });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                text.push(ALPHABET[(group >> (18 - index * 6)) as usize & 0x3F] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// Reads one connection's messages and forwards them to the emulator
+fn serve(id: usize, stream: TcpStream, events: Sender<Event>) {
+    let Ok(writer) = stream.try_clone() else {
+        return;
+    };
+    if events.send(Event::Connected(id, writer)).is_err() {
+        return;
+    }
+    let mut reader = BufReader::new(stream);
+    while let Ok(Some(message)) = read_message(&mut reader) {
+        if events.send(Event::Message(id, message)).is_err() {
+            return;
+        }
+    }
+    let _ = events.send(Event::Closed(id));
+}
+
+/// Reads a message framed with a `Content-Length` header, none at the end of
+/// the stream
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.by_ref().take(MAX_HEADER_LEN).read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if !line.ends_with('\n') && line.len() as u64 == MAX_HEADER_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "header line too long",
+            ));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message without a Content-Length",
+        ));
+    };
+    if length > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes is too long", length),
+        ));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
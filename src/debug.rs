@@ -0,0 +1,400 @@
+//! Breakpoints, watchpoints, the instruction history and read access to the
+//! registers for debugging frontends
+
+use alloc::{collections::VecDeque, vec::Vec};
+use core::{fmt, ops::Range};
+
+pub use crate::condition::{Condition, ConditionError};
+use crate::{
+    chip8::Chip8,
+    decode::{Instruction, decode},
+    error::Chip8Error,
+};
+
+/// What a watchpoint looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchTarget {
+    Memory(u16),
+    Register(u8),
+}
+
+impl fmt::Display for WatchTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchTarget::Memory(address) => write!(f, "0x{:03X}", address),
+            WatchTarget::Register(index) => write!(f, "V{:X}", index),
+        }
+    }
+}
+
+/// Which accesses trigger a watchpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub target: WatchTarget,
+    pub kind: WatchKind,
+}
+
+/// A subroutine call on the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// Address of the CALL instruction
+    pub call_site: u16,
+    /// Where execution continues after the subroutine returns
+    pub return_address: u16,
+    /// Address the CALL jumped to
+    pub subroutine: u16,
+}
+
+/// An instruction that was executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Executed {
+    pub address: u16,
+    pub opcode: u16,
+}
+
+/// The last instructions executed, oldest first
+#[derive(Debug, Clone)]
+pub(crate) struct History {
+    entries: VecDeque<Executed>,
+    capacity: usize,
+}
+
+impl History {
+    pub(crate) fn record(&mut self, address: u16, opcode: u16) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Executed { address, opcode });
+    }
+}
+
+/// Where `run_to`, `step_over` and `step_out` stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Target {
+    address: u16,
+    /// The deepest the stack can be at the address, so a recursive call
+    /// passing through it doesn't count
+    max_depth: usize,
+}
+
+/// Why execution stopped. The instruction at `address` hasn't run yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint {
+        address: u16,
+    },
+    /// The address given to `run_to`, or the end of a step over a call
+    Reached {
+        address: u16,
+    },
+    Watchpoint {
+        address: u16,
+        target: WatchTarget,
+        write: bool,
+    },
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Breakpoint { address } => write!(f, "Breakpoint at 0x{:03X}", address),
+            Stop::Reached { address } => write!(f, "Reached 0x{:03X}", address),
+            Stop::Watchpoint {
+                address,
+                target,
+                write,
+            } => write!(
+                f,
+                "Watchpoint: {} {} at 0x{:03X}",
+                target,
+                if *write { "written" } else { "read" },
+                address
+            ),
+        }
+    }
+}
+
+/// The registers and memory an instruction is going to read and write.
+/// Registers are bit masks indexed by register number
+struct Accesses {
+    register_reads: u16,
+    register_writes: u16,
+    memory_reads: Range<usize>,
+    memory_writes: Range<usize>,
+}
+
+impl Chip8 {
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// The V0-VF registers
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    /// The I register
+    pub fn index(&self) -> u32 {
+        self.i
+    }
+
+    /// Addresses of the calls into the subroutines being executed, innermost
+    /// last
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    /// The calls being executed, innermost last
+    pub fn call_stack(&self) -> Vec<Frame> {
+        self.stack()
+            .iter()
+            .zip(&self.subroutines)
+            .map(|(&call_site, &subroutine)| Frame {
+                call_site,
+                return_address: call_site.wrapping_add(2),
+                subroutine,
+            })
+            .collect()
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Overwrites a byte of memory, e.g. to patch a running program
+    pub fn poke(&mut self, address: u16, value: u8) -> Result<(), Chip8Error> {
+        let address = address as usize;
+        let byte = self
+            .memory
+            .get_mut(address)
+            .ok_or(Chip8Error::MemoryOutOfBounds { address })?;
+        *byte = value;
+        Ok(())
+    }
+
+    /// Stops execution before the instruction at `address` runs
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address, None);
+    }
+
+    /// Stops execution before the instruction at `address` runs, if
+    /// `condition` holds then. Replaces any breakpoint at the address
+    pub fn add_conditional_breakpoint(&mut self, address: u16, condition: Condition) {
+        self.breakpoints.insert(address, Some(condition));
+    }
+
+    /// Returns whether there was a breakpoint at `address`
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.keys().copied()
+    }
+
+    /// The condition of the breakpoint at `address`, if it has one
+    pub fn breakpoint_condition(&self, address: u16) -> Option<&Condition> {
+        self.breakpoints.get(&address)?.as_ref()
+    }
+
+    /// Stops execution the next time the PC reaches `address`, at any depth
+    /// of the stack. The instruction at the PC runs first, so running to it
+    /// goes around a loop once. Any stop, this one or a breakpoint's, ends it
+    pub fn run_to(&mut self, address: u16) {
+        self.run_target(address, usize::MAX);
+    }
+
+    /// Runs a subroutine call as a single step: when the instruction at the
+    /// PC is a CALL, execution stops once the subroutine returned and
+    /// returns true. Recursive calls reaching the return address deeper in
+    /// the stack don't count. Anything else is left to `cycle` and returns
+    /// false
+    pub fn step_over(&mut self) -> bool {
+        let is_call = self
+            .fetch_opcode()
+            .is_ok_and(|opcode| matches!(decode(opcode), Some(Instruction::Call { .. })));
+        if is_call {
+            self.run_target(self.pc.wrapping_add(2), self.sp as usize);
+        }
+        is_call
+    }
+
+    /// Stops execution once the subroutine being executed returns. Returns
+    /// false outside of subroutines
+    pub fn step_out(&mut self) -> bool {
+        let Some(&call_site) = self.stack().last() else {
+            return false;
+        };
+        self.run_target(call_site.wrapping_add(2), self.sp as usize - 1);
+        true
+    }
+
+    /// Forgets where `run_to`, `step_over` or `step_out` were going to stop
+    pub fn cancel_run_to(&mut self) {
+        self.run_to = None;
+    }
+
+    fn run_target(&mut self, address: u16, max_depth: usize) {
+        self.run_to = Some(Target { address, max_depth });
+        // The instruction at the PC runs before the target is checked
+        self.resume = true;
+    }
+
+    /// Stops execution before an instruction accesses the target. Instruction
+    /// fetches don't count as memory reads
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+
+    /// Returns whether the watchpoint existed
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|&w| w != watchpoint);
+        self.watchpoints.len() != count
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Starts remembering the last `len` executed instructions, e.g. for a
+    /// crash report. Off by default since it costs a little per instruction,
+    /// and the JIT interprets while it's on. Enabling it again changes the
+    /// length and forgets what was recorded
+    pub fn enable_history(&mut self, len: usize) {
+        self.history = (len > 0).then(|| History {
+            entries: VecDeque::with_capacity(len),
+            capacity: len,
+        });
+    }
+
+    /// The last executed instructions, oldest first. Empty unless
+    /// `enable_history` was called
+    pub fn history(&self) -> impl Iterator<Item = Executed> + '_ {
+        self.history
+            .iter()
+            .flat_map(|history| history.entries.iter().copied())
+    }
+
+    /// Why execution stopped since the last call, if it did. `run_frame` ends
+    /// the frame early when that happens
+    pub fn take_stop(&mut self) -> Option<Stop> {
+        self.stop.take()
+    }
+
+    /// Whether `opcode`, about to run at the PC, should stop execution
+    pub(crate) fn check_stop(&self, opcode: u16) -> Option<Stop> {
+        let address = self.pc;
+        if let Some(condition) = self.breakpoints.get(&address)
+            && condition
+                .as_ref()
+                .is_none_or(|condition| condition.holds(self))
+        {
+            return Some(Stop::Breakpoint { address });
+        }
+        if let Some(target) = self.run_to
+            && target.address == address
+            && self.stack().len() <= target.max_depth
+        {
+            return Some(Stop::Reached { address });
+        }
+        if self.watchpoints.is_empty() {
+            return None;
+        }
+
+        let accesses = self.accesses(opcode);
+        self.watchpoints.iter().find_map(|watchpoint| {
+            let (read, written) = match watchpoint.target {
+                WatchTarget::Register(index) => (
+                    accesses.register_reads & 1 << index != 0,
+                    accesses.register_writes & 1 << index != 0,
+                ),
+                WatchTarget::Memory(target) => (
+                    accesses.memory_reads.contains(&(target as usize)),
+                    accesses.memory_writes.contains(&(target as usize)),
+                ),
+            };
+            let write = match watchpoint.kind {
+                WatchKind::Read if read => false,
+                WatchKind::Write if written => true,
+                WatchKind::ReadWrite if read || written => written,
+                _ => return None,
+            };
+            Some(Stop::Watchpoint {
+                address,
+                target: watchpoint.target,
+                write,
+            })
+        })
+    }
+
+    /// Mirrors what the opcode handlers in `chip8.rs` touch, including the
+    /// effect of quirks
+    fn accesses(&self, opcode: u16) -> Accesses {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let n = (opcode & 0x000F) as usize;
+        let vx = 1 << x;
+        let vy = 1 << y;
+        let vf = 1 << 0xF;
+        // V0 through Vx, used by the load and store instructions
+        let up_to_x = ((1u32 << (x + 1)) - 1) as u16;
+        let i = self.i as usize;
+        let quirks = self.quirks();
+
+        let (register_reads, register_writes) = match opcode & 0xF000 {
+            0x3000 | 0x4000 => (vx, 0),
+            0x5000 | 0x9000 => (vx | vy, 0),
+            0x6000 | 0xC000 => (0, vx),
+            0x7000 => (vx, vx),
+            0x8000 => match n {
+                0x0 => (vy, vx),
+                0x1..=0x3 if quirks.vf_reset => (vx | vy, vx | vf),
+                0x1..=0x3 => (vx | vy, vx),
+                0x6 | 0xE if quirks.shift_vy => (vy, vx | vf),
+                0x6 | 0xE => (vx, vx | vf),
+                _ => (vx | vy, vx | vf),
+            },
+            0xB000 if quirks.jump_vx => (vx, 0),
+            0xB000 => (1, 0),
+            0xD000 => (vx | vy, vf),
+            0xE000 => (vx, 0),
+            0xF000 => match opcode & 0x00FF {
+                0x07 | 0x0A => (0, vx),
+                0x55 | 0x75 => (up_to_x, 0),
+                0x65 | 0x85 => (0, up_to_x),
+                _ => (vx, 0),
+            },
+            _ => (0, 0),
+        };
+
+        let (memory_reads, memory_writes) = match opcode & 0xF000 {
+            // A 16x16 sprite takes two bytes per row
+            0xD000 if n == 0 => (i..i + 32, 0..0),
+            0xD000 => (i..i + n, 0..0),
+            0xF000 => match opcode & 0x00FF {
+                0x33 => (0..0, i..i + 3),
+                0x55 => (0..0, i..i + x + 1),
+                0x65 => (i..i + x + 1, 0..0),
+                _ => (0..0, 0..0),
+            },
+            _ => (0..0, 0..0),
+        };
+
+        Accesses {
+            register_reads,
+            register_writes,
+            memory_reads,
+            memory_writes,
+        }
+    }
+}
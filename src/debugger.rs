@@ -0,0 +1,453 @@
+//! A command prompt on stdin for inspecting and controlling the emulator
+//! while its window keeps running. Commands are read on a separate thread
+//! and executed between frames
+
+use std::{
+    io::{self, BufRead, Write},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use chip_8_rs::{debug::Watchpoint, disasm};
+
+use crate::{
+    cli::{parse_address, parse_watchpoint},
+    session::Session,
+};
+
+const HELP: &str = "\
+Commands:
+  s, step [n]          execute n instructions (default 1)
+  n, next              step, running a subroutine call as a single step
+  finish               run until the current subroutine returns
+  u, until <addr>      run until the PC reaches addr
+  f, frame             run one frame while paused
+  c, continue          resume execution
+  p, pause             pause execution
+  r, regs              print the registers, timers and stack
+  bt, backtrace        print the subroutine calls being executed
+  m, mem <addr> [len]  dump len bytes of memory (default 64)
+  l, list [addr] [n]   disassemble n instructions (default 8) from addr or PC
+  sprite [addr] [n]    draw n bytes (default 15) from addr or I as a sprite
+  b, break <addr> [if <cond>]
+                       set a breakpoint, stopping only when the condition
+                       holds, e.g. b 0x230 if v[3] == 0x10 && dt == 0
+  d, delete <addr>     remove a breakpoint
+  w, watch <target>    watch a register or address, e.g. v3, 0x300:r
+  unwatch <target>     remove a watchpoint
+  info                 list breakpoints and watchpoints
+  poke <addr> <byte>   write a byte to memory
+  cheat [n on|off]     list the cheats or turn cheat n on or off
+  profile              print execution counts (needs --profile)
+  q, quit              exit the emulator
+
+Addresses can be given by their names in the symbol file (--symbols).";
+
+/// Whether the emulator should keep running after a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+pub struct Debugger {
+    commands: Receiver<String>,
+}
+
+impl Debugger {
+    /// Starts reading commands from stdin
+    pub fn spawn() -> Self {
+        let (sender, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        println!("Debugger ready, type help for a list of commands");
+        prompt();
+        Self { commands }
+    }
+
+    /// Executes the commands entered since the last call
+    pub fn poll(&mut self, session: &mut Session) -> Flow {
+        loop {
+            match self.commands.try_recv() {
+                Ok(line) => {
+                    let flow = execute(&line, session);
+                    if flow == Flow::Quit {
+                        return flow;
+                    }
+                    prompt();
+                }
+                Err(TryRecvError::Empty) => return Flow::Continue,
+                // Stdin was closed, keep running without the prompt
+                Err(TryRecvError::Disconnected) => return Flow::Continue,
+            }
+        }
+    }
+}
+
+fn prompt() {
+    print!("(chip8) ");
+    let _ = io::stdout().flush();
+}
+
+fn execute(line: &str, session: &mut Session) -> Flow {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return Flow::Continue;
+    };
+    let args: Vec<&str> = words.collect();
+
+    let result = match command {
+        "h" | "help" => {
+            println!("{}", HELP);
+            Ok(())
+        }
+        "s" | "step" => step(session, &args),
+        "n" | "next" => {
+            if session.chip8.step_over() {
+                session.paused = false;
+                Ok(())
+            } else {
+                step(session, &[])
+            }
+        }
+        "finish" => {
+            if session.chip8.step_out() {
+                session.paused = false;
+                Ok(())
+            } else {
+                Err("Not in a subroutine".to_string())
+            }
+        }
+        "u" | "until" => address_arg(session, &args, 0).map(|address| {
+            session.chip8.run_to(address);
+            session.paused = false;
+            println!("Running until {}", session.symbols.label(address));
+        }),
+        "f" | "frame" if session.paused => {
+            session.advance = true;
+            Ok(())
+        }
+        "f" | "frame" => Err("Pause first to run single frames".to_string()),
+        "c" | "continue" => {
+            session.chip8.cancel_run_to();
+            session.paused = false;
+            Ok(())
+        }
+        "p" | "pause" => {
+            session.chip8.cancel_run_to();
+            session.paused = true;
+            print_current(session);
+            Ok(())
+        }
+        "r" | "regs" => {
+            print_registers(session);
+            Ok(())
+        }
+        "bt" | "backtrace" => {
+            print_backtrace(session);
+            Ok(())
+        }
+        "m" | "mem" => dump_memory(session, &args),
+        "l" | "list" => list(session, &args),
+        "sprite" => sprite(session, &args),
+        "b" | "break" => break_command(session, &args),
+        "d" | "delete" => address_arg(session, &args, 0).map(|address| {
+            if !session.chip8.remove_breakpoint(address) {
+                println!("No breakpoint at {}", session.symbols.label(address));
+            }
+        }),
+        "w" | "watch" => watchpoint_arg(&args).map(|watchpoint| {
+            session.chip8.add_watchpoint(watchpoint);
+            println!("Watching {}", watchpoint.target);
+        }),
+        "unwatch" => watchpoint_arg(&args).map(|watchpoint| {
+            if !session.chip8.remove_watchpoint(watchpoint) {
+                println!("No such watchpoint");
+            }
+        }),
+        "info" => {
+            print_info(session);
+            Ok(())
+        }
+        "poke" => poke(session, &args),
+        "cheat" => cheat(session, &args),
+        "profile" => match session.chip8.profile() {
+            Some(profile) => {
+                println!("{}", profile);
+                Ok(())
+            }
+            None => Err("Profiling is off, run with --profile to enable it".to_string()),
+        },
+        "q" | "quit" => return Flow::Quit,
+        _ => Err(format!("Unknown command {}, type help for a list", command)),
+    };
+
+    if let Err(e) = result {
+        println!("{}", e);
+    }
+    Flow::Continue
+}
+
+/// An address, or the name of one from the symbol file
+fn address_arg(session: &Session, args: &[&str], index: usize) -> Result<u16, String> {
+    let arg = args.get(index).ok_or("Missing address")?;
+    match session.symbols.address(arg) {
+        Some(address) => Ok(address),
+        None => parse_address(arg),
+    }
+}
+
+fn count_arg(args: &[&str], index: usize, default: usize) -> Result<usize, String> {
+    match args.get(index) {
+        Some(arg) => arg.parse().map_err(|_| format!("Invalid count {}", arg)),
+        None => Ok(default),
+    }
+}
+
+/// Sets a breakpoint, with the condition after `if` when there's one
+fn break_command(session: &mut Session, args: &[&str]) -> Result<(), String> {
+    let address = address_arg(session, args, 0)?;
+    let label = session.symbols.label(address);
+    match args.get(1..) {
+        Some(["if", condition @ ..]) if !condition.is_empty() => {
+            let condition = condition
+                .join(" ")
+                .parse()
+                .map_err(|e| format!("Invalid condition: {}", e))?;
+            println!("Breakpoint set at {} if {}", label, condition);
+            session.chip8.add_conditional_breakpoint(address, condition);
+        }
+        Some([]) => {
+            session.chip8.add_breakpoint(address);
+            println!("Breakpoint set at {}", label);
+        }
+        _ => return Err("Usage: break <addr> [if <cond>]".to_string()),
+    }
+    Ok(())
+}
+
+fn watchpoint_arg(args: &[&str]) -> Result<Watchpoint, String> {
+    let arg = args.first().ok_or("Missing register or address")?;
+    parse_watchpoint(arg)
+}
+
+/// Lists the cheats, or turns one on or off
+fn cheat(session: &mut Session, args: &[&str]) -> Result<(), String> {
+    let cheats = &mut session.cheats;
+    match args {
+        [] => {
+            if cheats.cheats().is_empty() {
+                println!("No cheats, add some to <rom>.cht");
+            }
+            if !cheats.is_active() {
+                println!("Cheats are off, F4 turns them on");
+            }
+            for (index, cheat) in cheats.cheats().iter().enumerate() {
+                println!(
+                    "  {} [{}] {}{}",
+                    index,
+                    if cheat.enabled { "x" } else { " " },
+                    cheat.name,
+                    if cheat.freeze { " (frozen)" } else { "" }
+                );
+            }
+            Ok(())
+        }
+        [index, state] => {
+            let index = index
+                .parse()
+                .map_err(|_| format!("Invalid cheat {}", index))?;
+            let enabled = match *state {
+                "on" => true,
+                "off" => false,
+                _ => return Err(format!("Expected on or off, found {}", state)),
+            };
+            if cheats.set_enabled(index, enabled) {
+                Ok(())
+            } else {
+                Err(format!("No cheat {}", index))
+            }
+        }
+        _ => Err("Usage: cheat [n on|off]".to_string()),
+    }
+}
+
+/// Pauses and runs single instructions, stopping early at errors and
+/// breakpoints
+fn step(session: &mut Session, args: &[&str]) -> Result<(), String> {
+    let count = count_arg(args, 0, 1)?;
+    session.paused = true;
+    for _ in 0..count {
+        if let Err(e) = session.chip8.cycle() {
+            println!("{}", e);
+            break;
+        }
+        if let Some(stop) = session.chip8.take_stop() {
+            println!("{}", stop);
+            break;
+        }
+    }
+
+    print_current(session);
+    Ok(())
+}
+
+/// Prints the instruction the PC points at
+fn print_current(session: &Session) {
+    print_instructions(session, session.chip8.pc(), 1);
+}
+
+fn print_instructions(session: &Session, address: u16, count: usize) {
+    let start = address as usize;
+    let memory = session.chip8.memory();
+    let end = (start + count * 2).min(memory.len());
+    let Some(bytes) = memory.get(start..end) else {
+        return;
+    };
+
+    let symbols = &session.symbols;
+    for instruction in disasm::disassemble_program_with(bytes, address, symbols) {
+        if let Some(name) = symbols.name(instruction.address) {
+            println!("{}:", name);
+        }
+        let marker = if instruction.address == session.chip8.pc() {
+            ">"
+        } else {
+            " "
+        };
+        println!(
+            "{} {:03X}  {:04X}  {}",
+            marker, instruction.address, instruction.opcode, instruction.mnemonic
+        );
+    }
+}
+
+fn print_registers(session: &Session) {
+    let chip8 = &session.chip8;
+    println!(
+        "PC {:03X}  I {:03X}  DT {:02X}  ST {:02X}",
+        chip8.pc(),
+        chip8.index(),
+        chip8.delay_timer(),
+        chip8.sound_timer()
+    );
+    for (half, values) in chip8.registers().chunks(8).enumerate() {
+        let registers: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| format!("V{:X} {:02X}", half * 8 + index, value))
+            .collect();
+        println!("{}", registers.join("  "));
+    }
+
+    let stack: Vec<String> = chip8
+        .stack()
+        .iter()
+        .map(|address| format!("{:03X}", address))
+        .collect();
+    println!("Stack [{}]", stack.join(" "));
+}
+
+/// Lists the calls innermost first, starting with the instruction at the PC
+fn print_backtrace(session: &Session) {
+    let chip8 = &session.chip8;
+    let mut location = chip8.pc();
+    let frames = chip8.call_stack();
+    for (depth, frame) in frames.iter().rev().enumerate() {
+        println!(
+            "#{:<2} {:03X} in {}, called from {:03X}, returns to {:03X}",
+            depth,
+            location,
+            session.symbols.subroutine_name(frame.subroutine),
+            frame.call_site,
+            frame.return_address
+        );
+        location = frame.call_site;
+    }
+    println!("#{:<2} {:03X} in main", frames.len(), location);
+}
+
+fn dump_memory(session: &Session, args: &[&str]) -> Result<(), String> {
+    let start = address_arg(session, args, 0)? as usize;
+    let len = count_arg(args, 1, 64)?;
+    let memory = session.chip8.memory();
+    let end = (start + len).min(memory.len());
+
+    for (row, bytes) in memory[start..end].chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        println!("{:03X}  {}", start + row * 16, hex.join(" "));
+    }
+    Ok(())
+}
+
+fn list(session: &Session, args: &[&str]) -> Result<(), String> {
+    let address = match args.first() {
+        Some(_) => address_arg(session, args, 0)?,
+        None => session.chip8.pc(),
+    };
+    let count = count_arg(args, 1, 8)?;
+    print_instructions(session, address, count);
+    Ok(())
+}
+
+/// Draws memory one byte per row, the way DXYN would
+fn sprite(session: &Session, args: &[&str]) -> Result<(), String> {
+    let address = match args.first() {
+        Some(_) => address_arg(session, args, 0)? as u32,
+        None => session.chip8.index(),
+    };
+    let rows = count_arg(args, 1, 15)?;
+    let memory = session.chip8.memory();
+    let start = address as usize;
+    let end = (start + rows).min(memory.len());
+
+    for (row, byte) in memory[start.min(end)..end].iter().enumerate() {
+        let pixels: String = (0..8)
+            .map(|bit| if byte & 0x80 >> bit != 0 { '#' } else { '.' })
+            .collect();
+        println!("{:03X}  {:02X}  {}", start + row, byte, pixels);
+    }
+    Ok(())
+}
+
+fn print_info(session: &Session) {
+    let breakpoints: Vec<String> = session
+        .chip8
+        .breakpoints()
+        .map(|address| match session.symbols.name(address) {
+            Some(name) => format!("{:03X} ({})", address, name),
+            None => format!("{:03X}", address),
+        })
+        .collect();
+    println!("Breakpoints [{}]", breakpoints.join(" "));
+    for address in session.chip8.breakpoints() {
+        if let Some(condition) = session.chip8.breakpoint_condition(address) {
+            println!("Breakpoint {:03X} if {}", address, condition);
+        }
+    }
+
+    for watchpoint in session.chip8.watchpoints() {
+        println!("Watchpoint {} ({:?})", watchpoint.target, watchpoint.kind);
+    }
+}
+
+fn poke(session: &mut Session, args: &[&str]) -> Result<(), String> {
+    let address = address_arg(session, args, 0)?;
+    let arg = args.get(1).ok_or("Missing value")?;
+    let value = u8::from_str_radix(arg.strip_prefix("0x").unwrap_or(arg), 16)
+        .map_err(|_| format!("Invalid byte {}, expected 00-FF", arg))?;
+
+    session
+        .chip8
+        .poke(address, value)
+        .map_err(|e| e.to_string())
+}
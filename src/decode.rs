@@ -0,0 +1,353 @@
+//! Decodes opcodes into instructions with their operands pulled out, shared
+//! by the interpreter and the disassembler. The opcode's first nibble picks
+//! a decoder from a table, which only has to look at the nibbles that tell
+//! that group's instructions apart.
+//!
+//! Decoding doesn't depend on the platform. Where CHIP-8X reuses an opcode,
+//! it's decoded as the other platforms' instruction and the interpreter
+//! tells them apart by the quirks
+
+/// A decoded CHIP-8, SUPER-CHIP, XO-CHIP, MegaChip or CHIP-8X instruction. `x` and `y` are
+/// register numbers, `kk` a byte, `n` a nibble and `nnn` an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0010, MegaChip
+    MegaOff,
+    /// 0011, MegaChip
+    MegaOn,
+    /// 00BN, MegaChip
+    ScrollUp { n: u8 },
+    /// 00CN
+    ScrollDown { n: u8 },
+    /// 00E0
+    Clear,
+    /// 00EE
+    Return,
+    /// 00FB
+    ScrollRight,
+    /// 00FC
+    ScrollLeft,
+    /// 00FD
+    Exit,
+    /// 00FE
+    Lores,
+    /// 00FF
+    Hires,
+    /// 01KK NNNN, MegaChip. Sets I to KKNNNN, the low 16 bits being the
+    /// next opcode
+    LoadIndexLong { kk: u8 },
+    /// 02KK, MegaChip. 02A0 cycles the background color on CHIP-8X and 0230
+    /// clears the screen on hi-res CHIP-8
+    LoadPalette { kk: u8 },
+    /// 03KK, MegaChip
+    SpriteWidth { kk: u8 },
+    /// 04KK, MegaChip
+    SpriteHeight { kk: u8 },
+    /// 05KK, MegaChip
+    ScreenAlpha { kk: u8 },
+    /// 060N, MegaChip
+    PlaySample { n: u8 },
+    /// 0700, MegaChip
+    StopSample,
+    /// 080N, MegaChip
+    BlendMode { n: u8 },
+    /// 09KK, MegaChip
+    CollisionColor { kk: u8 },
+    /// 0NNN, a machine code routine on the original hardware
+    System { nnn: u16 },
+    /// 1NNN
+    Jump { nnn: u16 },
+    /// 2NNN
+    Call { nnn: u16 },
+    /// 3XKK
+    SkipEqual { x: u8, kk: u8 },
+    /// 4XKK
+    SkipNotEqual { x: u8, kk: u8 },
+    /// 5XY0
+    SkipEqualRegister { x: u8, y: u8 },
+    /// 5XY1, CHIP-8X. Adds the nibbles of Vy to Vx separately, each
+    /// wrapping at 8
+    AddNibbles { x: u8, y: u8 },
+    /// 6XKK
+    Load { x: u8, kk: u8 },
+    /// 7XKK
+    Add { x: u8, kk: u8 },
+    /// 8XY0
+    Move { x: u8, y: u8 },
+    /// 8XY1
+    Or { x: u8, y: u8 },
+    /// 8XY2
+    And { x: u8, y: u8 },
+    /// 8XY3
+    Xor { x: u8, y: u8 },
+    /// 8XY4
+    AddRegister { x: u8, y: u8 },
+    /// 8XY5
+    Sub { x: u8, y: u8 },
+    /// 8XY6
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7
+    SubReverse { x: u8, y: u8 },
+    /// 8XYE
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0
+    SkipNotEqualRegister { x: u8, y: u8 },
+    /// ANNN
+    LoadIndex { nnn: u16 },
+    /// BNNN, `x` is only used with the jump quirk. Sets the foreground color
+    /// as BXYN on CHIP-8X
+    JumpOffset { x: u8, nnn: u16 },
+    /// CXKK
+    Random { x: u8, kk: u8 },
+    /// DXYN, a 16x16 sprite when `n` is 0
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E
+    SkipKey { x: u8 },
+    /// EXA1
+    SkipNotKey { x: u8 },
+    /// EXF2, CHIP-8X's second keypad
+    SkipKey2 { x: u8 },
+    /// EXF5, CHIP-8X's second keypad
+    SkipNotKey2 { x: u8 },
+    /// F002, XO-CHIP. Loads the audio pattern from the 16 bytes at I
+    LoadPattern,
+    /// FX07
+    LoadDelay { x: u8 },
+    /// FX0A
+    WaitKey { x: u8 },
+    /// FX15
+    SetDelay { x: u8 },
+    /// FX18
+    SetSound { x: u8 },
+    /// FX1E
+    AddIndex { x: u8 },
+    /// FX29
+    Font { x: u8 },
+    /// FX30
+    BigFont { x: u8 },
+    /// FX33
+    Bcd { x: u8 },
+    /// FX3A, XO-CHIP. Sets the playback rate of the audio pattern
+    SetAudioPitch { x: u8 },
+    /// FX55
+    Store { x: u8 },
+    /// FX65
+    Restore { x: u8 },
+    /// FX75
+    StoreFlags { x: u8 },
+    /// FX85
+    RestoreFlags { x: u8 },
+    /// FXF8, CHIP-8X. Sets the pitch of the tone
+    SetPitch { x: u8 },
+    /// FXFB, CHIP-8X. Reads the input port
+    ReadPort { x: u8 },
+}
+
+impl Instruction {
+    /// Whether this is one of the instructions only MegaChip has. Other
+    /// interpreters skip them like any other 0NNN
+    pub fn is_megachip(self) -> bool {
+        matches!(
+            self,
+            Instruction::MegaOff
+                | Instruction::MegaOn
+                | Instruction::ScrollUp { .. }
+                | Instruction::LoadIndexLong { .. }
+                | Instruction::LoadPalette { .. }
+                | Instruction::SpriteWidth { .. }
+                | Instruction::SpriteHeight { .. }
+                | Instruction::ScreenAlpha { .. }
+                | Instruction::PlaySample { .. }
+                | Instruction::StopSample
+                | Instruction::BlendMode { .. }
+                | Instruction::CollisionColor { .. }
+        )
+    }
+}
+
+type Decoder = fn(u16) -> Option<Instruction>;
+
+/// Indexed by the opcode's first nibble
+const DECODERS: [Decoder; 16] = [
+    decode_0, decode_1, decode_2, decode_3, decode_4, decode_5, decode_6, decode_7, decode_8,
+    decode_9, decode_a, decode_b, decode_c, decode_d, decode_e, decode_f,
+];
+
+/// The instruction `opcode` encodes, or `None` when it's not one
+pub fn decode(opcode: u16) -> Option<Instruction> {
+    DECODERS[(opcode >> 12) as usize](opcode)
+}
+
+fn x(opcode: u16) -> u8 {
+    ((opcode & 0x0F00) >> 8) as u8
+}
+
+fn y(opcode: u16) -> u8 {
+    ((opcode & 0x00F0) >> 4) as u8
+}
+
+fn n(opcode: u16) -> u8 {
+    (opcode & 0x000F) as u8
+}
+
+fn kk(opcode: u16) -> u8 {
+    (opcode & 0x00FF) as u8
+}
+
+fn nnn(opcode: u16) -> u16 {
+    opcode & 0x0FFF
+}
+
+fn decode_0(opcode: u16) -> Option<Instruction> {
+    Some(match opcode {
+        0x0010 => Instruction::MegaOff,
+        0x0011 => Instruction::MegaOn,
+        0x00B0..=0x00BF => Instruction::ScrollUp { n: n(opcode) },
+        0x00C0..=0x00CF => Instruction::ScrollDown { n: n(opcode) },
+        0x00E0 => Instruction::Clear,
+        0x00EE => Instruction::Return,
+        0x00FB => Instruction::ScrollRight,
+        0x00FC => Instruction::ScrollLeft,
+        0x00FD => Instruction::Exit,
+        0x00FE => Instruction::Lores,
+        0x00FF => Instruction::Hires,
+        0x0100..=0x01FF => Instruction::LoadIndexLong { kk: kk(opcode) },
+        0x0200..=0x02FF => Instruction::LoadPalette { kk: kk(opcode) },
+        0x0300..=0x03FF => Instruction::SpriteWidth { kk: kk(opcode) },
+        0x0400..=0x04FF => Instruction::SpriteHeight { kk: kk(opcode) },
+        0x0500..=0x05FF => Instruction::ScreenAlpha { kk: kk(opcode) },
+        0x0600..=0x060F => Instruction::PlaySample { n: n(opcode) },
+        0x0700 => Instruction::StopSample,
+        0x0800..=0x080F => Instruction::BlendMode { n: n(opcode) },
+        0x0900..=0x09FF => Instruction::CollisionColor { kk: kk(opcode) },
+        _ => Instruction::System { nnn: nnn(opcode) },
+    })
+}
+
+fn decode_1(opcode: u16) -> Option<Instruction> {
+    Some(Instruction::Jump { nnn: nnn(opcode) })
+}
+
+fn decode_2(opcode: u16) -> Option<Instruction> {
+    Some(Instruction::Call { nnn: nnn(opcode) })
+}
+
+fn decode_3(opcode: u16) -> Option<Instruction> {
+    Some(Instruction::SkipEqual {
+        x: x(opcode),
+        kk: kk(opcode),
+    })
+}
+
+fn decode_4(opcode: u16) -> Option<Instruction> {
+    Some(Instruction::SkipNotEqual {
+        x: x(opcode),
+        kk: kk(opcode),
+    })
+}
+
+fn decode_5(opcode: u16) -> Option<Instruction> {
+    let (x, y) = (x(opcode), y(opcode));
+    match n(opcode) {
+        0x0 => Some(Instruction::SkipEqualRegister { x, y }),
+        0x1 => Some(Instruction::AddNibbles { x, y }),
+        _ => None,
+    }
+}
+
+fn decode_6(opcode: u16) -> Option<Instruction> {
+    Some(Instruction::Load {
+        x: x(opcode),
+        kk: kk(opcode),
+    })
+}
+
+fn decode_7(opcode: u16) -> Option<Instruction> {
+    Some(Instruction::Add {
+        x: x(opcode),
+        kk: kk(opcode),
+    })
+}
+
+fn decode_8(opcode: u16) -> Option<Instruction> {
+    let (x, y) = (x(opcode), y(opcode));
+    Some(match n(opcode) {
+        0x0 => Instruction::Move { x, y },
+        0x1 => Instruction::Or { x, y },
+        0x2 => Instruction::And { x, y },
+        0x3 => Instruction::Xor { x, y },
+        0x4 => Instruction::AddRegister { x, y },
+        0x5 => Instruction::Sub { x, y },
+        0x6 => Instruction::ShiftRight { x, y },
+        0x7 => Instruction::SubReverse { x, y },
+        0xE => Instruction::ShiftLeft { x, y },
+        _ => return None,
+    })
+}
+
+fn decode_9(opcode: u16) -> Option<Instruction> {
+    (n(opcode) == 0).then(|| Instruction::SkipNotEqualRegister {
+        x: x(opcode),
+        y: y(opcode),
+    })
+}
+
+fn decode_a(opcode: u16) -> Option<Instruction> {
+    Some(Instruction::LoadIndex { nnn: nnn(opcode) })
+}
+
+fn decode_b(opcode: u16) -> Option<Instruction> {
+    Some(Instruction::JumpOffset {
+        x: x(opcode),
+        nnn: nnn(opcode),
+    })
+}
+
+fn decode_c(opcode: u16) -> Option<Instruction> {
+    Some(Instruction::Random {
+        x: x(opcode),
+        kk: kk(opcode),
+    })
+}
+
+fn decode_d(opcode: u16) -> Option<Instruction> {
+    Some(Instruction::Draw {
+        x: x(opcode),
+        y: y(opcode),
+        n: n(opcode),
+    })
+}
+
+fn decode_e(opcode: u16) -> Option<Instruction> {
+    let x = x(opcode);
+    match kk(opcode) {
+        0x9E => Some(Instruction::SkipKey { x }),
+        0xA1 => Some(Instruction::SkipNotKey { x }),
+        0xF2 => Some(Instruction::SkipKey2 { x }),
+        0xF5 => Some(Instruction::SkipNotKey2 { x }),
+        _ => None,
+    }
+}
+
+fn decode_f(opcode: u16) -> Option<Instruction> {
+    let x = x(opcode);
+    Some(match kk(opcode) {
+        0x02 if x == 0 => Instruction::LoadPattern,
+        0x07 => Instruction::LoadDelay { x },
+        0x0A => Instruction::WaitKey { x },
+        0x15 => Instruction::SetDelay { x },
+        0x18 => Instruction::SetSound { x },
+        0x1E => Instruction::AddIndex { x },
+        0x29 => Instruction::Font { x },
+        0x30 => Instruction::BigFont { x },
+        0x33 => Instruction::Bcd { x },
+        0x3A => Instruction::SetAudioPitch { x },
+        0x55 => Instruction::Store { x },
+        0x65 => Instruction::Restore { x },
+        0x75 => Instruction::StoreFlags { x },
+        0x85 => Instruction::RestoreFlags { x },
+        0xF8 => Instruction::SetPitch { x },
+        0xFB => Instruction::ReadPort { x },
+        _ => return None,
+    })
+}
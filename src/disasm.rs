@@ -0,0 +1,352 @@
+//! Turns opcodes into the mnemonics from Cowgod's CHIP-8 technical reference,
+//! e.g. `6A02` becomes `LD VA, 0x02`
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    decode::{Instruction as Op, decode},
+    symbols::Symbols,
+};
+
+/// A single decoded instruction of a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u16,
+    pub opcode: u16,
+    pub mnemonic: String,
+}
+
+/// Decodes a single opcode. Opcodes the interpreter doesn't know are shown as
+/// raw data words
+pub fn disassemble(opcode: u16) -> String {
+    disassemble_with(opcode, &Symbols::new())
+}
+
+/// Decodes a single opcode, showing the addresses of jumps, calls and
+/// `LD I` by their names in `symbols` when they have one
+pub fn disassemble_with(opcode: u16, symbols: &Symbols) -> String {
+    let Some(instruction) = decode(opcode) else {
+        return format!("DW 0x{:04X}", opcode);
+    };
+
+    match instruction {
+        Op::MegaOff => "MEGAOFF".to_string(),
+        Op::MegaOn => "MEGAON".to_string(),
+        Op::ScrollUp { n } => format!("SCU {}", n),
+        Op::ScrollDown { n } => format!("SCD {}", n),
+        Op::Clear => "CLS".to_string(),
+        Op::Return => "RET".to_string(),
+        Op::ScrollRight => "SCR".to_string(),
+        Op::ScrollLeft => "SCL".to_string(),
+        Op::Exit => "EXIT".to_string(),
+        Op::Lores => "LOW".to_string(),
+        Op::Hires => "HIGH".to_string(),
+        Op::LoadIndexLong { kk } => format!("LDHI I, 0x{:02X}", kk),
+        Op::LoadPalette { kk } => format!("LDPAL {}", kk),
+        Op::SpriteWidth { kk } => format!("SPRW {}", kk),
+        Op::SpriteHeight { kk } => format!("SPRH {}", kk),
+        Op::ScreenAlpha { kk } => format!("ALPHA 0x{:02X}", kk),
+        Op::PlaySample { n } => format!("DIGISND {}", n),
+        Op::StopSample => "STOPSND".to_string(),
+        Op::BlendMode { n } => format!("BMODE {}", n),
+        Op::CollisionColor { kk } => format!("CCOL {}", kk),
+        Op::System { nnn } => format!("SYS 0x{:03X}", nnn),
+        Op::Jump { nnn } => format!("JP {}", symbols.label(nnn)),
+        Op::Call { nnn } => format!("CALL {}", symbols.label(nnn)),
+        Op::SkipEqual { x, kk } => format!("SE V{:X}, 0x{:02X}", x, kk),
+        Op::SkipNotEqual { x, kk } => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        Op::SkipEqualRegister { x, y } => format!("SE V{:X}, V{:X}", x, y),
+        Op::AddNibbles { x, y } => format!("ADDN V{:X}, V{:X}", x, y),
+        Op::Load { x, kk } => format!("LD V{:X}, 0x{:02X}", x, kk),
+        Op::Add { x, kk } => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        Op::Move { x, y } => format!("LD V{:X}, V{:X}", x, y),
+        Op::Or { x, y } => format!("OR V{:X}, V{:X}", x, y),
+        Op::And { x, y } => format!("AND V{:X}, V{:X}", x, y),
+        Op::Xor { x, y } => format!("XOR V{:X}, V{:X}", x, y),
+        Op::AddRegister { x, y } => format!("ADD V{:X}, V{:X}", x, y),
+        Op::Sub { x, y } => format!("SUB V{:X}, V{:X}", x, y),
+        Op::ShiftRight { x, y } => format!("SHR V{:X}, V{:X}", x, y),
+        Op::SubReverse { x, y } => format!("SUBN V{:X}, V{:X}", x, y),
+        Op::ShiftLeft { x, y } => format!("SHL V{:X}, V{:X}", x, y),
+        Op::SkipNotEqualRegister { x, y } => format!("SNE V{:X}, V{:X}", x, y),
+        Op::LoadIndex { nnn } => format!("LD I, {}", symbols.label(nnn)),
+        Op::JumpOffset { nnn, .. } => format!("JP V0, {}", symbols.label(nnn)),
+        Op::Random { x, kk } => format!("RND V{:X}, 0x{:02X}", x, kk),
+        Op::Draw { x, y, n } => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Op::SkipKey { x } => format!("SKP V{:X}", x),
+        Op::SkipNotKey { x } => format!("SKNP V{:X}", x),
+        Op::SkipKey2 { x } => format!("SKP2 V{:X}", x),
+        Op::SkipNotKey2 { x } => format!("SKNP2 V{:X}", x),
+        Op::LoadPattern => "AUDIO".to_string(),
+        Op::LoadDelay { x } => format!("LD V{:X}, DT", x),
+        Op::WaitKey { x } => format!("LD V{:X}, K", x),
+        Op::SetDelay { x } => format!("LD DT, V{:X}", x),
+        Op::SetSound { x } => format!("LD ST, V{:X}", x),
+        Op::AddIndex { x } => format!("ADD I, V{:X}", x),
+        Op::Font { x } => format!("LD F, V{:X}", x),
+        Op::BigFont { x } => format!("LD HF, V{:X}", x),
+        Op::Bcd { x } => format!("LD B, V{:X}", x),
+        Op::SetAudioPitch { x } => format!("PITCH V{:X}", x),
+        Op::Store { x } => format!("LD [I], V{:X}", x),
+        Op::Restore { x } => format!("LD V{:X}, [I]", x),
+        Op::StoreFlags { x } => format!("LD R, V{:X}", x),
+        Op::RestoreFlags { x } => format!("LD V{:X}, R", x),
+        Op::SetPitch { x } => format!("OUT V{:X}", x),
+        Op::ReadPort { x } => format!("IN V{:X}", x),
+    }
+}
+
+/// The instruction an opcode belongs to, written the way opcode tables do
+/// with `X`, `Y`, `N`, `KK` and `NNN` standing for the operands, e.g. `8124`
+/// is `8XY4`. Unknown opcodes are `????`
+pub fn pattern(opcode: u16) -> &'static str {
+    let Some(instruction) = decode(opcode) else {
+        return "????";
+    };
+
+    match instruction {
+        Op::MegaOff => "0010",
+        Op::MegaOn => "0011",
+        Op::ScrollUp { .. } => "00BN",
+        Op::ScrollDown { .. } => "00CN",
+        Op::Clear => "00E0",
+        Op::Return => "00EE",
+        Op::ScrollRight => "00FB",
+        Op::ScrollLeft => "00FC",
+        Op::Exit => "00FD",
+        Op::Lores => "00FE",
+        Op::Hires => "00FF",
+        Op::LoadIndexLong { .. } => "01KK",
+        Op::LoadPalette { .. } => "02KK",
+        Op::SpriteWidth { .. } => "03KK",
+        Op::SpriteHeight { .. } => "04KK",
+        Op::ScreenAlpha { .. } => "05KK",
+        Op::PlaySample { .. } => "060N",
+        Op::StopSample => "0700",
+        Op::BlendMode { .. } => "080N",
+        Op::CollisionColor { .. } => "09KK",
+        Op::System { .. } => "0NNN",
+        Op::Jump { .. } => "1NNN",
+        Op::Call { .. } => "2NNN",
+        Op::SkipEqual { .. } => "3XKK",
+        Op::SkipNotEqual { .. } => "4XKK",
+        Op::SkipEqualRegister { .. } => "5XY0",
+        Op::AddNibbles { .. } => "5XY1",
+        Op::Load { .. } => "6XKK",
+        Op::Add { .. } => "7XKK",
+        Op::Move { .. } => "8XY0",
+        Op::Or { .. } => "8XY1",
+        Op::And { .. } => "8XY2",
+        Op::Xor { .. } => "8XY3",
+        Op::AddRegister { .. } => "8XY4",
+        Op::Sub { .. } => "8XY5",
+        Op::ShiftRight { .. } => "8XY6",
+        Op::SubReverse { .. } => "8XY7",
+        Op::ShiftLeft { .. } => "8XYE",
+        Op::SkipNotEqualRegister { .. } => "9XY0",
+        Op::LoadIndex { .. } => "ANNN",
+        Op::JumpOffset { .. } => "BNNN",
+        Op::Random { .. } => "CXKK",
+        Op::Draw { .. } => "DXYN",
+        Op::SkipKey { .. } => "EX9E",
+        Op::SkipNotKey { .. } => "EXA1",
+        Op::SkipKey2 { .. } => "EXF2",
+        Op::SkipNotKey2 { .. } => "EXF5",
+        Op::LoadPattern => "F002",
+        Op::LoadDelay { .. } => "FX07",
+        Op::WaitKey { .. } => "FX0A",
+        Op::SetDelay { .. } => "FX15",
+        Op::SetSound { .. } => "FX18",
+        Op::AddIndex { .. } => "FX1E",
+        Op::Font { .. } => "FX29",
+        Op::BigFont { .. } => "FX30",
+        Op::Bcd { .. } => "FX33",
+        Op::SetAudioPitch { .. } => "FX3A",
+        Op::Store { .. } => "FX55",
+        Op::Restore { .. } => "FX65",
+        Op::StoreFlags { .. } => "FX75",
+        Op::RestoreFlags { .. } => "FX85",
+        Op::SetPitch { .. } => "FXF8",
+        Op::ReadPort { .. } => "FXFB",
+    }
+}
+
+/// Decodes a whole program as if it was loaded at `origin`, two bytes at a
+/// time. Data mixed in with the code is decoded as well, see
+/// `disassemble_reachable` for telling them apart. A trailing odd byte is
+/// shown as `DB`
+pub fn disassemble_program(program: &[u8], origin: u16) -> Vec<Instruction> {
+    disassemble_program_with(program, origin, &Symbols::new())
+}
+
+/// `disassemble_program` with the addresses named by `symbols`
+pub fn disassemble_program_with(
+    program: &[u8],
+    origin: u16,
+    symbols: &Symbols,
+) -> Vec<Instruction> {
+    program
+        .chunks(2)
+        .enumerate()
+        .map(|(index, bytes)| {
+            let address = origin.wrapping_add((index as u16).wrapping_mul(2));
+            match *bytes {
+                [high, low] => {
+                    let opcode = u16::from_be_bytes([high, low]);
+                    Instruction {
+                        address,
+                        opcode,
+                        mnemonic: disassemble_with(opcode, symbols),
+                    }
+                }
+                [byte] => Instruction {
+                    address,
+                    opcode: byte as u16,
+                    mnemonic: format!("DB 0x{:02X}", byte),
+                },
+                _ => unreachable!("chunks of two"),
+            }
+        })
+        .collect()
+}
+
+/// A line of a program disassembled with `disassemble_reachable`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Code(Instruction),
+    /// Bytes no instruction reaches, like sprites, up to `DATA_LINE_LEN` of
+    /// them
+    Data {
+        address: u16,
+        bytes: Vec<u8>,
+    },
+}
+
+impl Line {
+    pub fn address(&self) -> u16 {
+        match self {
+            Line::Code(instruction) => instruction.address,
+            Line::Data { address, .. } => *address,
+        }
+    }
+}
+
+/// Most bytes shown on a single `.byte` line
+pub const DATA_LINE_LEN: usize = 8;
+
+/// Decodes the instructions of a program loaded at `origin` that can run,
+/// following jumps, calls and skips from its first byte, and shows the bytes
+/// none of them reach as `.byte` data. Jumps with `BNNN` depend on V0, so
+/// the code they lead to is only found when something else reaches it too
+pub fn disassemble_reachable(program: &[u8], origin: u16) -> Vec<Line> {
+    disassemble_reachable_with(program, origin, &Symbols::new())
+}
+
+/// `disassemble_reachable` with the addresses named by `symbols`. Data is
+/// also split where they name an address, so labels can go there
+pub fn disassemble_reachable_with(program: &[u8], origin: u16, symbols: &Symbols) -> Vec<Line> {
+    let code = reachable(program, origin);
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < program.len() {
+        let address = origin.wrapping_add(offset as u16);
+        if code[offset] {
+            let instruction = match program.get(offset..offset + 2) {
+                Some(&[high, low]) => {
+                    let opcode = u16::from_be_bytes([high, low]);
+                    Instruction {
+                        address,
+                        opcode,
+                        mnemonic: disassemble_with(opcode, symbols),
+                    }
+                }
+                _ => Instruction {
+                    address,
+                    opcode: program[offset] as u16,
+                    mnemonic: format!("DB 0x{:02X}", program[offset]),
+                },
+            };
+            lines.push(Line::Code(instruction));
+            offset += 2;
+        } else {
+            let len = 1 + code[offset + 1..]
+                .iter()
+                .zip(offset + 1..)
+                .take(DATA_LINE_LEN - 1)
+                .take_while(|&(&code, next)| {
+                    !code && symbols.name(origin.wrapping_add(next as u16)).is_none()
+                })
+                .count();
+            lines.push(Line::Data {
+                address,
+                bytes: program[offset..offset + len].to_vec(),
+            });
+            offset += len;
+        }
+    }
+    lines
+}
+
+/// Which bytes of the program start an instruction or belong to one. Both
+/// bytes of an instruction are marked, so the ones after it start the next.
+/// The second word of `01NN NNNN` is kept as its own line, it decodes to
+/// whatever its bytes happen to be
+fn reachable(program: &[u8], origin: u16) -> Vec<bool> {
+    let mut code = vec![false; program.len()];
+    let mut pending = vec![0];
+    let offset_of = |address: u16| address.wrapping_sub(origin) as usize;
+
+    while let Some(offset) = pending.pop() {
+        // Instructions overlapping ones already found are left out, so
+        // every byte belongs to a single line
+        let Some(bytes) = program.get(offset..offset + 2) else {
+            continue;
+        };
+        if code[offset] || code[offset + 1] {
+            continue;
+        }
+        code[offset] = true;
+        code[offset + 1] = true;
+
+        let next = offset + 2;
+        let Some(instruction) = decode(u16::from_be_bytes([bytes[0], bytes[1]])) else {
+            continue;
+        };
+        match instruction {
+            Op::Jump { nnn } => pending.push(offset_of(nnn)),
+            Op::Call { nnn } => {
+                pending.push(next);
+                pending.push(offset_of(nnn));
+            }
+            Op::Return | Op::Exit | Op::JumpOffset { .. } => {}
+            Op::SkipEqual { .. }
+            | Op::SkipNotEqual { .. }
+            | Op::SkipEqualRegister { .. }
+            | Op::SkipNotEqualRegister { .. }
+            | Op::SkipKey { .. }
+            | Op::SkipNotKey { .. }
+            | Op::SkipKey2 { .. }
+            | Op::SkipNotKey2 { .. } => {
+                pending.push(next);
+                pending.push(next + 2);
+            }
+            // The address' low word runs as part of this instruction, unless
+            // its bytes already belong to another one
+            Op::LoadIndexLong { .. } => {
+                if let Some(low) = code.get_mut(next..next + 2)
+                    && !low.contains(&true)
+                {
+                    low.fill(true);
+                }
+                pending.push(next + 2);
+            }
+            _ => pending.push(next),
+        }
+    }
+    code
+}
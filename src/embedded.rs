@@ -0,0 +1,112 @@
+//! A `DisplaySink` that draws through the `embedded-graphics` traits, so the
+//! no_std core can run on boards with screens like the SSD1306 or ILI9341.
+//!
+//! ```ignore
+//! let mut display = EmbeddedDisplay::monochrome(ssd1306);
+//! chip8.run_frame(ipf, &mut keypad, &mut display, &mut NullFrontend)?;
+//! display.take_error()?;
+//! ssd1306.flush()?;
+//! ```
+
+use embedded_graphics_core::{
+    draw_target::DrawTarget,
+    geometry::{Point, Size},
+    pixelcolor::BinaryColor,
+    primitives::Rectangle,
+};
+
+use crate::frontend::DisplaySink;
+
+/// Draws every frame onto `target`, scaled up by the largest whole factor
+/// that fits and centred. The rest of the target is cleared to `off` when
+/// the resolution changes
+pub struct EmbeddedDisplay<D: DrawTarget> {
+    target: D,
+    on: D::Color,
+    off: D::Color,
+    /// Resolution of the last frame, to know when the borders need clearing
+    size: Option<(usize, usize)>,
+    error: Option<D::Error>,
+}
+
+impl<D: DrawTarget> EmbeddedDisplay<D> {
+    pub fn new(target: D, on: D::Color, off: D::Color) -> Self {
+        Self {
+            target,
+            on,
+            off,
+            size: None,
+            error: None,
+        }
+    }
+
+    pub fn target(&self) -> &D {
+        &self.target
+    }
+
+    /// The target, e.g. for flushing a buffered driver after each frame
+    pub fn target_mut(&mut self) -> &mut D {
+        &mut self.target
+    }
+
+    pub fn into_target(self) -> D {
+        self.target
+    }
+
+    /// The first error the target returned since the last call. `present`
+    /// can't return it, and stops drawing until it's taken
+    pub fn take_error(&mut self) -> Result<(), D::Error> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn draw(&mut self, pixels: &[bool], width: usize, height: usize) -> Result<(), D::Error> {
+        let bounds = self.target.bounding_box();
+        let scale = (bounds.size.width / width as u32)
+            .min(bounds.size.height / height as u32)
+            .max(1);
+        let size = Size::new(width as u32 * scale, height as u32 * scale);
+        let offset = Point::new(
+            bounds.size.width.saturating_sub(size.width) as i32 / 2,
+            bounds.size.height.saturating_sub(size.height) as i32 / 2,
+        );
+
+        if self.size != Some((width, height)) {
+            self.target.clear(self.off)?;
+            self.size = Some((width, height));
+        }
+
+        let (on, off) = (self.on, self.off);
+        let colors = (0..size.height).flat_map(move |y| {
+            let row = (y / scale) as usize * width;
+            (0..size.width).map(move |x| {
+                if pixels[row + (x / scale) as usize] {
+                    on
+                } else {
+                    off
+                }
+            })
+        });
+        self.target
+            .fill_contiguous(&Rectangle::new(bounds.top_left + offset, size), colors)
+    }
+}
+
+impl<D: DrawTarget<Color = BinaryColor>> EmbeddedDisplay<D> {
+    /// Lit pixels are `BinaryColor::On`, as on an SSD1306
+    pub fn monochrome(target: D) -> Self {
+        Self::new(target, BinaryColor::On, BinaryColor::Off)
+    }
+}
+
+impl<D: DrawTarget> DisplaySink for EmbeddedDisplay<D> {
+    fn present(&mut self, pixels: &[bool], width: usize, height: usize) {
+        if self.error.is_none()
+            && let Err(e) = self.draw(pixels, width, height)
+        {
+            self.error = Some(e);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use alloc::string::String;
+use core::{error::Error, fmt};
+#[cfg(feature = "std")]
+use std::io;
+
+/// What happens when the interpreter runs into an opcode it doesn't know
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownOpcodes {
+    /// Stop with `Chip8Error::UnknownOpcode`
+    #[default]
+    Halt,
+    /// Skip it
+    Ignore,
+    /// Skip it and keep the error for `Chip8::take_warnings`
+    Warn,
+}
+
+/// Everything that can go wrong while loading or running a ROM
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// A subroutine call was made with all stack slots in use
+    StackOverflow { address: u16 },
+    /// A return was executed with an empty stack
+    StackUnderflow { address: u16 },
+    /// An instruction accessed memory past the end of the address space
+    MemoryOutOfBounds { address: usize },
+    /// The ROM doesn't fit into the memory available for programs
+    RomTooLarge { size: usize, max: usize },
+    /// The opcode at `address` isn't part of the instruction set
+    UnknownOpcode { address: u16, opcode: u16 },
+    /// A 0NNN at `address` called a routine no hook handles, with
+    /// `SystemCalls::Strict`
+    UnhandledSystemCall { address: u16, routine: u16 },
+    /// A save state is truncated or wasn't produced by this emulator
+    InvalidSaveState,
+    /// A save state was taken with a different ROM than the one loaded
+    SaveStateRomMismatch,
+    /// A save state was written by a newer version of the emulator
+    SaveStateVersion { version: u16 },
+    /// A JSON snapshot couldn't be parsed, with the reason
+    InvalidSnapshot(String),
+    /// An IPS or BPS patch couldn't be applied, with the reason
+    InvalidPatch(String),
+    /// A fontset passed to `Chip8::set_font` was rejected, with the reason
+    InvalidFont(String),
+    /// A zip or gzip file couldn't be read or contains no ROM, with the
+    /// reason
+    InvalidArchive(String),
+    /// The ROM file couldn't be read
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::StackOverflow { address } => {
+                write!(f, "stack overflow at {:#05x}", address)
+            }
+            Chip8Error::StackUnderflow { address } => {
+                write!(f, "stack underflow at {:#05x}", address)
+            }
+            Chip8Error::MemoryOutOfBounds { address } => {
+                write!(f, "memory access out of bounds at {:#05x}", address)
+            }
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is {} bytes, the maximum is {}", size, max)
+            }
+            Chip8Error::UnknownOpcode { address, opcode } => {
+                write!(f, "unknown opcode {:#06x} at {:#05x}", opcode, address)
+            }
+            Chip8Error::UnhandledSystemCall { address, routine } => {
+                write!(
+                    f,
+                    "unhandled machine code routine {:#05x} called at {:#05x}",
+                    routine, address
+                )
+            }
+            Chip8Error::InvalidSaveState => write!(f, "invalid save state"),
+            Chip8Error::SaveStateRomMismatch => write!(f, "save state is for a different ROM"),
+            Chip8Error::SaveStateVersion { version } => {
+                write!(f, "save state version {} is too new", version)
+            }
+            Chip8Error::InvalidSnapshot(reason) => write!(f, "invalid snapshot: {}", reason),
+            Chip8Error::InvalidPatch(reason) => write!(f, "invalid patch: {}", reason),
+            Chip8Error::InvalidFont(reason) => write!(f, "invalid font: {}", reason),
+            Chip8Error::InvalidArchive(reason) => write!(f, "invalid archive: {}", reason),
+            #[cfg(feature = "std")]
+            Chip8Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Chip8Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Chip8Error {
+    fn from(e: io::Error) -> Self {
+        Chip8Error::Io(e)
+    }
+}
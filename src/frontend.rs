@@ -0,0 +1,83 @@
+//! Traits that connect the interpreter core to a frontend. The core drives
+//! them once per frame from `Chip8::run_frame`, so any windowing, terminal or
+//! embedded backend only has to implement these to run ROMs.
+
+/// Receives the framebuffer at the end of every frame
+pub trait DisplaySink {
+    /// `pixels` holds `width * height` entries, row by row
+    fn present(&mut self, pixels: &[bool], width: usize, height: usize);
+
+    /// Receives the framebuffer of MegaChip mode instead of `present`, as
+    /// 0xAARRGGBB colors. Frontends without color get every pixel that
+    /// isn't black as lit
+    fn present_colors(&mut self, colors: &[u32], width: usize, height: usize) {
+        let pixels: alloc::vec::Vec<bool> = colors
+            .iter()
+            .map(|&color| color & 0x00FF_FFFF != 0)
+            .collect();
+        self.present(&pixels, width, height);
+    }
+}
+
+/// Provides the state of the 16-key hex keypad
+pub trait InputSource {
+    /// Called at the start of every frame. Implementations update `keys` with
+    /// the pressed state of each key, indexed by its hex value
+    fn poll(&mut self, keys: &mut [bool; 16]);
+
+    /// `poll` for CHIP-8X's second keypad. Frontends with a single keypad
+    /// leave it unpressed
+    fn poll_second(&mut self, _keys: &mut [bool; 16]) {}
+}
+
+/// Plays the CHIP-8 beep
+pub trait AudioSink {
+    /// Called at the end of every frame with whether the sound timer is
+    /// still running
+    fn set_tone(&mut self, active: bool);
+
+    /// Called right before `set_tone` with the XO-CHIP audio pattern to play
+    /// instead of the beep, once the ROM loaded one. Frontends that can't
+    /// play it keep beeping
+    fn set_pattern(&mut self, _pattern: Option<AudioPattern>) {}
+}
+
+/// XO-CHIP's audio: 128 one-bit samples, most significant bit first, looped
+/// while the sound timer runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub bits: [u8; 16],
+    /// Set by FX3A, 64 plays 4000 samples per second and every 48 up or
+    /// down doubles or halves that
+    pub pitch: u8,
+}
+
+impl AudioPattern {
+    /// Samples per second
+    #[cfg(feature = "std")]
+    pub fn rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// The sample at `index`, wrapping around after 128
+    pub fn bit(&self, index: usize) -> bool {
+        let index = index % 128;
+        self.bits[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+}
+
+/// A frontend that draws nothing, reads no input and stays silent. Useful for
+/// running the core headless or for filling in a part a frontend lacks
+pub struct NullFrontend;
+
+impl DisplaySink for NullFrontend {
+    fn present(&mut self, _pixels: &[bool], _width: usize, _height: usize) {}
+}
+
+impl InputSource for NullFrontend {
+    fn poll(&mut self, _keys: &mut [bool; 16]) {}
+}
+
+impl AudioSink for NullFrontend {
+    fn set_tone(&mut self, _active: bool) {}
+}
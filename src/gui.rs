@@ -0,0 +1,465 @@
+//! Debugger panels drawn with egui over the pixels window: registers, timers,
+//! the stack, the disassembly around the PC, a sprite viewer and a memory
+//! editor. F1 shows and hides them
+
+use chip_8_rs::{Chip8, disasm};
+use egui::{ClippedPrimitive, Context, TexturesDelta, ViewportId};
+use egui_wgpu::{Renderer, RendererOptions, ScreenDescriptor};
+use log::{error, warn};
+use pixels::{PixelsContext, wgpu};
+use winit::{
+    event::{ElementState, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+    window::Window,
+};
+
+use crate::{cli::parse_address, session::Session};
+
+/// Shows and hides the panels
+const TOGGLE_KEY: KeyCode = KeyCode::F1;
+/// Instructions listed before and after the PC
+const LISTING_CONTEXT: u16 = 8;
+/// Size of a sprite pixel in the sprite viewer, in points
+const SPRITE_CELL: f32 = 10.0;
+/// Bytes per row of the memory editor
+const MEMORY_COLUMNS: usize = 16;
+
+/// Settings of the sprite viewer
+struct SpriteViewer {
+    /// Address typed into the panel. The sprite at I is shown while it's
+    /// empty
+    address: String,
+    rows: usize,
+    /// Draw a SUPER-CHIP 16x16 sprite, two bytes per row, like DXY0
+    large: bool,
+}
+
+impl Default for SpriteViewer {
+    fn default() -> Self {
+        Self {
+            address: String::new(),
+            rows: 15,
+            large: false,
+        }
+    }
+}
+
+/// State of the memory editor
+#[derive(Default)]
+struct MemoryEditor {
+    /// The byte being edited
+    selected: Option<u16>,
+    /// New value of the selected byte, in hex
+    value: String,
+    /// Address typed into the go to field
+    target: String,
+    /// Row to scroll to on the next frame
+    scroll_to: Option<usize>,
+}
+
+pub struct Gui {
+    context: Context,
+    state: egui_winit::State,
+    renderer: Renderer,
+    screen: ScreenDescriptor,
+    paint_jobs: Vec<ClippedPrimitive>,
+    textures: TexturesDelta,
+    visible: bool,
+    sprites: SpriteViewer,
+    memory: MemoryEditor,
+}
+
+impl Gui {
+    pub fn new(window: &Window, device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let context = Context::default();
+        let state = egui_winit::State::new(
+            context.clone(),
+            ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            None,
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+        let size = window.inner_size();
+
+        Self {
+            context,
+            state,
+            renderer: Renderer::new(device, format, RendererOptions::default()),
+            screen: ScreenDescriptor {
+                size_in_pixels: [size.width, size.height],
+                pixels_per_point: window.scale_factor() as f32,
+            },
+            paint_jobs: Vec::new(),
+            textures: TexturesDelta::default(),
+            visible: false,
+            sprites: SpriteViewer::default(),
+            memory: MemoryEditor::default(),
+        }
+    }
+
+    /// Passes a window event to egui. Returns whether egui used it, in which
+    /// case the emulator shouldn't see it
+    pub fn handle_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput { event, .. }
+                if event.physical_key == PhysicalKey::Code(TOGGLE_KEY) =>
+            {
+                if event.state == ElementState::Pressed && !event.repeat {
+                    self.visible = !self.visible;
+                }
+                return true;
+            }
+            WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
+                self.screen.size_in_pixels = [size.width, size.height];
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.screen.pixels_per_point = *scale_factor as f32;
+            }
+            _ => {}
+        }
+
+        self.visible && self.state.on_window_event(window, event).consumed
+    }
+
+    /// Lays out the panels for the current state of the session. The buttons
+    /// act on it right away
+    pub fn prepare(&mut self, window: &Window, session: &mut Session) {
+        let input = self.state.take_egui_input(window);
+        let visible = self.visible;
+        let sprites = &mut self.sprites;
+        let memory = &mut self.memory;
+        let output = self.context.run_ui(input, |ui| {
+            if visible {
+                panels(ui.ctx(), session, sprites, memory);
+            }
+        });
+
+        self.textures.append(output.textures_delta);
+        self.state
+            .handle_platform_output(window, output.platform_output);
+        self.paint_jobs = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+    }
+
+    /// Draws the panels on top of the emulator display
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        context: &PixelsContext,
+    ) {
+        for (id, delta) in &self.textures.set {
+            self.renderer
+                .update_texture(&context.device, &context.queue, *id, delta);
+        }
+        self.renderer.update_buffers(
+            &context.device,
+            &context.queue,
+            encoder,
+            &self.paint_jobs,
+            &self.screen,
+        );
+
+        {
+            let pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("egui"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                ..Default::default()
+            });
+            self.renderer
+                .render(&mut pass.forget_lifetime(), &self.paint_jobs, &self.screen);
+        }
+
+        for id in std::mem::take(&mut self.textures).free {
+            self.renderer.free_texture(&id);
+        }
+    }
+}
+
+fn panels(
+    context: &Context,
+    session: &mut Session,
+    sprites: &mut SpriteViewer,
+    memory: &mut MemoryEditor,
+) {
+    egui::Window::new("CPU")
+        .default_pos([8.0, 8.0])
+        .resizable(false)
+        .show(context, |ui| {
+            controls(ui, session);
+            ui.separator();
+            registers(ui, session);
+            ui.separator();
+            stack(ui, session);
+        });
+
+    egui::Window::new("Disassembly")
+        .default_pos([240.0, 8.0])
+        .resizable(false)
+        .show(context, |ui| listing(ui, session));
+
+    egui::Window::new("Sprite")
+        .default_pos([8.0, 360.0])
+        .resizable(false)
+        .show(context, |ui| sprite(ui, session, sprites));
+
+    egui::Window::new("Memory")
+        .default_pos([240.0, 360.0])
+        .resizable(false)
+        .show(context, |ui| memory_editor(ui, session, memory));
+}
+
+fn controls(ui: &mut egui::Ui, session: &mut Session) {
+    ui.horizontal(|ui| {
+        let label = if session.paused { "Continue" } else { "Pause" };
+        if ui.button(label).clicked() {
+            session.paused = !session.paused;
+        }
+        if ui
+            .add_enabled(session.paused, egui::Button::new("Step"))
+            .clicked()
+            && let Err(e) = session.chip8.cycle()
+        {
+            error!("Step failed: {}", e);
+        }
+        if ui
+            .add_enabled(session.paused, egui::Button::new("Step over"))
+            .clicked()
+        {
+            if session.chip8.step_over() {
+                session.paused = false;
+            } else if let Err(e) = session.chip8.cycle() {
+                error!("Step failed: {}", e);
+            }
+        }
+    });
+}
+
+fn registers(ui: &mut egui::Ui, session: &Session) {
+    let chip8 = &session.chip8;
+    egui::Grid::new("registers").striped(true).show(ui, |ui| {
+        for (index, value) in chip8.registers().iter().enumerate() {
+            ui.monospace(format!("V{:X} {:02X}", index, value));
+            if index % 4 == 3 {
+                ui.end_row();
+            }
+        }
+    });
+    ui.monospace(format!("PC {:03X}  I {:03X}", chip8.pc(), chip8.index()));
+    ui.monospace(format!(
+        "DT {:02X}  ST {:02X}",
+        chip8.delay_timer(),
+        chip8.sound_timer()
+    ));
+}
+
+fn stack(ui: &mut egui::Ui, session: &Session) {
+    ui.label("Call stack");
+    let chip8 = &session.chip8;
+    let mut location = chip8.pc();
+    let frames = chip8.call_stack();
+    // Innermost call first, like a backtrace
+    for (depth, frame) in frames.iter().rev().enumerate() {
+        ui.monospace(format!(
+            "#{:<2} {:03X} in {}",
+            depth,
+            location,
+            session.symbols.subroutine_name(frame.subroutine)
+        ))
+        .on_hover_text(format!(
+            "Called from {:03X}, returns to {:03X}",
+            frame.call_site, frame.return_address
+        ));
+        location = frame.call_site;
+    }
+    ui.monospace(format!("#{:<2} {:03X} in main", frames.len(), location));
+}
+
+fn listing(ui: &mut egui::Ui, session: &Session) {
+    let chip8 = &session.chip8;
+    let pc = chip8.pc();
+    // Stay aligned with the PC, instructions are two bytes
+    let start = pc.saturating_sub(LISTING_CONTEXT * 2) as usize;
+    let end = (pc as usize + LISTING_CONTEXT as usize * 2 + 2).min(chip8.memory().len());
+    let Some(bytes) = chip8.memory().get(start..end) else {
+        return;
+    };
+
+    for instruction in disasm::disassemble_program_with(bytes, start as u16, &session.symbols) {
+        if let Some(name) = session.symbols.name(instruction.address) {
+            ui.monospace(format!("{}:", name));
+        }
+        let text = format!(
+            "{} {:03X}  {:04X}  {}",
+            marker(chip8, instruction.address),
+            instruction.address,
+            instruction.opcode,
+            instruction.mnemonic
+        );
+        if instruction.address == pc {
+            ui.label(egui::RichText::new(text).monospace().strong());
+        } else {
+            ui.monospace(text);
+        }
+    }
+}
+
+/// Points at the PC and marks breakpoints
+fn marker(chip8: &Chip8, address: u16) -> &'static str {
+    if address == chip8.pc() {
+        ">"
+    } else if chip8.breakpoints().any(|breakpoint| breakpoint == address) {
+        "*"
+    } else {
+        " "
+    }
+}
+
+/// Draws the bytes at an address the way DXYN would, to check what I points
+/// at
+fn sprite(ui: &mut egui::Ui, session: &Session, viewer: &mut SpriteViewer) {
+    let chip8 = &session.chip8;
+    ui.horizontal(|ui| {
+        ui.label("Address");
+        ui.add(
+            egui::TextEdit::singleline(&mut viewer.address)
+                .hint_text(format!("I ({:03X})", chip8.index()))
+                .desired_width(64.0),
+        );
+    });
+    ui.checkbox(&mut viewer.large, "16x16");
+    ui.add_enabled(
+        !viewer.large,
+        egui::Slider::new(&mut viewer.rows, 1..=15).text("rows"),
+    );
+
+    let address = if viewer.address.trim().is_empty() {
+        chip8.index()
+    } else {
+        match parse_address(viewer.address.trim()) {
+            Ok(address) => address as u32,
+            Err(e) => {
+                ui.label(e);
+                return;
+            }
+        }
+    };
+    let (width, rows) = if viewer.large {
+        (16, 16)
+    } else {
+        (8, viewer.rows)
+    };
+    let bytes_per_row = width / 8;
+    let memory = chip8.memory();
+    let start = address as usize;
+    let end = (start + rows * bytes_per_row).min(memory.len());
+    let bytes = &memory[start.min(end)..end];
+
+    let size = egui::vec2(width as f32, rows as f32) * SPRITE_CELL;
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let origin = response.rect.min;
+    painter.rect_filled(response.rect, 0.0, ui.visuals().extreme_bg_color);
+    for (row, row_bytes) in bytes.chunks(bytes_per_row).enumerate() {
+        for column in 0..width {
+            let byte = row_bytes.get(column / 8).copied().unwrap_or(0);
+            if byte & 0x80 >> (column % 8) == 0 {
+                continue;
+            }
+            let min = origin + egui::vec2(column as f32, row as f32) * SPRITE_CELL;
+            painter.rect_filled(
+                egui::Rect::from_min_size(min, egui::Vec2::splat(SPRITE_CELL)),
+                0.0,
+                ui.visuals().strong_text_color(),
+            );
+        }
+    }
+}
+
+/// A hex dump of the whole memory. Clicking a byte selects it for editing,
+/// the new value is written when Enter is pressed
+fn memory_editor(ui: &mut egui::Ui, session: &mut Session, editor: &mut MemoryEditor) {
+    ui.horizontal(|ui| {
+        ui.label("Go to");
+        let response = ui.add(egui::TextEdit::singleline(&mut editor.target).desired_width(64.0));
+        if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+            match parse_address(editor.target.trim()) {
+                Ok(address) => {
+                    editor.scroll_to = Some(address as usize / MEMORY_COLUMNS);
+                    select(editor, &session.chip8, address);
+                }
+                Err(e) => error!("{}", e),
+            }
+        }
+    });
+
+    let chip8 = &session.chip8;
+    let row_height =
+        ui.text_style_height(&egui::TextStyle::Monospace) + ui.spacing().item_spacing.y;
+    let rows = chip8.memory().len() / MEMORY_COLUMNS;
+    let mut scroll = egui::ScrollArea::vertical().max_height(240.0);
+    if let Some(row) = editor.scroll_to.take() {
+        scroll = scroll.vertical_scroll_offset(row as f32 * row_height);
+    }
+    let mut clicked = None;
+    scroll.show_rows(ui, row_height, rows, |ui, visible| {
+        for row in visible {
+            let start = row * MEMORY_COLUMNS;
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 4.0;
+                ui.monospace(format!("{:03X}", start));
+                for (column, byte) in chip8.memory()[start..start + MEMORY_COLUMNS]
+                    .iter()
+                    .enumerate()
+                {
+                    let address = (start + column) as u16;
+                    let mut text = egui::RichText::new(format!("{:02X}", byte)).monospace();
+                    // Mark the instruction at the PC
+                    if address == chip8.pc() || address == chip8.pc() + 1 {
+                        text = text.strong();
+                    }
+                    let selected = editor.selected == Some(address);
+                    if ui.selectable_label(selected, text).clicked() {
+                        clicked = Some(address);
+                    }
+                }
+            });
+        }
+    });
+    if let Some(address) = clicked {
+        select(editor, chip8, address);
+    }
+
+    let Some(address) = editor.selected else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        ui.label(format!("{:03X} =", address));
+        let response = ui.add(egui::TextEdit::singleline(&mut editor.value).desired_width(32.0));
+        if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+            let value = editor.value.trim();
+            match u8::from_str_radix(value.strip_prefix("0x").unwrap_or(value), 16) {
+                Ok(value) => {
+                    if let Err(e) = session.chip8.poke(address, value) {
+                        error!("{}", e);
+                    }
+                }
+                Err(_) => warn!("Invalid byte {}, expected 00-FF", value),
+            }
+        }
+    });
+}
+
+fn select(editor: &mut MemoryEditor, chip8: &Chip8, address: u16) {
+    editor.selected = Some(address);
+    editor.value = format!("{:02X}", chip8.memory()[address as usize]);
+}
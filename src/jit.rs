@@ -0,0 +1,247 @@
+//! An experimental backend that translates basic blocks of CHIP-8 code into
+//! chains of closures, so a hot loop is decoded once instead of on every
+//! pass. Overkill for a machine this slow, but handy for headless analysis
+//! runs that push millions of instructions per frame.
+//!
+//! A block runs from its first instruction up to and including the next one
+//! that can jump, skip or wait. Compiled blocks keep the bytes they were
+//! made from and are recompiled when memory no longer holds those bytes, so
+//! self-modifying code, `poke` and loading a save state all work. A block
+//! that overwrites its own remaining instructions stops right after the
+//! write.
+//!
+//! Breakpoints, watchpoints, `run_to` targets, the profile and the history
+//! are checked or recorded per instruction by `Chip8::cycle`, so while any
+//! of them is set the JIT steps the interpreter instead. So it does while
+//! there's a 0NNN hook, which can change anything.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::iter;
+
+use crate::{
+    chip8::Chip8,
+    decode::{Instruction, decode},
+    error::Chip8Error,
+    frontend::{AudioSink, DisplaySink, InputSource},
+};
+
+/// Longest block compiled, so a long straight run of code is checked for
+/// changes every so often
+const MAX_BLOCK_INSTRUCTIONS: usize = 64;
+
+/// One slot for every address PC can hold. The `megachip` quirk's memory is
+/// far larger, but code only runs from its first 64KB
+const BLOCK_SLOTS: usize = 0x10000;
+
+type Op = Box<dyn Fn(&mut Chip8) -> Result<(), Chip8Error>>;
+
+struct Block {
+    /// The bytes the block was compiled from
+    code: Vec<u8>,
+    ops: Vec<Op>,
+    /// Which of `ops` write to memory and might overwrite the block
+    writes: Vec<bool>,
+}
+
+/// Compiled blocks for a `Chip8`, created once and passed the machine on
+/// every run. The same `Jit` shouldn't be used for two machines at once,
+/// though nothing breaks if it is: blocks that don't match the machine's
+/// memory are recompiled
+pub struct Jit {
+    /// Indexed by the address of the block's first instruction, `BLOCK_SLOTS`
+    /// long once the JIT first runs
+    blocks: Vec<Option<Block>>,
+    compiled: usize,
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Jit {
+    pub fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            compiled: 0,
+        }
+    }
+
+    /// How many blocks were compiled so far, recompiles included
+    pub fn compiled(&self) -> usize {
+        self.compiled
+    }
+
+    /// Drops every compiled block
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+
+    /// The JIT's version of `Chip8::run_frame`
+    pub fn run_frame(
+        &mut self,
+        chip8: &mut Chip8,
+        instructions: u32,
+        input: &mut impl InputSource,
+        display: &mut impl DisplaySink,
+        audio: &mut impl AudioSink,
+    ) -> Result<(), Chip8Error> {
+        input.poll(&mut chip8.keypad);
+        input.poll_second(&mut chip8.second_keypad);
+        self.run(chip8, instructions)?;
+        chip8.tick_timers();
+
+        chip8.present(display);
+        audio.set_pattern(chip8.audio_pattern());
+        audio.set_tone(chip8.sound_timer() > 0);
+        Ok(())
+    }
+
+    /// Executes `instructions` instructions, like calling `Chip8::cycle`
+    /// that many times. Timers are not touched
+    pub fn run(&mut self, chip8: &mut Chip8, instructions: u32) -> Result<(), Chip8Error> {
+        if debugging(chip8) {
+            for _ in 0..instructions {
+                chip8.cycle()?;
+                if chip8.stop.is_some() || chip8.vblank_wait {
+                    break;
+                }
+            }
+            return Ok(());
+        }
+
+        if self.blocks.is_empty() {
+            self.blocks = iter::repeat_with(|| None).take(BLOCK_SLOTS).collect();
+        }
+
+        let mut remaining = instructions as usize;
+        while remaining > 0 && !chip8.vblank_wait {
+            let start = chip8.pc as usize;
+            if start >= chip8.memory.len() {
+                // Outside of memory, the interpreter reports the error
+                chip8.cycle()?;
+                remaining -= 1;
+                continue;
+            }
+            let slot = &mut self.blocks[start];
+            if slot
+                .as_ref()
+                .is_none_or(|block| !chip8.memory[start..].starts_with(&block.code))
+            {
+                *slot = compile(&chip8.memory, start);
+                self.compiled += 1;
+            }
+            let Some(block) = slot.as_ref() else {
+                // Only the last byte of memory is left, the opcode can't be
+                // fetched
+                chip8.cycle()?;
+                remaining -= 1;
+                continue;
+            };
+
+            for (op, &writes) in block.ops.iter().zip(&block.writes).take(remaining) {
+                op(chip8)?;
+                remaining -= 1;
+                if writes && !chip8.memory[start..].starts_with(&block.code) || chip8.vblank_wait {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn debugging(chip8: &Chip8) -> bool {
+    !chip8.breakpoints.is_empty()
+        || !chip8.watchpoints.is_empty()
+        || chip8.run_to.is_some()
+        || chip8.profile.is_some()
+        || chip8.history.is_some()
+        || chip8.resume
+        || chip8.system_hook.is_some()
+}
+
+/// Translates the block starting at `start`, or returns `None` when there's
+/// no whole opcode there
+fn compile(memory: &[u8], start: usize) -> Option<Block> {
+    let mut block = Block {
+        code: Vec::new(),
+        ops: Vec::new(),
+        writes: Vec::new(),
+    };
+
+    let mut address = start;
+    while let Some(&[high, low]) = memory.get(address..address + 2) {
+        let opcode = u16::from_be_bytes([high, low]);
+        let instruction = decode(opcode);
+        block.code.extend_from_slice(&[high, low]);
+        block.writes.push(matches!(
+            instruction,
+            Some(Instruction::Bcd { .. } | Instruction::Store { .. })
+        ));
+        block.ops.push(translate(instruction, opcode));
+        address += 2;
+
+        if instruction.is_none_or(ends_block) || block.ops.len() == MAX_BLOCK_INSTRUCTIONS {
+            break;
+        }
+    }
+
+    (!block.ops.is_empty()).then_some(block)
+}
+
+/// Instructions after which the next one to run isn't necessarily the next
+/// one in memory
+fn ends_block(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Return
+            | Instruction::Jump { .. }
+            | Instruction::Call { .. }
+            | Instruction::JumpOffset { .. }
+            | Instruction::SkipEqual { .. }
+            | Instruction::SkipNotEqual { .. }
+            | Instruction::SkipEqualRegister { .. }
+            | Instruction::SkipNotEqualRegister { .. }
+            | Instruction::SkipKey { .. }
+            | Instruction::SkipNotKey { .. }
+            | Instruction::SkipKey2 { .. }
+            | Instruction::SkipNotKey2 { .. }
+            | Instruction::WaitKey { .. }
+            | Instruction::LoadIndexLong { .. }
+    )
+}
+
+/// The closure that runs an instruction. The simplest and most common ones
+/// are done inline, everything else goes through the interpreter's handler
+fn translate(instruction: Option<Instruction>, opcode: u16) -> Op {
+    let Some(instruction) = instruction else {
+        return Box::new(move |chip8| chip8.unknown_opcode(opcode));
+    };
+
+    match instruction {
+        Instruction::Jump { nnn } => Box::new(move |chip8| {
+            chip8.pc = nnn;
+            Ok(())
+        }),
+        Instruction::Load { x, kk } => Box::new(move |chip8| {
+            chip8.v[x as usize] = kk;
+            chip8.advance(2)
+        }),
+        Instruction::Add { x, kk } => Box::new(move |chip8| {
+            let x = x as usize;
+            chip8.v[x] = chip8.v[x].wrapping_add(kk);
+            chip8.advance(2)
+        }),
+        Instruction::Move { x, y } => Box::new(move |chip8| {
+            chip8.v[x as usize] = chip8.v[y as usize];
+            chip8.advance(2)
+        }),
+        Instruction::LoadIndex { nnn } => Box::new(move |chip8| {
+            chip8.i = nnn as u32;
+            chip8.advance(2)
+        }),
+        _ => Box::new(move |chip8| chip8.execute(instruction, opcode)),
+    }
+}
@@ -0,0 +1,60 @@
+//! Where the keypad sits on keyboards with other layouts than QWERTY. The
+//! keypad always takes the same 4x4 block of physical keys on the left, but
+//! frontends that match keys by the character they type need to know which
+//! characters those keys have
+
+use serde::Deserialize;
+
+/// The COSMAC VIP keypad, row by row
+pub const KEYPAD: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyboardLayout {
+    #[default]
+    Qwerty,
+    /// French
+    Azerty,
+    /// German and Central European
+    Qwertz,
+    Dvorak,
+    Colemak,
+}
+
+impl KeyboardLayout {
+    /// The characters of the block of keys the keypad takes, row by row. On
+    /// QWERTY:
+    ///
+    /// ```text
+    /// 1 2 3 4      1 2 3 C
+    /// Q W E R  ->  4 5 6 D
+    /// A S D F      7 8 9 E
+    /// Z X C V      A 0 B F
+    /// ```
+    fn rows(self) -> [&'static str; 4] {
+        match self {
+            KeyboardLayout::Qwerty => ["1234", "qwer", "asdf", "zxcv"],
+            // Without shift the number row types symbols
+            KeyboardLayout::Azerty => ["&é\"'", "azer", "qsdf", "wxcv"],
+            KeyboardLayout::Qwertz => ["1234", "qwer", "asdf", "yxcv"],
+            KeyboardLayout::Dvorak => ["1234", "',.p", "aoeu", ";qjk"],
+            KeyboardLayout::Colemak => ["1234", "qwfp", "arst", "zxcv"],
+        }
+    }
+
+    /// The lowercase character for each CHIP-8 key, indexed by key
+    pub fn keys(self) -> [char; 16] {
+        let mut keys = [' '; 16];
+        for (row, chars) in KEYPAD.iter().zip(self.rows()) {
+            for (&key, c) in row.iter().zip(chars.chars()) {
+                keys[key as usize] = c;
+            }
+        }
+        keys
+    }
+}
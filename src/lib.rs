@@ -0,0 +1,62 @@
+//! A CHIP-8 and SUPER-CHIP interpreter core, independent of any frontend.
+//!
+//! ```no_run
+//! use chip_8_rs::Chip8;
+//!
+//! let mut chip8 = Chip8::new();
+//! chip8.load_rom("pong.ch8").unwrap();
+//! chip8.key_down(0x1);
+//! chip8.cycle().unwrap();
+//! chip8.tick_timers();
+//! let pixels = chip8.get_display();
+//! ```
+//!
+//! Without the default `std` feature the crate is `no_std` and only needs
+//! `alloc`. ROMs are then passed to `Chip8::load`, and CXKK needs a seed
+//! from `Chip8::set_seed` since there's no entropy source to draw from.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod archive;
+pub mod asm;
+mod chip8;
+mod chip8x;
+mod condition;
+pub mod debug;
+pub mod decode;
+pub mod disasm;
+#[cfg(feature = "embedded-graphics")]
+pub mod embedded;
+mod error;
+pub mod frontend;
+#[cfg(feature = "jit")]
+pub mod jit;
+mod megachip;
+pub mod patch;
+pub mod plugin;
+pub mod profile;
+#[cfg(feature = "python")]
+mod python;
+mod quirks;
+pub mod romdb;
+#[cfg(feature = "serde")]
+mod serialize;
+mod state;
+pub mod symbols;
+pub mod system;
+pub mod trace;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use chip8::{
+    Chip8, FONT_SIZE, HIRES_HEIGHT, HIRES_WIDTH, LORES_HEIGHT, LORES_WIDTH, MEMORY_SIZE,
+    PROGRAM_START, STACK_DEPTH, XO_CHIP_MEMORY,
+};
+pub use chip8x::CHIP8X_PROGRAM_START;
+pub use error::{Chip8Error, UnknownOpcodes};
+pub use megachip::{Blend, MEGACHIP_HEIGHT, MEGACHIP_MEMORY, MEGACHIP_WIDTH, Sample};
+pub use quirks::Quirks;
+pub use state::{STATE_HEADER_SIZE, STATE_SIZE};
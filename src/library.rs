@@ -0,0 +1,82 @@
+//! The ROM library: every ROM in a directory tree, named after the title the
+//! `--database` or the built-in list has for it
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chip_8_rs::{archive, romdb};
+
+use crate::metadata::Database;
+
+/// How deep into subdirectories the scan goes, which also keeps it out of
+/// symlink loops
+const MAX_DEPTH: usize = 8;
+
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    /// The title if the ROM is known, or else its file name
+    pub name: String,
+    /// Lowercase hex SHA-1 of the ROM
+    pub hash: String,
+    /// Authors and release from the database
+    pub summary: Option<String>,
+}
+
+/// Finds the ROMs under `dir`, sorted by name. Hidden files and directories
+/// and archives are skipped, and so are files that can't be read
+pub fn scan(dir: &Path, database: Option<&Database>) -> Vec<LibraryEntry> {
+    let mut roms = Vec::new();
+    let mut pending = vec![(dir.to_path_buf(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if file_name.starts_with('.') {
+                continue;
+            }
+
+            if path.is_dir() {
+                if depth < MAX_DEPTH {
+                    pending.push((path, depth + 1));
+                }
+            } else if archive::is_rom_name(&file_name)
+                && let Ok(rom) = fs::read(&path)
+            {
+                roms.push(entry_for(path, file_name, &rom, database));
+            }
+        }
+    }
+
+    roms.sort_by_key(|entry| entry.name.to_lowercase());
+    roms
+}
+
+fn entry_for(
+    path: PathBuf,
+    file_name: String,
+    rom: &[u8],
+    database: Option<&Database>,
+) -> LibraryEntry {
+    let hash = romdb::sha1_hex(rom);
+    if let Some(metadata) = database.and_then(|database| database.lookup(&hash)) {
+        return LibraryEntry {
+            path,
+            name: metadata.title.clone(),
+            summary: Some(metadata.summary()),
+            hash,
+        };
+    }
+
+    let name = romdb::lookup(rom)
+        .map(|info| info.title)
+        .filter(|title| !title.is_empty())
+        .unwrap_or(file_name);
+    LibraryEntry {
+        path,
+        name,
+        hash,
+        summary: None,
+    }
+}
@@ -0,0 +1,182 @@
+//! Where the emulator's messages go: stderr, and a file when one is set.
+//! Which ones are shown is picked with a spec like `info,netplay=debug`, a
+//! level for everything and levels for single modules, set with `--log` or
+//! `log` in the config. Modules are the ones in `src`, e.g. `session`,
+//! `netplay` or `sdl`
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    str::FromStr,
+    sync::{Mutex, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Shown when no spec is given
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+static LOGGER: Logger = Logger {
+    filters: RwLock::new(Filters {
+        default: DEFAULT_LEVEL,
+        modules: Vec::new(),
+    }),
+    file: Mutex::new(None),
+};
+
+struct Logger {
+    filters: RwLock<Filters>,
+    file: Mutex<Option<File>>,
+}
+
+/// The level for everything, and the ones for modules that differ
+struct Filters {
+    default: LevelFilter,
+    /// Module path and its level
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filters {
+    fn parse(spec: &str) -> Result<Self, String> {
+        let mut filters = Self {
+            default: DEFAULT_LEVEL,
+            modules: Vec::new(),
+        };
+        for entry in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            match entry.split_once('=') {
+                Some((module, level)) => filters
+                    .modules
+                    .push((module.trim().to_string(), parse_level(level)?)),
+                None => filters.default = parse_level(entry)?,
+            }
+        }
+        Ok(filters)
+    }
+
+    /// The level of the module a message comes from, the most specific
+    /// filter that matches it winning
+    fn level(&self, target: &str) -> LevelFilter {
+        // Targets start with the crate's name, which filters leave out
+        let module = target.split_once("::").map_or(target, |(_, module)| module);
+        self.modules
+            .iter()
+            .filter(|(filter, _)| {
+                module == filter
+                    || module
+                        .strip_prefix(filter.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(filter, _)| filter.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    /// The most verbose level any module gets, messages above it aren't
+    /// even formatted
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, |max, level| max.max(level))
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    LevelFilter::from_str(level.trim()).map_err(|_| {
+        format!(
+            "Invalid log level {}, expected off, error, warn, info, debug or trace",
+            level
+        )
+    })
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filters
+            .read()
+            .is_ok_and(|filters| metadata.level() <= filters.level(metadata.target()))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        // Plain messages are what the emulator always printed, the level and
+        // module only come up for the ones that need attention or debugging
+        match record.level() {
+            Level::Info => eprintln!("{}", record.args()),
+            Level::Warn | Level::Error => {
+                eprintln!(
+                    "{}: {}",
+                    record.level().as_str().to_lowercase(),
+                    record.args()
+                )
+            }
+            Level::Debug | Level::Trace => {
+                eprintln!("[{} {}] {}", record.level(), record.target(), record.args())
+            }
+        }
+
+        if let Ok(mut file) = self.file.lock()
+            && let Some(file) = file.as_mut()
+        {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let _ = writeln!(
+                file,
+                "{}.{:03} {:<5} {}: {}",
+                time.as_secs(),
+                time.subsec_millis(),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock()
+            && let Some(file) = file.as_mut()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Installs the logger with the default level, so messages can be logged
+/// before the config is read
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(DEFAULT_LEVEL);
+    }
+}
+
+/// Applies the filter `spec` when there is one, and appends the messages to
+/// `file` as well when it's given
+pub fn configure(spec: Option<&str>, file: Option<&Path>) -> Result<(), String> {
+    if let Some(spec) = spec {
+        let filters = Filters::parse(spec)?;
+        log::set_max_level(filters.max_level());
+        if let Ok(mut current) = LOGGER.filters.write() {
+            *current = filters;
+        }
+    }
+    if let Some(path) = file {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))?;
+        if let Ok(mut current) = LOGGER.file.lock() {
+            *current = Some(file);
+        }
+    }
+    Ok(())
+}
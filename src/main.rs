@@ -1,132 +1,831 @@
+// Without a windowed frontend only the terminal is left, which doesn't use
+// the palette, hotkeys or save slots
+#![cfg_attr(not(any(feature = "sdl", feature = "pixels")), allow(dead_code))]
+
 use std::{
-    env,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
     time::{Duration, Instant},
 };
 
-use chip8::Chip8;
-use sdl2::{keyboard::Keycode, pixels::Color, rect::Rect};
+use chip_8_rs::{
+    Chip8, Chip8Error, PROGRAM_START, archive, asm, disasm,
+    frontend::NullFrontend,
+    jit::Jit,
+    patch,
+    plugin::{Event, Plugins},
+    symbols::Symbols,
+    trace,
+};
+use clap::Parser;
+use cli::{AsmArgs, BenchArgs, Cli, Command, DisasmArgs, Frontend, RunArgs, ScreenArgs, TraceArgs};
+use config::Config;
+use debugger::Flow;
+use log::{error, info};
+use metadata::RomMetadata;
+use movie::Movie;
+use pacer::FramePacer;
+
+mod audio;
+mod autosave;
+#[cfg(feature = "builtin-roms")]
+mod builtin;
+mod cheats;
+mod cli;
+mod config;
+mod crash;
+mod dap;
+mod debugger;
+#[cfg(feature = "egui")]
+mod gui;
+mod keyboard;
+#[cfg(feature = "sdl")]
+mod library;
+mod logger;
+mod metadata;
+mod movie;
+mod netplay;
+mod pacer;
+mod palette;
+#[cfg(feature = "pixels")]
+mod pixels;
+#[cfg(feature = "plugins")]
+mod plugins;
+#[cfg(feature = "sdl")]
+mod recent;
+#[cfg(feature = "remote")]
+mod remote;
+mod rom_profile;
+mod screenshot;
+#[cfg(feature = "sdl")]
+mod sdl;
+mod session;
+mod slots;
+mod stats;
+mod tui;
+
+/// Timers and the display are updated at 60Hz, the CPU executes a fixed
+/// number of instructions within each of those frames
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+const INSTRUCTIONS_PER_FRAME: u32 = 10;
+/// The ROM path that reads the ROM from stdin
+const STDIN_PATH: &str = "-";
+
+fn main() -> ExitCode {
+    logger::init();
+    crash::install_hook();
+    match Cli::parse().into_command() {
+        Command::Run(args) => run(*args),
+        Command::Disasm(args) => disasm(args),
+        Command::Asm(args) => asm(args),
+        Command::Trace(args) => trace(args),
+        #[cfg(feature = "json")]
+        Command::DumpState(args) => dump_state(args),
+        Command::Bench(args) => bench(args),
+        Command::Screen(args) => screen(args),
+    }
+}
+
+/// Fails when the ROM couldn't be loaded or a headless run stopped with an
+/// error, so scripts can check the exit status
+fn run(mut args: RunArgs) -> ExitCode {
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let spec = args.log.as_deref().or(config.log.as_deref());
+    let log_file = args.log_file.as_deref().or(config.log_file.as_deref());
+    if let Err(e) = logger::configure(spec, log_file) {
+        error!("{}", e);
+        return ExitCode::FAILURE;
+    }
+    if args.database.is_none() {
+        args.database = config.database.clone();
+    }
+    #[cfg(feature = "builtin-roms")]
+    if let Some(builtin) = args.builtin {
+        args.rom = Some(builtin.path());
+    }
+
+    if args.debug && args.rom.as_deref() == Some(Path::new(STDIN_PATH)) {
+        error!("The debugger reads commands from stdin, so the ROM can't come from it");
+        return ExitCode::FAILURE;
+    }
+
+    if args.headless || args.frontend == Frontend::Tui {
+        if args.debug {
+            error!("The debugger needs a window");
+            return ExitCode::FAILURE;
+        }
+        if args.dap.is_some() && !args.headless {
+            error!("The debug adapter needs a window or --headless");
+            return ExitCode::FAILURE;
+        }
+        if args.host.is_some() || args.connect.is_some() {
+            error!("Netplay needs a window");
+            return ExitCode::FAILURE;
+        }
+        if args.split_screen() {
+            error!("Running two machines side by side needs a window");
+            return ExitCode::FAILURE;
+        }
+        let Some(rom_path) = &args.rom else {
+            error!("A ROM path is required without a window");
+            return ExitCode::FAILURE;
+        };
+        let profile = match config.key_profile(&args, rom_path) {
+            Ok(profile) => profile,
+            Err(e) => {
+                error!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let Some((mut chip8, _)) = load_chip8(rom_path, &args) else {
+            return ExitCode::FAILURE;
+        };
+        let mut movie = match Movie::from_args(&args, &mut chip8) {
+            Ok(movie) => movie,
+            Err(e) => {
+                error!("Failed to open movie: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let Some(mut plugins) = load_plugins(&args, &mut chip8) else {
+            return ExitCode::FAILURE;
+        };
 
-mod chip8;
+        let status = if args.headless {
+            let result = crash::guard(&mut chip8, |chip8| {
+                run_headless(chip8, movie.as_mut(), &mut plugins, &args)
+            });
+            match result {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    error!("Emulation stopped: {}", e);
+                    ExitCode::FAILURE
+                }
+            }
+        } else {
+            let result = crash::guard(&mut chip8, |chip8| {
+                tui::run(
+                    chip8,
+                    movie.as_mut(),
+                    &mut plugins,
+                    args.instructions_per_frame(),
+                    &config,
+                    profile,
+                    args.mute,
+                )
+            });
+            match result {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    error!("Terminal error: {}", e);
+                    ExitCode::FAILURE
+                }
+            }
+        };
+        if let Some(movie) = &movie {
+            movie.finish();
+        }
+        print_profile(&chip8);
+        return status;
+    } else {
+        #[cfg(any(feature = "sdl", feature = "pixels"))]
+        run_window(&args, &config);
+    }
 
-const PIXEL_SIZE: u32 = 10;
-const WIDTH: u32 = 64 * PIXEL_SIZE;
-const HEIGHT: u32 = 32 * PIXEL_SIZE;
+    ExitCode::SUCCESS
+}
 
-fn main() {
-    // SDL
-    let sdl_ctx = sdl2::init().unwrap();
-    let video_subsystem = sdl_ctx.video().unwrap();
+/// Prints every instruction of the ROM as address, opcode and mnemonic, and
+/// the bytes no instruction reaches as `.byte` lines. Named addresses get a
+/// label
+fn disasm(args: DisasmArgs) -> ExitCode {
+    let rom = match read_rom(&args.rom) {
+        Ok(rom) => rom,
+        Err(e) => {
+            error!("Failed to read ROM: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
 
-    let window = video_subsystem
-        .window("chip-8-rs", WIDTH, HEIGHT)
-        .position_centered()
-        .build()
-        .unwrap();
+    let symbols = load_symbols(args.symbols.as_deref(), &args.rom);
 
-    let mut canvas = window.into_canvas().build().unwrap();
+    if args.linear {
+        for instruction in disasm::disassemble_program_with(&rom, PROGRAM_START, &symbols) {
+            print_label(&symbols, instruction.address);
+            print_instruction(&instruction);
+        }
+        return ExitCode::SUCCESS;
+    }
 
-    let mut event_pump = sdl_ctx.event_pump().unwrap();
-    let mut running: bool = true;
+    for line in disasm::disassemble_reachable_with(&rom, PROGRAM_START, &symbols) {
+        print_label(&symbols, line.address());
+        match line {
+            disasm::Line::Code(instruction) => print_instruction(&instruction),
+            disasm::Line::Data { address, bytes } => {
+                let bytes: Vec<String> =
+                    bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+                println!("{:04X}        .byte {}", address, bytes.join(", "));
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
 
-    // Args
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("Usage: chip8-rs <path_to_rom>");
-        return;
+fn print_label(symbols: &Symbols, address: u16) {
+    if let Some(name) = symbols.name(address) {
+        println!("{}:", name);
     }
+}
+
+fn print_instruction(instruction: &disasm::Instruction) {
+    println!(
+        "{:04X}  {:04X}  {}",
+        instruction.address, instruction.opcode, instruction.mnemonic
+    );
+}
 
-    // Emulator
+fn asm(args: AsmArgs) -> ExitCode {
+    let source = match std::fs::read_to_string(&args.source) {
+        Ok(source) => source,
+        Err(e) => {
+            error!("Failed to read {}: {}", args.source.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let (rom, symbols) = match asm::assemble_with_symbols(&source) {
+        Ok(assembled) => assembled,
+        Err(e) => {
+            error!("{}: {}", args.source.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let output = args
+        .output
+        .unwrap_or_else(|| args.source.with_extension("ch8"));
+    if let Err(e) = std::fs::write(&output, &rom) {
+        error!("Failed to write {}: {}", output.display(), e);
+        return ExitCode::FAILURE;
+    }
+    info!("Wrote {} bytes to {}", rom.len(), output.display());
+
+    if args.symbols {
+        let path = symbols_path(&output);
+        if let Err(e) = std::fs::write(&path, symbols.to_string()) {
+            error!("Failed to write {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+        info!("Wrote {} symbols to {}", symbols.len(), path.display());
+    }
+    ExitCode::SUCCESS
+}
+
+/// Prints a trace of the ROM, or compares it with a reference trace and
+/// fails at the first difference
+fn trace(args: TraceArgs) -> ExitCode {
+    let reference = match &args.compare {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(text) => Some(text),
+            Err(e) => {
+                error!("Failed to read {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
     let mut chip8 = Chip8::new();
-    if let Err(e) = chip8.load_rom(&args[1]) {
-        println!("Failed to load ROM: {}", e);
-        return;
-    }
-
-    while running {
-        let start = Instant::now();
-
-        for event in event_pump.poll_iter() {
-            match event {
-                sdl2::event::Event::Quit { .. }
-                | sdl2::event::Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => {
-                    println!("Exiting...");
-                    running = false;
-                }
-                sdl2::event::Event::KeyDown {
-                    keycode: Some(keycode),
-                    ..
-                } => {
-                    if let Some(key) = map_keycode_to_key(keycode) {
-                        chip8.key_down(key);
-                    }
-                }
-                sdl2::event::Event::KeyUp {
-                    keycode: Some(keycode),
-                    ..
-                } => {
-                    if let Some(key) = map_keycode_to_key(keycode) {
-                        chip8.key_up(key);
-                    }
+    if let Some(preset) = args.quirks {
+        chip8.set_quirks(preset.into());
+    }
+    if let Some(seed) = args.seed {
+        chip8.set_seed(seed);
+    }
+    if let Err(e) = read_rom(&args.rom).and_then(|rom| chip8.load(&rom)) {
+        error!("Failed to load ROM: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let Some(reference) = reference else {
+        let mut output: Box<dyn Write> = match &args.output {
+            Some(path) => match std::fs::File::create(path) {
+                Ok(file) => Box::new(io::BufWriter::new(file)),
+                Err(e) => {
+                    error!("Failed to create {}: {}", path.display(), e);
+                    return ExitCode::FAILURE;
                 }
-                _ => {}
+            },
+            None => Box::new(io::stdout().lock()),
+        };
+        let mut status = ExitCode::SUCCESS;
+        for number in 1..=args.instructions {
+            let written = writeln!(output, "{}", trace::line(&chip8));
+            if let Err(e) = written {
+                error!("Failed to write the trace: {}", e);
+                return ExitCode::FAILURE;
+            }
+            if let Err(e) = trace_step(&mut chip8, number, args.ipf) {
+                error!("Emulation stopped: {}", e);
+                status = ExitCode::FAILURE;
+                break;
+            }
+        }
+        return match output.flush() {
+            Ok(()) => status,
+            Err(e) => {
+                error!("Failed to write the trace: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    };
+
+    let mut previous = None;
+    for (number, expected) in (1..).zip(reference.lines()) {
+        let actual = trace::line(&chip8);
+        if let Some(mismatch) = trace::compare(&actual, expected) {
+            println!(
+                "Diverged at instruction {}: {} is {}, the reference has {}",
+                number, mismatch.field, mismatch.actual, mismatch.expected
+            );
+            if let Some(previous) = previous {
+                println!("  before:    {}", previous);
+            }
+            println!("  actual:    {}", actual);
+            println!("  reference: {}", expected);
+            return ExitCode::FAILURE;
+        }
+        if let Err(e) = trace_step(&mut chip8, number, args.ipf) {
+            error!("Emulation stopped at instruction {}: {}", number, e);
+            return ExitCode::FAILURE;
+        }
+        previous = Some(actual);
+    }
+    println!("Matched all {} instructions", reference.lines().count());
+    ExitCode::SUCCESS
+}
+
+/// Executes instruction `number`, counting from 1, and ticks the timers
+/// after every `ipf` instructions like `Chip8::run_frame` does
+fn trace_step(chip8: &mut Chip8, number: u64, ipf: u32) -> Result<(), Chip8Error> {
+    chip8.cycle()?;
+    if number.is_multiple_of(ipf as u64) {
+        chip8.tick_timers();
+    }
+    Ok(())
+}
+
+/// Runs the given number of frames with no input and prints the resulting
+/// state
+#[cfg(feature = "json")]
+fn dump_state(args: cli::DumpStateArgs) -> ExitCode {
+    let loaded = match (&args.state, &args.rom) {
+        (Some(path), _) => std::fs::read_to_string(path)
+            .map_err(Chip8Error::from)
+            .and_then(|json| Chip8::from_json(&json)),
+        (None, Some(rom)) => {
+            let mut chip8 = Chip8::new();
+            if let Some(preset) = args.quirks {
+                chip8.set_quirks(preset.into());
+            }
+            read_rom(rom).and_then(|rom| chip8.load(&rom).map(|()| chip8))
+        }
+        (None, None) => unreachable!("clap requires a ROM or a state"),
+    };
+    let mut chip8 = match loaded {
+        Ok(chip8) => chip8,
+        Err(e) => {
+            error!("Failed to load: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(seed) = args.seed {
+        chip8.set_seed(seed);
+    }
+
+    for _ in 0..args.frames {
+        if let Err(e) = chip8.run_frame(
+            args.ipf,
+            &mut NullFrontend,
+            &mut NullFrontend,
+            &mut NullFrontend,
+        ) {
+            error!("Emulation stopped: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+    println!("{}", chip8.to_json());
+    ExitCode::SUCCESS
+}
+
+/// Runs frames back to back for the given time, without drawing or waiting
+/// for the next frame. Each frame counts as the instructions it was given,
+/// like the stats overlay
+fn bench(args: BenchArgs) -> ExitCode {
+    let mut chip8 = Chip8::new();
+    if let Some(preset) = args.quirks {
+        chip8.set_quirks(preset.into());
+    }
+    chip8.set_seed(args.seed);
+    if let Err(e) = read_rom(&args.rom).and_then(|rom| chip8.load(&rom)) {
+        error!("Failed to load ROM: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let mut jit = args.jit.then(Jit::new);
+    let duration = Duration::from_secs_f64(args.seconds);
+    let start = Instant::now();
+    let mut frames: u64 = 0;
+    while start.elapsed() < duration {
+        let result = match &mut jit {
+            Some(jit) => jit.run_frame(
+                &mut chip8,
+                args.ipf,
+                &mut NullFrontend,
+                &mut NullFrontend,
+                &mut NullFrontend,
+            ),
+            None => chip8.run_frame(
+                args.ipf,
+                &mut NullFrontend,
+                &mut NullFrontend,
+                &mut NullFrontend,
+            ),
+        };
+        if let Err(e) = result {
+            error!("Emulation stopped after {} frames: {}", frames, e);
+            return ExitCode::FAILURE;
+        }
+        frames += 1;
+    }
+
+    let seconds = start.elapsed().as_secs_f64();
+    let frames_per_second = frames as f64 / seconds;
+    println!("Ran {} frames in {:.2}s", frames, seconds);
+    println!("{:.0} frames/s", frames_per_second);
+    println!("{:.0} instructions/s", frames_per_second * args.ipf as f64);
+    println!(
+        "{:.1}x real time",
+        frames_per_second * FRAME_DURATION.as_secs_f64()
+    );
+    ExitCode::SUCCESS
+}
+
+/// Runs whole frames while the instructions last, then single instructions
+/// for the rest, and shows the display
+fn screen(args: ScreenArgs) -> ExitCode {
+    let mut chip8 = Chip8::new();
+    if let Some(preset) = args.quirks {
+        chip8.set_quirks(preset.into());
+    }
+    chip8.set_seed(args.seed);
+    if let Err(e) = read_rom(&args.rom).and_then(|rom| chip8.load(&rom)) {
+        error!("Failed to load ROM: {}", e);
+        return ExitCode::FAILURE;
+    }
+    let mut movie = match args
+        .input
+        .as_deref()
+        .map(|path| Movie::play(path, &mut chip8))
+    {
+        Some(Ok(movie)) => Some(movie),
+        Some(Err(e)) => {
+            error!("Failed to open input: {}", e);
+            return ExitCode::FAILURE;
+        }
+        None => None,
+    };
+
+    let ipf = args.ipf as u64;
+    let mut result = Ok(());
+    for _ in 0..args.cycles / ipf {
+        result = chip8.run_frame(
+            args.ipf,
+            &mut movie::input(movie.as_mut(), &mut NullFrontend),
+            &mut NullFrontend,
+            &mut NullFrontend,
+        );
+        if result.is_err() {
+            break;
+        }
+    }
+    if result.is_ok() {
+        result = (0..args.cycles % ipf).try_for_each(|_| chip8.cycle());
+    }
+    if let Err(e) = result {
+        error!("Emulation stopped: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    match &args.output {
+        Some(path) => {
+            if let Err(e) = screenshot::save_pbm(&chip8, path) {
+                error!("Failed to write {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
             }
         }
+        None => print!("{}", screenshot::text(&chip8)),
+    }
+    ExitCode::SUCCESS
+}
 
-        chip8.cycle();
-        draw_display(&chip8, &mut canvas);
+/// Runs one of the windowed frontends, picked with `--frontend`
+#[cfg(any(feature = "sdl", feature = "pixels"))]
+fn run_window(args: &RunArgs, config: &Config) {
+    let palette = match config.palette(args) {
+        Ok(palette) => palette,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
 
-        let delay = 2000;
-        let elapsed = start.elapsed();
-        if elapsed < Duration::from_micros(delay) {
-            std::thread::sleep(Duration::from_micros(delay) - elapsed);
+    match args.frontend {
+        #[cfg(feature = "sdl")]
+        Frontend::Sdl => sdl::run(args, config, palette),
+        #[cfg(feature = "pixels")]
+        Frontend::Pixels if args.split_screen() => {
+            error!("Running two machines side by side needs the SDL frontend");
         }
+        #[cfg(feature = "pixels")]
+        Frontend::Pixels => pixels::run(args, config, palette),
+        Frontend::Tui => unreachable!("handled by run"),
     }
 }
 
-fn draw_display(chip8: &Chip8, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
+/// Loads the plugins given with `--plugin` and registers them with `chip8`
+#[cfg(feature = "plugins")]
+fn load_plugins(args: &RunArgs, chip8: &mut Chip8) -> Option<Plugins> {
+    let mut loaded = Plugins::new();
+    for path in &args.plugins {
+        match plugins::load(path) {
+            Ok(plugin) => {
+                info!("Loaded plugin {}", plugin.name());
+                loaded.register(plugin, chip8);
+            }
+            Err(e) => {
+                error!("Failed to load plugin {}: {}", path.display(), e);
+                return None;
+            }
+        }
+    }
+    Some(loaded)
+}
 
-    canvas.set_draw_color(Color::GREEN);
+#[cfg(not(feature = "plugins"))]
+fn load_plugins(_args: &RunArgs, _chip8: &mut Chip8) -> Option<Plugins> {
+    Some(Plugins::new())
+}
 
-    for y in 0..32 {
-        for x in 0..64 {
-            let index = y * 64 + x;
-            if chip8.get_display()[index] {
-                let _ = canvas.fill_rect(Rect::new(
-                    (x as u32 * 10) as i32,
-                    (y as u32 * 10) as i32,
-                    10,
-                    10,
-                ));
+/// Reads the ROM at `path`, or from stdin when the path is `-` so it can be
+/// piped in from an assembler or generator
+fn read_rom(path: &Path) -> Result<Vec<u8>, Chip8Error> {
+    if path != Path::new(STDIN_PATH) {
+        return archive::read_rom(path);
+    }
+    let mut data = Vec::new();
+    io::stdin().lock().read_to_end(&mut data)?;
+    archive::decompress(data)
+}
+
+/// Where the symbols of the ROM at `rom_path` are looked for when no symbol
+/// file is given, `asm --symbols` writes them there
+fn symbols_path(rom_path: &Path) -> PathBuf {
+    let mut path = rom_path.as_os_str().to_owned();
+    path.push(".sym");
+    PathBuf::from(path)
+}
+
+/// Reads the symbol file at `path`, or the one next to the ROM. Without
+/// either the addresses are shown as they are
+fn load_symbols(path: Option<&Path>, rom_path: &Path) -> Symbols {
+    let (path, given) = match path {
+        Some(path) => (path.to_path_buf(), true),
+        None => (symbols_path(rom_path), false),
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !given => return Symbols::new(),
+        Err(e) => {
+            error!("Failed to read {}: {}", path.display(), e);
+            return Symbols::new();
+        }
+    };
+    match Symbols::parse(&text) {
+        Ok(symbols) => {
+            info!("Loaded {} symbols from {}", symbols.len(), path.display());
+            symbols
+        }
+        Err(e) => {
+            error!("{}: {}", path.display(), e);
+            Symbols::new()
+        }
+    }
+}
+
+/// Sets up the machine from the command line and loads the ROM. Returns what
+/// the `--database` knows about the ROM as well
+fn load_chip8(rom_path: &Path, args: &RunArgs) -> Option<(Chip8, Option<RomMetadata>)> {
+    #[cfg(feature = "builtin-roms")]
+    let rom = match args.builtin {
+        // Unless another ROM was picked in the window since
+        Some(builtin) if rom_path == builtin.path() => Ok(builtin.rom().to_vec()),
+        _ => read_rom(rom_path),
+    };
+    #[cfg(not(feature = "builtin-roms"))]
+    let rom = read_rom(rom_path);
+    let mut rom = match rom {
+        Ok(rom) => rom,
+        Err(e) => {
+            error!("Failed to load ROM: {}", e);
+            return None;
+        }
+    };
+    // The database knows the original ROM, not the patched one
+    let metadata = match &args.database {
+        Some(database) => RomMetadata::lookup(database, &rom).unwrap_or_else(|e| {
+            error!("{}", e);
+            None
+        }),
+        None => None,
+    };
+    for path in &args.patches {
+        let patched = std::fs::read(path)
+            .map_err(Chip8Error::from)
+            .and_then(|patch| patch::apply(&rom, &patch));
+        match patched {
+            Ok(patched) => {
+                info!("Applied {}", path.display());
+                rom = patched;
+            }
+            Err(e) => {
+                error!("Failed to apply {}: {}", path.display(), e);
+                return None;
             }
         }
     }
 
-    canvas.present();
+    let mut chip8 = Chip8::new();
+    let preset = args
+        .quirks
+        .or_else(|| metadata.as_ref().and_then(RomMetadata::quirk_preset));
+    if let Some(preset) = preset {
+        chip8.set_quirks(preset.into());
+    }
+    if let Some(seed) = args.seed {
+        chip8.set_seed(seed);
+    }
+    for (address, condition) in &args.breakpoints {
+        match condition {
+            Some(condition) => chip8.add_conditional_breakpoint(*address, condition.clone()),
+            None => chip8.add_breakpoint(*address),
+        }
+    }
+    for &watchpoint in &args.watchpoints {
+        chip8.add_watchpoint(watchpoint);
+    }
+    if args.profile {
+        chip8.enable_profiling();
+    }
+    // For crash dumps. The JIT would interpret while it's recorded
+    if !args.jit {
+        chip8.enable_history(crash::HISTORY_LEN);
+    }
+    chip8.set_unknown_opcodes(args.unknown_opcodes.into());
+    if let Some(path) = &args.font {
+        let font = std::fs::read(path).map_err(Chip8Error::from);
+        if let Err(e) = font.and_then(|font| chip8.set_font(&font)) {
+            error!("Failed to load font {}: {}", path.display(), e);
+            return None;
+        }
+    }
+    if let Err(e) = chip8.load(&rom) {
+        error!("Failed to load ROM: {}", e);
+        return None;
+    }
+
+    if let Some(metadata) = &metadata {
+        info!("{}", metadata.summary());
+        let recommendations = metadata.recommendations();
+        if !recommendations.is_empty() {
+            info!("Recommended: {}", recommendations.join(", "));
+        }
+    } else if args.quirks.is_none()
+        && let Some(info) = chip8.rom_info()
+    {
+        info!("Recognized {}, using {} quirks", info.title, info.platform);
+    }
+    Some((chip8, metadata))
 }
 
-fn map_keycode_to_key(keycode: Keycode) -> Option<u8> {
-    match keycode {
-        Keycode::Num1 => Some(0x1),
-        Keycode::Num2 => Some(0x2),
-        Keycode::Num3 => Some(0x3),
-        Keycode::Num4 => Some(0xC),
-        Keycode::Q => Some(0x4),
-        Keycode::W => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::R => Some(0xD),
-        Keycode::A => Some(0x7),
-        Keycode::S => Some(0x8),
-        Keycode::D => Some(0x9),
-        Keycode::F => Some(0xE),
-        Keycode::Z => Some(0xA),
-        Keycode::X => Some(0x0),
-        Keycode::C => Some(0xB),
-        Keycode::V => Some(0xF),
-        _ => None,
+/// Prints the execution counts when the ROM ran with `--profile`
+fn print_profile(chip8: &Chip8) {
+    if let Some(profile) = chip8.profile() {
+        println!("{}", profile);
+    }
+}
+
+/// Runs the core without a window or audio until it hits an error, a
+/// breakpoint, a watchpoint or the frame limit given with `--frames`. With
+/// `--remote` or `--dap` a breakpoint or watchpoint pauses instead, until a
+/// client resumes
+fn run_headless(
+    chip8: &mut Chip8,
+    mut movie: Option<&mut Movie>,
+    plugins: &mut Plugins,
+    args: &RunArgs,
+) -> Result<(), Chip8Error> {
+    #[cfg(feature = "remote")]
+    let mut remote = match &args.remote {
+        Some(address) => Some(remote::Remote::spawn(address).map_err(|e| {
+            std::io::Error::new(e.kind(), format!("remote control on {}: {}", address, e))
+        })?),
+        None => None,
+    };
+    let mut dap = match &args.dap {
+        Some(address) => Some(dap::Dap::spawn(address).map_err(|e| {
+            std::io::Error::new(e.kind(), format!("debug adapter on {}: {}", address, e))
+        })?),
+        None => None,
+    };
+    let symbols = match &args.rom {
+        Some(rom_path) if dap.is_some() => load_symbols(args.symbols.as_deref(), rom_path),
+        _ => Symbols::new(),
+    };
+    // Only a client can pause, the editor gets to set breakpoints first
+    let mut paused = dap.is_some();
+    // Why the last frame stopped, reported by the debug adapter
+    let mut stopped = None;
+    let mut frames = 0;
+    let mut pacer = FramePacer::new();
+    let mut jit = args.jit.then(Jit::new);
+    while args.frames.is_none_or(|limit| frames < limit) {
+        #[cfg(feature = "remote")]
+        if let Some(remote) = &mut remote {
+            remote.poll(chip8, &mut paused);
+        }
+        if let Some(dap) = &mut dap
+            && dap.poll(chip8, &mut paused, stopped.take(), &symbols) == Flow::Quit
+        {
+            break;
+        }
+
+        if !paused {
+            let mut keys = NullFrontend;
+            let mut input = movie::input(movie.as_deref_mut(), &mut keys);
+            match &mut jit {
+                Some(jit) => jit.run_frame(
+                    chip8,
+                    args.instructions_per_frame(),
+                    &mut input,
+                    &mut NullFrontend,
+                    &mut NullFrontend,
+                )?,
+                None => chip8.run_frame(
+                    args.instructions_per_frame(),
+                    &mut input,
+                    &mut NullFrontend,
+                    &mut NullFrontend,
+                )?,
+            }
+            frames += 1;
+            plugins.frame(chip8);
+            session::report_warnings(chip8);
+
+            #[cfg(feature = "remote")]
+            if let Some(remote) = &mut remote {
+                remote.push_frame(chip8, frames);
+            }
+
+            if let Some(stop) = chip8.take_stop() {
+                info!("{}", stop);
+                plugins.event(chip8, &Event::Stopped(stop));
+                #[cfg(feature = "remote")]
+                if remote.is_some() {
+                    paused = true;
+                    continue;
+                }
+                if dap.is_some() {
+                    paused = true;
+                    stopped = Some(stop);
+                    continue;
+                }
+                break;
+            }
+        }
+
+        if !args.unthrottled {
+            pacer.wait();
+        }
+    }
+
+    if let Some(dap) = &mut dap {
+        dap.finish();
     }
+    Ok(())
 }
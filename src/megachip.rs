@@ -0,0 +1,351 @@
+//! MegaChip8, Revival Studios' extension with a 256x192 display of 32-bit
+//! color sprites, a 24-bit I register and sampled sound. It's only
+//! recognized with the `megachip` quirk, and a program switches into it
+//! with 0011 and back with 0010.
+//!
+//! In MegaChip mode DXYN draws a sprite of the size set with 03NN and 04NN,
+//! one palette index per byte with 0 being transparent. Frames are drawn
+//! off screen and only shown once 00E0 finishes them, which also starts the
+//! next one. VF is set when a sprite covers a pixel of the collision color
+//! picked with 09NN. Sprites in the font below `PROGRAM_START` are still
+//! drawn a bit per pixel, in white.
+//!
+//! The sample started by 060N is kept track of but not played yet.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::{
+    chip8::{Chip8, PROGRAM_START},
+    decode::Instruction,
+    error::Chip8Error,
+};
+
+/// Width and height of the MegaChip display
+pub const MEGACHIP_WIDTH: usize = 256;
+pub const MEGACHIP_HEIGHT: usize = 192;
+
+/// Memory with the `megachip` quirk, all of what the 24-bit I register can
+/// point at
+pub const MEGACHIP_MEMORY: usize = 1 << 24;
+
+const PIXELS: usize = MEGACHIP_WIDTH * MEGACHIP_HEIGHT;
+const OPAQUE_BLACK: u32 = 0xFF00_0000;
+const WHITE: u32 = 0xFFFF_FFFF;
+/// Palette index font pixels count as for collisions
+const FONT_INDEX: u8 = 0xFF;
+
+/// How a sprite's colors are combined with the pixels under them, set with
+/// 080N
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Blend {
+    #[default]
+    Normal,
+    /// A quarter of the sprite's color
+    Quarter,
+    /// Half of the sprite's color
+    Half,
+    Add,
+    Multiply,
+}
+
+impl Blend {
+    pub(crate) fn from_mode(mode: u8) -> Self {
+        match mode {
+            1 => Blend::Quarter,
+            2 => Blend::Half,
+            3 => Blend::Add,
+            4 => Blend::Multiply,
+            _ => Blend::Normal,
+        }
+    }
+
+    /// The N of the 080N that picks this
+    pub(crate) fn mode(self) -> u8 {
+        match self {
+            Blend::Normal => 0,
+            Blend::Quarter => 1,
+            Blend::Half => 2,
+            Blend::Add => 3,
+            Blend::Multiply => 4,
+        }
+    }
+
+    fn apply(self, below: u32, color: u32) -> u32 {
+        let channel = |value: u32, shift: u32| (value >> shift & 0xFF) as u8;
+        let mix = |below: u8, color: u8| -> u8 {
+            let (below, color) = (below as u32, color as u32);
+            (match self {
+                Blend::Normal => color,
+                Blend::Quarter => (below * 3 + color) / 4,
+                Blend::Half => (below + color) / 2,
+                Blend::Add => (below + color).min(0xFF),
+                Blend::Multiply => below * color / 0xFF,
+            }) as u8
+        };
+        [16, 8, 0].iter().fold(OPAQUE_BLACK, |result, &shift| {
+            result | (mix(channel(below, shift), channel(color, shift)) as u32) << shift
+        })
+    }
+}
+
+/// The sample started by 060N
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sample {
+    /// Address of the sample's header
+    pub address: u32,
+    /// Whether it starts over when it ends
+    pub looping: bool,
+}
+
+/// The state of MegaChip mode, which only exists while it's switched on
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct MegaChip {
+    /// Colors as 0xAARRGGBB, indexed by the bytes of sprites. Index 0 is
+    /// never drawn
+    pub(crate) palette: Vec<u32>,
+    pub(crate) sprite_width: usize,
+    pub(crate) sprite_height: usize,
+    /// Brightness of the whole screen, for fading in and out
+    pub(crate) alpha: u8,
+    pub(crate) blend: Blend,
+    pub(crate) collision: u8,
+    /// Palette indices of the frame being drawn, for collisions
+    pub(crate) indices: Vec<u8>,
+    /// The frame being drawn
+    pub(crate) frame: Vec<u32>,
+    /// The last finished frame
+    pub(crate) screen: Vec<u32>,
+    /// Pixels of `screen` that aren't black, for frontends without color
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) lit: Vec<bool>,
+    pub(crate) sample: Option<Sample>,
+}
+
+impl MegaChip {
+    pub(crate) fn new() -> Self {
+        Self {
+            palette: vec![OPAQUE_BLACK; 256],
+            sprite_width: 0,
+            sprite_height: 0,
+            alpha: 0xFF,
+            blend: Blend::Normal,
+            collision: 0,
+            indices: vec![0; PIXELS],
+            frame: vec![OPAQUE_BLACK; PIXELS],
+            screen: vec![OPAQUE_BLACK; PIXELS],
+            lit: vec![false; PIXELS],
+            sample: None,
+        }
+    }
+
+    /// Whether the buffers have the sizes the display needs, for states that
+    /// were deserialized
+    #[cfg(feature = "serde")]
+    pub(crate) fn is_valid(&self) -> bool {
+        self.palette.len() == 256
+            && self.indices.len() == PIXELS
+            && self.frame.len() == PIXELS
+            && self.screen.len() == PIXELS
+    }
+
+    pub(crate) fn update_lit(&mut self) {
+        self.lit.clear();
+        self.lit
+            .extend(self.screen.iter().map(|&color| color & 0x00FF_FFFF != 0));
+    }
+
+    /// 02NN, reads `count` colors from `address` into palette entries 1 and
+    /// up, four bytes each
+    pub(crate) fn load_palette(
+        &mut self,
+        memory: &[u8],
+        address: usize,
+        count: usize,
+    ) -> Result<(), Chip8Error> {
+        for index in 0..count.min(255) {
+            let start = address + index * 4;
+            let bytes = memory
+                .get(start..start + 4)
+                .ok_or(Chip8Error::MemoryOutOfBounds { address: start })?;
+            self.palette[index + 1] = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        Ok(())
+    }
+
+    /// 03NN and 04NN, where 0 stands for 256
+    pub(crate) fn set_sprite_size(&mut self, width: Option<u8>, height: Option<u8>) {
+        let size = |value: u8| if value == 0 { 256 } else { value as usize };
+        if let Some(width) = width {
+            self.sprite_width = size(width);
+        }
+        if let Some(height) = height {
+            self.sprite_height = size(height);
+        }
+    }
+
+    pub(crate) fn set_blend(&mut self, mode: u8) {
+        self.blend = Blend::from_mode(mode);
+    }
+
+    /// DXYN in MegaChip mode. Returns whether the sprite hit a pixel of the
+    /// collision color. Sprites are clipped at the edges of the screen
+    pub(crate) fn draw(
+        &mut self,
+        memory: &[u8],
+        address: usize,
+        x: usize,
+        y: usize,
+        font_rows: usize,
+    ) -> Result<bool, Chip8Error> {
+        let read = |address: usize| {
+            memory
+                .get(address)
+                .copied()
+                .ok_or(Chip8Error::MemoryOutOfBounds { address })
+        };
+
+        let mut collided = false;
+        if address < PROGRAM_START as usize {
+            for row in 0..font_rows {
+                let bits = read(address + row)?;
+                for col in 0..8 {
+                    if bits & 0x80 >> col != 0 {
+                        collided |= self.plot(x + col, y + row, FONT_INDEX, WHITE);
+                    }
+                }
+            }
+            return Ok(collided);
+        }
+
+        for row in 0..self.sprite_height {
+            for col in 0..self.sprite_width {
+                let index = read(address + row * self.sprite_width + col)?;
+                if index != 0 {
+                    let color = self.palette[index as usize];
+                    collided |= self.plot(x + col, y + row, index, color);
+                }
+            }
+        }
+        Ok(collided)
+    }
+
+    fn plot(&mut self, x: usize, y: usize, index: u8, color: u32) -> bool {
+        if x >= MEGACHIP_WIDTH || y >= MEGACHIP_HEIGHT {
+            return false;
+        }
+        let pixel = y * MEGACHIP_WIDTH + x;
+        let collided = self.indices[pixel] == self.collision && self.collision != 0;
+        self.indices[pixel] = index;
+        self.frame[pixel] = self.blend.apply(self.frame[pixel], color);
+        collided
+    }
+
+    /// 00E0 in MegaChip mode: shows the frame drawn so far, faded by the
+    /// screen alpha, and starts a blank one
+    pub(crate) fn finish_frame(&mut self) {
+        let alpha = self.alpha as u32;
+        for (shown, &color) in self.screen.iter_mut().zip(&self.frame) {
+            *shown = [16, 8, 0].iter().fold(OPAQUE_BLACK, |result, &shift| {
+                result | ((color >> shift & 0xFF) * alpha / 0xFF) << shift
+            });
+        }
+        self.update_lit();
+        self.frame.fill(OPAQUE_BLACK);
+        self.indices.fill(0);
+    }
+
+    /// Moves the frame being drawn by `dx` and `dy` pixels, filling what's
+    /// uncovered with black
+    pub(crate) fn scroll(&mut self, dx: isize, dy: isize) {
+        let source = |x: usize, y: usize| {
+            let x = x.checked_add_signed(-dx).filter(|&x| x < MEGACHIP_WIDTH)?;
+            let y = y.checked_add_signed(-dy).filter(|&y| y < MEGACHIP_HEIGHT)?;
+            Some(y * MEGACHIP_WIDTH + x)
+        };
+        let frame = self.frame.clone();
+        let indices = self.indices.clone();
+        for y in 0..MEGACHIP_HEIGHT {
+            for x in 0..MEGACHIP_WIDTH {
+                let pixel = y * MEGACHIP_WIDTH + x;
+                (self.frame[pixel], self.indices[pixel]) = match source(x, y) {
+                    Some(from) => (frame[from], indices[from]),
+                    None => (OPAQUE_BLACK, 0),
+                };
+            }
+        }
+    }
+}
+
+impl Chip8 {
+    /// Runs the instructions only MegaChip has, and the ones that work
+    /// differently in MegaChip mode
+    pub(crate) fn execute_megachip(
+        &mut self,
+        instruction: Instruction,
+        opcode: u16,
+    ) -> Result<(), Chip8Error> {
+        if !self.quirks().megachip {
+            // A machine code routine as far as other interpreters know
+            return self.system_call(opcode & 0x0FFF);
+        }
+
+        let i = self.i as usize;
+        match instruction {
+            Instruction::MegaOff => {
+                self.megachip = None;
+                self.display.fill(false);
+            }
+            Instruction::MegaOn => self.megachip = Some(Box::new(MegaChip::new())),
+            Instruction::LoadIndexLong { kk } => {
+                let address = self.pc as usize + 2;
+                let low = self
+                    .memory
+                    .get(address..address + 2)
+                    .ok_or(Chip8Error::MemoryOutOfBounds { address })?;
+                self.i = (kk as u32) << 16 | u16::from_be_bytes([low[0], low[1]]) as u32;
+                return self.advance(4);
+            }
+            Instruction::Draw { x, y, n } => {
+                let (x, y) = (self.v[x as usize] as usize, self.v[y as usize] as usize);
+                if let Some(megachip) = &mut self.megachip {
+                    let collided = megachip.draw(&self.memory, i, x, y, n as usize)?;
+                    self.v[0xF] = collided as u8;
+                }
+            }
+            _ => {
+                // Everything else needs MegaChip mode and is ignored outside it
+                if let Some(megachip) = &mut self.megachip {
+                    match instruction {
+                        Instruction::Clear => megachip.finish_frame(),
+                        Instruction::ScrollUp { n } => megachip.scroll(0, -(n as isize)),
+                        Instruction::ScrollDown { n } => megachip.scroll(0, n as isize),
+                        Instruction::ScrollRight => megachip.scroll(4, 0),
+                        Instruction::ScrollLeft => megachip.scroll(-4, 0),
+                        Instruction::LoadPalette { kk } => {
+                            megachip.load_palette(&self.memory, i, kk as usize)?
+                        }
+                        Instruction::SpriteWidth { kk } => megachip.set_sprite_size(Some(kk), None),
+                        Instruction::SpriteHeight { kk } => {
+                            megachip.set_sprite_size(None, Some(kk))
+                        }
+                        Instruction::ScreenAlpha { kk } => megachip.alpha = kk,
+                        Instruction::PlaySample { n } => {
+                            megachip.sample = Some(Sample {
+                                address: self.i,
+                                looping: n == 0,
+                            })
+                        }
+                        Instruction::StopSample => megachip.sample = None,
+                        Instruction::BlendMode { n } => megachip.set_blend(n),
+                        Instruction::CollisionColor { kk } => megachip.collision = kk,
+                        _ => unreachable!("only called for MegaChip instructions"),
+                    }
+                }
+            }
+        }
+        self.advance(2)
+    }
+}
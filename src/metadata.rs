@@ -0,0 +1,119 @@
+//! Titles, authors and recommended settings from the `programs.json` of the
+//! CHIP-8 community database (https://github.com/chip-8/chip-8-database),
+//! matched by the SHA-1 hash of the ROM
+
+use std::{collections::HashMap, fs, path::Path};
+
+use chip_8_rs::romdb;
+use serde::Deserialize;
+
+use crate::cli::QuirkPreset;
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    /// Usually a year, but not always a string
+    release: Option<serde_json::Value>,
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    tickrate: Option<u32>,
+}
+
+/// What the database knows about a ROM
+#[derive(Debug, Clone)]
+pub struct RomMetadata {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+    /// Platform ids like `superchip`, the preferred one first
+    pub platforms: Vec<String>,
+    /// Recommended instructions per frame
+    pub tickrate: Option<u32>,
+}
+
+/// The database read into memory, for looking up many ROMs
+pub struct Database {
+    programs: Vec<Program>,
+}
+
+impl Database {
+    /// Reads the database at `path`
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let programs = serde_json::from_str(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        Ok(Self { programs })
+    }
+
+    /// Looks up the ROM with the lowercase hex SHA-1 `hash`
+    pub fn lookup(&self, hash: &str) -> Option<RomMetadata> {
+        self.programs.iter().find_map(|program| {
+            let rom = program.roms.get(hash)?;
+            Some(RomMetadata {
+                title: program.title.clone(),
+                authors: program.authors.clone(),
+                release: program.release.as_ref().map(|release| match release {
+                    serde_json::Value::String(release) => release.clone(),
+                    release => release.to_string(),
+                }),
+                platforms: rom.platforms.clone(),
+                tickrate: rom.tickrate,
+            })
+        })
+    }
+}
+
+impl RomMetadata {
+    /// Looks the ROM up in the database at `path`
+    pub fn lookup(path: &Path, rom: &[u8]) -> Result<Option<Self>, String> {
+        Ok(Database::load(path)?.lookup(&romdb::sha1_hex(rom)))
+    }
+
+    /// Quirks for the first platform there's a preset for
+    pub fn quirk_preset(&self) -> Option<QuirkPreset> {
+        self.platforms
+            .iter()
+            .find_map(|platform| match platform.as_str() {
+                "originalChip8" | "hybridVIP" | "modernChip8" => Some(QuirkPreset::Chip8),
+                "chip48" => Some(QuirkPreset::Chip48),
+                "superchip1" | "superchip" => Some(QuirkPreset::Schip),
+                "megachip8" => Some(QuirkPreset::Megachip),
+                "chip8x" => Some(QuirkPreset::Chip8x),
+                _ => None,
+            })
+    }
+
+    /// The title followed by the authors and release, e.g.
+    /// `Blinky by Hans Christian Egeberg (1991)`
+    pub fn summary(&self) -> String {
+        let mut summary = self.title.clone();
+        if !self.authors.is_empty() {
+            summary.push_str(&format!(" by {}", self.authors.join(", ")));
+        }
+        if let Some(release) = &self.release {
+            summary.push_str(&format!(" ({})", release));
+        }
+        summary
+    }
+
+    /// The settings the database recommends, for showing on load
+    pub fn recommendations(&self) -> Vec<String> {
+        let mut recommendations = Vec::new();
+        if !self.platforms.is_empty() {
+            recommendations.push(format!("platform {}", self.platforms.join(" or ")));
+        }
+        if let Some(tickrate) = self.tickrate {
+            recommendations.push(format!("--ipf {}", tickrate));
+        }
+        recommendations
+    }
+}
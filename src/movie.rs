@@ -0,0 +1,237 @@
+//! Input movies: the keypad changes of a run, by frame, plus the RNG seed.
+//! Playing one back with the same ROM and quirks repeats the run exactly,
+//! which is what tool-assisted runs and regression tests need. The file is
+//! plain text:
+//!
+//! ```text
+//! chip8-movie 1
+//! seed 1234
+//! 12 5 down
+//! 20 5 up
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chip_8_rs::{Chip8, frontend::InputSource};
+use log::{error, info};
+
+use crate::cli::RunArgs;
+
+const HEADER: &str = "chip8-movie 1";
+
+/// A key going down or up at the start of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KeyEvent {
+    frame: u64,
+    key: u8,
+    pressed: bool,
+}
+
+enum Mode {
+    /// Saves the events to `path` when finished
+    Recording { path: PathBuf },
+    /// Replays the events, `next` is the first one that hasn't happened yet
+    Playing { next: usize },
+    /// Playback reached the end, the keypad is live again
+    Finished,
+}
+
+pub struct Movie {
+    mode: Mode,
+    seed: u64,
+    events: Vec<KeyEvent>,
+    frame: u64,
+    keys: [bool; 16],
+}
+
+impl Movie {
+    /// Starts the movie given with `--record` or `--play`, if any, and seeds
+    /// the RNG of `chip8` from it. Recording uses `--seed` or a random one
+    pub fn from_args(args: &RunArgs, chip8: &mut Chip8) -> io::Result<Option<Self>> {
+        let movie = if let Some(path) = &args.play {
+            let movie = Self::load(path)?;
+            info!(
+                "Playing {} ({} key events)",
+                path.display(),
+                movie.events.len()
+            );
+            movie
+        } else if let Some(path) = &args.record {
+            info!("Recording input to {}", path.display());
+            Self {
+                mode: Mode::Recording { path: path.clone() },
+                seed: args.seed.unwrap_or_else(rand::random),
+                events: Vec::new(),
+                frame: 0,
+                keys: [false; 16],
+            }
+        } else {
+            return Ok(None);
+        };
+
+        chip8.set_seed(movie.seed);
+        Ok(Some(movie))
+    }
+
+    /// Plays back the movie at `path` and seeds the RNG of `chip8` from it
+    pub fn play(path: &Path, chip8: &mut Chip8) -> io::Result<Self> {
+        let movie = Self::load(path)?;
+        chip8.set_seed(movie.seed);
+        Ok(movie)
+    }
+
+    fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |line: usize, message: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} line {}: {}", path.display(), line + 1, message),
+            )
+        };
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().enumerate();
+
+        if lines.next().map(|(_, line)| line.trim()) != Some(HEADER) {
+            return Err(invalid(0, "not a movie file"));
+        }
+        let seed = lines
+            .next()
+            .and_then(|(_, line)| line.trim().strip_prefix("seed "))
+            .and_then(|seed| seed.parse().ok())
+            .ok_or_else(|| invalid(1, "expected seed <number>"))?;
+
+        let mut events = Vec::new();
+        for (number, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let event = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [frame, key, state] => frame
+                    .parse()
+                    .ok()
+                    .zip(u8::from_str_radix(key, 16).ok())
+                    .and_then(|(frame, key)| {
+                        let pressed = match state {
+                            "down" => true,
+                            "up" => false,
+                            _ => return None,
+                        };
+                        (key < 16).then_some(KeyEvent {
+                            frame,
+                            key,
+                            pressed,
+                        })
+                    }),
+                _ => None,
+            };
+            events.push(event.ok_or_else(|| invalid(number, "expected <frame> <key> down|up"))?);
+        }
+
+        Ok(Self {
+            mode: Mode::Playing { next: 0 },
+            seed,
+            events,
+            frame: 0,
+            keys: [false; 16],
+        })
+    }
+
+    /// Goes back to the first frame, to stay in sync after the machine is
+    /// reset. A recording starts over
+    pub fn restart(&mut self) {
+        match self.mode {
+            Mode::Recording { .. } => self.events.clear(),
+            Mode::Playing { .. } | Mode::Finished => self.mode = Mode::Playing { next: 0 },
+        }
+        self.frame = 0;
+        self.keys = [false; 16];
+    }
+
+    /// Writes a recording to its file. Does nothing when playing
+    pub fn finish(&self) {
+        let Mode::Recording { path } = &self.mode else {
+            return;
+        };
+
+        let mut text = format!("{}\nseed {}\n", HEADER, self.seed);
+        for event in &self.events {
+            text.push_str(&format!(
+                "{} {:X} {}\n",
+                event.frame,
+                event.key,
+                if event.pressed { "down" } else { "up" }
+            ));
+        }
+        match fs::write(path, text) {
+            Ok(()) => info!(
+                "Saved {} key events to {}",
+                self.events.len(),
+                path.display()
+            ),
+            Err(e) => error!("Failed to save movie: {}", e),
+        }
+    }
+
+    /// Reads the keypad for the next frame. `live` is always polled, so the
+    /// frontend keeps seeing its own events, but during playback its keys
+    /// are ignored
+    pub fn poll(&mut self, live: &mut impl InputSource, keys: &mut [bool; 16]) {
+        let mut live_keys = self.keys;
+        live.poll(&mut live_keys);
+
+        match &mut self.mode {
+            Mode::Recording { .. } => {
+                for (key, (&old, &new)) in self.keys.iter().zip(&live_keys).enumerate() {
+                    if old != new {
+                        self.events.push(KeyEvent {
+                            frame: self.frame,
+                            key: key as u8,
+                            pressed: new,
+                        });
+                    }
+                }
+                self.keys = live_keys;
+            }
+            Mode::Playing { next } => {
+                while let Some(event) = self.events.get(*next).filter(|e| e.frame <= self.frame) {
+                    self.keys[event.key as usize] = event.pressed;
+                    *next += 1;
+                }
+                if *next == self.events.len() {
+                    info!("Movie finished, the keypad is live again");
+                    self.mode = Mode::Finished;
+                }
+            }
+            Mode::Finished => self.keys = live_keys,
+        }
+
+        *keys = self.keys;
+        self.frame += 1;
+    }
+}
+
+/// Wraps the frontend's input so `run_frame` reads the keypad through the
+/// movie, when there is one
+pub fn input<'a, I: InputSource>(
+    movie: Option<&'a mut Movie>,
+    live: &'a mut I,
+) -> MovieInput<'a, I> {
+    MovieInput { movie, live }
+}
+
+pub struct MovieInput<'a, I> {
+    movie: Option<&'a mut Movie>,
+    live: &'a mut I,
+}
+
+impl<I: InputSource> InputSource for MovieInput<'_, I> {
+    fn poll(&mut self, keys: &mut [bool; 16]) {
+        match &mut self.movie {
+            Some(movie) => movie.poll(self.live, keys),
+            None => self.live.poll(keys),
+        }
+    }
+}
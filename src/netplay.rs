@@ -0,0 +1,346 @@
+//! Two-player netplay over TCP. Both instances run the same ROM in lockstep:
+//! every frame each side sends its keypad and waits for the other's, and
+//! both run the frame with the two keypads combined. The host picks the
+//! quirks, instructions per frame and RNG seed, so the machines never
+//! diverge. Keys are applied a few frames after they're pressed, which
+//! hides the round trip on most connections.
+//!
+//! The protocol is a handshake line from the host,
+//! `chip8-netplay 1 <rom sha1> <quirks> <ipf> <seed>`, answered with `ok` or
+//! an error message, followed by one 6 byte packet per frame: the frame
+//! number and the keypad as a bit per key, both big endian.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use chip_8_rs::{Chip8, Quirks, frontend::InputSource, romdb};
+use log::{error, info};
+
+use crate::cli::RunArgs;
+
+const HEADER: &str = "chip8-netplay 1";
+/// Frames between a key being pressed and taking effect
+const INPUT_DELAY: u32 = 3;
+/// How long to wait for the other player's frame before giving up
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Netplay {
+    stream: TcpStream,
+    /// Instructions per frame, always the host's
+    instructions_per_frame: u32,
+    /// Frame the next poll is for
+    frame: u32,
+    /// Local keypads sent but not applied yet, the oldest first
+    pending: VecDeque<u16>,
+    /// The local keypad, kept apart from the combined one
+    keys: [bool; 16],
+    /// Set when the connection is lost, after which only local keys count
+    disconnected: bool,
+}
+
+impl Netplay {
+    /// Hosts or joins the game given with `--host` or `--connect`, if any.
+    /// Blocks until the other player is connected. `chip8` has to have its
+    /// ROM loaded, a joining player takes over the host's quirks and seed
+    pub fn from_args(args: &RunArgs, chip8: &mut Chip8) -> io::Result<Option<Self>> {
+        let netplay = if let Some(address) = &args.host {
+            let listener = TcpListener::bind(address)?;
+            info!("Waiting for the other player on {}", listener.local_addr()?);
+            let (stream, peer) = listener.accept()?;
+            info!("{} joined", peer);
+            Self::host(stream, args, chip8)?
+        } else if let Some(address) = &args.connect {
+            info!("Connecting to {}", address);
+            Self::join(TcpStream::connect(address)?, chip8)?
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(netplay))
+    }
+
+    fn host(mut stream: TcpStream, args: &RunArgs, chip8: &mut Chip8) -> io::Result<Self> {
+        let seed = args.seed.unwrap_or_else(rand::random);
+        let instructions_per_frame = args.instructions_per_frame();
+        chip8.set_seed(seed);
+        writeln!(
+            stream,
+            "{} {} {} {} {}",
+            HEADER,
+            romdb::sha1_hex(chip8.rom()),
+            encode_quirks(chip8.quirks()),
+            instructions_per_frame,
+            seed
+        )?;
+
+        let reply = read_line(&stream)?;
+        if reply != "ok" {
+            return Err(io::Error::other(format!("the other player {}", reply)));
+        }
+        Self::new(stream, instructions_per_frame)
+    }
+
+    fn join(mut stream: TcpStream, chip8: &mut Chip8) -> io::Result<Self> {
+        let line = read_line(&stream)?;
+        let Some(fields) = line.strip_prefix(HEADER) else {
+            return Err(invalid("not a chip-8-rs netplay host"));
+        };
+        let (hash, quirks, instructions_per_frame, seed) =
+            match fields.split_whitespace().collect::<Vec<_>>()[..] {
+                [hash, quirks, ipf, seed] => (
+                    hash,
+                    decode_quirks(quirks),
+                    ipf.parse().ok().filter(|&ipf| ipf > 0),
+                    seed.parse().ok(),
+                ),
+                _ => return Err(invalid("invalid handshake")),
+            };
+        let (Some(quirks), Some(instructions_per_frame), Some(seed)) =
+            (quirks, instructions_per_frame, seed)
+        else {
+            return Err(invalid("invalid handshake"));
+        };
+
+        if hash != romdb::sha1_hex(chip8.rom()) {
+            writeln!(stream, "is running a different ROM")?;
+            return Err(io::Error::other("the host is running a different ROM"));
+        }
+        writeln!(stream, "ok")?;
+        // Quirks decide where the ROM is loaded and how it starts, so the
+        // machine starts over with the host's
+        chip8.set_quirks(quirks);
+        chip8.set_seed(seed);
+        chip8.reset();
+        info!(
+            "Joined, using the host's quirks and {} instructions per frame",
+            instructions_per_frame
+        );
+        Self::new(stream, instructions_per_frame)
+    }
+
+    fn new(stream: TcpStream, instructions_per_frame: u32) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut netplay = Self {
+            stream,
+            instructions_per_frame,
+            frame: 0,
+            pending: VecDeque::new(),
+            keys: [false; 16],
+            disconnected: false,
+        };
+        // The first frames run before any keys can arrive
+        for frame in 0..INPUT_DELAY {
+            netplay.send(frame, 0)?;
+        }
+        Ok(netplay)
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    fn send(&mut self, frame: u32, keys: u16) -> io::Result<()> {
+        let mut packet = [0; 6];
+        packet[..4].copy_from_slice(&frame.to_be_bytes());
+        packet[4..].copy_from_slice(&keys.to_be_bytes());
+        self.stream.write_all(&packet)?;
+        self.pending.push_back(keys);
+        Ok(())
+    }
+
+    /// The other player's keypad for the current frame
+    fn receive(&mut self) -> io::Result<u16> {
+        let mut packet = [0; 6];
+        self.stream.read_exact(&mut packet)?;
+        let frame = u32::from_be_bytes([packet[0], packet[1], packet[2], packet[3]]);
+        if frame != self.frame {
+            return Err(invalid(&format!(
+                "expected frame {}, got {}",
+                self.frame, frame
+            )));
+        }
+        Ok(u16::from_be_bytes([packet[4], packet[5]]))
+    }
+
+    /// Sends the local keypad and combines the keys both players pressed
+    /// `INPUT_DELAY` frames ago
+    fn exchange(&mut self, local: u16) -> io::Result<u16> {
+        self.send(self.frame + INPUT_DELAY, local)?;
+        let remote = self.receive()?;
+        let local = self.pending.pop_front().unwrap_or_default();
+        self.frame += 1;
+        Ok(local | remote)
+    }
+
+    /// Reads the keypad for the next frame, blocking until the other player
+    /// has sent theirs
+    pub fn poll(&mut self, live: &mut impl InputSource, keys: &mut [bool; 16]) {
+        live.poll(&mut self.keys);
+        *keys = self.keys;
+        if self.disconnected {
+            return;
+        }
+
+        match self.exchange(pack(&self.keys)) {
+            Ok(combined) => unpack(combined, keys),
+            Err(e) => {
+                error!("Netplay connection lost: {}. Playing on alone", e);
+                self.disconnected = true;
+            }
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads up to the next newline a byte at a time, so none of the packets
+/// that follow the handshake are consumed
+fn read_line(mut stream: &TcpStream) -> io::Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0];
+    while line.len() < 256 {
+        stream.read_exact(&mut byte)?;
+        if byte[0] == b'\n' {
+            break;
+        }
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).trim().to_string())
+}
+
+fn pack(keys: &[bool; 16]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .fold(0, |bits, (key, &down)| bits | (down as u16) << key)
+}
+
+fn unpack(bits: u16, keys: &mut [bool; 16]) {
+    for (key, down) in keys.iter_mut().enumerate() {
+        *down = bits & 1 << key != 0;
+    }
+}
+
+/// The quirks as a digit each, e.g. `1001100000`
+fn encode_quirks(quirks: Quirks) -> String {
+    [
+        quirks.shift_vy,
+        quirks.load_store_increment_i,
+        quirks.load_store_increment_x,
+        quirks.jump_vx,
+        quirks.vf_reset,
+        quirks.clip_sprites,
+        quirks.megachip,
+        quirks.chip8x,
+        quirks.wait_for_release,
+        quirks.display_wait,
+    ]
+    .iter()
+    .map(|&on| if on { '1' } else { '0' })
+    .collect()
+}
+
+fn decode_quirks(text: &str) -> Option<Quirks> {
+    let flags = text
+        .chars()
+        .map(|c| match c {
+            '0' => Some(false),
+            '1' => Some(true),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let [
+        shift_vy,
+        load_store_increment_i,
+        load_store_increment_x,
+        jump_vx,
+        vf_reset,
+        clip_sprites,
+        megachip,
+        chip8x,
+        wait_for_release,
+        display_wait,
+    ] = flags[..]
+    else {
+        return None;
+    };
+    Some(Quirks {
+        shift_vy,
+        load_store_increment_i,
+        load_store_increment_x,
+        jump_vx,
+        vf_reset,
+        clip_sprites,
+        megachip,
+        chip8x,
+        wait_for_release,
+        display_wait,
+    })
+}
+
+/// Wraps the frontend's input so `run_frame` reads the keypad through the
+/// netplay connection, when there is one
+pub fn input<'a, I: InputSource>(
+    netplay: Option<&'a mut Netplay>,
+    live: &'a mut I,
+) -> NetplayInput<'a, I> {
+    NetplayInput { netplay, live }
+}
+
+pub struct NetplayInput<'a, I> {
+    netplay: Option<&'a mut Netplay>,
+    live: &'a mut I,
+}
+
+impl<I: InputSource> InputSource for NetplayInput<'_, I> {
+    fn poll(&mut self, keys: &mut [bool; 16]) {
+        match &mut self.netplay {
+            Some(netplay) => netplay.poll(self.live, keys),
+            None => self.live.poll(keys),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joining_takes_over_the_hosts_quirks() {
+        let rom = [0xC0, 0xFF, 0x12, 0x00];
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut host = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        writeln!(
+            host,
+            "{} {} {} 10 42",
+            HEADER,
+            romdb::sha1_hex(&rom),
+            encode_quirks(Quirks::CHIP8X)
+        )
+        .unwrap();
+
+        let mut joined = Chip8::new();
+        joined.load(&rom).unwrap();
+        let netplay = Netplay::join(stream, &mut joined).unwrap();
+        assert_eq!(read_line(&host).unwrap(), "ok");
+        assert_eq!(netplay.instructions_per_frame(), 10);
+
+        // The same machine the host loaded, the ROM at CHIP-8X's 0x300
+        let mut hosted = Chip8::new();
+        hosted.set_quirks(Quirks::CHIP8X);
+        hosted.set_seed(42);
+        hosted.load(&rom).unwrap();
+        assert_eq!(joined.pc(), 0x300);
+        for _ in 0..10 {
+            joined.cycle().unwrap();
+            hosted.cycle().unwrap();
+        }
+        assert!(joined.save_state() == hosted.save_state());
+    }
+}
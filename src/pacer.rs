@@ -0,0 +1,68 @@
+//! Keeps the frontends' loops at 60 frames per second
+
+use std::{
+    hint, thread,
+    time::{Duration, Instant},
+};
+
+use crate::FRAME_DURATION;
+
+/// Sleeping can wake up a millisecond or more late, so the last stretch
+/// before a deadline is spent spinning instead
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+/// How far the loop may fall behind before the schedule starts over, rather
+/// than running frames back to back to catch up
+const MAX_LAG: Duration = FRAME_DURATION.saturating_mul(4);
+
+/// Waits out the rest of every frame. Deadlines are exactly a frame apart,
+/// so time lost to a slow frame or a late wakeup is taken off the next wait
+/// instead of slowing the game down
+pub struct FramePacer {
+    deadline: Instant,
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        Self {
+            deadline: Instant::now() + FRAME_DURATION,
+        }
+    }
+
+    /// Waits until the current frame's time is up, then moves on to the
+    /// next frame
+    pub fn wait(&mut self) {
+        if let Some(remaining) = self.deadline.checked_duration_since(Instant::now())
+            && remaining > SPIN_MARGIN
+        {
+            thread::sleep(remaining - SPIN_MARGIN);
+        }
+        while Instant::now() < self.deadline {
+            hint::spin_loop();
+        }
+        self.advance();
+    }
+
+    /// When the current frame's time is up
+    #[cfg(feature = "pixels")]
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Moves on to the next frame, for loops that wait for the deadline
+    /// themselves
+    pub fn advance(&mut self) {
+        self.deadline += FRAME_DURATION;
+        let now = Instant::now();
+        if now > self.deadline + MAX_LAG {
+            // Paused in the debugger, the window was being dragged, or the
+            // machine can't keep up
+            self.deadline = now + FRAME_DURATION;
+        }
+    }
+
+    /// Makes the next frame due right away, with the schedule continuing
+    /// from there. For after frames that weren't paced, like fast-forwarding
+    pub fn reset(&mut self) {
+        self.deadline = Instant::now();
+    }
+}
@@ -0,0 +1,82 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// An RGB color, independent of the frontend drawing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgb(0xff, 0xff, 0xff);
+    pub const BLACK: Color = Color::rgb(0x00, 0x00, 0x00);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+/// Foreground and background colors of the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub foreground: Color,
+    pub background: Color,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PaletteName {
+    /// Green phosphor monitor
+    #[default]
+    Green,
+    /// Amber phosphor monitor
+    Amber,
+    /// Original Game Boy style LCD
+    Lcd,
+    /// White on black
+    White,
+}
+
+impl PaletteName {
+    pub fn palette(self) -> Palette {
+        let (foreground, background) = match self {
+            PaletteName::Green => (Color::rgb(0x33, 0xff, 0x33), Color::rgb(0x00, 0x11, 0x00)),
+            PaletteName::Amber => (Color::rgb(0xff, 0xb0, 0x00), Color::rgb(0x1a, 0x0f, 0x00)),
+            PaletteName::Lcd => (Color::rgb(0x0f, 0x38, 0x0f), Color::rgb(0x9b, 0xbc, 0x0f)),
+            PaletteName::White => (Color::WHITE, Color::BLACK),
+        };
+
+        Palette {
+            foreground,
+            background,
+        }
+    }
+}
+
+/// Parses `#rrggbb` or `#rgb`, with or without the leading `#`
+pub fn parse_hex_color(value: &str) -> Result<Color, String> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    let invalid = || format!("Invalid color {}, expected #rrggbb or #rgb", value);
+
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).map_err(|_| invalid());
+    match hex.len() {
+        6 => Ok(Color::rgb(
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        )),
+        // Each digit is repeated, so #f80 is the same as #ff8800
+        3 => Ok(Color::rgb(
+            channel(&hex[0..1])? * 0x11,
+            channel(&hex[1..2])? * 0x11,
+            channel(&hex[2..3])? * 0x11,
+        )),
+        _ => Err(invalid()),
+    }
+}
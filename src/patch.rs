@@ -0,0 +1,246 @@
+//! IPS and BPS patches, the formats community bugfixes and translations of
+//! ROMs are usually shipped in. Patches are applied to the ROM bytes before
+//! they're loaded, the file on disk is never changed
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::{error::Chip8Error, megachip::MEGACHIP_MEMORY};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+/// Largest ROM a patch can produce, the size of the largest memory. Sizes
+/// come from the patch, this keeps a broken one from asking for gigabytes
+const MAX_ROM_SIZE: usize = MEGACHIP_MEMORY;
+
+/// Applies an IPS or BPS patch to `rom` and returns the patched ROM. The
+/// format is detected from the patch's header
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, Chip8Error> {
+    let result = if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(String::from("not an IPS or BPS patch"))
+    };
+    result.map_err(Chip8Error::InvalidPatch)
+}
+
+/// Reads the patch from front to back, failing on truncation
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .position
+            .checked_add(count)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or("the patch is truncated")?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// A big endian number of `count` bytes
+    fn number(&mut self, count: usize) -> Result<usize, String> {
+        Ok(self
+            .bytes(count)?
+            .iter()
+            .fold(0, |number, &byte| number << 8 | byte as usize))
+    }
+
+    /// BPS's variable length number, 7 bits per byte with the last byte
+    /// marked by the high bit
+    fn varint(&mut self) -> Result<usize, String> {
+        let mut number: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.byte()?;
+            number = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|value| number.checked_add(value))
+                .ok_or("the patch has an invalid number")?;
+            if byte & 0x80 != 0 {
+                return Ok(number);
+            }
+            shift = shift
+                .checked_shl(7)
+                .ok_or("the patch has an invalid number")?;
+            number = number
+                .checked_add(shift)
+                .ok_or("the patch has an invalid number")?;
+        }
+    }
+}
+
+/// Records of offset, length and bytes, with a length of 0 marking a run of
+/// one repeated byte. The ROM grows when a record writes past its end
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = Reader {
+        data: patch,
+        position: IPS_MAGIC.len(),
+    };
+    let mut output = rom.to_vec();
+
+    loop {
+        if reader.data[reader.position..].starts_with(IPS_EOF)
+            && matches!(reader.data.len() - reader.position, 3 | 6)
+        {
+            reader.position += IPS_EOF.len();
+            break;
+        }
+        let offset = reader.number(3)?;
+        let length = reader.number(2)?;
+        if length == 0 {
+            let count = reader.number(2)?;
+            let value = reader.byte()?;
+            write(&mut output, offset, &[value].repeat(count));
+        } else {
+            write(&mut output, offset, reader.bytes(length)?);
+        }
+    }
+
+    // Some patches end with the size to truncate the ROM to
+    if reader.position + 3 == patch.len() {
+        output.truncate(reader.number(3)?);
+    }
+    Ok(output)
+}
+
+fn write(output: &mut Vec<u8>, offset: usize, bytes: &[u8]) {
+    if output.len() < offset + bytes.len() {
+        output.resize(offset + bytes.len(), 0);
+    }
+    output[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// Sizes and metadata followed by copy commands, then the CRC32s of the
+/// source, the target and the patch itself
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < BPS_MAGIC.len() + 12 {
+        return Err(String::from("the patch is truncated"));
+    }
+    let footer = patch.len() - 12;
+    let checksum = |offset: usize| {
+        u32::from_le_bytes([
+            patch[offset],
+            patch[offset + 1],
+            patch[offset + 2],
+            patch[offset + 3],
+        ])
+    };
+    if crc32(&patch[..footer + 8]) != checksum(footer + 8) {
+        return Err(String::from("the patch is corrupted"));
+    }
+    if crc32(rom) != checksum(footer) {
+        return Err(String::from("the patch is for a different ROM"));
+    }
+
+    let mut reader = Reader {
+        data: &patch[..footer],
+        position: BPS_MAGIC.len(),
+    };
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(String::from("the patch is for a different ROM"));
+    }
+    if target_size > MAX_ROM_SIZE {
+        return Err(format!(
+            "the patched ROM would be {} bytes, more than any memory holds",
+            target_size
+        ));
+    }
+
+    let mut output = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+    while reader.position < reader.data.len() {
+        let command = reader.varint()?;
+        let length = (command >> 2) + 1;
+        if output.len() + length > target_size {
+            return Err(String::from("the patch writes past the end of the ROM"));
+        }
+
+        match command & 3 {
+            // SourceRead
+            0 => {
+                let start = output.len();
+                let bytes = rom
+                    .get(start..start + length)
+                    .ok_or("the patch reads past the end of the ROM")?;
+                output.extend_from_slice(bytes);
+            }
+            // TargetRead
+            1 => output.extend_from_slice(reader.bytes(length)?),
+            // SourceCopy
+            2 => {
+                source_offset = relative(source_offset, reader.varint()?)?;
+                let bytes = source_offset
+                    .checked_add(length)
+                    .and_then(|end| rom.get(source_offset..end))
+                    .ok_or("the patch reads past the end of the ROM")?;
+                output.extend_from_slice(bytes);
+                source_offset += length;
+            }
+            // TargetCopy, which can overlap what it's writing
+            _ => {
+                target_offset = relative(target_offset, reader.varint()?)?;
+                for _ in 0..length {
+                    let byte = *output
+                        .get(target_offset)
+                        .ok_or("the patch copies bytes that weren't written yet")?;
+                    output.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if output.len() != target_size {
+        return Err(format!(
+            "the patch produced {} bytes instead of {}",
+            output.len(),
+            target_size
+        ));
+    }
+    if crc32(&output) != checksum(footer + 4) {
+        return Err(String::from(
+            "the patched ROM doesn't match the patch's checksum",
+        ));
+    }
+    Ok(output)
+}
+
+/// Moves `offset` by a BPS relative offset, a magnitude with the sign in the
+/// lowest bit
+fn relative(offset: usize, encoded: usize) -> Result<usize, String> {
+    let distance = encoded >> 1;
+    let moved = if encoded & 1 == 0 {
+        offset.checked_add(distance)
+    } else {
+        offset.checked_sub(distance)
+    };
+    moved.ok_or_else(|| String::from("the patch has an invalid offset"))
+}
+
+/// The CRC-32 used by zip and BPS
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                crc >> 1 ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
@@ -0,0 +1,511 @@
+//! A window drawn with `pixels` on top of `winit`, for platforms where the
+//! native SDL2 library is a pain to install. It has no sound or display
+//! effects
+
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use chip_8_rs::{
+    LORES_HEIGHT, LORES_WIDTH,
+    frontend::{DisplaySink, InputSource, NullFrontend},
+};
+use log::{error, info, warn};
+use pixels::{Pixels, SurfaceTexture};
+use winit::{
+    application::ApplicationHandler,
+    dpi::LogicalSize,
+    event::{ElementState, KeyEvent, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{Window, WindowId},
+};
+
+#[cfg(feature = "egui")]
+use crate::gui::Gui;
+use crate::{
+    cli::RunArgs,
+    config::Config,
+    dap::Dap,
+    debugger::{Debugger, Flow},
+    load_chip8, load_plugins,
+    movie::{self, Movie},
+    netplay::{self, Netplay},
+    pacer::FramePacer,
+    palette::Palette,
+    print_profile,
+    session::{Hotkey, Session},
+    stats::Stats,
+};
+
+/// Fast-forwards emulation while held
+const TURBO_KEY: KeyCode = KeyCode::Tab;
+
+/// The keypad layout, indexed by CHIP-8 key. Keys are matched by position,
+/// so it's the same on non-QWERTY keyboards
+const DEFAULT_LAYOUT: [KeyCode; 16] = [
+    KeyCode::KeyX,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::KeyQ,
+    KeyCode::KeyW,
+    KeyCode::KeyE,
+    KeyCode::KeyA,
+    KeyCode::KeyS,
+    KeyCode::KeyD,
+    KeyCode::KeyZ,
+    KeyCode::KeyC,
+    KeyCode::Digit4,
+    KeyCode::KeyR,
+    KeyCode::KeyF,
+    KeyCode::KeyV,
+];
+
+/// Draws the CHIP-8 display into a `pixels` frame buffer of the same size,
+/// which is scaled up to the window by the GPU. Frames are drawn to the
+/// window by `render`
+pub struct PixelsDisplay {
+    pixels: Pixels<'static>,
+    palette: Palette,
+    width: usize,
+    height: usize,
+    #[cfg(feature = "egui")]
+    pub gui: Gui,
+}
+
+impl PixelsDisplay {
+    pub fn new(window: Arc<Window>, palette: Palette) -> Result<Self, pixels::Error> {
+        let size = window.inner_size();
+        let surface = SurfaceTexture::new(size.width, size.height, window.clone());
+        let pixels = Pixels::new(LORES_WIDTH as u32, LORES_HEIGHT as u32, surface)?;
+
+        Ok(Self {
+            #[cfg(feature = "egui")]
+            gui: Gui::new(&window, pixels.device(), pixels.render_texture_format()),
+            pixels,
+            palette,
+            width: LORES_WIDTH,
+            height: LORES_HEIGHT,
+        })
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0
+            && height > 0
+            && let Err(e) = self.pixels.resize_surface(width, height)
+        {
+            error!("Failed to resize the window surface: {}", e);
+        }
+    }
+
+    /// Draws the last presented frame, plus the debugger panels when they're
+    /// shown
+    pub fn render(&mut self) {
+        #[cfg(feature = "egui")]
+        let result = self.pixels.render_with(|encoder, target, context| {
+            context.scaling_renderer.render(encoder, target);
+            self.gui.render(encoder, target, context);
+            Ok(())
+        });
+        #[cfg(not(feature = "egui"))]
+        let result = self.pixels.render();
+
+        if let Err(e) = result {
+            error!("Failed to render: {}", e);
+        }
+    }
+}
+
+impl PixelsDisplay {
+    /// The buffer follows the display resolution, switching between low and
+    /// high resolution and MegaChip mode. Returns whether it has the new size
+    fn resize_buffer(&mut self, width: usize, height: usize) -> bool {
+        if (width, height) != (self.width, self.height) {
+            if let Err(e) = self.pixels.resize_buffer(width as u32, height as u32) {
+                error!("Failed to resize the frame buffer: {}", e);
+                return false;
+            }
+            self.width = width;
+            self.height = height;
+        }
+        true
+    }
+}
+
+impl DisplaySink for PixelsDisplay {
+    fn present(&mut self, pixels: &[bool], width: usize, height: usize) {
+        if !self.resize_buffer(width, height) {
+            return;
+        }
+
+        let Palette {
+            foreground,
+            background,
+        } = self.palette;
+        for (&on, rgba) in pixels
+            .iter()
+            .zip(self.pixels.frame_mut().chunks_exact_mut(4))
+        {
+            let color = if on { foreground } else { background };
+            rgba.copy_from_slice(&[color.r, color.g, color.b, 0xff]);
+        }
+    }
+
+    fn present_colors(&mut self, colors: &[u32], width: usize, height: usize) {
+        if !self.resize_buffer(width, height) {
+            return;
+        }
+
+        for (&color, rgba) in colors
+            .iter()
+            .zip(self.pixels.frame_mut().chunks_exact_mut(4))
+        {
+            let [_, r, g, b] = color.to_be_bytes();
+            rgba.copy_from_slice(&[r, g, b, 0xff]);
+        }
+    }
+}
+
+/// Reads the keypad state and hotkeys from winit keyboard events
+pub struct PixelsInput {
+    bindings: HashMap<KeyCode, u8>,
+    keys: [bool; 16],
+    hotkeys: Vec<Hotkey>,
+    turbo: bool,
+    quit: bool,
+}
+
+impl PixelsInput {
+    pub fn new() -> Self {
+        Self {
+            bindings: DEFAULT_LAYOUT
+                .iter()
+                .enumerate()
+                .map(|(key, &code)| (code, key as u8))
+                .collect(),
+            keys: [false; 16],
+            hotkeys: Vec::new(),
+            turbo: false,
+            quit: false,
+        }
+    }
+
+    /// Whether Escape was pressed
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    /// Whether the fast-forward key is being held down
+    pub fn turbo_held(&self) -> bool {
+        self.turbo
+    }
+
+    /// Returns the hotkeys pressed since the last call
+    pub fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+
+    pub fn handle_key(&mut self, event: &KeyEvent) {
+        let PhysicalKey::Code(code) = event.physical_key else {
+            return;
+        };
+        let pressed = event.state == ElementState::Pressed;
+
+        if code == KeyCode::Escape {
+            self.quit = true;
+        } else if code == TURBO_KEY {
+            self.turbo = pressed;
+        } else if let Some(&key) = self.bindings.get(&code) {
+            self.keys[key as usize] = pressed;
+        } else if pressed
+            && !event.repeat
+            && let Some(hotkey) = map_keycode_to_hotkey(code)
+        {
+            self.hotkeys.push(hotkey);
+        }
+    }
+}
+
+impl InputSource for PixelsInput {
+    fn poll(&mut self, keys: &mut [bool; 16]) {
+        *keys = self.keys;
+    }
+}
+
+fn map_keycode_to_hotkey(code: KeyCode) -> Option<Hotkey> {
+    match code {
+        KeyCode::F5 => Some(Hotkey::SaveState),
+        KeyCode::F9 => Some(Hotkey::LoadState),
+        KeyCode::F7 => Some(Hotkey::NextSlot),
+        KeyCode::F6 => Some(Hotkey::PreviousSlot),
+        KeyCode::KeyP | KeyCode::Space => Some(Hotkey::Pause),
+        KeyCode::F2 => Some(Hotkey::Reset),
+        KeyCode::F10 => Some(Hotkey::Step),
+        KeyCode::Backslash => Some(Hotkey::FrameAdvance),
+        KeyCode::F12 => Some(Hotkey::Screenshot),
+        KeyCode::F3 => Some(Hotkey::ToggleHud),
+        KeyCode::F8 => Some(Hotkey::DumpProfile),
+        KeyCode::F4 => Some(Hotkey::ToggleCheats),
+        KeyCode::KeyM => Some(Hotkey::ToggleMute),
+        KeyCode::Equal | KeyCode::NumpadAdd => Some(Hotkey::SpeedUp),
+        KeyCode::Minus | KeyCode::NumpadSubtract => Some(Hotkey::SpeedDown),
+        _ => None,
+    }
+}
+
+/// Drives the emulator from the winit event loop, running a frame whenever
+/// the pacer's deadline passes
+struct App {
+    session: Session,
+    palette: Palette,
+    scale: u32,
+    window: Option<Arc<Window>>,
+    display: Option<PixelsDisplay>,
+    input: PixelsInput,
+    stats: Stats,
+    debugger: Option<Debugger>,
+    dap: Option<Dap>,
+    pacer: FramePacer,
+}
+
+impl App {
+    fn run_frame(&mut self, event_loop: &ActiveEventLoop) {
+        let (Some(window), Some(display)) = (&self.window, &mut self.display) else {
+            return;
+        };
+
+        for hotkey in self.input.take_hotkeys() {
+            self.session.handle_hotkey(hotkey);
+        }
+        if let Some(debugger) = &mut self.debugger
+            && debugger.poll(&mut self.session) == Flow::Quit
+        {
+            event_loop.exit();
+            return;
+        }
+        let session = &mut self.session;
+        if let Some(dap) = &mut self.dap
+            && dap.poll(
+                &mut session.chip8,
+                &mut session.paused,
+                session.stop.take(),
+                &session.symbols,
+            ) == Flow::Quit
+        {
+            event_loop.exit();
+            return;
+        }
+
+        let turbo = self.input.turbo_held();
+        let instructions = self.session.frame_instructions(turbo);
+        let running = self.session.run_next_frame();
+        let executed = if running { instructions } else { 0 };
+        if running
+            && let Err(e) = self.session.chip8.run_frame(
+                instructions,
+                &mut movie::input(
+                    self.session.movie.as_mut(),
+                    &mut netplay::input(self.session.netplay.as_mut(), &mut self.input),
+                ),
+                display,
+                &mut NullFrontend,
+            )
+        {
+            error!("Emulation stopped: {}", e);
+            event_loop.exit();
+            return;
+        }
+        if executed > 0 {
+            self.session.end_frame();
+        }
+        self.session.check_stop();
+
+        #[cfg(feature = "egui")]
+        display.gui.prepare(window, &mut self.session);
+        display.render();
+
+        // There's no text rendering, so the stats overlay goes into the title,
+        // as does the speed while it's being changed
+        let speed = self.session.speed_line();
+        if self.stats.record_frame(executed) || speed.is_some() {
+            let stats = if let Some(speed) = speed {
+                speed
+            } else if self.session.hud {
+                self.session.hud_lines(&self.stats).join(", ")
+            } else {
+                format!("{:.0} FPS, {:.0} IPS", self.stats.fps(), self.stats.ips())
+            };
+            window.set_title(&format!(
+                "chip-8-rs - {}{} - {}",
+                self.session.rom_name(),
+                if self.session.paused { " [paused]" } else { "" },
+                stats
+            ));
+        }
+
+        if turbo {
+            self.pacer.reset();
+        } else {
+            self.pacer.advance();
+        }
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+
+        let attributes = Window::default_attributes()
+            .with_title(format!("chip-8-rs - {}", self.session.rom_name()))
+            .with_inner_size(LogicalSize::new(
+                LORES_WIDTH as u32 * self.scale,
+                LORES_HEIGHT as u32 * self.scale,
+            ));
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(e) => {
+                error!("Failed to create window: {}", e);
+                event_loop.exit();
+                return;
+            }
+        };
+
+        match PixelsDisplay::new(window.clone(), self.palette) {
+            Ok(display) => self.display = Some(display),
+            Err(e) => {
+                error!("Failed to create renderer: {}", e);
+                event_loop.exit();
+                return;
+            }
+        }
+        self.window = Some(window);
+        self.pacer.reset();
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        #[cfg(feature = "egui")]
+        if let (Some(window), Some(display)) = (&self.window, &mut self.display)
+            && display.gui.handle_event(window, &event)
+        {
+            return;
+        }
+
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::KeyboardInput { event, .. } => self.input.handle_key(&event),
+            WindowEvent::Resized(size) => {
+                if let Some(display) = &mut self.display {
+                    display.resize(size.width, size.height);
+                }
+            }
+            // Keep showing the last frame when the window is uncovered or
+            // resized, even while paused
+            WindowEvent::RedrawRequested => {
+                if let Some(display) = &mut self.display {
+                    display.render();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.input.quit_requested() {
+            event_loop.exit();
+            return;
+        }
+
+        if Instant::now() >= self.pacer.deadline() {
+            self.run_frame(event_loop);
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(self.pacer.deadline()));
+    }
+}
+
+/// Opens a window and runs the emulator in it until the window is closed or
+/// Escape is pressed
+pub fn run(args: &RunArgs, config: &Config, palette: Palette) {
+    let Some(rom_path) = args.rom.clone() else {
+        error!("A ROM path is required with the pixels frontend");
+        return;
+    };
+    if !config.keypad.is_empty() {
+        warn!("Keypad bindings from the config aren't supported by the pixels frontend");
+    }
+    match config.key_profile(args, &rom_path) {
+        Ok(Some(_)) => warn!("Key profiles aren't supported by the pixels frontend"),
+        Ok(None) => {}
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    }
+
+    let Some((mut chip8, metadata)) = load_chip8(&rom_path, args) else {
+        return;
+    };
+    let movie = match Movie::from_args(args, &mut chip8) {
+        Ok(movie) => movie,
+        Err(e) => {
+            error!("Failed to open movie: {}", e);
+            return;
+        }
+    };
+    let netplay = match Netplay::from_args(args, &mut chip8) {
+        Ok(netplay) => netplay,
+        Err(e) => {
+            error!("Netplay failed: {}", e);
+            return;
+        }
+    };
+
+    let event_loop = match EventLoop::new() {
+        Ok(event_loop) => event_loop,
+        Err(e) => {
+            error!("Failed to create event loop: {}", e);
+            return;
+        }
+    };
+
+    let Some(plugins) = load_plugins(args, &mut chip8) else {
+        return;
+    };
+    let dap = match &args.dap {
+        Some(address) => match Dap::spawn(address) {
+            Ok(dap) => Some(dap),
+            Err(e) => {
+                error!("Failed to start the debug adapter on {}: {}", address, e);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut app = App {
+        session: Session::new(chip8, movie, netplay, metadata, rom_path, args, palette),
+        palette,
+        scale: args.scale,
+        window: None,
+        display: None,
+        input: PixelsInput::new(),
+        stats: Stats::new(),
+        debugger: args.debug.then(Debugger::spawn),
+        dap,
+        pacer: FramePacer::new(),
+    };
+    app.session.plugins = plugins;
+    if let Err(e) = event_loop.run_app(&mut app) {
+        error!("Event loop error: {}", e);
+    }
+
+    if let Some(movie) = &app.session.movie {
+        movie.finish();
+    }
+    if let Some(dap) = &mut app.dap {
+        dap.finish();
+    }
+    print_profile(&app.session.chip8);
+    app.session.save_rom_profile();
+    info!("Exiting...");
+}
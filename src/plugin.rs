@@ -0,0 +1,133 @@
+//! Extension points for features that live outside this crate, like input
+//! recorders, trainers or overlays. A frontend owns a `Plugins` registry,
+//! calls `frame` after every frame it runs and `event` for what happens
+//! outside of frames, such as a reset.
+//!
+//! Plugins built as dynamic libraries declare their constructor with
+//! `declare_plugin!`. Rust has no stable ABI, so such a library has to be
+//! built with the same compiler and the same version of this crate as the
+//! program that loads it.
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{chip8::Chip8, debug::Stop};
+
+/// Something that happened to the machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    KeyDown(u8),
+    KeyUp(u8),
+    /// Execution stopped at a breakpoint or watchpoint
+    Stopped(Stop),
+    /// The ROM was restarted
+    Reset,
+    /// A save state replaced the machine state
+    StateLoaded,
+}
+
+pub trait Plugin {
+    /// Shown when the plugin is loaded
+    fn name(&self) -> &str;
+
+    /// Called once, when the plugin is registered
+    fn initialize(&mut self, _chip8: &mut Chip8) {}
+
+    /// Called after every frame, with the number of frames run so far
+    fn on_frame(&mut self, _chip8: &mut Chip8, _frame: u64) {}
+
+    /// Called for key changes, stops and anything the frontend reports
+    fn on_event(&mut self, _chip8: &mut Chip8, _event: &Event) {}
+}
+
+/// The plugins of a frontend, called in the order they were registered
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Box<dyn Plugin>>,
+    frame: u64,
+    /// Keypad at the end of the last frame, to report key changes
+    keys: [bool; 16],
+}
+
+impl Plugins {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a plugin and initializes it
+    pub fn register(&mut self, mut plugin: Box<dyn Plugin>, chip8: &mut Chip8) {
+        plugin.initialize(chip8);
+        self.plugins.push(plugin);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.plugins.iter().map(|plugin| plugin.name())
+    }
+
+    /// Reports the keys that changed during the frame, then runs the frame
+    /// callbacks
+    pub fn frame(&mut self, chip8: &mut Chip8) {
+        if self.plugins.is_empty() {
+            return;
+        }
+
+        let (old_keys, keys) = (self.keys, *chip8.keypad());
+        for (key, (&old, &new)) in old_keys.iter().zip(&keys).enumerate() {
+            if old != new {
+                let key = key as u8;
+                self.event(
+                    chip8,
+                    &if new {
+                        Event::KeyDown(key)
+                    } else {
+                        Event::KeyUp(key)
+                    },
+                );
+            }
+        }
+        self.keys = keys;
+
+        self.frame += 1;
+        for plugin in &mut self.plugins {
+            plugin.on_frame(chip8, self.frame);
+        }
+    }
+
+    pub fn event(&mut self, chip8: &mut Chip8, event: &Event) {
+        for plugin in &mut self.plugins {
+            plugin.on_event(chip8, event);
+        }
+    }
+}
+
+/// Exports the constructor of a plugin from a dynamic library, for programs
+/// that load plugins at runtime. The library needs
+/// `crate-type = ["cdylib"]`.
+///
+/// ```ignore
+/// struct Lives;
+///
+/// impl Plugin for Lives {
+///     fn name(&self) -> &str {
+///         "infinite lives"
+///     }
+///
+///     fn on_frame(&mut self, chip8: &mut Chip8, _frame: u64) {
+///         let _ = chip8.poke(0x3F0, 3);
+///     }
+/// }
+///
+/// declare_plugin!(Lives);
+/// ```
+#[macro_export]
+macro_rules! declare_plugin {
+    ($constructor:expr) => {
+        #[unsafe(no_mangle)]
+        pub fn chip8_plugin() -> ::std::boxed::Box<dyn $crate::plugin::Plugin> {
+            ::std::boxed::Box::new($constructor)
+        }
+    };
+}
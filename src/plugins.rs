@@ -0,0 +1,30 @@
+//! Loads the plugins given with `--plugin` from dynamic libraries that export
+//! a constructor with `chip_8_rs::declare_plugin!`
+
+use std::path::Path;
+
+use chip_8_rs::plugin::Plugin;
+use libloading::{Library, Symbol};
+
+/// Name of the constructor `declare_plugin!` exports
+const CONSTRUCTOR: &[u8] = b"chip8_plugin";
+
+type Constructor = fn() -> Box<dyn Plugin>;
+
+/// Loads the plugin in the library at `path`. The library stays loaded until
+/// the program exits, since the plugin's code lives in it
+pub fn load(path: &Path) -> Result<Box<dyn Plugin>, String> {
+    // Safety: loading runs the library's initializers and the constructor is
+    // trusted to have the signature `declare_plugin!` gives it. Both are as
+    // safe as the library, which the user chose to run
+    let plugin = unsafe {
+        let library = Library::new(path).map_err(|e| e.to_string())?;
+        let constructor: Symbol<Constructor> = library.get(CONSTRUCTOR).map_err(|_| {
+            "no plugin constructor, is it declared with declare_plugin!?".to_string()
+        })?;
+        let plugin = constructor();
+        std::mem::forget(library);
+        plugin
+    };
+    Ok(plugin)
+}
@@ -0,0 +1,121 @@
+//! Counts how often each instruction and address is executed, so ROM authors
+//! can see where a program spends its time
+
+use alloc::{boxed::Box, collections::BTreeMap, format, vec::Vec};
+use core::fmt;
+
+use crate::{chip8::Chip8, disasm};
+
+/// How many rows of each table `Display` prints
+const TOP_ROWS: usize = 16;
+/// Width of the longest bar in the histograms
+const BAR_WIDTH: u64 = 40;
+
+/// Execution counts collected while profiling is enabled
+#[derive(Debug, Clone)]
+pub struct Profile {
+    by_address: Box<[u64; 4096]>,
+    by_pattern: BTreeMap<&'static str, u64>,
+    total: u64,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            by_address: Box::new([0; 4096]),
+            by_pattern: BTreeMap::new(),
+            total: 0,
+        }
+    }
+}
+
+impl Profile {
+    pub(crate) fn record(&mut self, address: u16, opcode: u16) {
+        self.by_address[address as usize & 0xFFF] += 1;
+        *self.by_pattern.entry(disasm::pattern(opcode)).or_default() += 1;
+        self.total += 1;
+    }
+
+    /// Number of instructions executed since profiling was enabled
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Executions per instruction pattern such as `DXYN`, most frequent first
+    pub fn by_pattern(&self) -> Vec<(&'static str, u64)> {
+        let mut counts: Vec<_> = self
+            .by_pattern
+            .iter()
+            .map(|(&pattern, &count)| (pattern, count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    /// Executions per address, most frequent first. Addresses that never
+    /// ran are left out
+    pub fn by_address(&self) -> Vec<(u16, u64)> {
+        let mut counts: Vec<_> = self
+            .by_address
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(address, &count)| (address as u16, count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+
+    fn write_row(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        label: &str,
+        count: u64,
+        max: u64,
+    ) -> fmt::Result {
+        let percent = count as f64 * 100.0 / self.total as f64;
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(max) as usize);
+        writeln!(f, "  {:<6} {:>12} {:>6.2}%  {}", label, count, percent, bar)
+    }
+}
+
+/// Histograms of the most executed instruction patterns and addresses
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Profile: {} instructions executed", self.total)?;
+        if self.total == 0 {
+            return Ok(());
+        }
+
+        writeln!(f, "Instructions:")?;
+        let patterns = self.by_pattern();
+        let max = patterns[0].1;
+        for &(pattern, count) in patterns.iter().take(TOP_ROWS) {
+            self.write_row(f, pattern, count, max)?;
+        }
+
+        writeln!(f, "Addresses:")?;
+        let addresses = self.by_address();
+        let max = addresses[0].1;
+        for &(address, count) in addresses.iter().take(TOP_ROWS) {
+            self.write_row(f, &format!("0x{:03X}", address), count, max)?;
+        }
+        Ok(())
+    }
+}
+
+impl Chip8 {
+    /// Starts counting executed instructions. Counting costs a little time
+    /// per instruction, so it's off by default. Does nothing when already
+    /// enabled
+    pub fn enable_profiling(&mut self) {
+        if self.profile.is_none() {
+            self.profile = Some(Box::default());
+        }
+    }
+
+    /// The counts collected so far, if profiling is enabled
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+}
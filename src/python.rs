@@ -0,0 +1,162 @@
+//! A Python module exposing `Chip8`, for poking at the interpreter from a
+//! notebook. Build and install it into the active virtualenv with:
+//!
+//! ```text
+//! maturin develop --features python
+//! ```
+//!
+//! ```python
+//! import numpy as np
+//! from chip_8_rs import Chip8
+//!
+//! chip8 = Chip8()
+//! chip8.load(open("pong.ch8", "rb").read())
+//! chip8.run_frame(10)
+//! screen = np.frombuffer(chip8.display(), dtype=np.uint8).reshape(chip8.height, chip8.width)
+//! ```
+
+use pyo3::{
+    exceptions::PyRuntimeError,
+    prelude::*,
+    types::{PyBytes, PyList},
+};
+
+use crate::{chip8::Chip8, disasm, error::Chip8Error, frontend::NullFrontend};
+
+impl From<Chip8Error> for PyErr {
+    fn from(e: Chip8Error) -> Self {
+        PyRuntimeError::new_err(e.to_string())
+    }
+}
+
+/// A `Chip8` driven from Python. Keys are set with `key_down`/`key_up` and
+/// the display is read back with `display`
+#[pyclass(name = "Chip8")]
+pub struct PyChip8 {
+    chip8: Chip8,
+}
+
+#[pymethods]
+impl PyChip8 {
+    #[new]
+    fn new() -> Self {
+        Self {
+            chip8: Chip8::new(),
+        }
+    }
+
+    /// Resets the machine and loads the ROM bytes at 0x200
+    fn load(&mut self, rom: &[u8]) -> PyResult<()> {
+        self.chip8 = Chip8::new();
+        self.chip8.load(rom)?;
+        Ok(())
+    }
+
+    /// Restarts the loaded ROM
+    fn reset(&mut self) {
+        self.chip8.reset();
+    }
+
+    /// Executes a single instruction
+    fn step(&mut self) -> PyResult<()> {
+        self.chip8.cycle()?;
+        Ok(())
+    }
+
+    /// Runs one 60Hz frame: `instructions` steps followed by a timer tick
+    #[pyo3(signature = (instructions = 10))]
+    fn run_frame(&mut self, instructions: u32) -> PyResult<()> {
+        self.chip8.run_frame(
+            instructions,
+            &mut NullFrontend,
+            &mut NullFrontend,
+            &mut NullFrontend,
+        )?;
+        Ok(())
+    }
+
+    fn tick_timers(&mut self) {
+        self.chip8.tick_timers();
+    }
+
+    fn key_down(&mut self, key: u8) {
+        self.chip8.key_down(key);
+    }
+
+    fn key_up(&mut self, key: u8) {
+        self.chip8.key_up(key);
+    }
+
+    /// V0 to VF as a list
+    #[getter]
+    fn v<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.chip8.registers())
+    }
+
+    #[getter]
+    fn i(&self) -> u32 {
+        self.chip8.index()
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.chip8.pc()
+    }
+
+    /// Return addresses, the innermost call last
+    #[getter]
+    fn stack(&self) -> Vec<u16> {
+        self.chip8.stack().to_vec()
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        self.chip8.delay_timer()
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        self.chip8.sound_timer()
+    }
+
+    #[getter]
+    fn width(&self) -> usize {
+        self.chip8.display_width()
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.chip8.display_height()
+    }
+
+    /// The display as one byte per pixel, 1 for on and 0 for off, row by
+    /// row. `numpy.frombuffer` reads it without copying
+    fn display<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let pixels: Vec<u8> = self
+            .chip8
+            .get_display()
+            .iter()
+            .map(|&on| on as u8)
+            .collect();
+        PyBytes::new(py, &pixels)
+    }
+
+    /// All 4096 bytes of memory
+    fn memory<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.chip8.memory())
+    }
+
+    /// The instruction at `address`, or at the PC, in Cowgod's mnemonics
+    #[pyo3(signature = (address = None))]
+    fn disassemble(&self, address: Option<u16>) -> String {
+        let address = address.unwrap_or(self.chip8.pc()) as usize & 0xFFF;
+        let memory = self.chip8.memory();
+        let opcode = u16::from_be_bytes([memory[address], memory[(address + 1) & 0xFFF]]);
+        disasm::disassemble(opcode)
+    }
+}
+
+#[pymodule]
+fn chip_8_rs(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyChip8>()
+}
@@ -0,0 +1,98 @@
+/// Behaviors that differ between CHIP-8 interpreters. ROMs written for one
+/// platform often misbehave when run with another platform's quirks.
+///
+/// The default leaves every quirk off, which matches how this interpreter
+/// has always behaved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    /// 8XY6/8XYE shift Vy and store the result in Vx, instead of shifting Vx
+    /// in place
+    pub shift_vy: bool,
+    /// FX55/FX65 leave I pointing past the last register accessed
+    pub load_store_increment_i: bool,
+    /// FX55/FX65 leave I pointing at the last register accessed, one short
+    /// of `load_store_increment_i`. Ignored when that is set
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub load_store_increment_x: bool,
+    /// BNNN behaves as BXNN and jumps to XNN + Vx instead of NNN + V0
+    pub jump_vx: bool,
+    /// 8XY1/8XY2/8XY3 reset Vf to 0
+    pub vf_reset: bool,
+    /// Sprites are clipped at the edges of the screen instead of wrapping
+    pub clip_sprites: bool,
+    /// FX0A waits for a key to be pressed and released again instead of
+    /// only pressed
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub wait_for_release: bool,
+    /// DXYN waits for the next vertical blank, so nothing else runs until
+    /// the frame is over
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub display_wait: bool,
+    /// MegaChip's instructions are run instead of skipped, I is 24 bits wide
+    /// and memory grows to `MEGACHIP_MEMORY`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub megachip: bool,
+    /// CHIP-8X's color and sound instructions are run, programs start at
+    /// `CHIP8X_PROGRAM_START` and BNNN sets colors instead of jumping
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub chip8x: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter
+    pub const CHIP8: Quirks = Quirks {
+        shift_vy: true,
+        load_store_increment_i: true,
+        load_store_increment_x: false,
+        jump_vx: false,
+        vf_reset: true,
+        clip_sprites: true,
+        wait_for_release: true,
+        display_wait: true,
+        megachip: false,
+        chip8x: false,
+    };
+
+    /// CHIP-48 on the HP-48, which SUPER-CHIP was built on. Its FX55/FX65
+    /// move I one less than the original interpreter's
+    pub const CHIP48: Quirks = Quirks {
+        shift_vy: false,
+        load_store_increment_i: false,
+        load_store_increment_x: true,
+        jump_vx: true,
+        vf_reset: false,
+        clip_sprites: true,
+        wait_for_release: true,
+        display_wait: false,
+        megachip: false,
+        chip8x: false,
+    };
+
+    /// SUPER-CHIP 1.1 on the HP-48
+    pub const SCHIP: Quirks = Quirks {
+        shift_vy: false,
+        load_store_increment_i: false,
+        load_store_increment_x: false,
+        jump_vx: true,
+        vf_reset: false,
+        clip_sprites: true,
+        wait_for_release: true,
+        display_wait: false,
+        megachip: false,
+        chip8x: false,
+    };
+
+    /// MegaChip8, SUPER-CHIP with MegaChip's instructions
+    pub const MEGACHIP: Quirks = Quirks {
+        megachip: true,
+        ..Quirks::SCHIP
+    };
+
+    /// CHIP-8X on the VIP with the color and sound boards, the original
+    /// interpreter otherwise
+    pub const CHIP8X: Quirks = Quirks {
+        chip8x: true,
+        ..Quirks::CHIP8
+    };
+}
@@ -0,0 +1,77 @@
+//! The most recently played ROMs, kept in `recent.txt` next to the config
+//! with one path per line, the latest first
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::config;
+
+/// How many ROMs are remembered
+const MAX_RECENT: usize = 9;
+
+#[derive(Default)]
+pub struct RecentRoms {
+    paths: Vec<PathBuf>,
+}
+
+impl RecentRoms {
+    /// Reads the list, which is empty when it wasn't saved yet or can't be
+    /// read. ROMs that were moved or deleted since are left out
+    pub fn load() -> Self {
+        let paths = file()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .map(|contents| {
+                contents
+                    .lines()
+                    .map(PathBuf::from)
+                    .filter(|path| path.is_file())
+                    .take(MAX_RECENT)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { paths }
+    }
+
+    /// The latest first
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Moves `rom_path` to the top of the list and saves it. Only ROM files
+    /// are remembered, not stdin or the built-in ROMs
+    pub fn add(&mut self, rom_path: &Path) -> io::Result<()> {
+        if !rom_path.is_file() {
+            return Ok(());
+        }
+        let path = rom_path.canonicalize()?;
+        self.paths.retain(|recent| *recent != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(MAX_RECENT);
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(file) = file() else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut contents = String::new();
+        for path in &self.paths {
+            // Paths that aren't UTF-8 or have a line break are just forgotten
+            let Some(path) = path.to_str().filter(|path| !path.contains('\n')) else {
+                continue;
+            };
+            contents.push_str(path);
+            contents.push('\n');
+        }
+        fs::write(file, contents)
+    }
+}
+
+fn file() -> Option<PathBuf> {
+    Some(config::app_dir()?.join("recent.txt"))
+}
@@ -0,0 +1,314 @@
+//! A WebSocket server for driving a headless run from a browser dashboard
+//! or a script. Every message is a JSON object with a `type`:
+//!
+//! ```text
+//! {"type": "pause"}
+//! {"type": "resume"}
+//! {"type": "step", "count": 10}
+//! {"type": "key", "key": 5, "down": true}
+//! {"type": "registers"}
+//! {"type": "memory", "address": 512, "length": 64}
+//! {"type": "subscribe", "frames": true}
+//! ```
+//!
+//! `registers` and `memory` are answered with a message of the same type,
+//! subscribed clients get a `frame` message with the display after every
+//! frame, and anything that fails is answered with an `error`, including a
+//! `step` of more than 100000 instructions. Connections are handled on
+//! their own threads, requests are executed between frames
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use chip_8_rs::Chip8;
+use log::info;
+use serde::{Deserialize, Serialize};
+use tungstenite::{Message, WebSocket};
+
+/// How long a connection waits for a request before sending what's queued
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// Most instructions one `step` runs, requests run between frames and a
+/// longer one would hold up the emulator and every other connection
+const MAX_STEPS: u32 = 100_000;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    Pause,
+    Resume,
+    Step {
+        #[serde(default = "one")]
+        count: u32,
+    },
+    Key {
+        key: u8,
+        down: bool,
+    },
+    Registers,
+    Memory {
+        address: u16,
+        #[serde(default = "one")]
+        length: u32,
+    },
+    Subscribe {
+        frames: bool,
+    },
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Response {
+    Registers {
+        v: [u8; 16],
+        i: u32,
+        pc: u16,
+        stack: Vec<u16>,
+        delay_timer: u8,
+        sound_timer: u8,
+        paused: bool,
+    },
+    Memory {
+        address: u16,
+        bytes: Vec<u8>,
+    },
+    Frame {
+        frame: u64,
+        width: usize,
+        height: usize,
+        /// A string of `#` and `.` per row
+        rows: Vec<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+enum Event {
+    Connected(usize, Sender<String>),
+    Request(usize, String),
+    Closed(usize),
+}
+
+struct Client {
+    outbox: Sender<String>,
+    frames: bool,
+}
+
+pub struct Remote {
+    events: Receiver<Event>,
+    clients: HashMap<usize, Client>,
+}
+
+impl Remote {
+    /// Starts accepting connections on `address`
+    pub fn spawn(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        info!(
+            "Remote control listening on ws://{}",
+            listener.local_addr()?
+        );
+
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let sender = sender.clone();
+                thread::spawn(move || serve(id, stream, sender));
+            }
+        });
+
+        Ok(Self {
+            events,
+            clients: HashMap::new(),
+        })
+    }
+
+    /// Executes the requests received since the last call
+    pub fn poll(&mut self, chip8: &mut Chip8, paused: &mut bool) {
+        loop {
+            match self.events.try_recv() {
+                Ok(Event::Connected(id, outbox)) => {
+                    self.clients.insert(
+                        id,
+                        Client {
+                            outbox,
+                            frames: false,
+                        },
+                    );
+                }
+                Ok(Event::Closed(id)) => {
+                    self.clients.remove(&id);
+                }
+                Ok(Event::Request(id, text)) => {
+                    let response = match serde_json::from_str(&text) {
+                        Ok(request) => self.execute(id, request, chip8, paused),
+                        Err(e) => Some(Response::Error {
+                            message: e.to_string(),
+                        }),
+                    };
+                    if let Some(response) = response {
+                        self.send(id, &response);
+                    }
+                }
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return,
+            }
+        }
+    }
+
+    fn execute(
+        &mut self,
+        id: usize,
+        request: Request,
+        chip8: &mut Chip8,
+        paused: &mut bool,
+    ) -> Option<Response> {
+        match request {
+            Request::Pause => *paused = true,
+            Request::Resume => *paused = false,
+            Request::Step { count } => {
+                if count > MAX_STEPS {
+                    return Some(Response::Error {
+                        message: format!("can't step more than {} instructions at once", MAX_STEPS),
+                    });
+                }
+                for _ in 0..count {
+                    if let Err(e) = chip8.cycle() {
+                        return Some(Response::Error {
+                            message: e.to_string(),
+                        });
+                    }
+                }
+            }
+            Request::Key { key, down } => {
+                if key >= 16 {
+                    return Some(Response::Error {
+                        message: format!("key {} doesn't exist", key),
+                    });
+                }
+                if down {
+                    chip8.key_down(key);
+                } else {
+                    chip8.key_up(key);
+                }
+            }
+            Request::Registers => {
+                return Some(Response::Registers {
+                    v: *chip8.registers(),
+                    i: chip8.index(),
+                    pc: chip8.pc(),
+                    stack: chip8.stack().to_vec(),
+                    delay_timer: chip8.delay_timer(),
+                    sound_timer: chip8.sound_timer(),
+                    paused: *paused,
+                });
+            }
+            Request::Memory { address, length } => {
+                let memory = chip8.memory();
+                let start = (address as usize).min(memory.len());
+                let end = start.saturating_add(length as usize).min(memory.len());
+                return Some(Response::Memory {
+                    address,
+                    bytes: memory[start..end].to_vec(),
+                });
+            }
+            Request::Subscribe { frames } => {
+                if let Some(client) = self.clients.get_mut(&id) {
+                    client.frames = frames;
+                }
+            }
+        }
+        None
+    }
+
+    /// Sends the display to the clients that subscribed to frames
+    pub fn push_frame(&mut self, chip8: &Chip8, frame: u64) {
+        if !self.clients.values().any(|client| client.frames) {
+            return;
+        }
+
+        let width = chip8.display_width();
+        let response = Response::Frame {
+            frame,
+            width,
+            height: chip8.display_height(),
+            rows: chip8
+                .get_display()
+                .chunks(width)
+                .map(|row| row.iter().map(|&on| if on { '#' } else { '.' }).collect())
+                .collect(),
+        };
+        let text = serde_json::to_string(&response).expect("responses are valid JSON");
+        for client in self.clients.values().filter(|client| client.frames) {
+            let _ = client.outbox.send(text.clone());
+        }
+    }
+
+    fn send(&self, id: usize, response: &Response) {
+        if let Some(client) = self.clients.get(&id) {
+            let text = serde_json::to_string(response).expect("responses are valid JSON");
+            let _ = client.outbox.send(text);
+        }
+    }
+}
+
+/// Runs one connection: forwards its requests to the emulator and writes
+/// back whatever the emulator queued for it
+fn serve(id: usize, stream: TcpStream, events: Sender<Event>) {
+    let Ok(mut socket) = tungstenite::accept(stream) else {
+        return;
+    };
+    if socket
+        .get_ref()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .is_err()
+    {
+        return;
+    }
+    let (outbox, queued) = mpsc::channel();
+    if events.send(Event::Connected(id, outbox)).is_err() {
+        return;
+    }
+
+    while relay(&mut socket, &events, &queued, id).is_ok() {}
+    let _ = events.send(Event::Closed(id));
+}
+
+fn relay(
+    socket: &mut WebSocket<TcpStream>,
+    events: &Sender<Event>,
+    queued: &Receiver<String>,
+    id: usize,
+) -> Result<(), tungstenite::Error> {
+    match socket.read() {
+        Ok(Message::Text(text)) => {
+            if events.send(Event::Request(id, text.to_string())).is_err() {
+                return Err(tungstenite::Error::ConnectionClosed);
+            }
+        }
+        Ok(Message::Close(_)) => return Err(tungstenite::Error::ConnectionClosed),
+        Ok(_) => {}
+        Err(tungstenite::Error::Io(e))
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) => {}
+        Err(e) => return Err(e),
+    }
+
+    while let Ok(text) = queued.try_recv() {
+        socket.send(Message::text(text))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Settings remembered for each ROM, kept in `roms.toml` next to the config
+//! with a table per ROM file name, e.g.
+//!
+//! ```toml
+//! ["pong2.ch8"]
+//! ipf = 15
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomProfile {
+    /// Instructions per frame last picked with the speed hotkeys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipf: Option<u32>,
+}
+
+impl RomProfile {
+    /// The profile of the ROM at `rom_path`, empty when none was saved or
+    /// the file can't be read
+    pub fn load(rom_path: &Path) -> Self {
+        let profile = key(rom_path)
+            .and_then(|key| load_all().ok()?.remove(&key))
+            .unwrap_or_default();
+        Self {
+            ipf: profile.ipf.filter(|&ipf| ipf > 0),
+        }
+    }
+
+    /// Stores the profile of the ROM at `rom_path`, keeping the other ROMs'.
+    /// Only ROM files have a profile, not stdin or the built-in ROMs
+    pub fn save(&self, rom_path: &Path) -> Result<(), String> {
+        let (Some(file), Some(key)) = (file(), key(rom_path)) else {
+            return Ok(());
+        };
+        let mut profiles = load_all()?;
+        profiles.insert(key, self.clone());
+
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let contents = toml::to_string(&profiles).map_err(|e| e.to_string())?;
+        fs::write(&file, contents).map_err(|e| format!("{}: {}", file.display(), e))
+    }
+}
+
+/// Every ROM's profile, none when the file wasn't written yet
+fn load_all() -> Result<BTreeMap<String, RomProfile>, String> {
+    let Some(file) = file() else {
+        return Ok(BTreeMap::new());
+    };
+    match fs::read_to_string(&file) {
+        Ok(contents) => toml::from_str(&contents)
+            .map_err(|e| format!("Invalid ROM profiles {}: {}", file.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(format!("{}: {}", file.display(), e)),
+    }
+}
+
+/// Profiles are looked up by the ROM's file name, like the config's
+/// `rom_profiles`
+fn key(rom_path: &Path) -> Option<String> {
+    if !rom_path.is_file() {
+        return None;
+    }
+    rom_path.file_name()?.to_str().map(|name| name.to_string())
+}
+
+fn file() -> Option<PathBuf> {
+    Some(config::app_dir()?.join("roms.toml"))
+}
@@ -0,0 +1,88 @@
+//! Recognizes known ROMs by their SHA-1 hash, so the right quirks can be
+//! picked without the user knowing which platform a ROM was written for.
+//! The list lives in `roms.txt` next to this file
+
+use alloc::string::{String, ToString};
+use core::fmt;
+
+use crate::quirks::Quirks;
+
+const KNOWN_ROMS: &str = include_str!("roms.txt");
+
+/// The interpreter a ROM was written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter
+    Chip8,
+    /// CHIP-48 on the HP-48
+    Chip48,
+    /// SUPER-CHIP 1.1
+    Schip,
+    /// MegaChip8
+    Megachip,
+    /// CHIP-8X with the VIP's color board
+    Chip8x,
+}
+
+impl Platform {
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::CHIP8,
+            Platform::Chip48 => Quirks::CHIP48,
+            Platform::Schip => Quirks::SCHIP,
+            Platform::Megachip => Quirks::MEGACHIP,
+            Platform::Chip8x => Quirks::CHIP8X,
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Chip8 => write!(f, "chip8"),
+            Platform::Chip48 => write!(f, "chip48"),
+            Platform::Schip => write!(f, "schip"),
+            Platform::Megachip => write!(f, "megachip"),
+            Platform::Chip8x => write!(f, "chip8x"),
+        }
+    }
+}
+
+/// What's known about a ROM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub title: String,
+    pub platform: Platform,
+}
+
+/// Lowercase hex SHA-1 of the ROM bytes, the key of the ROM databases
+pub fn sha1_hex(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+/// Looks the ROM up in the built-in list
+pub fn lookup(rom: &[u8]) -> Option<RomInfo> {
+    let hash = sha1_hex(rom);
+    KNOWN_ROMS
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .find_map(|line| {
+            let mut fields = line.splitn(3, char::is_whitespace);
+            if !fields.next()?.eq_ignore_ascii_case(&hash) {
+                return None;
+            }
+            let platform = match fields.next()? {
+                "chip8" => Platform::Chip8,
+                "chip48" => Platform::Chip48,
+                "schip" => Platform::Schip,
+                "megachip" => Platform::Megachip,
+                "chip8x" => Platform::Chip8x,
+                _ => return None,
+            };
+            Some(RomInfo {
+                title: fields.next().unwrap_or_default().trim().to_string(),
+                platform,
+            })
+        })
+}
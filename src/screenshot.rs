@@ -0,0 +1,115 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chip_8_rs::Chip8;
+
+use crate::palette::Palette;
+
+/// Writes the current display to `<dir>/<rom name>-<timestamp>.png`, each
+/// CHIP-8 pixel being `scale` pixels wide. MegaChip's colors are kept, other
+/// displays are drawn in `palette`. Returns the path written to
+pub fn save(
+    chip8: &Chip8,
+    palette: Palette,
+    scale: u32,
+    dir: &Path,
+    rom_path: &Path,
+) -> io::Result<PathBuf> {
+    let rom_name = rom_path
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "chip8".to_string());
+    let path = dir.join(format!("{}-{}.png", rom_name, timestamp()));
+
+    let width = chip8.display_width() as u32 * scale;
+    let height = chip8.display_height() as u32 * scale;
+    let colors = chip8.get_colors();
+    let mut data = Vec::with_capacity((width * height * 3) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let index = (y / scale) as usize * chip8.display_width() + (x / scale) as usize;
+            if let Some(colors) = &colors {
+                data.extend_from_slice(&colors[index].to_be_bytes()[1..]);
+                continue;
+            }
+            let color = if chip8.get_display()[index] {
+                palette.foreground
+            } else {
+                palette.background
+            };
+            data.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(&path)?), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(io::Error::other)?;
+
+    Ok(path)
+}
+
+/// The display as a row of `#` for lit and `.` for dark pixels per line
+pub fn text(chip8: &Chip8) -> String {
+    let mut text = String::new();
+    for row in chip8.get_display().chunks(chip8.display_width()) {
+        text.extend(row.iter().map(|&lit| if lit { '#' } else { '.' }));
+        text.push('\n');
+    }
+    text
+}
+
+/// Writes the display to `path` as a plain PBM image, a pixel per CHIP-8
+/// pixel with lit ones black. Being text, it diffs well
+pub fn save_pbm(chip8: &Chip8, path: &Path) -> io::Result<()> {
+    let mut pbm = format!("P1\n{} {}\n", chip8.display_width(), chip8.display_height());
+    for row in chip8.get_display().chunks(chip8.display_width()) {
+        let pixels: Vec<&str> = row.iter().map(|&lit| if lit { "1" } else { "0" }).collect();
+        pbm.push_str(&pixels.join(" "));
+        pbm.push('\n');
+    }
+    fs::write(path, pbm)
+}
+
+/// The current UTC time as `YYYYMMDD-HHMMSS-mmm`
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let time = secs % 86_400;
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        now.subsec_millis()
+    )
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date
+/// https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
@@ -0,0 +1,1134 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use chip_8_rs::{
+    LORES_HEIGHT, LORES_WIDTH,
+    frontend::{AudioSink, DisplaySink, InputSource, NullFrontend},
+};
+
+use crate::{
+    cli::RunArgs,
+    config::{Config, KeyProfile, parse_chip8_key},
+    dap::Dap,
+    debugger::{Debugger, Flow},
+    keyboard::{KEYPAD, KeyboardLayout},
+    load_chip8, load_plugins,
+    movie::{self, Movie},
+    netplay::{self, Netplay},
+    pacer::FramePacer,
+    palette::{self, Palette},
+    print_profile,
+    recent::RecentRoms,
+    session::{Hotkey, KeypadOverlay, Session, quirks_name, report_warnings},
+    stats::Stats,
+};
+use log::{error, info, warn};
+use sdl2::{
+    AudioSubsystem, EventPump,
+    event::{Event, WindowEvent},
+    keyboard::Keycode,
+    pixels::Color,
+    pixels::PixelFormatEnum,
+    rect::Rect,
+    render::{BlendMode, Canvas, Texture, TextureCreator},
+    video::{Window, WindowContext},
+};
+
+mod audio;
+mod menu;
+mod picker;
+mod text;
+mod touch;
+
+pub use audio::SdlAudio;
+use menu::PauseMenu;
+pub use picker::pick_rom;
+use touch::TouchKeypad;
+
+/// How much of a pixel's brightness is kept each frame after it turns off
+/// when ghosting is enabled
+const GHOST_DECAY: f32 = 0.6;
+/// Ghost pixels dimmer than this aren't drawn anymore
+const GHOST_CUTOFF: f32 = 0.05;
+
+/// How the display is drawn
+pub struct DisplayOptions {
+    pub palette: Palette,
+    /// Only scale by whole numbers when the window is resized
+    pub integer_scaling: bool,
+    /// Fade pixels out over a few frames instead of switching them off
+    /// instantly, which hides most of the flicker caused by XOR drawing
+    pub ghosting: bool,
+    /// Darken the gaps between pixel rows and the edges of the screen to
+    /// mimic a CRT monitor
+    pub crt: bool,
+}
+
+/// One machine's display in the window
+#[derive(Default)]
+struct Pane<'a> {
+    /// Streaming texture with the size of the current resolution, recreated
+    /// when the resolution changes
+    texture: Option<(Texture<'a>, usize, usize)>,
+    /// Brightness of every pixel between 0.0 and 1.0, used for ghosting
+    intensity: Vec<f32>,
+}
+
+/// Draws the CHIP-8 display onto an SDL window canvas. The pixels are
+/// written into a texture of the display's resolution once per frame and the
+/// GPU scales it up to the window. With several machines side by side each
+/// gets a pane, and the window is drawn once the last one presented
+pub struct SdlDisplay<'a> {
+    canvas: &'a mut Canvas<Window>,
+    textures: &'a TextureCreator<WindowContext>,
+    panes: Vec<Pane<'a>>,
+    /// The pane `present` draws into
+    pane: usize,
+    options: DisplayOptions,
+    /// Lines of text drawn over the top left corner of the display
+    overlay: Vec<String>,
+    /// The keys of the touch keypad that are held, while it's shown
+    touch_keypad: Option<[bool; 16]>,
+    /// The keypad state drawn over the top right corner
+    keypad_overlay: Option<KeypadOverlay>,
+    /// Lines of the pause menu, drawn in the middle of the window
+    menu: Vec<String>,
+}
+
+impl<'a> SdlDisplay<'a> {
+    pub fn new(
+        canvas: &'a mut Canvas<Window>,
+        textures: &'a TextureCreator<WindowContext>,
+        options: DisplayOptions,
+    ) -> Self {
+        // Scale without smoothing, so pixels stay sharp squares
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+        Self {
+            canvas,
+            textures,
+            panes: vec![Pane::default()],
+            pane: 0,
+            options,
+            overlay: Vec::new(),
+            touch_keypad: None,
+            keypad_overlay: None,
+            menu: Vec::new(),
+        }
+    }
+
+    /// Sets the text shown on top of the display from the next frame on. An
+    /// empty list hides the overlay
+    pub fn set_overlay(&mut self, lines: Vec<String>) {
+        self.overlay = lines;
+    }
+
+    /// Shows the pause menu's lines, or hides it when there are none
+    pub fn set_menu(&mut self, lines: Vec<String>) {
+        self.menu = lines;
+    }
+
+    /// Draws the pause menu in a box in the middle of the window
+    fn draw_menu(&mut self) {
+        if self.menu.is_empty() {
+            return;
+        }
+
+        let (window_width, window_height) = self.canvas.output_size().unwrap_or((0, 0));
+        let scale = (window_height / 120).max(1);
+        let line_height = text::line_height(scale);
+        let padding = 4 * scale;
+        let longest = self.menu.iter().map(|line| line.chars().count()).max();
+        let width = longest.unwrap_or(0) as u32 * text::char_advance(scale) + padding * 2;
+        let height = self.menu.len() as u32 * line_height + padding * 2;
+        let left = (window_width.saturating_sub(width) / 2) as i32;
+        let top = (window_height.saturating_sub(height) / 2) as i32;
+
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 210));
+        let _ = self.canvas.fill_rect(Rect::new(left, top, width, height));
+        self.canvas.set_blend_mode(BlendMode::None);
+
+        for (row, line) in self.menu.iter().enumerate() {
+            text::draw_text(
+                self.canvas,
+                line,
+                left + padding as i32,
+                top + (padding + row as u32 * line_height) as i32,
+                scale,
+                Color::WHITE,
+            );
+        }
+    }
+
+    /// Shows the touch keypad with the given keys held, or hides it
+    pub fn set_touch_keypad(&mut self, pressed: Option<[bool; 16]>) {
+        self.touch_keypad = pressed;
+    }
+
+    /// Shows the keypad overlay, or hides it
+    pub fn set_keypad_overlay(&mut self, keypad: Option<KeypadOverlay>) {
+        self.keypad_overlay = keypad;
+    }
+
+    /// Draws the keypad in the top right corner with the held keys lit up
+    /// and the labels of the keys the program checks highlighted
+    fn draw_keypad_overlay(&mut self) {
+        let Some(keypad) = &self.keypad_overlay else {
+            return;
+        };
+
+        let (window_width, window_height) = self.canvas.output_size().unwrap_or((0, 0));
+        let scale = (window_height / 200).max(1);
+        let cell = 9 * scale;
+        let padding = 2 * scale;
+        let left = window_width as i32 - (4 * cell + padding * 2) as i32;
+
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 180));
+        let _ = self.canvas.fill_rect(Rect::new(
+            left,
+            0,
+            4 * cell + padding * 2,
+            4 * cell + padding * 2,
+        ));
+        for (row, keys) in KEYPAD.iter().enumerate() {
+            for (col, &key) in keys.iter().enumerate() {
+                let x = left + (padding + col as u32 * cell) as i32;
+                let y = (padding + row as u32 * cell) as i32;
+                if keypad.held[key as usize] {
+                    self.canvas.set_draw_color(Color::RGBA(255, 255, 255, 120));
+                    let _ = self
+                        .canvas
+                        .fill_rect(Rect::new(x, y, cell - scale, cell - scale));
+                }
+                let color = if keypad.polled[key as usize] {
+                    Color::YELLOW
+                } else {
+                    Color::GRAY
+                };
+                text::draw_text(
+                    self.canvas,
+                    &format!("{:X}", key),
+                    x + 3 * scale as i32,
+                    y + 2 * scale as i32,
+                    scale,
+                    color,
+                );
+            }
+        }
+        self.canvas.set_blend_mode(BlendMode::None);
+    }
+
+    fn draw_overlay(&mut self) {
+        if self.overlay.is_empty() {
+            return;
+        }
+
+        let (_, window_height) = self.canvas.output_size().unwrap_or((0, 0));
+        let scale = (window_height / 200).max(1);
+        let line_height = text::line_height(scale);
+        let padding = 2 * scale;
+        let longest = self.overlay.iter().map(|line| line.chars().count()).max();
+        let width = longest.unwrap_or(0) as u32 * text::char_advance(scale) + padding * 2;
+        let height = self.overlay.len() as u32 * line_height + padding * 2;
+
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 180));
+        let _ = self.canvas.fill_rect(Rect::new(0, 0, width, height));
+        self.canvas.set_blend_mode(BlendMode::None);
+
+        for (row, line) in self.overlay.iter().enumerate() {
+            text::draw_text(
+                self.canvas,
+                line,
+                padding as i32,
+                (padding + row as u32 * line_height) as i32,
+                scale,
+                Color::WHITE,
+            );
+        }
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        let _ = self.canvas.window_mut().set_title(title);
+    }
+
+    /// Splits the window into `count` panes side by side
+    pub fn set_panes(&mut self, count: usize) {
+        self.panes.resize_with(count.max(1), Pane::default);
+        self.pane = 0;
+    }
+
+    /// Makes the next `present` draw into pane `index`, counted from the
+    /// left
+    pub fn select_pane(&mut self, index: usize) {
+        self.pane = index.min(self.panes.len() - 1);
+    }
+
+    /// Updates the brightness of every pixel for this frame
+    fn update_intensity(&mut self, pixels: &[bool]) {
+        let pane = &mut self.panes[self.pane];
+        if pane.intensity.len() != pixels.len() {
+            // The resolution changed, start over
+            pane.intensity = vec![0.0; pixels.len()];
+        }
+
+        for (intensity, &pixel) in pane.intensity.iter_mut().zip(pixels) {
+            *intensity = if pixel {
+                1.0
+            } else if self.options.ghosting {
+                *intensity * GHOST_DECAY
+            } else {
+                0.0
+            };
+        }
+    }
+
+    /// Writes the pixels into the texture, creating it first if the
+    /// resolution changed. `colors` replaces the palette and the phosphor
+    /// persistence in MegaChip mode
+    fn upload(
+        &mut self,
+        width: usize,
+        height: usize,
+        colors: Option<&[u32]>,
+    ) -> Result<(), String> {
+        let pane = &mut self.panes[self.pane];
+        if !matches!(pane.texture, Some((_, w, h)) if (w, h) == (width, height)) {
+            let texture = self
+                .textures
+                .create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+                .map_err(|e| e.to_string())?;
+            pane.texture = Some((texture, width, height));
+        }
+        let Some((texture, _, _)) = &mut pane.texture else {
+            unreachable!("the texture was just created");
+        };
+
+        let Palette {
+            foreground,
+            background,
+        } = self.options.palette;
+        let intensity = &pane.intensity;
+        texture.with_lock(None, |buffer, pitch| {
+            if let Some(colors) = colors {
+                for (y, row) in colors.chunks(width).enumerate() {
+                    let line = &mut buffer[y * pitch..y * pitch + width * 3];
+                    for (pixel, &color) in line.chunks_mut(3).zip(row) {
+                        pixel.copy_from_slice(&color.to_be_bytes()[1..]);
+                    }
+                }
+                return;
+            }
+            for (y, row) in intensity.chunks(width).enumerate() {
+                let line = &mut buffer[y * pitch..y * pitch + width * 3];
+                for (pixel, &intensity) in line.chunks_mut(3).zip(row) {
+                    let intensity = if intensity < GHOST_CUTOFF {
+                        0.0
+                    } else {
+                        intensity
+                    };
+                    let color = blend(background, foreground, intensity);
+                    pixel.copy_from_slice(&[color.r, color.g, color.b]);
+                }
+            }
+        })
+    }
+}
+
+impl SdlDisplay<'_> {
+    /// Uploads the current pane's frame, then draws the window if it was
+    /// the last pane
+    fn present_pane(&mut self, width: usize, height: usize, colors: Option<&[u32]>) {
+        if let Err(e) = self.upload(width, height, colors) {
+            error!("Failed to draw the display: {}", e);
+        }
+        if self.pane + 1 == self.panes.len() {
+            self.draw();
+        }
+    }
+
+    fn draw(&mut self) {
+        self.canvas.set_draw_color(self.options.palette.background);
+        self.canvas.clear();
+
+        let (window_width, window_height) = self.canvas.output_size().unwrap_or((0, 0));
+        let column_width = window_width as f64 / self.panes.len() as f64;
+        for index in 0..self.panes.len() {
+            let Some((texture, width, height)) = &self.panes[index].texture else {
+                continue;
+            };
+            let (width, height) = (*width, *height);
+
+            // Keep the aspect ratio and center the image in its column,
+            // letterboxing whatever space is left over
+            let mut scale = f64::min(
+                column_width / width as f64,
+                window_height as f64 / height as f64,
+            );
+            if self.options.integer_scaling {
+                scale = scale.floor().max(1.0);
+            }
+            let offset_x =
+                column_width * index as f64 + (column_width - width as f64 * scale) / 2.0;
+            let offset_y = (window_height as f64 - height as f64 * scale) / 2.0;
+
+            let edge_x = |x: usize| (offset_x + x as f64 * scale).round() as i32;
+            let edge_y = |y: usize| (offset_y + y as f64 * scale).round() as i32;
+
+            let target = Rect::new(
+                edge_x(0),
+                edge_y(0),
+                (edge_x(width) - edge_x(0)) as u32,
+                (edge_y(height) - edge_y(0)) as u32,
+            );
+            let _ = self.canvas.copy(texture, None, target);
+
+            if self.options.crt {
+                let rows: Vec<i32> = (0..=height).map(edge_y).collect();
+                self.draw_crt_effect(&rows, (edge_x(0), edge_x(width)));
+            }
+        }
+
+        self.draw_overlay();
+        self.draw_keypad_overlay();
+        self.draw_menu();
+        if let Some(pressed) = &self.touch_keypad {
+            touch::draw(self.canvas, pressed);
+        }
+        self.canvas.present();
+    }
+}
+
+impl DisplaySink for SdlDisplay<'_> {
+    fn present(&mut self, pixels: &[bool], width: usize, height: usize) {
+        self.update_intensity(pixels);
+        self.present_pane(width, height, None);
+    }
+
+    fn present_colors(&mut self, colors: &[u32], width: usize, height: usize) {
+        self.present_pane(width, height, Some(colors));
+    }
+}
+
+/// Number of gradient steps in the CRT vignette
+const VIGNETTE_STEPS: u32 = 12;
+/// Darkness of the outermost vignette step, out of 255
+const VIGNETTE_ALPHA: u8 = 28;
+/// Darkness of the scanlines, out of 255
+const SCANLINE_ALPHA: u8 = 110;
+
+impl SdlDisplay<'_> {
+    /// Draws scanlines at the bottom of every row of display pixels, whose
+    /// top edges are given in `rows`, and a vignette over the image spanning
+    /// `left..right`
+    fn draw_crt_effect(&mut self, rows: &[i32], (left, right): (i32, i32)) {
+        self.canvas.set_blend_mode(BlendMode::Blend);
+
+        self.canvas
+            .set_draw_color(Color::RGBA(0, 0, 0, SCANLINE_ALPHA));
+        for row in rows.windows(2) {
+            let pixel_height = (row[1] - row[0]) as u32;
+            if pixel_height < 3 {
+                // Too small for a visible gap between rows
+                break;
+            }
+            let thickness = (pixel_height / 3).max(1);
+            let _ = self.canvas.fill_rect(Rect::new(
+                left,
+                row[1] - thickness as i32,
+                (right - left) as u32,
+                thickness,
+            ));
+        }
+
+        // Overlapping translucent bands along each edge, which get darker
+        // towards the corners
+        let top = rows[0];
+        let width = (right - left) as u32;
+        let height = (rows[rows.len() - 1] - top) as u32;
+        let band = (width.min(height) / 2 / VIGNETTE_STEPS).max(1);
+        self.canvas
+            .set_draw_color(Color::RGBA(0, 0, 0, VIGNETTE_ALPHA));
+        for step in 0..VIGNETTE_STEPS {
+            let inset = step * band;
+            if inset * 2 >= width.min(height) {
+                break;
+            }
+            let thickness = (band * (VIGNETTE_STEPS - step) / VIGNETTE_STEPS).max(1);
+            let inner_width = width - inset * 2;
+            let inner_height = height - inset * 2;
+            let (x, y) = (left + inset as i32, top + inset as i32);
+            let _ = self.canvas.fill_rects(&[
+                Rect::new(x, y, inner_width, thickness),
+                Rect::new(
+                    x,
+                    y + (inner_height - thickness) as i32,
+                    inner_width,
+                    thickness,
+                ),
+                Rect::new(x, y, thickness, inner_height),
+                Rect::new(
+                    x + (inner_width - thickness) as i32,
+                    y,
+                    thickness,
+                    inner_height,
+                ),
+            ]);
+        }
+
+        self.canvas.set_blend_mode(BlendMode::None);
+    }
+}
+
+impl From<palette::Color> for Color {
+    fn from(color: palette::Color) -> Self {
+        Color::RGB(color.r, color.g, color.b)
+    }
+}
+
+/// Linear interpolation between two colors, `t` = 0.0 gives `from`
+fn blend(from: palette::Color, to: palette::Color, t: f32) -> Color {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color::RGB(
+        channel(from.r, to.r),
+        channel(from.g, to.g),
+        channel(from.b, to.b),
+    )
+}
+
+/// Fast-forwards emulation while held
+const TURBO_KEY: Keycode = Keycode::Tab;
+
+/// Reads the keypad state and hotkeys from SDL keyboard and touch events
+pub struct SdlInput<'a> {
+    event_pump: &'a mut EventPump,
+    keymap: KeyMap,
+    keys: [bool; 16],
+    touch: TouchKeypad,
+    /// The window's width over its height, for finding the touched key
+    aspect: f32,
+    hotkeys: Vec<Hotkey>,
+    /// Keys for the pause menu: every key while it's open, Escape otherwise
+    menu_keys: Vec<Keycode>,
+    menu_open: bool,
+    turbo: bool,
+    quit: bool,
+}
+
+impl<'a> SdlInput<'a> {
+    pub fn new(event_pump: &'a mut EventPump, keymap: KeyMap, (width, height): (u32, u32)) -> Self {
+        Self {
+            event_pump,
+            keymap,
+            keys: [false; 16],
+            touch: TouchKeypad::default(),
+            aspect: width as f32 / height.max(1) as f32,
+            hotkeys: Vec::new(),
+            menu_keys: Vec::new(),
+            menu_open: false,
+            turbo: false,
+            quit: false,
+        }
+    }
+
+    /// The keys held on the touch keypad, once the window has been touched
+    pub fn touch_keypad(&self) -> Option<[bool; 16]> {
+        self.touch.visible().then(|| {
+            let mut keys = [false; 16];
+            self.touch.press(&mut keys);
+            keys
+        })
+    }
+
+    /// Whether the window was closed
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    /// While the pause menu is open its keys aren't hotkeys or keypad keys
+    pub fn set_menu_open(&mut self, open: bool) {
+        self.menu_open = open;
+    }
+
+    /// Returns the keys for the pause menu pressed since the last call
+    pub fn take_menu_keys(&mut self) -> Vec<Keycode> {
+        std::mem::take(&mut self.menu_keys)
+    }
+
+    pub fn keymap(&self) -> &KeyMap {
+        &self.keymap
+    }
+
+    /// Whether the fast-forward key is being held down
+    pub fn turbo_held(&self) -> bool {
+        self.turbo
+    }
+
+    /// Returns the hotkeys pressed since the last call
+    pub fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+
+    /// Processes all pending SDL events. Must be called once per frame, even
+    /// when the emulator isn't running, to keep the window responsive
+    pub fn pump_events(&mut self) {
+        for event in self.event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => {
+                    self.quit = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } if self.menu_open || keycode == Keycode::Escape => {
+                    self.menu_keys.push(keycode);
+                }
+                Event::KeyDown {
+                    keycode: Some(TURBO_KEY),
+                    ..
+                } => {
+                    self.turbo = true;
+                }
+                Event::KeyUp {
+                    keycode: Some(TURBO_KEY),
+                    ..
+                } => {
+                    self.turbo = false;
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(key) = self.keymap.key(keycode) {
+                        self.keys[key as usize] = true;
+                    } else if let Some(hotkey) = map_keycode_to_hotkey(keycode) {
+                        self.hotkeys.push(hotkey);
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    if let Some(key) = self.keymap.key(keycode) {
+                        self.keys[key as usize] = false;
+                    }
+                }
+                Event::FingerDown {
+                    finger_id, x, y, ..
+                }
+                | Event::FingerMotion {
+                    finger_id, x, y, ..
+                } => self.touch.finger_down(finger_id, x, y, self.aspect),
+                Event::FingerUp { finger_id, .. } => self.touch.finger_up(finger_id),
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(width, height),
+                    ..
+                } => self.aspect = width as f32 / height.max(1) as f32,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl InputSource for SdlInput<'_> {
+    fn poll(&mut self, keys: &mut [bool; 16]) {
+        *keys = self.keys;
+        self.touch.press(keys);
+    }
+}
+
+/// Emulator commands by key, in the order the help overlay lists them
+const HOTKEYS: [(Keycode, Hotkey); 21] = [
+    (Keycode::F1, Hotkey::ToggleHelp),
+    (Keycode::P, Hotkey::Pause),
+    (Keycode::Space, Hotkey::Pause),
+    (Keycode::F10, Hotkey::Step),
+    (Keycode::Backslash, Hotkey::FrameAdvance),
+    (Keycode::F2, Hotkey::Reset),
+    (Keycode::F5, Hotkey::SaveState),
+    (Keycode::F9, Hotkey::LoadState),
+    (Keycode::F6, Hotkey::PreviousSlot),
+    (Keycode::F7, Hotkey::NextSlot),
+    (Keycode::F12, Hotkey::Screenshot),
+    (Keycode::F3, Hotkey::ToggleHud),
+    (Keycode::F11, Hotkey::ToggleKeypad),
+    (Keycode::F4, Hotkey::ToggleCheats),
+    (Keycode::F8, Hotkey::DumpProfile),
+    (Keycode::M, Hotkey::ToggleMute),
+    (Keycode::Backquote, Hotkey::SwitchMachine),
+    (Keycode::Equals, Hotkey::SpeedUp),
+    (Keycode::KpPlus, Hotkey::SpeedUp),
+    (Keycode::Minus, Hotkey::SpeedDown),
+    (Keycode::KpMinus, Hotkey::SpeedDown),
+];
+
+/// The keypad for one of the machines side by side, which sees no keys
+/// pressed while the other one has the input
+struct PaneInput<'a, I> {
+    input: &'a mut I,
+    active: bool,
+}
+
+impl<I: InputSource> InputSource for PaneInput<'_, I> {
+    fn poll(&mut self, keys: &mut [bool; 16]) {
+        if self.active {
+            self.input.poll(keys);
+        } else {
+            *keys = [false; 16];
+        }
+    }
+
+    fn poll_second(&mut self, keys: &mut [bool; 16]) {
+        if self.active {
+            self.input.poll_second(keys);
+        } else {
+            *keys = [false; 16];
+        }
+    }
+}
+
+fn map_keycode_to_hotkey(keycode: Keycode) -> Option<Hotkey> {
+    HOTKEYS
+        .iter()
+        .find(|&&(key, _)| key == keycode)
+        .map(|&(_, hotkey)| hotkey)
+}
+
+/// The help overlay: what each CHIP-8 key is bound to, then the keys that
+/// aren't passed to the program
+fn help_lines(keymap: &KeyMap) -> Vec<String> {
+    let mut lines = vec!["KEYPAD".to_string()];
+    for row in KEYPAD {
+        let keys: Vec<String> = row
+            .iter()
+            .map(|&key| format!("{:X} {:<5}", key, keymap.names(key).join("/")))
+            .collect();
+        lines.push(keys.join(" ").trim_end().to_string());
+    }
+
+    lines.push(String::new());
+    lines.push("HOTKEYS".to_string());
+    lines.push(format!("{:<6}menu", "ESC"));
+    lines.push(format!("{:<6}turbo", TURBO_KEY.name()));
+    for (keycode, hotkey) in HOTKEYS {
+        lines.push(format!("{:<6}{}", keycode.name(), hotkey.description()));
+    }
+    lines
+}
+
+/// Maps physical keys to CHIP-8 keypad keys
+pub struct KeyMap {
+    bindings: HashMap<Keycode, u8>,
+}
+
+impl KeyMap {
+    /// Builds the keypad for the keyboard `layout`, mirroring the COSMAC VIP
+    /// keypad on the left side of the keyboard, with the given overrides
+    /// applied, then adds the keys of a two-player profile. Overrides map a
+    /// CHIP-8 key written as a hex digit to an SDL key name
+    pub fn with_overrides(
+        layout: KeyboardLayout,
+        overrides: &BTreeMap<String, String>,
+        profile: Option<&KeyProfile>,
+    ) -> Result<Self, String> {
+        // SDL's keycodes for characters are their code points
+        let mut layout = layout
+            .keys()
+            .map(|c| Keycode::from_i32(c as i32).expect("a character's keycode"));
+
+        for (key, name) in overrides {
+            let key = parse_chip8_key(key)?;
+            layout[key as usize] = keycode(name)?;
+        }
+
+        let mut bindings: HashMap<Keycode, u8> = layout
+            .iter()
+            .enumerate()
+            .map(|(key, &keycode)| (keycode, key as u8))
+            .collect();
+        if let Some(profile) = profile {
+            for (key, name) in profile.bindings()? {
+                bindings.insert(keycode(name)?, key);
+            }
+        }
+
+        Ok(Self { bindings })
+    }
+
+    fn key(&self, keycode: Keycode) -> Option<u8> {
+        self.bindings.get(&keycode).copied()
+    }
+
+    /// Names of the keys bound to the CHIP-8 `key`, sorted
+    fn names(&self, key: u8) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|&(_, &bound)| bound == key)
+            .map(|(keycode, _)| keycode.name())
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+fn keycode(name: &str) -> Result<Keycode, String> {
+    Keycode::from_name(name).ok_or(format!("Unknown key name: {}", name))
+}
+
+/// The window and audio, kept open while ROMs are switched
+struct Devices {
+    canvas: Canvas<Window>,
+    event_pump: EventPump,
+    audio: AudioSubsystem,
+}
+
+/// What ended the run of a ROM
+enum Exit {
+    Quit,
+    /// Load ROM was picked in the pause menu
+    PickRom,
+}
+
+/// Opens a window and runs the emulator in it until the window is closed or
+/// Quit is picked in the pause menu. Shows the ROM picker when no ROM was
+/// given, and again when Load ROM is picked
+pub fn run(args: &RunArgs, config: &Config, palette: Palette) {
+    let sdl_ctx = sdl2::init().unwrap();
+    let video_subsystem = sdl_ctx.video().unwrap();
+    let audio_subsystem = sdl_ctx.audio().unwrap();
+
+    let panes = if args.split_screen() { 2 } else { 1 };
+    let window = video_subsystem
+        .window(
+            "chip-8-rs",
+            LORES_WIDTH as u32 * args.scale * panes,
+            LORES_HEIGHT as u32 * args.scale,
+        )
+        .position_centered()
+        .resizable()
+        .build()
+        .unwrap();
+
+    let mut canvas = window.into_canvas();
+    if args.vsync || config.vsync {
+        canvas = canvas.present_vsync();
+    }
+    let mut devices = Devices {
+        canvas: canvas.build().unwrap(),
+        event_pump: sdl_ctx.event_pump().unwrap(),
+        audio: audio_subsystem,
+    };
+
+    // The editor stays connected while other ROMs are picked
+    let mut dap = match &args.dap {
+        Some(address) => match Dap::spawn(address) {
+            Ok(dap) => Some(dap),
+            Err(e) => {
+                error!("Failed to start the debug adapter on {}: {}", address, e);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let library_dir = config.rom_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+    let mut rom_dir = library_dir.clone();
+    let mut recent = RecentRoms::load();
+    let mut rom_path = args.rom.clone();
+    loop {
+        // Without a ROM on the command line, let the user pick one
+        let path = match rom_path.take() {
+            Some(path) => path,
+            None => match pick_rom(
+                &mut devices.canvas,
+                &mut devices.event_pump,
+                &rom_dir,
+                recent.paths(),
+                &library_dir,
+                args.database.as_deref(),
+            ) {
+                Some(path) => path,
+                None => return,
+            },
+        };
+        let exit = run_rom(
+            args,
+            config,
+            palette,
+            &path,
+            &mut devices,
+            &mut recent,
+            dap.as_mut(),
+        );
+        match exit {
+            Some(Exit::PickRom) => {
+                // The picker opens where the last ROM was
+                if let Some(dir) = path.parent().filter(|dir| dir.is_dir()) {
+                    rom_dir = dir.to_path_buf();
+                }
+            }
+            Some(Exit::Quit) | None => break,
+        }
+    }
+
+    if let Some(dap) = &mut dap {
+        dap.finish();
+    }
+}
+
+/// Runs the ROM at `rom_path` in the window. Returns `None` when it couldn't
+/// be started or emulation stopped with an error
+fn run_rom(
+    args: &RunArgs,
+    config: &Config,
+    palette: Palette,
+    rom_path: &Path,
+    devices: &mut Devices,
+    recent: &mut RecentRoms,
+    mut dap: Option<&mut Dap>,
+) -> Option<Exit> {
+    // The two-player profile can depend on the ROM
+    let keymap = config
+        .key_profile(args, rom_path)
+        .and_then(|profile| KeyMap::with_overrides(config.layout, &config.keypad, profile));
+    let keymap = match keymap {
+        Ok(keymap) => keymap,
+        Err(e) => {
+            error!("{}", e);
+            return None;
+        }
+    };
+
+    // Emulator
+    let (mut chip8, metadata) = load_chip8(rom_path, args)?;
+    if let Err(e) = recent.add(rom_path) {
+        error!("Failed to save the recent ROMs: {}", e);
+    }
+    let movie = match Movie::from_args(args, &mut chip8) {
+        Ok(movie) => movie,
+        Err(e) => {
+            error!("Failed to open movie: {}", e);
+            return None;
+        }
+    };
+    let netplay = match Netplay::from_args(args, &mut chip8) {
+        Ok(netplay) => netplay,
+        Err(e) => {
+            error!("Netplay failed: {}", e);
+            return None;
+        }
+    };
+
+    let window_size = devices.canvas.window().size();
+    let textures = devices.canvas.texture_creator();
+    let mut display = SdlDisplay::new(
+        &mut devices.canvas,
+        &textures,
+        DisplayOptions {
+            palette,
+            integer_scaling: args.integer_scale,
+            ghosting: args.ghosting || config.ghosting,
+            crt: args.crt || config.crt,
+        },
+    );
+    let mut input = SdlInput::new(&mut devices.event_pump, keymap, window_size);
+    let mut audio = SdlAudio::new(&devices.audio, config.audio).unwrap();
+
+    let other = match (&args.side_by_side, args.compare_quirks) {
+        (Some(path), _) => Some(load_chip8(path, args)?.0),
+        (None, Some(preset)) => {
+            let (mut other, _) = load_chip8(rom_path, args)?;
+            // Reloads the ROM, where it goes depends on the quirks
+            other.set_quirks(preset.into());
+            other.reset();
+            // Different random numbers would make the runs drift apart for
+            // reasons other than the quirks
+            let seed = chip8.seed().unwrap_or_else(rand::random);
+            chip8.set_seed(seed);
+            other.set_seed(seed);
+            info!(
+                "Comparing {} quirks on the left with {} on the right",
+                quirks_name(chip8.quirks()),
+                quirks_name(other.quirks())
+            );
+            Some(other)
+        }
+        (None, None) => None,
+    };
+    if other.is_some() {
+        display.set_panes(2);
+    }
+
+    let plugins = load_plugins(args, &mut chip8)?;
+    let mut session = Session::new(
+        chip8,
+        movie,
+        netplay,
+        metadata,
+        rom_path.to_path_buf(),
+        args,
+        palette,
+    );
+    session.plugins = plugins;
+    session.other = other;
+    let rom_name = session.rom_name();
+    display.set_title(&format!("chip-8-rs - {}", rom_name));
+    let mut stats = Stats::new();
+    let mut debugger = args.debug.then(Debugger::spawn);
+    let mut menu = PauseMenu::default();
+    if session.other.is_none() && session.can_resume() {
+        menu.offer_resume(&mut session);
+        input.set_menu_open(true);
+    }
+
+    let mut exit = Some(Exit::Quit);
+    let mut pacer = FramePacer::new();
+    while !input.quit_requested() {
+        input.pump_events();
+        for hotkey in input.take_hotkeys() {
+            session.handle_hotkey(hotkey);
+        }
+        let mut action = None;
+        for keycode in input.take_menu_keys() {
+            if session.netplay.is_some() {
+                // Both machines have to keep running, so there's nothing to
+                // do but leave
+                action = Some(menu::Action::Quit);
+            } else {
+                action = action.or(menu.key(keycode, &mut session));
+            }
+        }
+        match action {
+            Some(menu::Action::Hotkey(hotkey)) => session.handle_hotkey(hotkey),
+            // Movies are tied to the ROM they were recorded with
+            Some(menu::Action::LoadRom) if session.movie.is_some() => {
+                warn!("Can't load another ROM while a movie is recorded or played");
+            }
+            Some(menu::Action::LoadRom) => {
+                exit = Some(Exit::PickRom);
+                break;
+            }
+            Some(menu::Action::Quit) => break,
+            None => {}
+        }
+        input.set_menu_open(menu.is_open());
+        if let Some(debugger) = &mut debugger
+            && debugger.poll(&mut session) == Flow::Quit
+        {
+            break;
+        }
+        if let Some(dap) = &mut dap
+            && dap.poll(
+                &mut session.chip8,
+                &mut session.paused,
+                session.stop.take(),
+                &session.symbols,
+            ) == Flow::Quit
+        {
+            break;
+        }
+
+        display.set_overlay(if session.help {
+            help_lines(input.keymap())
+        } else if session.hud {
+            session.hud_lines(&stats)
+        } else {
+            session.speed_line().into_iter().collect()
+        });
+        display.set_touch_keypad(input.touch_keypad());
+        display.set_keypad_overlay(session.keypad_overlay());
+        display.set_menu(menu.lines(&session));
+
+        audio.set_muted(session.muted);
+
+        let turbo = input.turbo_held();
+        let instructions = session.frame_instructions(turbo);
+        let running = session.run_next_frame();
+        let chip8 = &mut session.chip8;
+        let executed = if running { instructions } else { 0 };
+        // Each machine has the keypad unless it's switched between them
+        let focused = |machine| !args.independent_input || session.focus == machine;
+        display.select_pane(0);
+        if !running {
+            // Keep showing the last frame so the window doesn't go blank
+            // when it's uncovered or resized
+            chip8.present(&mut display);
+            if let Some(other) = &session.other {
+                display.select_pane(1);
+                other.present(&mut display);
+            }
+            audio.set_tone(false);
+        } else if let Err(e) = chip8.run_frame(
+            instructions,
+            &mut movie::input(
+                session.movie.as_mut(),
+                &mut netplay::input(
+                    session.netplay.as_mut(),
+                    &mut PaneInput {
+                        input: &mut input,
+                        active: focused(0),
+                    },
+                ),
+            ),
+            &mut display,
+            &mut audio,
+        ) {
+            error!("Emulation stopped: {}", e);
+            exit = None;
+            break;
+        } else if let Some(other) = &mut session.other {
+            display.select_pane(1);
+            let mut input = PaneInput {
+                input: &mut input,
+                active: focused(1),
+            };
+            if let Err(e) =
+                other.run_frame(instructions, &mut input, &mut display, &mut NullFrontend)
+            {
+                error!("Emulation of the right ROM stopped: {}", e);
+                exit = None;
+                break;
+            }
+            report_warnings(other);
+        }
+        if executed > 0 {
+            session.end_frame();
+        }
+        session.check_stop();
+
+        if stats.record_frame(executed) {
+            display.set_title(&format!(
+                "chip-8-rs - {}{} - {:.0} FPS, {:.0} IPS",
+                rom_name,
+                if session.paused { " [paused]" } else { "" },
+                stats.fps(),
+                stats.ips()
+            ));
+        }
+
+        if turbo {
+            pacer.reset();
+        } else {
+            pacer.wait();
+        }
+    }
+
+    if let Some(movie) = &session.movie {
+        movie.finish();
+    }
+    print_profile(&session.chip8);
+    session.save_rom_profile();
+    // A machine that stopped with an error isn't worth resuming
+    if exit.is_some() {
+        session.save_autosave();
+    }
+    if matches!(exit, Some(Exit::Quit)) {
+        info!("Exiting...");
+    }
+    exit
+}
@@ -0,0 +1,79 @@
+use chip_8_rs::frontend::{AudioPattern, AudioSink};
+use sdl2::{
+    AudioSubsystem,
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+};
+
+use crate::audio::{Oscillator, Tone};
+
+const SAMPLE_RATE: i32 = 44_100;
+
+struct Beep(Oscillator);
+
+impl AudioCallback for Beep {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.0.fill(out);
+    }
+}
+
+/// Plays the beep or the XO-CHIP audio pattern while the sound timer is
+/// running
+pub struct SdlAudio {
+    device: AudioDevice<Beep>,
+    playing: bool,
+    pattern: Option<AudioPattern>,
+    muted: bool,
+}
+
+impl SdlAudio {
+    pub fn new(audio_subsystem: &AudioSubsystem, tone: Tone) -> Result<Self, String> {
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+            Beep(Oscillator::new(tone, spec.freq as u32))
+        })?;
+
+        Ok(Self {
+            device,
+            playing: false,
+            pattern: None,
+            muted: false,
+        })
+    }
+
+    /// Keeps the device silent from the next frame on, however the sound
+    /// timer is set
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+}
+
+impl AudioSink for SdlAudio {
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        // Locking blocks the audio thread, so only when it changed
+        if pattern != self.pattern {
+            self.device.lock().0.set_pattern(pattern);
+            self.pattern = pattern;
+        }
+    }
+
+    fn set_tone(&mut self, active: bool) {
+        let active = active && !self.muted;
+        if active == self.playing {
+            return;
+        }
+
+        if active {
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+        self.playing = active;
+    }
+}
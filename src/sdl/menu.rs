@@ -0,0 +1,218 @@
+//! The menu Escape opens over the paused game, navigated with the arrow keys
+//! and Enter. It also asks whether to resume a ROM that was quit midway
+
+use sdl2::keyboard::Keycode;
+
+use crate::session::{Hotkey, Session};
+
+/// What the run loop should do for a menu choice
+pub enum Action {
+    Hotkey(Hotkey),
+    /// Go back to the ROM picker and run the ROM picked there
+    LoadRom,
+    Quit,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Item {
+    Resume,
+    Reset,
+    LoadRom,
+    SaveState,
+    LoadState,
+    Options,
+    Quit,
+}
+
+const ITEMS: [Item; 7] = [
+    Item::Resume,
+    Item::Reset,
+    Item::LoadRom,
+    Item::SaveState,
+    Item::LoadState,
+    Item::Options,
+    Item::Quit,
+];
+
+/// A setting on the options page
+struct Setting {
+    name: &'static str,
+    /// The hotkey that toggles it while playing
+    hotkey: Hotkey,
+    on: fn(&Session) -> bool,
+}
+
+/// The last line of the options page goes back to the main page
+const OPTIONS: [Setting; 3] = [
+    Setting {
+        name: "sound",
+        hotkey: Hotkey::ToggleMute,
+        on: |session| !session.muted,
+    },
+    Setting {
+        name: "stats",
+        hotkey: Hotkey::ToggleHud,
+        on: |session| session.hud,
+    },
+    Setting {
+        name: "keypad overlay",
+        hotkey: Hotkey::ToggleKeypad,
+        on: |session| session.keypad_overlay,
+    },
+];
+
+/// The choices when a ROM starts with a state saved the last time it quit
+const RESUME_CHOICES: [&str; 2] = ["continue last game", "start over"];
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum Page {
+    #[default]
+    Main,
+    Options,
+    Resume,
+}
+
+#[derive(Default)]
+pub struct PauseMenu {
+    open: bool,
+    /// Index of the highlighted line on the current page
+    selected: usize,
+    page: Page,
+}
+
+impl PauseMenu {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens the page asking whether to continue from the state saved when
+    /// the ROM was last quit, pausing until it's answered
+    pub fn offer_resume(&mut self, session: &mut Session) {
+        *self = Self {
+            open: true,
+            page: Page::Resume,
+            ..Self::default()
+        };
+        session.paused = true;
+    }
+
+    /// Handles a key pressed while the menu is open, or Escape while it's
+    /// closed, which opens it and pauses the game
+    pub fn key(&mut self, keycode: Keycode, session: &mut Session) -> Option<Action> {
+        if !self.open {
+            if keycode == Keycode::Escape {
+                *self = Self {
+                    open: true,
+                    ..Self::default()
+                };
+                session.paused = true;
+            }
+            return None;
+        }
+
+        let lines = match self.page {
+            Page::Main => ITEMS.len(),
+            Page::Options => OPTIONS.len() + 1,
+            Page::Resume => RESUME_CHOICES.len(),
+        };
+        match keycode {
+            Keycode::Up => self.selected = (self.selected + lines - 1) % lines,
+            Keycode::Down => self.selected = (self.selected + 1) % lines,
+            Keycode::Escape if self.page == Page::Options => self.show_main(),
+            Keycode::Escape if self.page == Page::Resume => {
+                session.discard_autosave();
+                self.resume(session);
+            }
+            Keycode::Escape => self.resume(session),
+            Keycode::Return if self.page == Page::Resume => {
+                if self.selected == 0 {
+                    session.resume_autosave();
+                } else {
+                    session.discard_autosave();
+                }
+                self.resume(session);
+            }
+            Keycode::Return if self.page == Page::Options => match OPTIONS.get(self.selected) {
+                Some(setting) => return Some(Action::Hotkey(setting.hotkey)),
+                None => self.show_main(),
+            },
+            Keycode::Return => match ITEMS[self.selected] {
+                Item::Resume => self.resume(session),
+                Item::Reset => {
+                    self.resume(session);
+                    return Some(Action::Hotkey(Hotkey::Reset));
+                }
+                Item::LoadRom => return Some(Action::LoadRom),
+                Item::SaveState => return Some(Action::Hotkey(Hotkey::SaveState)),
+                Item::LoadState => return Some(Action::Hotkey(Hotkey::LoadState)),
+                Item::Options => {
+                    self.page = Page::Options;
+                    self.selected = 0;
+                }
+                Item::Quit => return Some(Action::Quit),
+            },
+            _ => {}
+        }
+        None
+    }
+
+    fn resume(&mut self, session: &mut Session) {
+        self.open = false;
+        session.paused = false;
+    }
+
+    fn show_main(&mut self) {
+        self.page = Page::Main;
+        self.selected = ITEMS
+            .iter()
+            .position(|&item| item == Item::Options)
+            .unwrap_or(0);
+    }
+
+    /// The lines of the current page with the highlighted one marked, empty
+    /// while the menu is closed
+    pub fn lines(&self, session: &Session) -> Vec<String> {
+        if !self.open {
+            return Vec::new();
+        }
+
+        let labels: Vec<String> = match self.page {
+            Page::Options => OPTIONS
+                .iter()
+                .map(|setting| {
+                    let on = if (setting.on)(session) { "on" } else { "off" };
+                    format!("{:<15}{}", setting.name, on)
+                })
+                .chain(["back".to_string()])
+                .collect(),
+            Page::Resume => RESUME_CHOICES
+                .iter()
+                .map(|label| label.to_string())
+                .collect(),
+            Page::Main => ITEMS
+                .iter()
+                .map(|item| {
+                    match item {
+                        Item::Resume => "resume",
+                        Item::Reset => "reset",
+                        Item::LoadRom => "load rom",
+                        Item::SaveState => "save state",
+                        Item::LoadState => "load state",
+                        Item::Options => "options",
+                        Item::Quit => "quit",
+                    }
+                    .to_string()
+                })
+                .collect(),
+        };
+
+        labels
+            .into_iter()
+            .enumerate()
+            .map(|(index, label)| {
+                let marker = if index == self.selected { '>' } else { ' ' };
+                format!("{} {}", marker, label)
+            })
+            .collect()
+    }
+}
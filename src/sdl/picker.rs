@@ -0,0 +1,285 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::error;
+use sdl2::{
+    EventPump, event::Event, keyboard::Keycode, pixels::Color, rect::Rect, render::Canvas,
+    video::Window,
+};
+
+use chip_8_rs::archive;
+
+use super::text;
+use crate::{
+    library::{self, LibraryEntry},
+    metadata::Database,
+};
+
+/// Archives shown next to the ROMs, see `chip_8_rs::archive`
+#[cfg(feature = "archives")]
+const ARCHIVE_EXTENSIONS: [&str; 2] = ["zip", "gz"];
+#[cfg(not(feature = "archives"))]
+const ARCHIVE_EXTENSIONS: [&str; 0] = [];
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+    /// Shown at the bottom while the entry is selected
+    info: Option<String>,
+}
+
+/// Shows an in-window file browser starting at `dir` and blocks until the
+/// user picks a ROM with Enter. The `recent` ROMs are listed on top, and
+/// picked right away with their number key. Tab switches to the library of
+/// all ROMs under `library_dir` and back, named with the `database` when
+/// there is one. Returns `None` if the window is closed or Escape is pressed
+pub fn pick_rom(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut EventPump,
+    dir: &Path,
+    recent: &[PathBuf],
+    library_dir: &Path,
+    database: Option<&Path>,
+) -> Option<PathBuf> {
+    let list_dir = |dir: &Path| recent_entries(recent).chain(list_dir(dir)).collect();
+    let mut dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let mut entries: Vec<Entry> = list_dir(&dir);
+    let mut selected: usize = 0;
+    let mut in_library = false;
+    // The entries of the view that isn't shown. The library is scanned the
+    // first time it's shown
+    let mut hidden: Option<Vec<Entry>> = None;
+
+    loop {
+        for event in event_pump.poll_iter() {
+            let Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } = event
+            else {
+                if let Event::Quit { .. } = event {
+                    return None;
+                }
+                continue;
+            };
+
+            match keycode {
+                Keycode::Escape => return None,
+                Keycode::Tab => {
+                    in_library = !in_library;
+                    let other = hidden.take().unwrap_or_else(|| {
+                        draw_message(canvas, "Scanning...");
+                        scan_library(library_dir, database)
+                    });
+                    hidden = Some(std::mem::replace(&mut entries, other));
+                    selected = 0;
+                }
+                Keycode::Num1
+                | Keycode::Num2
+                | Keycode::Num3
+                | Keycode::Num4
+                | Keycode::Num5
+                | Keycode::Num6
+                | Keycode::Num7
+                | Keycode::Num8
+                | Keycode::Num9 => {
+                    let index = (keycode.into_i32() - Keycode::Num1.into_i32()) as usize;
+                    if let Some(path) = recent.get(index) {
+                        return Some(path.clone());
+                    }
+                }
+                Keycode::Up => selected = selected.saturating_sub(1),
+                Keycode::Down => selected = (selected + 1).min(entries.len().saturating_sub(1)),
+                Keycode::PageUp => selected = selected.saturating_sub(10),
+                Keycode::PageDown => {
+                    selected = (selected + 10).min(entries.len().saturating_sub(1))
+                }
+                Keycode::Backspace if !in_library => {
+                    if let Some(parent) = dir.parent() {
+                        dir = parent.to_path_buf();
+                        entries = list_dir(&dir);
+                        selected = 0;
+                    }
+                }
+                Keycode::Return => {
+                    let Some(entry) = entries.get(selected) else {
+                        continue;
+                    };
+                    if !entry.is_dir {
+                        return Some(entry.path.clone());
+                    }
+                    dir = entry.path.clone();
+                    entries = list_dir(&dir);
+                    selected = 0;
+                }
+                _ => {}
+            }
+        }
+
+        let title = if in_library {
+            format!("Library: {}", library_dir.display())
+        } else {
+            format!("Select a ROM: {}", dir.display())
+        };
+        draw(canvas, &title, &entries, selected);
+        std::thread::sleep(std::time::Duration::from_millis(16));
+    }
+}
+
+/// The recent ROMs with their number keys
+fn recent_entries(recent: &[PathBuf]) -> impl Iterator<Item = Entry> {
+    recent.iter().zip(1..).map(|(path, number)| Entry {
+        name: format!(
+            "{} {}",
+            number,
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ),
+        path: path.clone(),
+        is_dir: false,
+        info: None,
+    })
+}
+
+/// The library as entries, with the database's summary or the hash of the
+/// selected ROM shown below the list
+fn scan_library(dir: &Path, database: Option<&Path>) -> Vec<Entry> {
+    let database =
+        database.and_then(|path| Database::load(path).inspect_err(|e| error!("{}", e)).ok());
+    library::scan(dir, database.as_ref())
+        .into_iter()
+        .map(|entry: LibraryEntry| Entry {
+            name: entry.name,
+            info: Some(entry.summary.unwrap_or(format!("SHA-1 {}", entry.hash))),
+            path: entry.path,
+            is_dir: false,
+        })
+        .collect()
+}
+
+/// Subdirectories followed by ROM files, each sorted by name, with a `..`
+/// entry on top unless `dir` is the root
+fn list_dir(dir: &Path) -> Vec<Entry> {
+    let mut dirs = Vec::new();
+    let mut roms = Vec::new();
+
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            dirs.push(Entry {
+                name: format!("{}/", name),
+                path,
+                is_dir: true,
+                info: None,
+            });
+        } else if archive::is_rom_name(&name)
+            || path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ARCHIVE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        {
+            roms.push(Entry {
+                name,
+                path,
+                is_dir: false,
+                info: None,
+            });
+        }
+    }
+
+    dirs.sort_by(|a, b| a.name.cmp(&b.name));
+    roms.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let parent = dir.parent().map(|parent| Entry {
+        name: "../".to_string(),
+        path: parent.to_path_buf(),
+        is_dir: true,
+        info: None,
+    });
+    parent.into_iter().chain(dirs).chain(roms).collect()
+}
+
+/// Shows a single line while the picker is busy
+fn draw_message(canvas: &mut Canvas<Window>, message: &str) {
+    let (_, height) = canvas.output_size().unwrap_or((0, 0));
+    let scale = (height / 160).max(1);
+    let margin = (text::line_height(scale) / 2) as i32;
+
+    canvas.set_draw_color(Color::BLACK);
+    canvas.clear();
+    text::draw_text(canvas, message, margin, margin, scale, Color::GREEN);
+    canvas.present();
+}
+
+fn draw(canvas: &mut Canvas<Window>, title: &str, entries: &[Entry], selected: usize) {
+    let (width, height) = canvas.output_size().unwrap_or((0, 0));
+    let scale = (height / 160).max(1);
+    let line_height = text::line_height(scale);
+    let margin = (line_height / 2) as i32;
+
+    canvas.set_draw_color(Color::BLACK);
+    canvas.clear();
+
+    text::draw_text(canvas, title, margin, margin, scale, Color::GREEN);
+
+    let info = entries
+        .get(selected)
+        .and_then(|entry| entry.info.as_deref());
+    let list_top = margin + 2 * line_height as i32;
+    // The info line takes the bottom row and a gap above it
+    let list_bottom = height as i32
+        - margin
+        - if info.is_some() {
+            2 * line_height as i32
+        } else {
+            0
+        };
+    let visible = ((list_bottom - list_top) / line_height as i32).max(1) as usize;
+    // Keep the selection roughly centered once the list starts scrolling
+    let first = selected
+        .saturating_sub(visible / 2)
+        .min(entries.len().saturating_sub(visible));
+
+    for (row, (index, entry)) in entries
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(visible)
+        .enumerate()
+    {
+        let y = list_top + (row as u32 * line_height) as i32;
+        let color = if index == selected {
+            canvas.set_draw_color(Color::GREEN);
+            let _ = canvas.fill_rect(Rect::new(0, y - scale as i32, width, line_height));
+            Color::BLACK
+        } else {
+            Color::GREEN
+        };
+        text::draw_text(canvas, &entry.name, margin, y, scale, color);
+    }
+
+    if let Some(info) = info {
+        let y = height as i32 - margin - line_height as i32;
+        text::draw_text(canvas, info, margin, y, scale, Color::GREEN);
+    }
+
+    if entries.is_empty() {
+        text::draw_text(
+            canvas,
+            "No ROMs here",
+            margin,
+            list_top,
+            scale,
+            Color::GREEN,
+        );
+    }
+
+    canvas.present();
+}
@@ -0,0 +1,125 @@
+//! A tiny 3x5 pixel font for drawing overlays and menus without depending on
+//! SDL_ttf. Lowercase letters are drawn as uppercase.
+
+use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window};
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// Glyphs for ASCII 0x20 (space) to 0x5F (underscore), one row per byte with
+/// the leftmost pixel in bit 2
+const GLYPHS: [[u8; 5]; 64] = [
+    [0b000, 0b000, 0b000, 0b000, 0b000], // space
+    [0b010, 0b010, 0b010, 0b000, 0b010], // !
+    [0b101, 0b101, 0b000, 0b000, 0b000], // "
+    [0b101, 0b111, 0b101, 0b111, 0b101], // #
+    [0b011, 0b110, 0b010, 0b011, 0b110], // $
+    [0b101, 0b001, 0b010, 0b100, 0b101], // %
+    [0b010, 0b101, 0b010, 0b101, 0b011], // &
+    [0b010, 0b010, 0b000, 0b000, 0b000], // '
+    [0b010, 0b100, 0b100, 0b100, 0b010], // (
+    [0b010, 0b001, 0b001, 0b001, 0b010], // )
+    [0b101, 0b010, 0b101, 0b000, 0b000], // *
+    [0b000, 0b010, 0b111, 0b010, 0b000], // +
+    [0b000, 0b000, 0b000, 0b010, 0b100], // ,
+    [0b000, 0b000, 0b111, 0b000, 0b000], // -
+    [0b000, 0b000, 0b000, 0b000, 0b010], // .
+    [0b001, 0b001, 0b010, 0b100, 0b100], // /
+    [0b111, 0b101, 0b101, 0b101, 0b111], // 0
+    [0b010, 0b110, 0b010, 0b010, 0b111], // 1
+    [0b110, 0b001, 0b010, 0b100, 0b111], // 2
+    [0b110, 0b001, 0b010, 0b001, 0b110], // 3
+    [0b101, 0b101, 0b111, 0b001, 0b001], // 4
+    [0b111, 0b100, 0b110, 0b001, 0b110], // 5
+    [0b011, 0b100, 0b111, 0b101, 0b111], // 6
+    [0b111, 0b001, 0b010, 0b010, 0b010], // 7
+    [0b111, 0b101, 0b111, 0b101, 0b111], // 8
+    [0b111, 0b101, 0b111, 0b001, 0b110], // 9
+    [0b000, 0b010, 0b000, 0b010, 0b000], // :
+    [0b000, 0b010, 0b000, 0b010, 0b100], // ;
+    [0b001, 0b010, 0b100, 0b010, 0b001], // <
+    [0b000, 0b111, 0b000, 0b111, 0b000], // =
+    [0b100, 0b010, 0b001, 0b010, 0b100], // >
+    [0b110, 0b001, 0b010, 0b000, 0b010], // ?
+    [0b111, 0b101, 0b101, 0b100, 0b111], // @
+    [0b010, 0b101, 0b111, 0b101, 0b101], // A
+    [0b110, 0b101, 0b110, 0b101, 0b110], // B
+    [0b011, 0b100, 0b100, 0b100, 0b011], // C
+    [0b110, 0b101, 0b101, 0b101, 0b110], // D
+    [0b111, 0b100, 0b110, 0b100, 0b111], // E
+    [0b111, 0b100, 0b110, 0b100, 0b100], // F
+    [0b011, 0b100, 0b101, 0b101, 0b011], // G
+    [0b101, 0b101, 0b111, 0b101, 0b101], // H
+    [0b111, 0b010, 0b010, 0b010, 0b111], // I
+    [0b001, 0b001, 0b001, 0b101, 0b010], // J
+    [0b101, 0b101, 0b110, 0b101, 0b101], // K
+    [0b100, 0b100, 0b100, 0b100, 0b111], // L
+    [0b101, 0b111, 0b111, 0b101, 0b101], // M
+    [0b110, 0b101, 0b101, 0b101, 0b101], // N
+    [0b010, 0b101, 0b101, 0b101, 0b010], // O
+    [0b110, 0b101, 0b110, 0b100, 0b100], // P
+    [0b010, 0b101, 0b101, 0b110, 0b011], // Q
+    [0b110, 0b101, 0b110, 0b101, 0b101], // R
+    [0b011, 0b100, 0b010, 0b001, 0b110], // S
+    [0b111, 0b010, 0b010, 0b010, 0b010], // T
+    [0b101, 0b101, 0b101, 0b101, 0b111], // U
+    [0b101, 0b101, 0b101, 0b101, 0b010], // V
+    [0b101, 0b101, 0b111, 0b111, 0b101], // W
+    [0b101, 0b101, 0b010, 0b101, 0b101], // X
+    [0b101, 0b101, 0b010, 0b010, 0b010], // Y
+    [0b111, 0b001, 0b010, 0b100, 0b111], // Z
+    [0b110, 0b100, 0b100, 0b100, 0b110], // [
+    [0b100, 0b100, 0b010, 0b001, 0b001], // \
+    [0b011, 0b001, 0b001, 0b001, 0b011], // ]
+    [0b010, 0b101, 0b000, 0b000, 0b000], // ^
+    [0b000, 0b000, 0b000, 0b000, 0b111], // _
+];
+
+/// Horizontal distance between the starts of two characters
+pub fn char_advance(scale: u32) -> u32 {
+    (GLYPH_WIDTH + 1) * scale
+}
+
+/// Vertical distance between the tops of two lines
+pub fn line_height(scale: u32) -> u32 {
+    (GLYPH_HEIGHT + 2) * scale
+}
+
+/// Draws `text` with its top left corner at (x, y), each font pixel being
+/// `scale` window pixels wide
+pub fn draw_text(
+    canvas: &mut Canvas<Window>,
+    text: &str,
+    x: i32,
+    y: i32,
+    scale: u32,
+    color: Color,
+) {
+    canvas.set_draw_color(color);
+
+    for (index, c) in text.chars().enumerate() {
+        let glyph = glyph(c);
+        let glyph_x = x + (index as u32 * char_advance(scale)) as i32;
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> col) != 0 {
+                    let _ = canvas.fill_rect(Rect::new(
+                        glyph_x + (col * scale) as i32,
+                        y + (row as u32 * scale) as i32,
+                        scale,
+                        scale,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn glyph(c: char) -> &'static [u8; 5] {
+    let c = c.to_ascii_uppercase();
+    match c {
+        ' '..='_' => &GLYPHS[c as usize - 0x20],
+        _ => &GLYPHS['?' as usize - 0x20],
+    }
+}
@@ -0,0 +1,117 @@
+//! A 4x4 keypad in the bottom right corner of the window for touch screens.
+//! It shows up the first time the window is touched, from then on a finger
+//! holds the key under it until it's lifted or slides off
+
+use std::collections::HashMap;
+
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{BlendMode, Canvas},
+    video::Window,
+};
+
+use super::text;
+use crate::keyboard::KEYPAD;
+
+/// Side of the keypad as a fraction of the window's height
+const SIZE: f32 = 0.45;
+/// Gap between the keypad and the window's edges, and between its keys, as
+/// a fraction of a key
+const GAP: f32 = 0.1;
+
+#[derive(Default)]
+pub struct TouchKeypad {
+    /// Hidden until the window is touched
+    visible: bool,
+    /// The key each finger is holding, by SDL finger id
+    fingers: HashMap<i64, u8>,
+}
+
+impl TouchKeypad {
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// A finger touched or moved to `(x, y)`, normalized to the window's size
+    /// like SDL's finger events. `aspect` is the window's width over its
+    /// height
+    pub fn finger_down(&mut self, finger: i64, x: f32, y: f32, aspect: f32) {
+        self.visible = true;
+        match key_at(x, y, aspect) {
+            Some(key) => {
+                self.fingers.insert(finger, key);
+            }
+            None => {
+                self.fingers.remove(&finger);
+            }
+        }
+    }
+
+    pub fn finger_up(&mut self, finger: i64) {
+        self.fingers.remove(&finger);
+    }
+
+    /// Presses the keys held by fingers in `keys`
+    pub fn press(&self, keys: &mut [bool; 16]) {
+        for &key in self.fingers.values() {
+            keys[key as usize] = true;
+        }
+    }
+}
+
+/// The key at `(x, y)`, normalized to the size of a window with the
+/// `aspect` ratio
+fn key_at(x: f32, y: f32, aspect: f32) -> Option<u8> {
+    let x = x * aspect;
+    let (left, top, key) = layout(aspect);
+    let (col, row) = ((x - left) / key, (y - top) / key);
+    if !(0.0..4.0).contains(&col) || !(0.0..4.0).contains(&row) {
+        return None;
+    }
+    // Touches in the gaps still count, a finger is rarely that precise
+    Some(KEYPAD[row as usize][col as usize])
+}
+
+/// The keypad's left and top edge and the distance between two keys'
+/// edges, in units of the window's height
+fn layout(aspect: f32) -> (f32, f32, f32) {
+    let key = SIZE / 4.0;
+    let gap = key * GAP;
+    (aspect - SIZE - gap, 1.0 - SIZE - gap, key)
+}
+
+/// Draws the keypad translucently over the display, with the keys in
+/// `pressed` lit up
+pub fn draw(canvas: &mut Canvas<Window>, pressed: &[bool; 16]) {
+    let (width, height) = canvas.output_size().unwrap_or((0, 0));
+    if height == 0 {
+        return;
+    }
+    let height = height as f32;
+    let (left, top, key) = layout(width as f32 / height);
+    let scale = ((key * height) as u32 / 16).max(1);
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    for (row, keys) in KEYPAD.iter().enumerate() {
+        for (col, &chip8_key) in keys.iter().enumerate() {
+            let x = (left + col as f32 * key) * height;
+            let y = (top + row as f32 * key) * height;
+            let side = (key * (1.0 - GAP) * height) as u32;
+            let alpha = if pressed[chip8_key as usize] { 160 } else { 60 };
+            canvas.set_draw_color(Color::RGBA(255, 255, 255, alpha));
+            let _ = canvas.fill_rect(Rect::new(x as i32, y as i32, side, side));
+
+            let label = format!("{:X}", chip8_key);
+            text::draw_text(
+                canvas,
+                &label,
+                x as i32 + (side - text::char_advance(scale) + scale) as i32 / 2,
+                y as i32 + (side - 5 * scale) as i32 / 2,
+                scale,
+                Color::WHITE,
+            );
+        }
+    }
+    canvas.set_blend_mode(BlendMode::None);
+}
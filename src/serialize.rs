@@ -0,0 +1,244 @@
+//! `Serialize` and `Deserialize` for `Chip8`, behind the `serde` feature.
+//! Covers the same machine state as `save_state` plus the keypad, the loaded
+//! ROM and the quirks. Debugging state like breakpoints isn't included. To
+//! stay readable in text formats, memory and the ROM are stored as rows of
+//! hex and the display as rows of `#` and `.` at the current resolution.
+//! MegaChip mode's state is only there while it's on. Snapshots carry a
+//! `version`, and ones written by a newer emulator are refused
+
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+#[cfg(feature = "json")]
+use crate::error::Chip8Error;
+use crate::{
+    chip8::{Chip8, DEFAULT_PITCH, MEMORY_SIZE, XO_CHIP_MEMORY},
+    chip8x::Chip8X,
+    megachip::MegaChip,
+    quirks::Quirks,
+};
+
+/// Bytes per row of hex
+const HEX_ROW: usize = 32;
+
+/// Version of the snapshot format, those from before it had one are 0
+const SNAPSHOT_VERSION: u16 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    #[serde(default)]
+    version: u16,
+    memory: Vec<String>,
+    v: [u8; 16],
+    i: u32,
+    pc: u16,
+    stack: Vec<u16>,
+    sp: u8,
+    hires: bool,
+    #[serde(default)]
+    two_page: bool,
+    display: Vec<String>,
+    delay_timer: u8,
+    sound_timer: u8,
+    keypad: [bool; 16],
+    rpl: [u8; 16],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    audio_pattern: Option<[u8; 16]>,
+    #[serde(default = "default_pitch")]
+    audio_pitch: u8,
+    rom: Vec<String>,
+    quirks: Quirks,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    megachip: Option<Box<MegaChip>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chip8x: Option<Box<Chip8X>>,
+}
+
+fn default_pitch() -> u8 {
+    DEFAULT_PITCH
+}
+
+fn to_hex(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(HEX_ROW)
+        .map(|row| row.iter().map(|byte| format!("{:02X}", byte)).collect())
+        .collect()
+}
+
+fn from_hex<E: Error>(rows: &[String]) -> Result<Vec<u8>, E> {
+    let mut bytes = Vec::new();
+    for row in rows {
+        if row.len() % 2 != 0 || !row.is_ascii() {
+            return Err(E::custom(format!("invalid hex row {:?}", row)));
+        }
+        for i in (0..row.len()).step_by(2) {
+            let byte = u8::from_str_radix(&row[i..i + 2], 16)
+                .map_err(|_| E::custom(format!("invalid hex row {:?}", row)))?;
+            bytes.push(byte);
+        }
+    }
+    Ok(bytes)
+}
+
+impl Serialize for Chip8 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let display = self
+            .get_display()
+            .chunks(self.display_width())
+            .map(|row| row.iter().map(|&on| if on { '#' } else { '.' }).collect())
+            .collect();
+
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            memory: to_hex(&self.memory),
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            stack: self.stack.clone(),
+            sp: self.sp,
+            hires: self.hires,
+            two_page: self.two_page,
+            display,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            keypad: self.keypad,
+            rpl: self.rpl,
+            audio_pattern: self.audio_pattern,
+            audio_pitch: self.audio_pitch,
+            rom: to_hex(&self.rom),
+            quirks: self.quirks(),
+            megachip: self.megachip.clone(),
+            chip8x: self.chip8x.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Chip8 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let snapshot = Snapshot::deserialize(deserializer)?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(D::Error::custom(format!(
+                "snapshot version {} is newer than this emulator's {}",
+                snapshot.version, SNAPSHOT_VERSION
+            )));
+        }
+        let memory = from_hex::<D::Error>(&snapshot.memory)?;
+        let rom = from_hex::<D::Error>(&snapshot.rom)?;
+        // MegaChip's memory doesn't depend on the size the machine was
+        // created with
+        let memory_size = match snapshot.quirks.megachip {
+            false => memory.len(),
+            true => MEMORY_SIZE,
+        };
+        if !(MEMORY_SIZE..=XO_CHIP_MEMORY).contains(&memory_size) {
+            return Err(D::Error::invalid_length(
+                memory.len(),
+                &"4096 to 65536 bytes of memory",
+            ));
+        }
+        if !(1..=u8::MAX as usize).contains(&snapshot.stack.len()) {
+            return Err(D::Error::invalid_length(
+                snapshot.stack.len(),
+                &"1 to 255 stack slots",
+            ));
+        }
+        let mut chip8 = Chip8::with_sizes(memory_size, snapshot.stack.len());
+        // Quirks first, they decide how much memory there is
+        chip8.set_quirks(snapshot.quirks);
+        chip8.load(&rom).map_err(D::Error::custom)?;
+
+        if memory.len() != chip8.memory.len() {
+            return Err(D::Error::invalid_length(
+                memory.len(),
+                &format!("{} bytes of memory", chip8.memory.len()).as_str(),
+            ));
+        }
+        if snapshot.i as usize >= chip8.memory.len().max(0x10000) {
+            return Err(D::Error::custom(format!(
+                "I register {:#X} is wider than the quirks allow",
+                snapshot.i
+            )));
+        }
+        if snapshot.sp as usize > snapshot.stack.len() {
+            return Err(D::Error::custom(format!(
+                "stack pointer {} is past the end of the stack",
+                snapshot.sp
+            )));
+        }
+        chip8.memory.copy_from_slice(&memory);
+        chip8.v = snapshot.v;
+        chip8.i = snapshot.i;
+        chip8.pc = snapshot.pc;
+        chip8.stack = snapshot.stack;
+        chip8.sp = snapshot.sp;
+        chip8.restore_subroutines();
+        chip8.hires = snapshot.hires;
+        chip8.two_page = snapshot.two_page;
+        chip8.delay_timer = snapshot.delay_timer;
+        chip8.sound_timer = snapshot.sound_timer;
+        chip8.keypad = snapshot.keypad;
+        chip8.rpl = snapshot.rpl;
+        chip8.audio_pattern = snapshot.audio_pattern;
+        chip8.audio_pitch = snapshot.audio_pitch;
+        if let Some(mut megachip) = snapshot.megachip {
+            if !snapshot.quirks.megachip || !megachip.is_valid() {
+                return Err(D::Error::custom("invalid MegaChip state"));
+            }
+            megachip.update_lit();
+            chip8.megachip = Some(megachip);
+        }
+        if let Some(chip8x) = snapshot.chip8x {
+            if !snapshot.quirks.chip8x || !chip8x.is_valid() {
+                return Err(D::Error::custom("invalid CHIP-8X state"));
+            }
+            chip8.chip8x = Some(chip8x);
+        }
+
+        let (width, height) = (chip8.display_width(), chip8.display_height());
+        if snapshot.display.len() != height
+            || snapshot
+                .display
+                .iter()
+                .any(|row| row.chars().count() != width)
+        {
+            return Err(D::Error::custom(format!(
+                "expected a display of {} rows of {} pixels",
+                height, width
+            )));
+        }
+        // In MegaChip mode the rows only show what's lit, the colors say the
+        // rest
+        let pixels = match chip8.megachip {
+            Some(_) => &mut [][..],
+            None => &mut chip8.display[..],
+        };
+        for (pixel, c) in pixels
+            .iter_mut()
+            .zip(snapshot.display.iter().flat_map(|row| row.chars()))
+        {
+            *pixel = match c {
+                '#' => true,
+                '.' => false,
+                _ => return Err(D::Error::custom(format!("invalid pixel {:?}", c))),
+            };
+        }
+
+        Ok(chip8)
+    }
+}
+
+#[cfg(feature = "json")]
+impl Chip8 {
+    /// The state as pretty-printed JSON, for inspecting it in scripts or
+    /// comparing it with another emulator's dump
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("the state is always valid JSON")
+    }
+
+    /// Restores a machine from the output of `to_json`
+    pub fn from_json(json: &str) -> Result<Self, Chip8Error> {
+        serde_json::from_str(json).map_err(|e| Chip8Error::InvalidSnapshot(e.to_string()))
+    }
+}
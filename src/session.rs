@@ -0,0 +1,518 @@
+use std::{
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use chip_8_rs::{
+    Chip8, Quirks,
+    debug::Stop,
+    plugin::{Event, Plugins},
+    symbols::Symbols,
+};
+use clap::ValueEnum;
+use log::{error, info, warn};
+
+use crate::{
+    INSTRUCTIONS_PER_FRAME, autosave,
+    cheats::Cheats,
+    cli::{QuirkPreset, RunArgs},
+    crash,
+    metadata::RomMetadata,
+    movie::Movie,
+    netplay::Netplay,
+    palette::Palette,
+    rom_profile::RomProfile,
+    screenshot,
+    slots::SaveSlots,
+    stats::Stats,
+};
+
+/// How much faster emulation runs while the turbo key is held. Frame limiting
+/// is skipped as well, so the actual speedup is bounded by the host
+const TURBO_MULTIPLIER: u32 = 8;
+
+/// How many frames after the program last checked a key the keypad overlay
+/// still shows it as used
+const RECENT_POLL_FRAMES: u32 = 120;
+
+/// The speeds the speed hotkeys step through, in instructions per frame
+const SPEEDS: [u32; 16] = [
+    1, 2, 3, 5, 7, 10, 15, 20, 30, 50, 75, 100, 150, 200, 500, 1000,
+];
+
+/// How long the speed stays on screen after it was changed
+const SPEED_SHOWN: Duration = Duration::from_secs(2);
+
+/// Emulator commands bound to keys outside the CHIP-8 keypad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    SaveState,
+    LoadState,
+    NextSlot,
+    PreviousSlot,
+    Pause,
+    Reset,
+    Step,
+    Screenshot,
+    ToggleHud,
+    DumpProfile,
+    ToggleCheats,
+    ToggleMute,
+    ToggleKeypad,
+    ToggleHelp,
+    SwitchMachine,
+    FrameAdvance,
+    SpeedUp,
+    SpeedDown,
+}
+
+impl Hotkey {
+    /// What the hotkey does, for the help overlay
+    pub fn description(self) -> &'static str {
+        match self {
+            Hotkey::SaveState => "save state",
+            Hotkey::LoadState => "load state",
+            Hotkey::NextSlot => "next slot",
+            Hotkey::PreviousSlot => "previous slot",
+            Hotkey::Pause => "pause",
+            Hotkey::Reset => "reset",
+            Hotkey::Step => "step",
+            Hotkey::Screenshot => "screenshot",
+            Hotkey::ToggleHud => "stats",
+            Hotkey::DumpProfile => "print profile",
+            Hotkey::ToggleCheats => "cheats",
+            Hotkey::ToggleMute => "mute",
+            Hotkey::ToggleKeypad => "keypad overlay",
+            Hotkey::ToggleHelp => "help",
+            Hotkey::SwitchMachine => "switch machine",
+            Hotkey::FrameAdvance => "next frame",
+            Hotkey::SpeedUp => "faster",
+            Hotkey::SpeedDown => "slower",
+        }
+    }
+}
+
+/// What the keypad overlay shows, indexed by CHIP-8 key
+pub struct KeypadOverlay {
+    pub held: [bool; 16],
+    /// Keys the program checked in the last few seconds
+    pub polled: [bool; 16],
+}
+
+/// The running emulator plus the frontend state the hotkeys act on
+pub struct Session {
+    pub chip8: Chip8,
+    /// The machine on the right with `--side-by-side`. Save states, cheats,
+    /// plugins and the debugger only see the left one
+    pub other: Option<Chip8>,
+    /// Which machine gets the keypad with `--independent-input`, 0 for the
+    /// left one
+    pub focus: usize,
+    /// Input movie being recorded or played back
+    pub movie: Option<Movie>,
+    /// Connection to the other player, when playing over the network
+    pub netplay: Option<Netplay>,
+    pub plugins: Plugins,
+    /// Patches from the ROM's cheat file
+    pub cheats: Cheats,
+    /// Names for addresses from the ROM's symbol file, for the debugger
+    pub symbols: Symbols,
+    slots: SaveSlots,
+    /// The state saved when the ROM was last quit, until it's resumed or
+    /// discarded
+    autosave: Option<Vec<u8>>,
+    pub paused: bool,
+    /// Why the last frame stopped, until the debug adapter reports it
+    pub stop: Option<Stop>,
+    /// Runs a single frame while paused
+    pub advance: bool,
+    instructions_per_frame: u32,
+    /// When the speed was last changed with the hotkeys
+    speed_changed: Option<Instant>,
+    /// What's remembered about the ROM between runs
+    rom_profile: RomProfile,
+    palette: Palette,
+    screenshot_dir: PathBuf,
+    screenshot_scale: u32,
+    rom_path: PathBuf,
+    /// What the `--database` knows about the ROM
+    metadata: Option<RomMetadata>,
+    /// Whether the stats overlay is shown
+    pub hud: bool,
+    /// Whether the frontend keeps the audio silent
+    pub muted: bool,
+    /// Whether the keypad overlay is shown
+    pub keypad_overlay: bool,
+    /// Whether the help overlay is shown instead of the stats
+    pub help: bool,
+    /// Frames since the program last checked each key
+    poll_age: [u32; 16],
+}
+
+impl Session {
+    pub fn new(
+        chip8: Chip8,
+        movie: Option<Movie>,
+        netplay: Option<Netplay>,
+        metadata: Option<RomMetadata>,
+        rom_path: PathBuf,
+        args: &RunArgs,
+        palette: Palette,
+    ) -> Self {
+        // The other player's machine wouldn't be patched
+        let cheats = if netplay.is_some() {
+            Cheats::default()
+        } else {
+            Cheats::load(&rom_path).unwrap_or_else(|e| {
+                error!("Failed to load cheats: {}", e);
+                Cheats::default()
+            })
+        };
+
+        // Movies and netplay have to start from the beginning
+        let autosave = if movie.is_none() && netplay.is_none() {
+            autosave::load(&chip8)
+        } else {
+            None
+        };
+
+        // The speed on the command line wins over the one picked last time
+        let rom_profile = RomProfile::load(&rom_path);
+        let instructions_per_frame = args
+            .speed_arg()
+            .or(rom_profile.ipf)
+            .unwrap_or(INSTRUCTIONS_PER_FRAME);
+
+        Self {
+            chip8,
+            other: None,
+            focus: 0,
+            movie,
+            netplay,
+            plugins: Plugins::new(),
+            cheats,
+            symbols: crate::load_symbols(args.symbols.as_deref(), &rom_path),
+            slots: SaveSlots::new(&rom_path),
+            autosave,
+            paused: args.debug || args.dap.is_some(),
+            stop: None,
+            advance: false,
+            instructions_per_frame,
+            speed_changed: None,
+            rom_profile,
+            palette,
+            screenshot_dir: args.screenshot_dir.clone(),
+            screenshot_scale: args.screenshot_scale,
+            rom_path,
+            metadata,
+            hud: false,
+            muted: args.mute,
+            keypad_overlay: false,
+            help: false,
+            poll_age: [RECENT_POLL_FRAMES; 16],
+        }
+    }
+
+    /// Applies cheats, runs the plugins and prints warnings after a frame
+    /// was executed
+    pub fn end_frame(&mut self) {
+        self.cheats.apply(&mut self.chip8);
+        self.plugins.frame(&mut self.chip8);
+        report_warnings(&mut self.chip8);
+
+        let polled = self.chip8.take_polled_keys();
+        for (key, age) in self.poll_age.iter_mut().enumerate() {
+            *age = if polled & 1 << key != 0 {
+                0
+            } else {
+                age.saturating_add(1)
+            };
+        }
+    }
+
+    /// The keypad overlay's contents, when it's shown
+    pub fn keypad_overlay(&self) -> Option<KeypadOverlay> {
+        self.keypad_overlay.then(|| KeypadOverlay {
+            held: *self.chip8.keypad(),
+            polled: self.poll_age.map(|age| age < RECENT_POLL_FRAMES),
+        })
+    }
+
+    /// Pauses when the last frame stopped at a breakpoint or watchpoint, so
+    /// it can be stepped over or resumed
+    pub fn check_stop(&mut self) {
+        if let Some(stop) = self.chip8.take_stop() {
+            self.paused = true;
+            self.stop = Some(stop);
+            info!("{}", stop);
+            self.plugins.event(&mut self.chip8, &Event::Stopped(stop));
+        }
+    }
+
+    /// Title of the running ROM from the database, or its file name, shown
+    /// in the window title
+    pub fn rom_name(&self) -> String {
+        if let Some(metadata) = &self.metadata {
+            return metadata.title.clone();
+        }
+        self.rom_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Whether the next frame runs, which it does unless paused or once
+    /// after a frame advance
+    pub fn run_next_frame(&mut self) -> bool {
+        !self.paused || std::mem::take(&mut self.advance)
+    }
+
+    /// Number of instructions to execute in the next frame. Netplay always
+    /// runs at the host's speed
+    pub fn frame_instructions(&self, turbo: bool) -> u32 {
+        if let Some(netplay) = &self.netplay {
+            netplay.instructions_per_frame()
+        } else if turbo {
+            self.instructions_per_frame * TURBO_MULTIPLIER
+        } else {
+            self.instructions_per_frame
+        }
+    }
+
+    /// Whether there's a state saved when the ROM was last quit
+    pub fn can_resume(&self) -> bool {
+        self.autosave.is_some()
+    }
+
+    /// Continues from the state saved when the ROM was last quit. Never
+    /// while a movie is recorded or played, it has to start from the
+    /// beginning
+    pub fn resume_autosave(&mut self) {
+        if self.movie.is_some() {
+            warn!("Can't resume while a movie is recorded or played");
+            return;
+        }
+        let Some(state) = self.autosave.take() else {
+            return;
+        };
+        match self.chip8.load_state(&state) {
+            Ok(()) => {
+                info!("Resumed where the ROM was left");
+                self.cheats.rearm();
+                self.plugins.event(&mut self.chip8, &Event::StateLoaded);
+            }
+            Err(e) => error!("Failed to resume: {}", e),
+        }
+    }
+
+    /// Starts the ROM over instead of resuming it
+    pub fn discard_autosave(&mut self) {
+        self.autosave = None;
+    }
+
+    /// Saves the state to resume from next time. Movies and netplay always
+    /// start over, and side by side there'd be a second machine to save. A
+    /// state that wasn't resumed or discarded yet is kept
+    pub fn save_autosave(&self) {
+        if self.movie.is_some()
+            || self.netplay.is_some()
+            || self.other.is_some()
+            || self.autosave.is_some()
+        {
+            return;
+        }
+        if let Err(e) = autosave::save(&self.chip8) {
+            error!("Failed to save the state to resume from: {}", e);
+        }
+    }
+
+    /// The speed for a few seconds after it was changed
+    pub fn speed_line(&self) -> Option<String> {
+        self.speed_changed
+            .filter(|changed| changed.elapsed() < SPEED_SHOWN)
+            .map(|_| format!("SPEED {} IPF", self.instructions_per_frame))
+    }
+
+    /// Remembers the speed picked with the hotkeys for the next time the ROM
+    /// is run
+    pub fn save_rom_profile(&mut self) {
+        if self.speed_changed.is_none() {
+            return;
+        }
+        self.rom_profile.ipf = Some(self.instructions_per_frame);
+        if let Err(e) = self.rom_profile.save(&self.rom_path) {
+            error!("Failed to save the ROM's speed: {}", e);
+        }
+    }
+
+    fn change_speed(&mut self, faster: bool) {
+        let current = self.instructions_per_frame;
+        let next = if faster {
+            SPEEDS.iter().find(|&&speed| speed > current)
+        } else {
+            SPEEDS.iter().rev().find(|&&speed| speed < current)
+        };
+        if let Some(&speed) = next {
+            self.instructions_per_frame = speed;
+        }
+        self.speed_changed = Some(Instant::now());
+    }
+
+    /// Lines shown by the stats overlay
+    pub fn hud_lines(&self, stats: &Stats) -> Vec<String> {
+        let mut quirks = quirks_name(self.chip8.quirks());
+        if let Some(other) = &self.other {
+            quirks = format!("{} / {}", quirks, quirks_name(other.quirks()));
+        }
+
+        vec![
+            format!("FPS {:.0}", stats.fps()),
+            format!("IPS {:.0}", stats.ips()),
+            format!(
+                "DT {:3} ST {:3}",
+                self.chip8.delay_timer(),
+                self.chip8.sound_timer()
+            ),
+            format!("QUIRKS {}", quirks),
+        ]
+    }
+
+    pub fn handle_hotkey(&mut self, hotkey: Hotkey) {
+        // Both machines have to stay in lockstep
+        if self.netplay.is_some()
+            && matches!(
+                hotkey,
+                Hotkey::LoadState
+                    | Hotkey::Pause
+                    | Hotkey::Reset
+                    | Hotkey::Step
+                    | Hotkey::FrameAdvance
+                    | Hotkey::SpeedUp
+                    | Hotkey::SpeedDown
+            )
+        {
+            warn!("Not available during netplay");
+            return;
+        }
+        // Movies replay their input from the start of the ROM
+        if self.movie.is_some() && hotkey == Hotkey::LoadState {
+            warn!("Can't load a state while a movie is recorded or played");
+            return;
+        }
+
+        match hotkey {
+            Hotkey::SaveState => match self.slots.save(&self.chip8) {
+                Ok(()) => info!("Saved state to slot {}", self.slots.slot()),
+                Err(e) => error!("Failed to save state: {}", e),
+            },
+            Hotkey::LoadState => match self.slots.load(&mut self.chip8) {
+                Ok(()) => {
+                    info!("Loaded state from slot {}", self.slots.slot());
+                    self.cheats.rearm();
+                    self.plugins.event(&mut self.chip8, &Event::StateLoaded);
+                }
+                Err(e) => error!("Failed to load state: {}", e),
+            },
+            Hotkey::NextSlot => {
+                self.slots.next();
+                info!("Selected save slot {}", self.slots.slot());
+            }
+            Hotkey::PreviousSlot => {
+                self.slots.previous();
+                info!("Selected save slot {}", self.slots.slot());
+            }
+            Hotkey::Pause => {
+                self.paused = !self.paused;
+                info!("{}", if self.paused { "Paused" } else { "Resumed" });
+            }
+            Hotkey::Reset => {
+                self.chip8.reset();
+                if let Some(other) = &mut self.other {
+                    other.reset();
+                }
+                if let Some(movie) = &mut self.movie {
+                    movie.restart();
+                }
+                self.cheats.rearm();
+                self.plugins.event(&mut self.chip8, &Event::Reset);
+                info!("Reset");
+            }
+            Hotkey::Screenshot => match screenshot::save(
+                &self.chip8,
+                self.palette,
+                self.screenshot_scale,
+                &self.screenshot_dir,
+                &self.rom_path,
+            ) {
+                Ok(path) => info!("Saved screenshot to {}", path.display()),
+                Err(e) => error!("Failed to save screenshot: {}", e),
+            },
+            Hotkey::ToggleHud => self.hud = !self.hud,
+            Hotkey::ToggleKeypad => self.keypad_overlay = !self.keypad_overlay,
+            Hotkey::ToggleHelp => self.help = !self.help,
+            Hotkey::SwitchMachine => {
+                if self.other.is_some() {
+                    self.focus = 1 - self.focus;
+                    let side = if self.focus == 0 { "left" } else { "right" };
+                    info!("The keypad goes to the {} machine", side);
+                }
+            }
+            Hotkey::ToggleMute => {
+                self.muted = !self.muted;
+                info!("{}", if self.muted { "Muted" } else { "Unmuted" });
+            }
+            Hotkey::ToggleCheats => {
+                if self.cheats.cheats().is_empty() {
+                    warn!("No cheats, add some to <rom>.cht");
+                } else if self.cheats.toggle_all() {
+                    info!("Cheats on");
+                } else {
+                    info!("Cheats off");
+                }
+            }
+            Hotkey::DumpProfile => match self.chip8.profile() {
+                Some(profile) => println!("{}", profile),
+                None => warn!("Profiling is off, run with --profile to enable it"),
+            },
+            // Like Step the result is drawn by the main loop, which runs the
+            // frame with the keys currently held
+            Hotkey::FrameAdvance => self.advance = self.paused,
+            Hotkey::SpeedUp => self.change_speed(true),
+            Hotkey::SpeedDown => self.change_speed(false),
+            Hotkey::Step => {
+                // Stepping only makes sense while paused, the paused branch
+                // of the main loop then draws the result
+                if self.paused
+                    && let Err(e) = self.chip8.cycle()
+                {
+                    error!("Step failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // The window frontends unwind through here when they panic
+        if thread::panicking() {
+            crash::write_dump(&self.chip8);
+        }
+    }
+}
+
+/// The name of the preset with `quirks`, or `custom`
+pub fn quirks_name(quirks: Quirks) -> String {
+    QuirkPreset::matching(quirks)
+        .and_then(|preset| preset.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_else(|| "custom".to_string())
+}
+
+/// Prints the unknown opcodes skipped since the last call
+pub fn report_warnings(chip8: &mut Chip8) {
+    for warning in chip8.take_warnings() {
+        warn!("{}", warning);
+    }
+}
@@ -0,0 +1,52 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chip_8_rs::Chip8;
+
+const SLOT_COUNT: u8 = 4;
+
+/// Save state slots stored next to the ROM as `<rom>.state<N>`
+pub struct SaveSlots {
+    rom_path: PathBuf,
+    slot: u8,
+}
+
+impl SaveSlots {
+    pub fn new<P: AsRef<Path>>(rom_path: P) -> Self {
+        Self {
+            rom_path: rom_path.as_ref().to_path_buf(),
+            slot: 1,
+        }
+    }
+
+    pub fn slot(&self) -> u8 {
+        self.slot
+    }
+
+    pub fn next(&mut self) {
+        self.slot = self.slot % SLOT_COUNT + 1;
+    }
+
+    pub fn previous(&mut self) {
+        self.slot = (self.slot + SLOT_COUNT - 2) % SLOT_COUNT + 1;
+    }
+
+    pub fn save(&self, chip8: &Chip8) -> io::Result<()> {
+        fs::write(self.path(), chip8.save_state())
+    }
+
+    pub fn load(&self, chip8: &mut Chip8) -> io::Result<()> {
+        let data = fs::read(self.path())?;
+        chip8
+            .load_state(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn path(&self) -> PathBuf {
+        let mut path = self.rom_path.clone().into_os_string();
+        path.push(format!(".state{}", self.slot));
+        PathBuf::from(path)
+    }
+}
@@ -0,0 +1,301 @@
+//! Binary snapshots of the complete interpreter state, used for save states.
+//!
+//! A snapshot starts with a header of the magic `C8SV`, the format version
+//! and the SHA-1 of the ROM it was taken with. Version 0 snapshots predate the
+//! header and start with `C8ST` right before the body, they're still read.
+//! Their body only ever grew by the XO-CHIP audio pattern and pitch, which
+//! the oldest ones lack and get the defaults for. Everything else added
+//! since, memory past 4KB and the MegaChip and CHIP-8X state, is only saved
+//! by machines that have it and was saved the same way from the start
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{
+    HIRES_HEIGHT, HIRES_WIDTH, MEGACHIP_HEIGHT, MEGACHIP_WIDTH,
+    chip8::{Chip8, DEFAULT_PITCH, MEMORY_SIZE, STACK_DEPTH},
+    chip8x::Chip8X,
+    error::Chip8Error,
+    megachip::{Blend, MegaChip, Sample},
+};
+
+const MAGIC: &[u8; 4] = b"C8SV";
+/// What version 0 snapshots start with instead of the header
+const LEGACY_MAGIC: &[u8; 4] = b"C8ST";
+/// Bumped whenever the layout of the body changes. `load_state` keeps
+/// reading the older versions
+pub const STATE_VERSION: u16 = 1;
+/// Magic, version and the ROM's SHA-1
+pub const STATE_HEADER_SIZE: usize = 4 + 2 + 20;
+/// Where the stack starts in the body
+const STACK_OFFSET: usize = MEMORY_SIZE + 16 + 2 + 2;
+/// Whether an audio pattern was loaded, the pattern and the pitch
+const AUDIO_SIZE: usize = 1 + 16 + 1;
+
+/// Size of a snapshot of a machine with the default memory and stack, in
+/// bytes: the header, then the first 4KB of memory, V registers, I, PC,
+/// stack, SP, display (one byte per pixel), resolution flags, timers, RPL
+/// flags and the XO-CHIP audio pattern with a flag for whether one was
+/// loaded and the pitch. The stack takes 2 bytes per slot, and memory past
+/// 4KB follows. With the `megachip` quirk the high bits of I and the
+/// MegaChip flag come next, and while MegaChip mode is on so does its
+/// state. The `chip8x` quirk adds the colors and pitch after that
+pub const STATE_SIZE: usize = STATE_HEADER_SIZE + BODY_SIZE;
+/// The snapshot after the header
+const BODY_SIZE: usize =
+    STACK_OFFSET + STACK_DEPTH * 2 + 1 + HIRES_WIDTH * HIRES_HEIGHT + 1 + 1 + 1 + 16 + AUDIO_SIZE;
+
+/// The high bits of I and the MegaChip flag
+const MEGACHIP_EXTENSION_SIZE: usize = 1 + 1;
+/// Background, strip colors and pitch
+const CHIP8X_SIZE: usize = 1 + Chip8X::ZONES + 1;
+const MEGACHIP_PIXELS: usize = MEGACHIP_WIDTH * MEGACHIP_HEIGHT;
+/// Palette, sprite size, alpha, blend mode, collision color, the three
+/// buffers and the sample
+const MEGACHIP_MODE_SIZE: usize =
+    256 * 4 + 2 + 2 + 1 + 1 + 1 + MEGACHIP_PIXELS * (1 + 4 + 4) + 1 + 4 + 1;
+
+impl Chip8 {
+    /// Serializes everything needed to resume execution later. The keypad is
+    /// not included since it belongs to the frontend
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(STATE_SIZE);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&STATE_VERSION.to_be_bytes());
+        out.extend_from_slice(&rom_hash(&self.rom));
+        out.extend_from_slice(&self.memory[..MEMORY_SIZE]);
+        out.extend_from_slice(&self.v);
+        out.extend_from_slice(&(self.i as u16).to_be_bytes());
+        out.extend_from_slice(&self.pc.to_be_bytes());
+        for &address in &self.stack {
+            out.extend_from_slice(&address.to_be_bytes());
+        }
+        out.push(self.sp);
+        out.extend(self.display.iter().map(|&pixel| pixel as u8));
+        out.push(self.hires as u8 | (self.two_page as u8) << 1);
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        out.extend_from_slice(&self.rpl);
+        out.push(self.audio_pattern.is_some() as u8);
+        out.extend_from_slice(&self.audio_pattern.unwrap_or_default());
+        out.push(self.audio_pitch);
+        out.extend_from_slice(&self.memory[MEMORY_SIZE..]);
+        if self.quirks().megachip {
+            self.save_megachip(&mut out);
+        }
+        if let Some(chip8x) = &self.chip8x {
+            out.push(chip8x.background);
+            out.extend_from_slice(&chip8x.zones);
+            out.push(chip8x.pitch);
+        }
+        out
+    }
+
+    fn save_megachip(&self, out: &mut Vec<u8>) {
+        out.push((self.i >> 16) as u8);
+        out.push(self.megachip.is_some() as u8);
+        let Some(megachip) = &self.megachip else {
+            return;
+        };
+        for color in &megachip.palette {
+            out.extend_from_slice(&color.to_be_bytes());
+        }
+        // 256 doesn't fit in a byte
+        out.extend_from_slice(&(megachip.sprite_width as u16).to_be_bytes());
+        out.extend_from_slice(&(megachip.sprite_height as u16).to_be_bytes());
+        out.push(megachip.alpha);
+        out.push(megachip.blend.mode());
+        out.push(megachip.collision);
+        out.extend_from_slice(&megachip.indices);
+        for buffer in [&megachip.frame, &megachip.screen] {
+            for color in buffer {
+                out.extend_from_slice(&color.to_be_bytes());
+            }
+        }
+        let sample = megachip.sample;
+        out.push(sample.is_some() as u8);
+        out.extend_from_slice(&sample.map_or(0, |sample| sample.address).to_be_bytes());
+        out.push(sample.is_some_and(|sample| sample.looping) as u8);
+    }
+
+    /// Snapshots don't have the call targets, this reads them back from the
+    /// CALL instructions on the stack instead
+    pub(crate) fn restore_subroutines(&mut self) {
+        for (subroutine, &call_site) in self.subroutines.iter_mut().zip(&self.stack) {
+            let call_site = call_site as usize;
+            *subroutine = match self.memory.get(call_site..call_site + 2) {
+                Some(&[high, low]) => u16::from_be_bytes([high, low]) & 0x0FFF,
+                _ => 0,
+            };
+        }
+    }
+
+    /// Restores a snapshot produced by `save_state`, which has to come from
+    /// a machine with the same ROM and sizes of memory and stack. The
+    /// current state is left untouched if the snapshot is invalid
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        if let Some(body) = data.strip_prefix(LEGACY_MAGIC) {
+            // There's no hash to check the ROM against, and no version. The
+            // two layouts differ in size, so whichever fits is the one
+            return self.load_body(body).or_else(|_| {
+                let body = self.with_default_audio(body);
+                self.load_body(&body)
+            });
+        }
+
+        let header = data
+            .get(..STATE_HEADER_SIZE)
+            .filter(|header| header.starts_with(MAGIC))
+            .ok_or(Chip8Error::InvalidSaveState)?;
+        let version = u16::from_be_bytes([header[4], header[5]]);
+        if version > STATE_VERSION {
+            return Err(Chip8Error::SaveStateVersion { version });
+        }
+        if header[6..] != rom_hash(&self.rom) {
+            return Err(Chip8Error::SaveStateRomMismatch);
+        }
+        // Version 1 only added the header. Once the body changes, the older
+        // versions get migrated here like version 0's are
+        self.load_body(&data[STATE_HEADER_SIZE..])
+    }
+
+    /// Migrates a version 0 body from before the audio pattern was saved,
+    /// adding the audio of a machine that never loaded one after the RPL
+    /// flags
+    fn with_default_audio(&self, body: &[u8]) -> Vec<u8> {
+        let offset =
+            STACK_OFFSET + self.stack.len() * 2 + 1 + HIRES_WIDTH * HIRES_HEIGHT + 1 + 1 + 1 + 16;
+        let Some((before, after)) = body.split_at_checked(offset) else {
+            return body.to_vec();
+        };
+        let mut migrated = Vec::with_capacity(body.len() + AUDIO_SIZE);
+        migrated.extend_from_slice(before);
+        migrated.push(0);
+        migrated.extend_from_slice(&[0; 16]);
+        migrated.push(DEFAULT_PITCH);
+        migrated.extend_from_slice(after);
+        migrated
+    }
+
+    /// Restores the body of a snapshot, which has the current layout
+    fn load_body(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let megachip = self.quirks().megachip;
+        let sp_offset = STACK_OFFSET + self.stack.len() * 2;
+        let mut size =
+            BODY_SIZE + (self.stack.len() - STACK_DEPTH) * 2 + (self.memory.len() - MEMORY_SIZE);
+        if megachip {
+            size += match data.get(size + MEGACHIP_EXTENSION_SIZE - 1) {
+                Some(1) => MEGACHIP_EXTENSION_SIZE + MEGACHIP_MODE_SIZE,
+                _ => MEGACHIP_EXTENSION_SIZE,
+            };
+        }
+        if self.chip8x.is_some() {
+            size += CHIP8X_SIZE;
+        }
+        if data.len() != size || data[sp_offset] as usize > self.stack.len() {
+            return Err(Chip8Error::InvalidSaveState);
+        }
+
+        let mut reader = Reader { data, pos: 0 };
+        self.pressed_key = None;
+        self.vblank_wait = false;
+        self.memory[..MEMORY_SIZE].copy_from_slice(reader.take(MEMORY_SIZE));
+        self.v.copy_from_slice(reader.take(16));
+        self.i = reader.u16() as u32;
+        self.pc = reader.u16();
+        for address in self.stack.iter_mut() {
+            *address = reader.u16();
+        }
+        self.sp = reader.u8();
+        self.restore_subroutines();
+        for (pixel, &byte) in self
+            .display
+            .iter_mut()
+            .zip(reader.take(HIRES_WIDTH * HIRES_HEIGHT))
+        {
+            *pixel = byte != 0;
+        }
+        let resolution = reader.u8();
+        self.hires = resolution & 1 != 0;
+        self.two_page = resolution & 2 != 0;
+        self.delay_timer = reader.u8();
+        self.sound_timer = reader.u8();
+        self.rpl.copy_from_slice(reader.take(16));
+        let loaded = reader.u8() != 0;
+        let pattern = reader.take(16);
+        self.audio_pattern = loaded.then(|| pattern.try_into().expect("16 bytes"));
+        self.audio_pitch = reader.u8();
+        let rest = self.memory.len() - MEMORY_SIZE;
+        self.memory[MEMORY_SIZE..].copy_from_slice(reader.take(rest));
+        if megachip {
+            self.load_megachip(&mut reader);
+        }
+        if let Some(chip8x) = &mut self.chip8x {
+            chip8x.background = reader.u8();
+            chip8x.zones.copy_from_slice(reader.take(Chip8X::ZONES));
+            chip8x.pitch = reader.u8();
+        }
+        Ok(())
+    }
+
+    fn load_megachip(&mut self, reader: &mut Reader) {
+        self.i |= (reader.u8() as u32) << 16;
+        if reader.u8() == 0 {
+            self.megachip = None;
+            return;
+        }
+
+        let mut megachip = MegaChip::new();
+        for color in megachip.palette.iter_mut() {
+            *color = reader.u32();
+        }
+        megachip.sprite_width = reader.u16() as usize;
+        megachip.sprite_height = reader.u16() as usize;
+        megachip.alpha = reader.u8();
+        megachip.blend = Blend::from_mode(reader.u8());
+        megachip.collision = reader.u8();
+        megachip
+            .indices
+            .copy_from_slice(reader.take(MEGACHIP_PIXELS));
+        for color in megachip.frame.iter_mut().chain(megachip.screen.iter_mut()) {
+            *color = reader.u32();
+        }
+        let playing = reader.u8() != 0;
+        let address = reader.u32();
+        let looping = reader.u8() != 0;
+        megachip.sample = playing.then_some(Sample { address, looping });
+        megachip.update_lit();
+        self.megachip = Some(Box::new(megachip));
+    }
+}
+
+/// Identifies the ROM a snapshot was taken with
+fn rom_hash(rom: &[u8]) -> [u8; 20] {
+    sha1_smol::Sha1::from(rom).digest().bytes()
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        bytes
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        let bytes = self.take(2);
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    }
+
+    fn u32(&mut self) -> u32 {
+        let bytes = self.take(4);
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+}
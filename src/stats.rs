@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+/// How often the measured rates are refreshed
+const SAMPLE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Measures frames and instructions per second over fixed sample periods
+pub struct Stats {
+    sample_start: Instant,
+    frames: u32,
+    instructions: u64,
+    fps: f64,
+    ips: f64,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            sample_start: Instant::now(),
+            frames: 0,
+            instructions: 0,
+            fps: 0.0,
+            ips: 0.0,
+        }
+    }
+
+    /// Records a frame that executed `instructions` instructions. Returns true
+    /// when a sample period ended and the rates were updated
+    pub fn record_frame(&mut self, instructions: u32) -> bool {
+        self.frames += 1;
+        self.instructions += instructions as u64;
+
+        let elapsed = self.sample_start.elapsed();
+        if elapsed < SAMPLE_PERIOD {
+            return false;
+        }
+
+        let secs = elapsed.as_secs_f64();
+        self.fps = self.frames as f64 / secs;
+        self.ips = self.instructions as f64 / secs;
+        self.frames = 0;
+        self.instructions = 0;
+        self.sample_start = Instant::now();
+        true
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    pub fn ips(&self) -> f64 {
+        self.ips
+    }
+}
@@ -0,0 +1,158 @@
+//! Names for addresses in a program, shown by the disassembler and the
+//! debugger instead of bare numbers. A symbol file has a name and an address
+//! per line, the way Octo declares constants:
+//!
+//! ```text
+//! # Written by `asm --symbols`
+//! :const main 0x202
+//! draw_player 0x2A4
+//! ```
+//!
+//! The `:const` is optional, `#` starts a comment.
+//!
+//! ```
+//! use chip_8_rs::symbols::Symbols;
+//!
+//! let symbols = Symbols::parse(":const draw_player 0x2A4").unwrap();
+//! assert_eq!(symbols.name(0x2A4), Some("draw_player"));
+//! assert_eq!(symbols.address("draw_player"), Some(0x2A4));
+//! ```
+
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{error::Error, fmt};
+
+/// Why a symbol file couldn't be parsed, with the line it happened on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for SymbolError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    /// The name shown for each address, the first one given for it
+    names: BTreeMap<u16, String>,
+    addresses: BTreeMap<String, u16>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a symbol file
+    pub fn parse(text: &str) -> Result<Self, SymbolError> {
+        let mut symbols = Self::new();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| SymbolError {
+                line: index + 1,
+                message,
+            };
+            let code = line.split('#').next().unwrap_or("");
+            let mut words = code.split_whitespace().peekable();
+            words.next_if_eq(&":const");
+            let (Some(name), Some(address), None) = (words.next(), words.next(), words.next())
+            else {
+                if code.trim().is_empty() {
+                    continue;
+                }
+                return Err(error(format!(
+                    "Expected a name and an address, found {}",
+                    code.trim()
+                )));
+            };
+            let address = parse_number(address)
+                .ok_or_else(|| error(format!("Invalid address {}", address)))?;
+            if symbols.addresses.contains_key(name) {
+                return Err(error(format!("{} is defined twice", name)));
+            }
+            symbols.insert(name, address);
+        }
+        Ok(symbols)
+    }
+
+    /// Names `address`. An address that already has a name keeps it, the new
+    /// one can still be looked up
+    pub fn insert(&mut self, name: &str, address: u16) {
+        self.names
+            .entry(address)
+            .or_insert_with(|| name.to_string());
+        self.addresses.insert(name.to_string(), address);
+    }
+
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).map(String::as_str)
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    /// The name of `address`, or the address in hex when it has none
+    pub fn label(&self, address: u16) -> String {
+        match self.name(address) {
+            Some(name) => name.to_string(),
+            None => format!("0x{:03X}", address),
+        }
+    }
+
+    /// What debug views call the subroutine at `address`, its name or `sub_`
+    /// and the address
+    pub fn subroutine_name(&self, address: u16) -> String {
+        match self.name(address) {
+            Some(name) => name.to_string(),
+            None => format!("sub_{:03X}", address),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    /// Every name with its address, ordered by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)> {
+        self.addresses
+            .iter()
+            .map(|(name, &address)| (name.as_str(), address))
+    }
+}
+
+/// Writes the symbols as a symbol file, ordered by address
+impl fmt::Display for Symbols {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut symbols: Vec<_> = self.iter().collect();
+        symbols.sort_by_key(|&(name, address)| (address, name));
+        for (name, address) in symbols {
+            writeln!(f, ":const {} 0x{:03X}", name, address)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads decimal, `0x` hex and `0b` binary numbers like Octo does
+fn parse_number(text: &str) -> Option<u16> {
+    if let Some(hex) = text.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix("0b") {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
@@ -0,0 +1,70 @@
+//! 0NNN, which called a machine code routine at NNN on the original
+//! hardware. Interpreters can't run those routines and skip them, but an
+//! embedder can register a hook that stands in for them, for example to give
+//! a test harness a way to report results or to add native extensions.
+//!
+//! Without MegaChip's or CHIP-8X's quirks, the opcodes those platforms took
+//! from the 0NNN range are routines too.
+
+use alloc::boxed::Box;
+
+use crate::{chip8::Chip8, error::Chip8Error};
+
+/// Stands in for the machine code routine at the address it's passed.
+/// Returns whether it knows the routine; PC is moved past the 0NNN
+/// afterwards either way
+pub type SystemHook = Box<dyn FnMut(&mut Chip8, u16) -> bool + Send + Sync>;
+
+/// What happens to a routine no hook handles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SystemCalls {
+    /// Skip it, like interpreters always have
+    #[default]
+    Ignore,
+    /// Stop with `Chip8Error::UnhandledSystemCall`
+    Strict,
+}
+
+impl Chip8 {
+    /// Runs `hook` for every 0NNN instead of skipping it. Replaces the hook
+    /// set before
+    pub fn set_system_hook(&mut self, hook: SystemHook) {
+        self.system_hook = Some(hook);
+    }
+
+    pub fn clear_system_hook(&mut self) {
+        self.system_hook = None;
+    }
+
+    /// What to do with routines the hook doesn't handle, or all of them
+    /// when there's no hook
+    pub fn set_system_calls(&mut self, system_calls: SystemCalls) {
+        self.system_calls = system_calls;
+    }
+
+    pub fn system_calls(&self) -> SystemCalls {
+        self.system_calls
+    }
+
+    /// 0NNN
+    pub(crate) fn system_call(&mut self, routine: u16) -> Result<(), Chip8Error> {
+        // The hook gets the whole machine, so it's taken out while it runs
+        let handled = match self.system_hook.take() {
+            Some(mut hook) => {
+                let handled = hook(self, routine);
+                // Unless the hook replaced itself
+                self.system_hook.get_or_insert(hook);
+                handled
+            }
+            None => false,
+        };
+
+        if !handled && self.system_calls == SystemCalls::Strict {
+            return Err(Chip8Error::UnhandledSystemCall {
+                address: self.pc,
+                routine,
+            });
+        }
+        self.advance(2)
+    }
+}
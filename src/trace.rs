@@ -0,0 +1,96 @@
+//! Instruction traces: one line per instruction with the registers before it
+//! runs, for comparing a run line by line with another emulator's.
+//!
+//! A line is a list of `NAME=value` fields in hex:
+//!
+//! ```text
+//! PC=0200 OP=00E0 I=0000 V0=00 V1=00 ... VF=00 SP=0 DT=00 ST=00
+//! ```
+//!
+//! When comparing, only the fields both lines have are checked, names ignore
+//! case and values may carry a `0x` prefix. A reference trace from an
+//! emulator that logs less, or logs in another order, can be compared once
+//! it's rewritten into fields, without having to match this format exactly.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use crate::chip8::Chip8;
+
+/// The trace line of the instruction about to be executed
+pub fn line(chip8: &Chip8) -> String {
+    let pc = chip8.pc() as usize;
+    let memory = chip8.memory();
+    let opcode = match memory.get(pc..pc + 2) {
+        Some(&[high, low]) => format!("{:02X}{:02X}", high, low),
+        _ => String::from("----"),
+    };
+
+    let mut line = format!("PC={:04X} OP={} I={:04X}", pc, opcode, chip8.index());
+    for (register, value) in chip8.registers().iter().enumerate() {
+        let _ = write!(line, " V{:X}={:02X}", register, value);
+    }
+    let _ = write!(
+        line,
+        " SP={:X} DT={:02X} ST={:02X}",
+        chip8.stack().len(),
+        chip8.delay_timer(),
+        chip8.sound_timer()
+    );
+    line
+}
+
+/// A field that has a different value in two trace lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub field: String,
+    pub actual: String,
+    pub expected: String,
+}
+
+/// Compares the fields that both lines have, and returns the first that
+/// differs
+pub fn compare(actual: &str, expected: &str) -> Option<Mismatch> {
+    let actual_fields = fields(actual);
+    for (name, expected_value) in fields(expected) {
+        let Some((_, actual_value)) = actual_fields
+            .iter()
+            .find(|(other, _)| other.eq_ignore_ascii_case(name))
+        else {
+            continue;
+        };
+        if !same_value(actual_value, expected_value) {
+            return Some(Mismatch {
+                field: name.to_ascii_uppercase(),
+                actual: actual_value.to_string(),
+                expected: expected_value.to_string(),
+            });
+        }
+    }
+    None
+}
+
+fn fields(line: &str) -> Vec<(&str, &str)> {
+    line.split_whitespace()
+        .filter_map(|field| field.split_once('='))
+        .collect()
+}
+
+/// Values are compared as hex numbers, or as text when they aren't numbers
+fn same_value(a: &str, b: &str) -> bool {
+    let number = |value: &str| {
+        let digits = value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+            .unwrap_or(value);
+        u32::from_str_radix(digits, 16).ok()
+    };
+    match (number(a), number(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.eq_ignore_ascii_case(b),
+    }
+}
@@ -0,0 +1,276 @@
+//! A terminal frontend drawing the display with half-block characters, so
+//! the emulator can run over SSH or without a graphical environment
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use chip_8_rs::{
+    Chip8,
+    frontend::{AudioSink, DisplaySink, InputSource},
+    plugin::Plugins,
+};
+use crossterm::{
+    cursor,
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute, queue,
+    style::Print,
+    terminal,
+};
+use log::error;
+
+use crate::{
+    config::{Config, KeyProfile},
+    movie::{self, Movie},
+    pacer::FramePacer,
+    session,
+};
+
+/// Without key release events, a key counts as held for this many frames
+/// after it was last pressed or auto-repeated by the terminal
+const KEY_HOLD_FRAMES: u8 = 8;
+
+/// Puts the terminal into raw mode on the alternate screen and restores it
+/// when dropped
+struct RawTerminal {
+    release_events: bool,
+}
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+
+        // Terminals implementing the kitty keyboard protocol report key
+        // releases, everything else only sends presses
+        let release_events = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if release_events {
+            execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        Ok(Self { release_events })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        if self.release_events {
+            let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The key named `name` if it's a single character, lowercased like key
+/// events are matched
+fn single_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+/// Reads the keypad from terminal key events
+pub struct TuiInput {
+    layout: [char; 16],
+    /// Keys of a two-player profile, taking precedence over the layout
+    profile: Vec<(char, u8)>,
+    keys: [bool; 16],
+    /// Frames left until each key is released, only used when the terminal
+    /// doesn't report key releases
+    hold: [u8; 16],
+    release_events: bool,
+    quit: bool,
+}
+
+impl TuiInput {
+    /// The layout and overrides are taken from the config, only single
+    /// character key names can be used in a terminal. The same goes for the
+    /// keys of a two-player profile, which were checked by
+    /// `Config::key_profile`
+    fn new(config: &Config, profile: Option<&KeyProfile>, release_events: bool) -> Self {
+        let mut layout = config.layout.keys();
+        for (key, name) in &config.keypad {
+            if let (Ok(key @ 0..=15), Some(c)) = (u8::from_str_radix(key, 16), single_char(name)) {
+                layout[key as usize] = c;
+            }
+        }
+        let profile = profile
+            .and_then(|profile| profile.bindings().ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(key, name)| Some((single_char(name)?, key)))
+            .collect();
+
+        Self {
+            layout,
+            profile,
+            keys: [false; 16],
+            hold: [0; 16],
+            release_events,
+            quit: false,
+        }
+    }
+
+    /// Whether Escape or Ctrl+C was pressed
+    fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.code == KeyCode::Esc
+            || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+        {
+            self.quit = true;
+            return;
+        }
+
+        let KeyCode::Char(c) = key.code else {
+            return;
+        };
+        let c = c.to_ascii_lowercase();
+        let profile_key = self
+            .profile
+            .iter()
+            .find(|&&(k, _)| k == c)
+            .map(|&(_, key)| key as usize);
+        let Some(index) = profile_key.or_else(|| self.layout.iter().position(|&k| k == c)) else {
+            return;
+        };
+
+        match key.kind {
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                self.keys[index] = true;
+                self.hold[index] = KEY_HOLD_FRAMES;
+            }
+            KeyEventKind::Release => self.keys[index] = false,
+        }
+    }
+}
+
+impl InputSource for TuiInput {
+    fn poll(&mut self, keys: &mut [bool; 16]) {
+        if !self.release_events {
+            for (key, hold) in self.keys.iter_mut().zip(self.hold.iter_mut()) {
+                *hold = hold.saturating_sub(1);
+                *key = *hold > 0;
+            }
+        }
+
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            if let Ok(Event::Key(key)) = event::read() {
+                self.handle_key(key);
+            }
+        }
+
+        *keys = self.keys;
+    }
+}
+
+/// Draws the display with half-block characters, two pixel rows per line
+#[derive(Default)]
+pub struct TuiDisplay {
+    last_frame: Vec<bool>,
+}
+
+impl DisplaySink for TuiDisplay {
+    fn present(&mut self, pixels: &[bool], width: usize, height: usize) {
+        if pixels == self.last_frame {
+            return;
+        }
+        self.last_frame = pixels.to_vec();
+
+        let mut stdout = io::stdout().lock();
+        let _ = queue!(stdout, cursor::MoveTo(0, 0));
+        for y in (0..height).step_by(2) {
+            let line: String = (0..width)
+                .map(|x| {
+                    let top = pixels[y * width + x];
+                    let bottom = y + 1 < height && pixels[(y + 1) * width + x];
+                    match (top, bottom) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    }
+                })
+                .collect();
+            let _ = queue!(stdout, Print(line), cursor::MoveToNextLine(1));
+        }
+        let _ = queue!(stdout, terminal::Clear(terminal::ClearType::FromCursorDown));
+        let _ = stdout.flush();
+    }
+}
+
+/// Rings the terminal bell every time the tone starts, unless muted
+#[derive(Default)]
+pub struct TuiAudio {
+    beeping: bool,
+    muted: bool,
+}
+
+impl AudioSink for TuiAudio {
+    fn set_tone(&mut self, active: bool) {
+        if active && !self.beeping && !self.muted {
+            let mut stdout = io::stdout();
+            let _ = queue!(stdout, Print('\x07'));
+            let _ = stdout.flush();
+        }
+        self.beeping = active;
+    }
+}
+
+/// Runs `chip8` in the terminal until Escape is pressed or it hits an error
+pub fn run(
+    chip8: &mut Chip8,
+    mut movie: Option<&mut Movie>,
+    plugins: &mut Plugins,
+    instructions_per_frame: u32,
+    config: &Config,
+    profile: Option<&KeyProfile>,
+    muted: bool,
+) -> io::Result<()> {
+    let terminal = RawTerminal::enter()?;
+    let mut input = TuiInput::new(config, profile, terminal.release_events);
+    let mut display = TuiDisplay::default();
+    let mut audio = TuiAudio {
+        muted,
+        ..TuiAudio::default()
+    };
+
+    let mut pacer = FramePacer::new();
+    let result = loop {
+        if let Err(e) = chip8.run_frame(
+            instructions_per_frame,
+            &mut movie::input(movie.as_deref_mut(), &mut input),
+            &mut display,
+            &mut audio,
+        ) {
+            break Err(e);
+        }
+        plugins.frame(chip8);
+        if input.quit_requested() {
+            break Ok(());
+        }
+
+        pacer.wait();
+    };
+
+    // Leave the alternate screen before printing anything
+    drop(terminal);
+    session::report_warnings(chip8);
+    if let Err(e) = result {
+        error!("Emulation stopped: {}", e);
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! Bindings for running the core in a browser, used by the page in `web/`.
+//! The crate is only a `cdylib` for this build, so no_std users can depend
+//! on it. Build them with:
+//!
+//! ```text
+//! cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+//! wasm-bindgen --target web --out-dir web/pkg target/wasm32-unknown-unknown/release/chip_8_rs.wasm
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use crate::{chip8::Chip8, frontend::NullFrontend};
+
+/// A `Chip8` the page drives once per animation frame. Input goes through
+/// `key_down`/`key_up` and the display is read back with `pixels`
+#[wasm_bindgen]
+pub struct WebChip8 {
+    chip8: Chip8,
+}
+
+impl Default for WebChip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl WebChip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            chip8: Chip8::new(),
+        }
+    }
+
+    /// Resets the machine and loads the ROM bytes at 0x200
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        self.chip8 = Chip8::new();
+        self.chip8.load(rom)?;
+        Ok(())
+    }
+
+    pub fn key_down(&mut self, key: u8) {
+        self.chip8.key_down(key);
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        self.chip8.key_up(key);
+    }
+
+    /// Runs one 60Hz frame. Keys are set directly, so nothing is polled
+    pub fn run_frame(&mut self, instructions: u32) -> Result<(), JsError> {
+        self.chip8.run_frame(
+            instructions,
+            &mut NullFrontend,
+            &mut NullFrontend,
+            &mut NullFrontend,
+        )?;
+        Ok(())
+    }
+
+    pub fn width(&self) -> usize {
+        self.chip8.display_width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.chip8.display_height()
+    }
+
+    /// The display as one byte per pixel, 1 for on and 0 for off
+    pub fn pixels(&self) -> Vec<u8> {
+        self.chip8
+            .get_display()
+            .iter()
+            .map(|&on| on as u8)
+            .collect()
+    }
+
+    /// Whether the beep should be playing
+    pub fn sound_active(&self) -> bool {
+        self.chip8.sound_timer() > 0
+    }
+}
@@ -0,0 +1,32 @@
+//! ROMs read from compressed files
+
+#![cfg(feature = "archives")]
+
+use chip_8_rs::{Chip8Error, MEGACHIP_MEMORY, archive};
+
+/// A gzip file holding `size` zero bytes in uncompressed deflate blocks.
+/// Its checksums are left out, the reader gives up before them
+fn gzip(size: usize) -> Vec<u8> {
+    let mut data = vec![0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 0xFF];
+    let mut left = size;
+    while left > 0 {
+        let len = left.min(0xFFFF);
+        left -= len;
+        data.push((left == 0) as u8);
+        data.extend_from_slice(&(len as u16).to_le_bytes());
+        data.extend_from_slice(&(!len as u16).to_le_bytes());
+        data.resize(data.len() + len, 0);
+    }
+    data
+}
+
+#[test]
+fn archives_larger_than_any_memory_are_rejected() {
+    match archive::decompress(gzip(MEGACHIP_MEMORY + 1)) {
+        Err(Chip8Error::InvalidArchive(reason)) => assert!(reason.contains("larger"), "{}", reason),
+        other => panic!(
+            "expected an invalid archive, got {:?}",
+            other.map(|rom| rom.len())
+        ),
+    }
+}
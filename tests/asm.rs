@@ -0,0 +1,97 @@
+//! Assembling Octo source: labels, blocks and the errors reported
+
+use chip_8_rs::asm::{AsmError, assemble};
+
+fn error_line(source: &str) -> usize {
+    let AsmError { line, .. } = assemble(source).unwrap_err();
+    line
+}
+
+#[test]
+fn labels_are_resolved_forward_and_backward() {
+    let rom = assemble(
+        "
+        : start
+            jump done
+            v0 := 1
+        : back
+            jump back
+        : done
+            :call start
+        ",
+    )
+    .unwrap();
+    assert_eq!(rom, [0x12, 0x06, 0x60, 0x01, 0x12, 0x04, 0x22, 0x00]);
+}
+
+#[test]
+fn blocks_nest() {
+    let rom = assemble(
+        "
+        loop
+            if v0 == 3 begin
+                v1 := 1
+            else
+                if v0 key then v2 := 2
+            end
+            v0 += 1
+            while v0 != 5
+        again
+        ",
+    )
+    .unwrap();
+    assert_eq!(
+        rom,
+        [
+            0x30, 0x03, // SE V0, 3
+            0x12, 0x08, // JP else
+            0x61, 0x01, // LD V1, 1
+            0x12, 0x0C, // JP end
+            0xE0, 0xA1, // SKNP V0
+            0x62, 0x02, // LD V2, 2
+            0x70, 0x01, // ADD V0, 1
+            0x40, 0x05, // SNE V0, 5
+            0x12, 0x14, // JP past the loop
+            0x12, 0x00, // JP loop
+        ]
+    );
+}
+
+#[test]
+fn errors_have_the_line_they_happened_on() {
+    assert_eq!(error_line("v0 := 1\n\njump nowhere"), 3);
+    assert_eq!(error_line("v0 := 1\nv1 := 300"), 2);
+    assert_eq!(error_line("loop\n  v0 += 1\n"), 1);
+    assert_eq!(error_line("v0 := 1\nend"), 2);
+    assert_eq!(error_line(": a\n: a"), 2);
+}
+
+#[test]
+fn addresses_past_0xfff_are_rejected() {
+    // The jump and 0xDFE bytes of data put the label at 0x1000, past the
+    // jump's reach
+    let data = "0 ".repeat(0xDFE);
+    assert_eq!(error_line(&format!("jump far\n{}\n: far", data)), 1);
+    let data = "0 ".repeat(0xE00);
+    assert_eq!(error_line(&format!("{}\n: far\njump far", data)), 3);
+    assert_eq!(error_line(&format!("{}\nloop\nagain", data)), 3);
+
+    // Right below the limit is still fine
+    let data = "0 ".repeat(0xDFE);
+    let rom = assemble(&format!("{}\n: far\njump far", data)).unwrap();
+    assert_eq!(rom[rom.len() - 2..], [0x1F, 0xFE]);
+}
+
+#[test]
+fn programs_past_the_end_of_memory_are_rejected() {
+    // 0xE00 bytes fill a 4KB machine
+    let data = "0 ".repeat(0xDFE);
+    assert_eq!(assemble(&format!("{}\nclear", data)).unwrap().len(), 0xE00);
+    assert_eq!(error_line(&format!("{}\nclear\n0", data)), 3);
+    assert_eq!(error_line(&format!("{}\n0\nclear", data)), 3);
+
+    // Far more than 64KB, which would overflow the addresses of labels
+    let error = assemble(&"0 ".repeat(0x10000)).unwrap_err();
+    assert_eq!(error.line, 1);
+    assert!(error.message.contains("doesn't fit"), "{}", error);
+}
@@ -0,0 +1,72 @@
+//! XO-CHIP's audio pattern and pitch, handed to the frontend every frame
+
+mod common;
+
+use chip_8_rs::{
+    Quirks, asm,
+    frontend::{AudioPattern, AudioSink, NullFrontend},
+};
+
+const PATTERN: &str = "
+: main
+	i := pattern
+	audio
+	v0 := 112
+	pitch := v0
+	v1 := 30
+	buzzer := v1
+	loop again
+: pattern
+	0xFF 0x00 0xF0 0x0F 0xAA 0x55 0x00 0xFF
+	0x80 0x01 0x00 0x00 0x00 0x00 0x00 0x01
+";
+
+/// Remembers what it was asked to play last
+#[derive(Default)]
+struct Recorder {
+    pattern: Option<AudioPattern>,
+    active: bool,
+}
+
+impl AudioSink for Recorder {
+    fn set_tone(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.pattern = pattern;
+    }
+}
+
+#[test]
+fn frontends_get_the_pattern() {
+    let rom = asm::assemble(PATTERN).unwrap();
+    let mut chip8 = common::machine(&rom, Quirks::default());
+    assert_eq!(chip8.audio_pattern(), None);
+
+    let mut audio = Recorder::default();
+    chip8
+        .run_frame(10, &mut NullFrontend, &mut NullFrontend, &mut audio)
+        .unwrap();
+    let pattern = audio.pattern.expect("F002 loaded a pattern");
+    assert!(audio.active);
+    assert_eq!(pattern.bits[..4], [0xFF, 0x00, 0xF0, 0x0F]);
+    assert_eq!(pattern.pitch, 112);
+    // 48 above the default doubles the rate
+    assert_eq!(pattern.rate(), 8000.0);
+    assert!(
+        pattern.bit(0) && !pattern.bit(8) && pattern.bit(64) && !pattern.bit(72) && pattern.bit(79)
+    );
+    assert!(pattern.bit(128) && pattern.bit(127), "wraps around");
+}
+
+#[test]
+fn save_states_keep_the_pattern() {
+    let rom = asm::assemble(PATTERN).unwrap();
+    let mut chip8 = common::machine(&rom, Quirks::default());
+    common::run_frames(&mut chip8, 1, 10);
+
+    let mut restored = common::machine(&rom, Quirks::default());
+    restored.load_state(&chip8.save_state()).unwrap();
+    assert_eq!(restored.audio_pattern(), chip8.audio_pattern());
+}
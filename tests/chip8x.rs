@@ -0,0 +1,88 @@
+//! CHIP-8X, run from hand-assembled programs since the assembler doesn't
+//! know its instructions
+
+mod common;
+
+use chip_8_rs::{CHIP8X_PROGRAM_START, Chip8Error, Quirks, frontend::NullFrontend};
+
+/// Colors the top left zone green, switches the background to black and
+/// draws a 0 in it and another one in the default red next to it
+const COLORS: &[u8] = &[
+    0x60, 0x00, // LD V0, 0
+    0x61, 0x00, // LD V1, 0
+    0x62, 0x04, // LD V2, 4 (green)
+    0xB0, 0x20, // BXY0, the zone at V0 and V1 gets the color in V2
+    0x02, 0xA0, // the next background color, black
+    0xA0, 0x00, // LD I, 0 (the font's 0)
+    0xD0, 0x05, // DRW V0, V0, 5
+    0x65, 0x08, // LD V5, 8
+    0xD5, 0x15, // DRW V5, V1, 5
+    0x63, 0x35, // LD V3, 0x35
+    0x64, 0x13, // LD V4, 0x13
+    0x53, 0x41, // ADDN V3, V4
+    0x13, 0x18, // JP 0x318
+];
+
+#[test]
+fn programs_start_at_0x300() {
+    let chip8 = common::machine(COLORS, Quirks::CHIP8X);
+    assert_eq!(chip8.pc(), CHIP8X_PROGRAM_START);
+    assert_eq!(chip8.memory()[0x300..0x302], COLORS[..2]);
+}
+
+#[test]
+fn colors_zones() {
+    let mut chip8 = common::machine(COLORS, Quirks::CHIP8X);
+    // Both draws wait for the end of their frame
+    common::run_frames(&mut chip8, 3, 20);
+
+    let colors = chip8.get_colors().expect("CHIP-8X has colors");
+    assert_eq!(colors[0], 0xFF00_FF00);
+    assert_eq!(colors[8], 0xFFFF_0000);
+    assert_eq!(colors[4], 0xFF00_0000);
+}
+
+#[test]
+fn adds_nibbles_separately() {
+    let mut chip8 = common::machine(COLORS, Quirks::CHIP8X);
+    common::run_frames(&mut chip8, 3, 20);
+    assert_eq!(chip8.registers()[3], 0x40);
+}
+
+#[test]
+fn second_keypad() {
+    let rom = [0x60, 0x07, 0xE0, 0xF2, 0x61, 0x01, 0x62, 0x01, 0x13, 0x08];
+    let mut chip8 = common::machine(&rom, Quirks::CHIP8X);
+    chip8.key_down(7);
+    common::run_frames(&mut chip8, 1, 4);
+    assert_eq!(chip8.registers()[1..3], [1, 1]);
+
+    let mut chip8 = common::machine(&rom, Quirks::CHIP8X);
+    chip8.second_key_down(7);
+    common::run_frames(&mut chip8, 1, 4);
+    assert_eq!(chip8.registers()[1..3], [0, 1]);
+}
+
+#[test]
+fn unknown_without_the_quirk() {
+    let mut chip8 = common::machine(&[0x53, 0x41], Quirks::CHIP8);
+    let result = chip8.run_frame(1, &mut NullFrontend, &mut NullFrontend, &mut NullFrontend);
+    assert!(matches!(result, Err(Chip8Error::UnknownOpcode { .. })));
+    assert!(chip8.get_colors().is_none());
+}
+
+#[test]
+fn save_state_keeps_colors() {
+    let mut chip8 = common::machine(COLORS, Quirks::CHIP8X);
+    common::run_frames(&mut chip8, 3, 20);
+    let state = chip8.save_state();
+
+    let mut restored = common::machine(COLORS, Quirks::CHIP8X);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.get_colors(), chip8.get_colors());
+    assert!(
+        common::machine(COLORS, Quirks::CHIP8)
+            .load_state(&state)
+            .is_err()
+    );
+}
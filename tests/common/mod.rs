@@ -0,0 +1,101 @@
+//! Helpers shared by the tests: small test ROMs, machines with a fixed
+//! seed, running them for a few frames, and comparing the screen they end
+//! on with a snapshot in `tests/snapshots`, one row of `#` and `.` per line.
+//!
+//! A test without a snapshot fails and prints the screen. Run the tests with
+//! `CHIP8_BLESS=1` to write the snapshots of the current screens, then look
+//! at the diff before committing them.
+
+// Not every test file uses every helper
+#![allow(dead_code)]
+
+use std::{env, fs, path::PathBuf};
+
+use chip_8_rs::{Chip8, Chip8Error, Quirks, frontend::NullFrontend};
+
+/// Counts V0 up forever
+pub const COUNT: &[u8] = &[
+    0x70, 0x01, // ADD V0, 1
+    0x12, 0x00, // JP 0x200
+];
+
+/// Counts V0 down from 3 in a subroutine that calls itself until it's 0
+pub const RECURSION: &[u8] = &[
+    0x60, 0x03, // LD V0, 3
+    0x22, 0x06, // CALL 0x206
+    0x12, 0x04, // JP 0x204
+    0x30, 0x00, // SE V0, 0
+    0x12, 0x0C, // JP 0x20C
+    0x00, 0xEE, // RET
+    0x70, 0xFF, // ADD V0, -1
+    0x22, 0x06, // CALL 0x206
+    0x00, 0xEE, // RET
+];
+
+/// A machine with `quirks` and a fixed seed, with `rom` loaded
+pub fn machine(rom: &[u8], quirks: Quirks) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.set_quirks(quirks);
+    chip8.set_seed(0);
+    chip8.load(rom).expect("ROM fits into memory");
+    chip8
+}
+
+/// Runs `frames` frames without input, panicking if the ROM crashes
+pub fn run_frames(chip8: &mut Chip8, frames: u32, instructions_per_frame: u32) {
+    for frame in 0..frames {
+        if let Err(e) = chip8.run_frame(
+            instructions_per_frame,
+            &mut NullFrontend,
+            &mut NullFrontend,
+            &mut NullFrontend,
+        ) {
+            panic!("stopped in frame {}: {}", frame, e);
+        }
+    }
+}
+
+/// Runs a short frame without input, returning the error if the ROM stops
+pub fn run_frame(chip8: &mut Chip8) -> Result<(), Chip8Error> {
+    chip8.run_frame(3, &mut NullFrontend, &mut NullFrontend, &mut NullFrontend)
+}
+
+/// The display as a row of `#` and `.` per line
+pub fn render(chip8: &Chip8) -> String {
+    chip8
+        .get_display()
+        .chunks(chip8.display_width())
+        .map(|row| {
+            let mut line: String = row.iter().map(|&on| if on { '#' } else { '.' }).collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+/// Compares the display with the snapshot called `name`, or records it when
+/// blessing
+pub fn assert_snapshot(name: &str, chip8: &Chip8) {
+    let screen = render(chip8);
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let path = dir.join(format!("{}.txt", name));
+    if env::var_os("CHIP8_BLESS").is_some() {
+        fs::create_dir_all(&dir).expect("snapshot directory is writable");
+        fs::write(&path, &screen).expect("snapshot is writable");
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "no snapshot {}, run with CHIP8_BLESS=1 to record it:\n{}",
+            name, screen
+        )
+    });
+    assert!(
+        screen == expected,
+        "the screen doesn't match snapshot {}.\nexpected:\n{}\nactual:\n{}",
+        name,
+        expected,
+        screen
+    );
+}
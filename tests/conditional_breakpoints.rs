@@ -0,0 +1,63 @@
+//! Breakpoints that only stop when their condition holds
+
+mod common;
+
+use chip_8_rs::{
+    Quirks,
+    debug::{Condition, Stop},
+};
+
+fn condition(source: &str) -> Condition {
+    source.parse().unwrap()
+}
+
+#[test]
+fn stops_once_the_condition_holds() {
+    let mut chip8 = common::machine(common::COUNT, Quirks::default());
+    chip8.add_conditional_breakpoint(0x200, condition("v[0] == 3 && dt == 0"));
+    common::run_frames(&mut chip8, 1, 100);
+
+    assert_eq!(chip8.take_stop(), Some(Stop::Breakpoint { address: 0x200 }));
+    assert_eq!(chip8.registers()[0], 3);
+}
+
+#[test]
+fn conditions_read_memory_and_combine() {
+    let mut chip8 = common::machine(common::COUNT, Quirks::default());
+    chip8.add_conditional_breakpoint(
+        0x202,
+        condition("!(v0 < 5) && (m[0x200] == 0x70 || key[1]) && v0 & 1"),
+    );
+    common::run_frames(&mut chip8, 1, 100);
+
+    assert!(chip8.take_stop().is_some());
+    assert_eq!(chip8.registers()[0], 5);
+}
+
+#[test]
+fn invalid_conditions_are_rejected() {
+    for source in ["v[16] == 0", "v0 ==", "dt = 0", "(v0", "x == 1", "0xZZ"] {
+        assert!(source.parse::<Condition>().is_err(), "{} parsed", source);
+    }
+}
+
+#[test]
+fn deep_nesting_is_rejected() {
+    let nested = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+    assert!(nested.parse::<Condition>().is_err());
+    assert!("!".repeat(100_000).parse::<Condition>().is_err());
+
+    let shallow = format!("{}1{}", "(".repeat(10), ")".repeat(10));
+    assert!(shallow.parse::<Condition>().is_ok());
+}
+
+#[test]
+fn long_chains_are_rejected() {
+    let chain = format!("0{}", "+1".repeat(50_000));
+    assert!(chain.parse::<Condition>().is_err());
+    let chain = format!("v0 == 0{}", " || v0 == 1".repeat(50_000));
+    assert!(chain.parse::<Condition>().is_err());
+
+    let short = format!("0{} == 100", "+1".repeat(100));
+    assert!(short.parse::<Condition>().is_ok());
+}
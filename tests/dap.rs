@@ -0,0 +1,282 @@
+//! The debug adapter, driven over TCP like an editor would against a
+//! headless run of the emulator
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process::{Child, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use serde_json::{Value, json};
+
+/// The emulator running `common::RECURSION` headless with the debug adapter
+/// on a free port, killed when dropped
+struct Emulator {
+    child: Child,
+    port: u16,
+}
+
+impl Emulator {
+    fn start(name: &str) -> Self {
+        let rom = env::temp_dir().join(format!("chip-8-rs-dap-{}.ch8", name));
+        fs::write(&rom, common::RECURSION).expect("temp dir is writable");
+        let mut child = Command::new(env!("CARGO_BIN_EXE_chip-8-rs"))
+            .arg("run")
+            .arg(&rom)
+            .args(["--headless", "--dap", "127.0.0.1:0"])
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("emulator starts");
+
+        // The port is only known once it's logged. The rest of the log is
+        // drained, the emulator would fail writing it to a closed pipe
+        let mut lines = BufReader::new(child.stderr.take().unwrap()).lines();
+        let port = lines
+            .by_ref()
+            .map_while(Result::ok)
+            .find_map(|line| {
+                let address = line.strip_prefix("Debug adapter listening on ")?;
+                let (address, _) = address.split_once(',')?;
+                address.rsplit_once(':')?.1.parse().ok()
+            })
+            .expect("debug adapter listens");
+        thread::spawn(move || lines.for_each(drop));
+        Self { child, port }
+    }
+
+    fn connect(&self) -> Client {
+        let stream = TcpStream::connect(("127.0.0.1", self.port)).expect("connects");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            stream,
+            seq: 0,
+        }
+    }
+}
+
+impl Drop for Emulator {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+    seq: u64,
+}
+
+impl Client {
+    fn send_raw(&mut self, bytes: &[u8]) {
+        self.stream.write_all(bytes).unwrap();
+    }
+
+    fn send(&mut self, command: &str, arguments: Value) {
+        self.seq += 1;
+        let body = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        self.send_raw(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes());
+    }
+
+    /// The next message, `None` once the adapter closed the connection
+    fn receive(&mut self) -> Option<Value> {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.parse().ok();
+            }
+        }
+        let mut body = vec![0; length?];
+        self.reader.read_exact(&mut body).ok()?;
+        serde_json::from_slice(&body).ok()
+    }
+
+    /// Skips messages until the response to `command`
+    fn response(&mut self, command: &str) -> Value {
+        loop {
+            let message = self.receive().expect("adapter responds");
+            if message["type"] == "response" && message["command"] == command {
+                return message;
+            }
+        }
+    }
+
+    /// Skips messages until the `stopped` event, returning its reason
+    fn stopped(&mut self) -> String {
+        loop {
+            let message = self.receive().expect("adapter reports the stop");
+            if message["type"] == "event" && message["event"] == "stopped" {
+                return message["body"]["reason"].as_str().unwrap().to_string();
+            }
+        }
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.send(command, arguments);
+        self.response(command)
+    }
+
+    /// Starts the session stopped on entry
+    fn launch(&mut self) {
+        assert_eq!(self.request("initialize", json!({}))["success"], true);
+        self.send("launch", json!({ "stopOnEntry": true }));
+        self.send("configurationDone", json!({}));
+        assert_eq!(self.stopped(), "entry");
+    }
+
+    fn pc(&mut self) -> String {
+        let response = self.request("variables", json!({ "variablesReference": 1 }));
+        let variables = response["body"]["variables"].as_array().unwrap();
+        let pc = variables.iter().find(|v| v["name"] == "PC").unwrap();
+        pc["value"].as_str().unwrap().to_string()
+    }
+}
+
+#[test]
+fn reads_memory_as_base64() {
+    let emulator = Emulator::start("memory");
+    let mut client = emulator.connect();
+    client.launch();
+
+    let arguments = |offset: i64, count: u64| {
+        let mut arguments = json!({ "memoryReference": "0x200", "count": count });
+        arguments["offset"] = offset.into();
+        arguments
+    };
+    for (count, data) in [(1, "YA=="), (2, "YAM="), (3, "YAMi"), (4, "YAMiBg==")] {
+        let response = client.request("readMemory", arguments(0, count));
+        assert_eq!(response["body"]["address"], "0x200");
+        assert_eq!(response["body"]["data"], data);
+    }
+
+    // The last 2 bytes of 4KB, and 2 more past the end
+    let response = client.request("readMemory", arguments(0xDFE, 4));
+    assert_eq!(response["body"]["address"], "0xFFE");
+    assert_eq!(response["body"]["unreadableBytes"], 2);
+
+    let response = client.request("readMemory", json!({ "memoryReference": "x", "count": 1 }));
+    assert_eq!(response["success"], false);
+
+    // Never more than all of memory, and offsets that overflow are errors
+    let response = client.request("readMemory", arguments(-0x200, u64::MAX));
+    assert_eq!(response["body"]["address"], "0x000");
+    assert_eq!(response["body"]["unreadableBytes"], 0);
+    let response = client.request("readMemory", arguments(i64::MAX, 1));
+    assert_eq!(response["success"], false);
+}
+
+#[test]
+fn disassembles_around_a_reference() {
+    let emulator = Emulator::start("disassemble");
+    let mut client = emulator.connect();
+    client.launch();
+
+    let response = client.request(
+        "disassemble",
+        json!({
+            "memoryReference": "0x204",
+            "instructionOffset": -1,
+            "instructionCount": 2,
+        }),
+    );
+    let instructions = response["body"]["instructions"].as_array().unwrap();
+    assert_eq!(instructions[0]["address"], "0x202");
+    assert_eq!(instructions[0]["instructionBytes"], "22 06");
+    assert_eq!(instructions[1]["address"], "0x204");
+
+    let response = client.request(
+        "disassemble",
+        json!({ "memoryReference": "0xFFE", "instructionOffset": 1, "instructionCount": 1 }),
+    );
+    assert_eq!(
+        response["body"]["instructions"][0]["presentationHint"],
+        "invalid"
+    );
+
+    // One instruction per byte of memory at most, and offsets that overflow
+    // are errors
+    let response = client.request(
+        "disassemble",
+        json!({ "memoryReference": "0x200", "instructionCount": u64::MAX }),
+    );
+    let instructions = response["body"]["instructions"].as_array().unwrap();
+    assert_eq!(instructions.len(), 4096);
+    for offset in [i64::MAX, i64::MIN / 2 - 1] {
+        let response = client.request(
+            "disassemble",
+            json!({
+                "memoryReference": "0x200",
+                "instructionOffset": offset,
+                "instructionCount": 1,
+            }),
+        );
+        assert_eq!(response["success"], false);
+    }
+}
+
+#[test]
+fn stops_at_breakpoints_and_steps_over_calls() {
+    let emulator = Emulator::start("breakpoints");
+    let mut client = emulator.connect();
+    client.launch();
+
+    let response = client.request(
+        "setInstructionBreakpoints",
+        json!({ "breakpoints": [
+            { "instructionReference": "0x20C", "offset": 2, "condition": "v0 == 1" },
+            { "instructionReference": "nowhere" },
+        ]}),
+    );
+    let breakpoints = &response["body"]["breakpoints"];
+    assert_eq!(breakpoints[0]["verified"], true);
+    assert_eq!(breakpoints[1]["verified"], false);
+
+    client.send("next", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), "step");
+    assert_eq!(client.pc(), "0x202");
+
+    // Stepping over the call stops inside it at the breakpoint
+    client.send("next", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), "breakpoint");
+    assert_eq!(client.pc(), "0x20E");
+
+    client.send("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), "step");
+    assert_eq!(client.pc(), "0x210");
+}
+
+#[test]
+fn drops_connections_with_oversized_messages() {
+    let emulator = Emulator::start("oversized");
+    let mut client = emulator.connect();
+    client.send_raw(b"Content-Length: 99999999999999\r\n\r\n");
+    assert!(client.receive().is_none());
+
+    // The emulator is still there for the next editor
+    let mut client = emulator.connect();
+    client.launch();
+}
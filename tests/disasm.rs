@@ -0,0 +1,90 @@
+//! Telling code from data by following the program from its start
+
+use chip_8_rs::{
+    PROGRAM_START,
+    disasm::{Line, disassemble_program, disassemble_reachable},
+};
+
+/// Draws the sprite after the jump, then calls a subroutine behind another
+/// sprite
+const SPRITES: &[u8] = &[
+    0x12, 0x04, // JP 0x204
+    0xF0, 0x90, // a sprite
+    0xA2, 0x02, // LD I, 0x202
+    0xD0, 0x02, // DRW V0, V0, 2
+    0x22, 0x0E, // CALL 0x20E
+    0x12, 0x0A, // JP 0x20A
+    0x3C, 0x42, // another sprite
+    0x00, 0xEE, // RET
+];
+
+fn addresses(lines: &[Line], code: bool) -> Vec<u16> {
+    lines
+        .iter()
+        .filter(|line| matches!(line, Line::Code(_)) == code)
+        .map(Line::address)
+        .collect()
+}
+
+#[test]
+fn sprites_between_code_are_data() {
+    let lines = disassemble_reachable(SPRITES, PROGRAM_START);
+    assert_eq!(
+        addresses(&lines, true),
+        [0x200, 0x204, 0x206, 0x208, 0x20A, 0x20E]
+    );
+    assert_eq!(
+        lines[1],
+        Line::Data {
+            address: 0x202,
+            bytes: vec![0xF0, 0x90]
+        }
+    );
+    assert_eq!(addresses(&lines, false), [0x202, 0x20C]);
+}
+
+#[test]
+fn both_sides_of_a_skip_are_code() {
+    let program = [
+        0x30, 0x00, // SE V0, 0
+        0x12, 0x08, // JP 0x208
+        0x00, 0xFD, // EXIT
+        0xFF, 0xFF, // never reached
+        0x12, 0x08, // JP 0x208
+    ];
+    let lines = disassemble_reachable(&program, PROGRAM_START);
+    assert_eq!(addresses(&lines, true), [0x200, 0x202, 0x204, 0x208]);
+    assert_eq!(addresses(&lines, false), [0x206]);
+}
+
+#[test]
+fn long_data_is_split_into_lines() {
+    let mut program = vec![0x12, 0x00];
+    program.extend([0xAA; 10]);
+    let lines = disassemble_reachable(&program, PROGRAM_START);
+    assert_eq!(addresses(&lines, false), [0x202, 0x20A]);
+}
+
+#[test]
+fn long_loads_dont_claim_code_twice() {
+    let program = [
+        0x22, 0x05, // CALL 0x205
+        0x01, 0x00, // LD I, long, its address overlapping the subroutine
+        0x00, // padding
+        0x00, 0xFD, // EXIT
+    ];
+    let lines = disassemble_reachable(&program, PROGRAM_START);
+    assert_eq!(addresses(&lines, true), [0x200, 0x202, 0x205]);
+    assert_eq!(addresses(&lines, false), [0x204]);
+}
+
+#[test]
+fn programs_past_64kb_wrap_around() {
+    // As large as a MegaChip ROM can be
+    let program = vec![0x00; 0x20000];
+    let instructions = disassemble_program(&program, PROGRAM_START);
+    assert_eq!(instructions.len(), 0x10000);
+    assert_eq!(instructions[0x8000].address, PROGRAM_START);
+    let lines = disassemble_reachable(&program, PROGRAM_START);
+    assert!(!lines.is_empty());
+}
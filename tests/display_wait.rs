@@ -0,0 +1,60 @@
+//! The COSMAC VIP's interpreter waited for the vertical blank after every
+//! DXYN, which the `display_wait` quirk brings back
+
+mod common;
+
+use chip_8_rs::{Quirks, frontend::NullFrontend, jit::Jit};
+
+/// Draws, counts the draw in V1 and loops
+const DRAW_LOOP: &[u8] = &[0xA0, 0x00, 0xD0, 0x01, 0x71, 0x01, 0x12, 0x02];
+
+#[test]
+fn one_draw_per_frame_with_the_quirk() {
+    let quirks = Quirks {
+        display_wait: true,
+        ..Quirks::default()
+    };
+    let mut chip8 = common::machine(DRAW_LOOP, quirks);
+    common::run_frames(&mut chip8, 3, 100);
+    // The third draw is still waiting for the end of its frame's vertical
+    // blank to be counted
+    assert_eq!(chip8.registers()[1], 2);
+
+    let mut compiled = common::machine(DRAW_LOOP, quirks);
+    let mut jit = Jit::new();
+    for _ in 0..3 {
+        jit.run_frame(
+            &mut compiled,
+            100,
+            &mut NullFrontend,
+            &mut NullFrontend,
+            &mut NullFrontend,
+        )
+        .unwrap();
+    }
+    assert!(compiled.save_state() == chip8.save_state());
+}
+
+#[test]
+fn draws_run_freely_without_it() {
+    let mut chip8 = common::machine(DRAW_LOOP, Quirks::default());
+    common::run_frames(&mut chip8, 3, 100);
+    assert!(chip8.registers()[1] > 2);
+}
+
+#[test]
+fn cycles_wait_for_the_timers() {
+    let quirks = Quirks {
+        display_wait: true,
+        ..Quirks::default()
+    };
+    let mut chip8 = common::machine(DRAW_LOOP, quirks);
+    for _ in 0..10 {
+        chip8.cycle().unwrap();
+    }
+    assert_eq!(chip8.registers()[1], 0);
+
+    chip8.tick_timers();
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.registers()[1], 1);
+}
@@ -0,0 +1,68 @@
+//! The hex digits FX29 and FX30 point at, and fontsets replacing the small
+//! ones
+
+mod common;
+
+use chip_8_rs::{Chip8Error, FONT_SIZE, Quirks};
+
+/// Points I at the digit in V0 and draws it
+const DRAW_DIGIT: &[u8] = &[0x60, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06];
+
+/// Every digit a filled 8x5 block
+fn blocks() -> Vec<u8> {
+    vec![0xFF; FONT_SIZE]
+}
+
+#[test]
+fn digits_come_from_the_font() {
+    let mut chip8 = common::machine(DRAW_DIGIT, Quirks::default());
+    chip8.set_font(&blocks()).unwrap();
+    common::run_frames(&mut chip8, 1, 10);
+
+    let display = chip8.get_display();
+    assert!((0..5).all(|y| display[y * 64..y * 64 + 8].iter().all(|&on| on)));
+    assert_eq!(chip8.memory()[..FONT_SIZE], blocks()[..]);
+}
+
+#[test]
+fn kept_across_resets() {
+    let mut chip8 = common::machine(DRAW_DIGIT, Quirks::default());
+    chip8.set_font(&blocks()).unwrap();
+    chip8.reset();
+    assert_eq!(chip8.memory()[..FONT_SIZE], blocks()[..]);
+}
+
+#[test]
+fn rejects_invalid_fonts() {
+    let mut chip8 = common::machine(DRAW_DIGIT, Quirks::default());
+    let original = chip8.memory()[..FONT_SIZE].to_vec();
+
+    assert!(matches!(
+        chip8.set_font(&[0xF0; 40]),
+        Err(Chip8Error::InvalidFont(_))
+    ));
+    let mut blank = blocks();
+    blank[15..20].fill(0);
+    let error = chip8.set_font(&blank).unwrap_err();
+    assert_eq!(error.to_string(), "invalid font: the digit 3 is blank");
+    assert_eq!(chip8.memory()[..FONT_SIZE], original[..]);
+}
+
+#[test]
+fn big_digits_follow_the_font() {
+    // LD V0, 8; LD HF, V0; DRW V1, V1, 10
+    let rom = [0x60, 0x08, 0xF0, 0x30, 0xD1, 0x1A, 0x12, 0x06];
+    let mut chip8 = common::machine(&rom, Quirks::SCHIP);
+    common::run_frames(&mut chip8, 1, 10);
+
+    assert_eq!(chip8.index(), FONT_SIZE as u32 + 80);
+    let rendered = common::render(&chip8);
+    let rows: Vec<&str> = rendered.lines().map(|row| &row[..8]).take(10).collect();
+    assert_eq!(
+        rows,
+        [
+            "########", "########", "##....##", "##....##", "########", "########", "##....##",
+            "##....##", "########", "########",
+        ]
+    );
+}
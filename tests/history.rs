@@ -0,0 +1,30 @@
+//! The instruction history kept for crash dumps
+
+mod common;
+
+use chip_8_rs::{Quirks, debug::Executed};
+
+#[test]
+fn keeps_the_last_instructions() {
+    let mut chip8 = common::machine(common::COUNT, Quirks::default());
+    chip8.enable_history(3);
+    common::run_frames(&mut chip8, 1, 5);
+
+    let history: Vec<Executed> = chip8.history().collect();
+    let executed = |address, opcode| Executed { address, opcode };
+    assert_eq!(
+        history,
+        [
+            executed(0x200, 0x7001),
+            executed(0x202, 0x1200),
+            executed(0x200, 0x7001),
+        ]
+    );
+}
+
+#[test]
+fn is_off_by_default() {
+    let mut chip8 = common::machine(common::COUNT, Quirks::default());
+    common::run_frames(&mut chip8, 1, 5);
+    assert_eq!(chip8.history().count(), 0);
+}
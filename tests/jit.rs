@@ -0,0 +1,122 @@
+//! The JIT has to end up in exactly the state the interpreter does, compared
+//! through save states after running the same program on both
+
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use chip_8_rs::{Quirks, asm, frontend::NullFrontend, jit::Jit};
+
+/// Overwrites the instruction right after the store, which is compiled into
+/// the same block, so the block has to stop and be recompiled
+const SELF_MODIFYING: &str = "
+: main
+	i := target
+	v0 := 0x61
+	v1 := 0x2A
+	save v1
+: target
+	v1 := 0
+	v2 := 1
+	loop again
+";
+
+/// Rewrites the byte added to v1 on every pass, so the loop's block is
+/// compiled with different code over and over
+const PATCHED_LOOP: &str = "
+: main
+	v3 := 1
+	loop
+		v0 += 1
+		i := step
+		i += v3
+		save v0
+: step
+		v1 += 0
+	again
+";
+
+fn demo(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(format!("roms/{}.ch8", name));
+    fs::read(path).expect("demo ROM exists")
+}
+
+/// Runs `rom` on the interpreter and the JIT in the same steps, checking
+/// that their states agree after every frame
+fn assert_same_as_interpreter(rom: &[u8], frames: u32, instructions_per_frame: u32) {
+    let mut interpreted = common::machine(rom, Quirks::default());
+    let mut compiled = common::machine(rom, Quirks::default());
+    let mut jit = Jit::new();
+
+    for frame in 0..frames {
+        interpreted
+            .run_frame(
+                instructions_per_frame,
+                &mut NullFrontend,
+                &mut NullFrontend,
+                &mut NullFrontend,
+            )
+            .expect("the interpreter runs the ROM");
+        jit.run_frame(
+            &mut compiled,
+            instructions_per_frame,
+            &mut NullFrontend,
+            &mut NullFrontend,
+            &mut NullFrontend,
+        )
+        .expect("the JIT runs the ROM");
+        assert!(
+            interpreted.save_state() == compiled.save_state(),
+            "the JIT differs from the interpreter after frame {}",
+            frame
+        );
+    }
+}
+
+#[test]
+fn demos() {
+    for name in ["maze", "bounce", "keys"] {
+        assert_same_as_interpreter(&demo(name), 120, 37);
+    }
+}
+
+#[test]
+fn self_modifying_code() {
+    let rom = asm::assemble(SELF_MODIFYING).unwrap();
+    assert_same_as_interpreter(&rom, 1, 10);
+
+    let mut chip8 = common::machine(&rom, Quirks::default());
+    Jit::new().run(&mut chip8, 10).unwrap();
+    assert_eq!(chip8.registers()[1], 0x2A);
+    assert_eq!(chip8.registers()[2], 1);
+}
+
+#[test]
+fn patched_loop() {
+    let rom = asm::assemble(PATCHED_LOOP).unwrap();
+    assert_same_as_interpreter(&rom, 10, 101);
+}
+
+#[test]
+fn poke_invalidates_blocks() {
+    let rom = asm::assemble(SELF_MODIFYING).unwrap();
+    let mut chip8 = common::machine(&rom, Quirks::default());
+    let mut jit = Jit::new();
+    jit.run(&mut chip8, 10).unwrap();
+
+    // Turn `v2 := 1` into `v2 := 7` and run it again
+    chip8.reset();
+    chip8.poke(0x20D, 7).unwrap();
+    jit.run(&mut chip8, 10).unwrap();
+    assert_eq!(chip8.registers()[2], 7);
+}
+
+#[test]
+fn megachip_memory() {
+    // Code only runs from the first 64KB of MegaChip's 16MB
+    let mut chip8 = common::machine(common::COUNT, Quirks::MEGACHIP);
+    let mut jit = Jit::new();
+    jit.run(&mut chip8, 100).unwrap();
+    assert_eq!(chip8.registers()[0], 50);
+    assert_eq!(jit.compiled(), 1);
+}
@@ -0,0 +1,62 @@
+//! FX0A, which either continues as soon as a key is pressed or waits for it
+//! to be released again, and which keys programs check
+
+mod common;
+
+use chip_8_rs::{Chip8, Quirks};
+
+/// Waits for a key into V0, then sets V1 to show it carried on
+const WAIT_KEY: &[u8] = &[0xF0, 0x0A, 0x61, 0x01, 0x12, 0x04];
+
+fn waited(chip8: &Chip8) -> bool {
+    chip8.registers()[1] == 1
+}
+
+#[test]
+fn continues_on_press() {
+    let mut chip8 = common::machine(WAIT_KEY, Quirks::default());
+    common::run_frames(&mut chip8, 1, 4);
+    assert!(!waited(&chip8));
+
+    chip8.key_down(0xA);
+    common::run_frames(&mut chip8, 1, 4);
+    assert!(waited(&chip8));
+    assert_eq!(chip8.registers()[0], 0xA);
+}
+
+#[test]
+fn waits_for_release_with_the_quirk() {
+    let quirks = Quirks {
+        wait_for_release: true,
+        ..Quirks::default()
+    };
+    let mut chip8 = common::machine(WAIT_KEY, quirks);
+    chip8.key_down(0xA);
+    common::run_frames(&mut chip8, 1, 4);
+    assert!(!waited(&chip8));
+
+    // The key pressed first is stored, even with another one held when it's
+    // released
+    chip8.key_down(0x3);
+    chip8.key_up(0xA);
+    common::run_frames(&mut chip8, 1, 4);
+    assert!(waited(&chip8));
+    assert_eq!(chip8.registers()[0], 0xA);
+}
+
+#[test]
+fn reports_polled_keys() {
+    // Checks key 5 with EX9E and key 0xC with EXA1, then loops
+    let rom = [
+        0x60, 0x05, 0x61, 0x0C, 0xE0, 0x9E, 0x00, 0xE0, 0xE1, 0xA1, 0x00, 0xE0, 0x12, 0x0C,
+    ];
+    let mut chip8 = common::machine(&rom, Quirks::default());
+    common::run_frames(&mut chip8, 1, 6);
+    assert_eq!(chip8.take_polled_keys(), 1 << 0x5 | 1 << 0xC);
+    // Taking them clears them
+    assert_eq!(chip8.take_polled_keys(), 0);
+
+    let mut chip8 = common::machine(WAIT_KEY, Quirks::default());
+    common::run_frames(&mut chip8, 1, 1);
+    assert_eq!(chip8.take_polled_keys(), u16::MAX);
+}
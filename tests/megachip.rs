@@ -0,0 +1,72 @@
+//! MegaChip mode, run from hand-assembled programs since the assembler
+//! doesn't know its instructions
+
+mod common;
+
+use chip_8_rs::{MEGACHIP_HEIGHT, MEGACHIP_WIDTH, Quirks};
+
+/// Loads a palette with one color and draws a 2x1 sprite using it and the
+/// transparent index at (5, 5), then finishes the frame and loops
+const DRAW_SPRITE: &[u8] = &[
+    0x00, 0x11, // MEGAON
+    0x01, 0x00, 0x02, 0x1C, // LDHI I, 0x00021C
+    0x02, 0x01, // LDPAL 1
+    0x01, 0x00, 0x02, 0x20, // LDHI I, 0x000220
+    0x03, 0x02, // SPRW 2
+    0x04, 0x01, // SPRH 1
+    0x60, 0x05, // LD V0, 5
+    0xD0, 0x01, // DRW V0, V0, 1
+    0x00, 0xE0, // CLS, which shows the frame
+    0x12, 0x10, // JP 0x210
+    0x00, 0x00, // padding
+    0x00, 0x00, // padding
+    0xFF, 0x12, 0x34, 0x56, // palette
+    0x01, 0x00, // sprite
+];
+
+#[test]
+fn draws_in_color() {
+    let mut chip8 = common::machine(DRAW_SPRITE, Quirks::MEGACHIP);
+    common::run_frames(&mut chip8, 1, 20);
+
+    assert_eq!(chip8.display_width(), MEGACHIP_WIDTH);
+    assert_eq!(chip8.display_height(), MEGACHIP_HEIGHT);
+    let colors = chip8.get_colors().expect("MegaChip mode is on");
+    assert_eq!(colors[5 * MEGACHIP_WIDTH + 5], 0xFF12_3456);
+    assert_eq!(colors[5 * MEGACHIP_WIDTH + 6], 0xFF00_0000);
+    assert!(chip8.get_display()[5 * MEGACHIP_WIDTH + 5]);
+}
+
+#[test]
+fn skipped_without_the_quirk() {
+    let mut chip8 = common::machine(DRAW_SPRITE, Quirks::SCHIP);
+    common::run_frames(&mut chip8, 1, 20);
+
+    assert!(chip8.get_colors().is_none());
+    assert_eq!(chip8.display_width(), 64);
+}
+
+#[test]
+fn save_state_keeps_megachip_mode() {
+    let mut chip8 = common::machine(DRAW_SPRITE, Quirks::MEGACHIP);
+    common::run_frames(&mut chip8, 1, 20);
+    let state = chip8.save_state();
+
+    let mut restored = common::machine(DRAW_SPRITE, Quirks::MEGACHIP);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.get_colors(), chip8.get_colors());
+    assert_eq!(restored.index(), chip8.index());
+    assert!(restored.save_state() == state);
+
+    // A machine without the quirk doesn't have the memory for it
+    let mut schip = common::machine(DRAW_SPRITE, Quirks::SCHIP);
+    assert!(schip.load_state(&state).is_err());
+}
+
+#[test]
+fn index_reaches_past_64k() {
+    let rom = [0x01, 0x12, 0x34, 0x56, 0x12, 0x04];
+    let mut chip8 = common::machine(&rom, Quirks::MEGACHIP);
+    common::run_frames(&mut chip8, 1, 2);
+    assert_eq!(chip8.index(), 0x12_3456);
+}
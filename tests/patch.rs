@@ -0,0 +1,113 @@
+//! Applying IPS and BPS patches to ROM bytes
+
+use chip_8_rs::{Chip8Error, patch};
+
+/// The CRC-32 BPS checksums use
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                crc >> 1 ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// BPS's variable length number
+fn varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte | 0x80);
+            return;
+        }
+        out.push(byte);
+        value -= 1;
+    }
+}
+
+/// A BPS patch turning `source` into `target` with `commands`, checksums
+/// included
+fn bps(source: &[u8], target: &[u8], target_size: usize, commands: &[u8]) -> Vec<u8> {
+    let mut patch = b"BPS1".to_vec();
+    varint(&mut patch, source.len());
+    varint(&mut patch, target_size);
+    varint(&mut patch, 0);
+    patch.extend_from_slice(commands);
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+    patch
+}
+
+fn error(result: Result<Vec<u8>, Chip8Error>) -> String {
+    match result {
+        Err(Chip8Error::InvalidPatch(reason)) => reason,
+        other => panic!("expected an invalid patch, got {:?}", other),
+    }
+}
+
+const ROM: &[u8] = &[1, 2, 3, 4];
+
+#[test]
+fn ips_records_overwrite_bytes() {
+    let patch = b"PATCH\x00\x00\x01\x00\x02\xAA\xBBEOF";
+    assert_eq!(patch::apply(ROM, patch).unwrap(), [1, 0xAA, 0xBB, 4]);
+}
+
+#[test]
+fn ips_runs_grow_the_rom() {
+    // 3 bytes of 0x11 from offset 3
+    let patch = b"PATCH\x00\x00\x03\x00\x00\x00\x03\x11EOF";
+    assert_eq!(
+        patch::apply(ROM, patch).unwrap(),
+        [1, 2, 3, 0x11, 0x11, 0x11]
+    );
+}
+
+#[test]
+fn truncated_patches_are_rejected() {
+    let patch = b"PATCH\x00\x00\x01\x00\x05\xAA";
+    assert_eq!(error(patch::apply(ROM, patch)), "the patch is truncated");
+    assert!(patch::apply(ROM, b"BPS1").is_err());
+    assert!(patch::apply(ROM, b"not a patch").is_err());
+}
+
+#[test]
+fn bps_commands_build_the_target() {
+    let target = [1, 2, 9, 4];
+    let commands = [
+        0x84, // SourceRead 2
+        0x81, 9,    // TargetRead 1
+        0x80, // SourceRead 1
+    ];
+    let patch = bps(ROM, &target, target.len(), &commands);
+    assert_eq!(patch::apply(ROM, &patch).unwrap(), target);
+}
+
+#[test]
+fn bps_checksums_are_checked() {
+    let target = [1, 2, 9, 4];
+    let mut patch = bps(ROM, &target, target.len(), &[0x84, 0x81, 9, 0x80]);
+    assert_eq!(
+        error(patch::apply(&[1, 2, 3, 5], &patch)),
+        "the patch is for a different ROM"
+    );
+
+    // Wrong bytes make the patch's own checksum fail
+    patch[7] = 0x82;
+    assert_eq!(error(patch::apply(ROM, &patch)), "the patch is corrupted");
+
+    // A valid patch whose output isn't the target it promised
+    let patch = bps(ROM, &[1, 2, 8, 4], 4, &[0x84, 0x81, 9, 0x80]);
+    assert!(error(patch::apply(ROM, &patch)).contains("checksum"));
+}
+
+#[test]
+fn bps_targets_larger_than_any_memory_are_rejected() {
+    let patch = bps(ROM, ROM, 1 << 40, &[0x80]);
+    assert!(error(patch::apply(ROM, &patch)).contains("more than any memory holds"));
+}
@@ -0,0 +1,134 @@
+//! The save state header: its version and the ROM a state belongs to. And
+//! the JSON snapshots, which are checked as strictly
+
+mod common;
+
+#[cfg(feature = "json")]
+use chip_8_rs::{Chip8, XO_CHIP_MEMORY};
+use chip_8_rs::{
+    Chip8Error, HIRES_HEIGHT, HIRES_WIDTH, MEMORY_SIZE, Quirks, STACK_DEPTH, STATE_HEADER_SIZE,
+};
+
+#[test]
+fn refuses_states_of_other_roms() {
+    let mut chip8 = common::machine(common::COUNT, Quirks::default());
+    common::run_frames(&mut chip8, 1, 10);
+    let state = chip8.save_state();
+
+    let mut other = common::machine(&[0x12, 0x00], Quirks::default());
+    assert!(matches!(
+        other.load_state(&state),
+        Err(Chip8Error::SaveStateRomMismatch)
+    ));
+}
+
+#[test]
+fn refuses_newer_versions() {
+    let chip8 = common::machine(common::COUNT, Quirks::default());
+    let mut state = chip8.save_state();
+    state[4..6].copy_from_slice(&u16::MAX.to_be_bytes());
+
+    let mut restored = common::machine(common::COUNT, Quirks::default());
+    assert!(matches!(
+        restored.load_state(&state),
+        Err(Chip8Error::SaveStateVersion { version: u16::MAX })
+    ));
+}
+
+#[test]
+fn loads_states_from_before_the_header() {
+    let mut chip8 = common::machine(common::COUNT, Quirks::default());
+    common::run_frames(&mut chip8, 1, 10);
+    let mut legacy = b"C8ST".to_vec();
+    legacy.extend_from_slice(&chip8.save_state()[STATE_HEADER_SIZE..]);
+
+    // Version 0 had no ROM hash, so any ROM takes it
+    let mut restored = common::machine(&[0x12, 0x00], Quirks::default());
+    restored.load_state(&legacy).unwrap();
+    assert_eq!(restored.registers()[0], 5);
+    assert!(restored.save_state()[STATE_HEADER_SIZE..] == chip8.save_state()[STATE_HEADER_SIZE..]);
+}
+
+#[test]
+fn loads_states_from_before_the_audio_pattern() {
+    let mut chip8 = common::machine(common::COUNT, Quirks::default());
+    common::run_frames(&mut chip8, 1, 10);
+    let body = &chip8.save_state()[STATE_HEADER_SIZE..];
+
+    // The audio followed the RPL flags
+    let audio =
+        MEMORY_SIZE + 16 + 2 + 2 + STACK_DEPTH * 2 + 1 + HIRES_WIDTH * HIRES_HEIGHT + 3 + 16;
+    let mut legacy = b"C8ST".to_vec();
+    legacy.extend_from_slice(&body[..audio]);
+    legacy.extend_from_slice(&body[audio + 18..]);
+
+    let mut restored = common::machine(common::COUNT, Quirks::default());
+    restored.load_state(&legacy).unwrap();
+    assert_eq!(restored.registers()[0], 5);
+    assert!(restored.save_state()[STATE_HEADER_SIZE..] == *body);
+
+    legacy.pop();
+    assert!(matches!(
+        restored.load_state(&legacy),
+        Err(Chip8Error::InvalidSaveState)
+    ));
+}
+
+/// The JSON of a machine that ran for a frame, to be tampered with
+#[cfg(feature = "json")]
+fn snapshot() -> serde_json::Value {
+    let mut chip8 = common::machine(common::RECURSION, Quirks::default());
+    common::run_frames(&mut chip8, 1, 3);
+    serde_json::from_str(&chip8.to_json()).unwrap()
+}
+
+#[cfg(feature = "json")]
+fn from_json(snapshot: &serde_json::Value) -> Result<Chip8, Chip8Error> {
+    Chip8::from_json(&snapshot.to_string())
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn json_snapshots_round_trip() {
+    let mut chip8 = common::machine(common::RECURSION, Quirks::default());
+    common::run_frames(&mut chip8, 1, 3);
+    let restored = Chip8::from_json(&chip8.to_json()).unwrap();
+    assert!(restored.save_state() == chip8.save_state());
+    assert_eq!(restored.stack(), [0x202]);
+    assert_eq!(restored.to_json(), chip8.to_json());
+
+    // From before snapshots had a version
+    let mut snapshot = snapshot();
+    snapshot.as_object_mut().unwrap().remove("version");
+    from_json(&snapshot).unwrap();
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn refuses_broken_json_snapshots() {
+    let json = snapshot().to_string();
+    assert!(matches!(
+        Chip8::from_json(&json[..json.len() / 2]),
+        Err(Chip8Error::InvalidSnapshot(_))
+    ));
+
+    let mut newer = snapshot();
+    newer["version"] = u16::MAX.into();
+    // More than 16-bit addresses reach
+    let mut memory = snapshot();
+    memory["memory"] = vec!["00".repeat(32); XO_CHIP_MEMORY / 32 + 1].into();
+    let mut short_memory = snapshot();
+    short_memory["memory"].as_array_mut().unwrap().pop();
+    let mut stack = snapshot();
+    stack["stack"] = vec![0; 256].into();
+    let mut sp = snapshot();
+    sp["sp"] = 17.into();
+    let mut hex = snapshot();
+    hex["memory"][0] = "0G".into();
+    for snapshot in [newer, memory, short_memory, stack, sp, hex] {
+        assert!(matches!(
+            from_json(&snapshot),
+            Err(Chip8Error::InvalidSnapshot(_))
+        ));
+    }
+}
@@ -0,0 +1,127 @@
+//! Machines with more memory or a deeper stack than the original 4KB and 16
+//! slots
+
+mod common;
+
+use chip_8_rs::{Chip8, Chip8Error, MEMORY_SIZE, PROGRAM_START, Quirks, XO_CHIP_MEMORY, jit::Jit};
+
+/// Calls itself until the stack is full
+const RECURSE: &[u8] = &[0x22, 0x00];
+
+fn machine(memory_size: usize, stack_depth: usize, rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::with_sizes(memory_size, stack_depth);
+    chip8.set_quirks(Quirks::default());
+    chip8.load(rom).expect("ROM fits into memory");
+    chip8
+}
+
+fn overflows_after(chip8: &mut Chip8) -> usize {
+    let mut calls = 0;
+    loop {
+        match chip8.cycle() {
+            Ok(()) => calls += 1,
+            Err(Chip8Error::StackOverflow { .. }) => return calls,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+#[test]
+fn large_roms_fit_into_xo_chip_memory() {
+    let rom = vec![0x12; 8000];
+    let mut chip8 = Chip8::new();
+    assert!(matches!(
+        chip8.load(&rom),
+        Err(Chip8Error::RomTooLarge { max: 3584, .. })
+    ));
+
+    let chip8 = machine(XO_CHIP_MEMORY, 16, &rom);
+    assert_eq!(chip8.memory().len(), XO_CHIP_MEMORY);
+    let start = PROGRAM_START as usize;
+    assert_eq!(chip8.memory()[start..start + rom.len()], rom[..]);
+}
+
+#[test]
+fn stack_depth() {
+    assert_eq!(
+        overflows_after(&mut common::machine(RECURSE, Quirks::default())),
+        16
+    );
+
+    let mut chip8 = machine(MEMORY_SIZE, 64, RECURSE);
+    assert_eq!(chip8.stack_depth(), 64);
+    assert_eq!(overflows_after(&mut chip8), 64);
+
+    // Still 64 deep after a reset
+    chip8.reset();
+    assert_eq!(overflows_after(&mut chip8), 64);
+}
+
+#[test]
+fn save_states_need_the_same_sizes() {
+    let mut chip8 = machine(XO_CHIP_MEMORY, 32, RECURSE);
+    for _ in 0..20 {
+        chip8.cycle().unwrap();
+    }
+    chip8.poke(0xFFFF, 0xAB).unwrap();
+    let state = chip8.save_state();
+
+    let mut restored = machine(XO_CHIP_MEMORY, 32, RECURSE);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.stack().len(), 20);
+    assert_eq!(restored.memory()[0xFFFF], 0xAB);
+
+    for mut other in [
+        machine(MEMORY_SIZE, 32, RECURSE),
+        machine(XO_CHIP_MEMORY, 16, RECURSE),
+    ] {
+        assert!(matches!(
+            other.load_state(&state),
+            Err(Chip8Error::InvalidSaveState)
+        ));
+    }
+}
+
+/// `v0 := 0` up to the end of 64KB, with `last` in the final 2 bytes
+fn up_to_the_end(last: [u8; 2]) -> Chip8 {
+    let mut rom = [0x60, 0x00].repeat((XO_CHIP_MEMORY - PROGRAM_START as usize) / 2);
+    let end = rom.len();
+    rom[end - 2..].copy_from_slice(&last);
+    machine(XO_CHIP_MEMORY, 16, &rom)
+}
+
+#[test]
+fn instructions_at_the_end_of_memory() {
+    let instructions = (XO_CHIP_MEMORY - PROGRAM_START as usize) as u32 / 2;
+
+    // A jump in the last 2 bytes is fine, even without a JIT block for it
+    let mut chip8 = up_to_the_end([0x12, 0x00]);
+    for _ in 0..instructions {
+        chip8.cycle().unwrap();
+    }
+    assert_eq!(chip8.pc(), PROGRAM_START);
+    let mut chip8 = up_to_the_end([0x12, 0x00]);
+    Jit::new().run(&mut chip8, instructions).unwrap();
+    assert_eq!(chip8.pc(), PROGRAM_START);
+
+    // Anything else there, or a skip right before it, takes PC past 16 bits
+    let skip = || {
+        let mut chip8 = up_to_the_end([0x12, 0x00]);
+        chip8.poke(0xFFFC, 0x30).unwrap();
+        chip8
+    };
+    let past_the_end = || up_to_the_end([0x60, 0x00]);
+    for machine in [&skip as &dyn Fn() -> Chip8, &past_the_end] {
+        let mut chip8 = machine();
+        let result = (0..instructions).try_for_each(|_| chip8.cycle());
+        assert!(matches!(result, Err(Chip8Error::MemoryOutOfBounds { .. })));
+        let result = Jit::new().run(&mut machine(), instructions);
+        assert!(matches!(result, Err(Chip8Error::MemoryOutOfBounds { .. })));
+    }
+}
+
+#[test]
+#[should_panic(expected = "memory size")]
+fn rejects_memory_past_16_bits() {
+    Chip8::with_sizes(XO_CHIP_MEMORY + 1, 16);
+}
@@ -0,0 +1,93 @@
+//! Snapshots of the screen after running the demo ROMs and small programs
+//! that draw differently depending on the quirks, see `common`
+
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use chip_8_rs::{Quirks, asm};
+
+const INSTRUCTIONS_PER_FRAME: u32 = 10;
+
+fn demo(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(format!("roms/{}.ch8", name));
+    fs::read(path).expect("demo ROM exists")
+}
+
+/// Draws the 8 of the font so it overlaps the bottom right corner
+const CORNER_SPRITE: &str = "
+: main
+	v0 := 62
+	v1 := 29
+	v2 := 8
+	i := hex v2
+	sprite v0 v1 5
+	loop again
+";
+
+#[test]
+fn maze() {
+    let mut chip8 = common::machine(&demo("maze"), Quirks::default());
+    common::run_frames(&mut chip8, 60, INSTRUCTIONS_PER_FRAME);
+    common::assert_snapshot("maze", &chip8);
+}
+
+#[test]
+fn bounce() {
+    let mut chip8 = common::machine(&demo("bounce"), Quirks::default());
+    common::run_frames(&mut chip8, 90, INSTRUCTIONS_PER_FRAME);
+    common::assert_snapshot("bounce", &chip8);
+}
+
+#[test]
+fn keys() {
+    let mut chip8 = common::machine(&demo("keys"), Quirks::default());
+    common::run_frames(&mut chip8, 5, INSTRUCTIONS_PER_FRAME);
+    chip8.key_down(0xA);
+    common::run_frames(&mut chip8, 5, INSTRUCTIONS_PER_FRAME);
+    chip8.key_up(0xA);
+    common::run_frames(&mut chip8, 5, INSTRUCTIONS_PER_FRAME);
+    common::assert_snapshot("keys", &chip8);
+}
+
+#[test]
+fn sprites_wrap() {
+    let rom = asm::assemble(CORNER_SPRITE).unwrap();
+    let mut chip8 = common::machine(&rom, Quirks::default());
+    common::run_frames(&mut chip8, 1, INSTRUCTIONS_PER_FRAME);
+    common::assert_snapshot("sprites_wrap", &chip8);
+}
+
+#[test]
+fn sprites_clip() {
+    let rom = asm::assemble(CORNER_SPRITE).unwrap();
+    let quirks = Quirks {
+        clip_sprites: true,
+        ..Quirks::default()
+    };
+    let mut chip8 = common::machine(&rom, quirks);
+    common::run_frames(&mut chip8, 1, INSTRUCTIONS_PER_FRAME);
+    common::assert_snapshot("sprites_clip", &chip8);
+}
+
+#[test]
+fn hires_sprite() {
+    let rom = asm::assemble(
+        "
+: ball
+	0x07 0xE0 0x1F 0xF8 0x3F 0xFC 0x7F 0xFE 0x7F 0xFE 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF
+	0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0x7F 0xFE 0x7F 0xFE 0x3F 0xFC 0x1F 0xF8 0x07 0xE0
+: main
+	hires
+	v0 := 56
+	v1 := 24
+	i := ball
+	sprite v0 v1 0
+	loop again
+",
+    )
+    .unwrap();
+    let mut chip8 = common::machine(&rom, Quirks::SCHIP);
+    common::run_frames(&mut chip8, 1, INSTRUCTIONS_PER_FRAME);
+    common::assert_snapshot("hires_sprite", &chip8);
+}
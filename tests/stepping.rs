@@ -0,0 +1,78 @@
+//! Stepping over calls and running to an address
+
+mod common;
+
+use chip_8_rs::{Quirks, debug::Stop};
+
+#[test]
+fn steps_over_a_call() {
+    let mut chip8 = common::machine(common::RECURSION, Quirks::default());
+    assert!(!chip8.step_over(), "LD isn't a call");
+    chip8.cycle().unwrap();
+    assert!(chip8.step_over());
+    common::run_frames(&mut chip8, 1, 100);
+
+    assert_eq!(chip8.take_stop(), Some(Stop::Reached { address: 0x204 }));
+    assert_eq!(chip8.registers()[0], 0);
+    assert!(chip8.stack().is_empty());
+}
+
+#[test]
+fn recursive_calls_returning_deeper_dont_stop() {
+    let mut chip8 = common::machine(common::RECURSION, Quirks::default());
+    chip8.add_breakpoint(0x20E);
+    common::run_frames(&mut chip8, 1, 100);
+    assert_eq!(chip8.take_stop(), Some(Stop::Breakpoint { address: 0x20E }));
+    chip8.remove_breakpoint(0x20E);
+
+    assert!(chip8.step_over());
+    common::run_frames(&mut chip8, 1, 100);
+    assert_eq!(chip8.take_stop(), Some(Stop::Reached { address: 0x210 }));
+    assert_eq!(chip8.stack().len(), 1);
+    assert_eq!(chip8.registers()[0], 0);
+
+    assert!(chip8.step_out());
+    common::run_frames(&mut chip8, 1, 100);
+    assert_eq!(chip8.take_stop(), Some(Stop::Reached { address: 0x204 }));
+    assert!(!chip8.step_out(), "main isn't a subroutine");
+}
+
+#[test]
+fn running_to_the_pc_goes_around_the_loop() {
+    let mut chip8 = common::machine(common::COUNT, Quirks::default());
+    chip8.run_to(0x200);
+    common::run_frames(&mut chip8, 1, 100);
+
+    assert_eq!(chip8.take_stop(), Some(Stop::Reached { address: 0x200 }));
+    assert_eq!(chip8.registers()[0], 1);
+}
+
+#[test]
+fn breakpoints_on_the_way_end_the_run() {
+    let mut chip8 = common::machine(common::RECURSION, Quirks::default());
+    chip8.add_breakpoint(0x206);
+    chip8.run_to(0x204);
+    common::run_frames(&mut chip8, 1, 100);
+    assert_eq!(chip8.take_stop(), Some(Stop::Breakpoint { address: 0x206 }));
+
+    chip8.remove_breakpoint(0x206);
+    common::run_frames(&mut chip8, 1, 100);
+    assert_eq!(chip8.take_stop(), None);
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn call_stacks_of_loaded_states() {
+    use chip_8_rs::Chip8;
+
+    // A state can hold any return address, even one at the end of memory
+    let chip8 = common::machine(common::RECURSION, Quirks::default());
+    let mut snapshot: serde_json::Value = serde_json::from_str(&chip8.to_json()).unwrap();
+    snapshot["stack"][0] = 0xFFFE.into();
+    snapshot["sp"] = 1.into();
+    let chip8 = Chip8::from_json(&snapshot.to_string()).unwrap();
+
+    let frames = chip8.call_stack();
+    assert_eq!(frames[0].call_site, 0xFFFE);
+    assert_eq!(frames[0].return_address, 0);
+}
@@ -0,0 +1,57 @@
+//! Naming addresses in the disassembly with a symbol file
+
+use chip_8_rs::{
+    PROGRAM_START, asm,
+    disasm::{Line, disassemble_reachable_with},
+    symbols::Symbols,
+};
+
+#[test]
+fn jumps_and_calls_show_names() {
+    let symbols = Symbols::parse(
+        "# comments and blank lines are skipped
+
+         :const main 0x202
+         draw_player 0x208",
+    )
+    .unwrap();
+    let program = [0x12, 0x02, 0x22, 0x08, 0xA2, 0x08, 0x12, 0x02, 0x00, 0xEE];
+
+    let mnemonics: Vec<String> = disassemble_reachable_with(&program, PROGRAM_START, &symbols)
+        .into_iter()
+        .filter_map(|line| match line {
+            Line::Code(instruction) => Some(instruction.mnemonic),
+            Line::Data { .. } => None,
+        })
+        .collect();
+    assert_eq!(
+        mnemonics,
+        [
+            "JP main",
+            "CALL draw_player",
+            "LD I, draw_player",
+            "JP main",
+            "RET"
+        ]
+    );
+}
+
+#[test]
+fn bad_lines_are_reported() {
+    let error = Symbols::parse("main 0x202\nplayer\n").unwrap_err();
+    assert_eq!(error.line, 2);
+    let error = Symbols::parse("main 0x202\nmain 0x204\n").unwrap_err();
+    assert_eq!(error.line, 2);
+}
+
+#[test]
+fn assembler_labels_round_trip() {
+    let (_, symbols) = asm::assemble_with_symbols(
+        ": draw  return
+         : main  draw  loop again",
+    )
+    .unwrap();
+    assert_eq!(symbols.address("draw"), Some(0x202));
+    assert_eq!(symbols.address("main"), Some(0x204));
+    assert_eq!(Symbols::parse(&symbols.to_string()).unwrap(), symbols);
+}
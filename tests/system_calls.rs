@@ -0,0 +1,58 @@
+//! 0NNN routines, skipped or run by a hook
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use chip_8_rs::{Chip8Error, Quirks, system::SystemCalls};
+
+/// Calls routine 0x123, then sets V1 to show it carried on
+const CALLS_ROUTINE: &[u8] = &[0x01, 0x23, 0x61, 0x01, 0x12, 0x04];
+
+#[test]
+fn skipped_without_a_hook() {
+    let mut chip8 = common::machine(CALLS_ROUTINE, Quirks::default());
+    common::run_frame(&mut chip8).unwrap();
+    assert_eq!(chip8.registers()[1], 1);
+}
+
+#[test]
+fn hook_stands_in_for_the_routine() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let mut chip8 = common::machine(CALLS_ROUTINE, Quirks::default());
+    chip8.set_system_calls(SystemCalls::Strict);
+    let seen = Arc::clone(&calls);
+    chip8.set_system_hook(Box::new(move |chip8, routine| {
+        seen.lock().unwrap().push(routine);
+        chip8.poke(0x300, 0x42).unwrap();
+        true
+    }));
+
+    common::run_frame(&mut chip8).unwrap();
+    assert_eq!(*calls.lock().unwrap(), [0x123]);
+    assert_eq!(chip8.memory()[0x300], 0x42);
+    assert_eq!(chip8.registers()[1], 1);
+}
+
+#[test]
+fn strict_stops_on_unhandled_routines() {
+    let mut chip8 = common::machine(CALLS_ROUTINE, Quirks::default());
+    chip8.set_system_calls(SystemCalls::Strict);
+    chip8.set_system_hook(Box::new(|_, routine| routine != 0x123));
+
+    let result = common::run_frame(&mut chip8);
+    assert!(matches!(
+        result,
+        Err(Chip8Error::UnhandledSystemCall {
+            address: 0x200,
+            routine: 0x123
+        })
+    ));
+}
+
+#[test]
+fn megachip_opcodes_are_routines_without_the_quirk() {
+    let mut chip8 = common::machine(&[0x00, 0x11, 0x12, 0x02], Quirks::default());
+    chip8.set_system_calls(SystemCalls::Strict);
+    assert!(common::run_frame(&mut chip8).is_err());
+}
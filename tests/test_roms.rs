@@ -0,0 +1,112 @@
+//! Runs the community test ROMs headless and compares the screen they end on
+//! with its snapshot, see `common`.
+//!
+//! The ROMs aren't part of this repository since they come under their own
+//! licenses. Put them in `tests/roms`, or point `CHIP8_TEST_ROMS` at a
+//! directory holding them, with the file names below:
+//!
+//! - https://github.com/Timendus/chip8-test-suite
+//! - https://github.com/corax89/chip8-test-rom
+//!
+//! Tests whose ROM is missing pass after printing a note. When recording
+//! their snapshots, check by eye that every test on the screens passes.
+
+mod common;
+
+use std::{env, fs, path::PathBuf};
+
+use chip_8_rs::Quirks;
+
+/// Enough for every ROM to finish its tests and end in its idle loop
+const FRAMES: u32 = 120;
+const INSTRUCTIONS_PER_FRAME: u32 = 1000;
+
+struct TestRom {
+    /// File name in the ROM directory, without the `.ch8`
+    name: &'static str,
+    /// Name of the snapshot
+    screen: &'static str,
+    quirks: Quirks,
+    /// A byte written to memory before running, for the ROMs that read
+    /// their settings from it instead of asking for a key
+    setup: Option<(u16, u8)>,
+}
+
+impl TestRom {
+    const fn new(name: &'static str, quirks: Quirks) -> Self {
+        Self {
+            name,
+            screen: name,
+            quirks,
+            setup: None,
+        }
+    }
+
+    fn run(&self) {
+        let rom_path = rom_dir().join(format!("{}.ch8", self.name));
+        let Ok(rom) = fs::read(&rom_path) else {
+            eprintln!("skipping {}, {} not found", self.name, rom_path.display());
+            return;
+        };
+
+        let mut chip8 = common::machine(&rom, self.quirks);
+        if let Some((address, value)) = self.setup {
+            chip8.poke(address, value).expect("setup address is valid");
+        }
+        common::run_frames(&mut chip8, FRAMES, INSTRUCTIONS_PER_FRAME);
+        common::assert_snapshot(self.screen, &chip8);
+    }
+}
+
+fn rom_dir() -> PathBuf {
+    env::var_os("CHIP8_TEST_ROMS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/roms"))
+}
+
+#[test]
+fn chip8_logo() {
+    TestRom::new("1-chip8-logo", Quirks::default()).run();
+}
+
+#[test]
+fn ibm_logo() {
+    TestRom::new("2-ibm-logo", Quirks::default()).run();
+}
+
+#[test]
+fn corax_plus() {
+    TestRom::new("3-corax+", Quirks::default()).run();
+}
+
+#[test]
+fn flags() {
+    TestRom::new("4-flags", Quirks::default()).run();
+}
+
+#[test]
+fn quirks_chip8() {
+    TestRom {
+        // The platform to test, 1 is CHIP-8
+        setup: Some((0x1FF, 1)),
+        screen: "5-quirks-chip8",
+        ..TestRom::new("5-quirks", Quirks::CHIP8)
+    }
+    .run();
+}
+
+#[test]
+fn quirks_schip() {
+    TestRom {
+        // 2 is SUPER-CHIP as on modern interpreters
+        setup: Some((0x1FF, 2)),
+        screen: "5-quirks-schip",
+        ..TestRom::new("5-quirks", Quirks::SCHIP)
+    }
+    .run();
+}
+
+#[test]
+fn corax89_opcodes() {
+    TestRom::new("test_opcode", Quirks::default()).run();
+}
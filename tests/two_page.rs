@@ -0,0 +1,61 @@
+//! Hi-res CHIP-8, detected from the jump every such ROM starts with
+
+mod common;
+
+use chip_8_rs::Quirks;
+
+/// `1260` followed by the interpreter patch, with the program proper at
+/// 0x2C0 drawing a 0 at (0, 60), below where the standard display ends
+fn two_page_rom(clear: bool) -> Vec<u8> {
+    let mut rom = vec![0x12, 0x60];
+    rom.resize(0xC0, 0);
+    rom.extend_from_slice(&[
+        0x60, 0x00, // LD V0, 0
+        0x61, 0x3C, // LD V1, 60
+        0xA0, 0x00, // LD I, 0
+        0xD0, 0x15, // DRW V0, V1, 5
+    ]);
+    if clear {
+        rom.extend_from_slice(&[0x02, 0x30]); // hi-res CLS
+    }
+    let end = 0x200 + rom.len() as u16;
+    rom.extend_from_slice(&(0x1000 | end).to_be_bytes());
+    rom
+}
+
+#[test]
+fn draws_on_a_64x64_display() {
+    let mut chip8 = common::machine(&two_page_rom(false), Quirks::CHIP8);
+    common::run_frames(&mut chip8, 1, 10);
+
+    assert_eq!(chip8.display_width(), 64);
+    assert_eq!(chip8.display_height(), 64);
+    assert!(chip8.get_display()[60 * 64]);
+}
+
+#[test]
+fn clears_with_0230() {
+    let mut chip8 = common::machine(&two_page_rom(true), Quirks::CHIP8);
+    // The draw waits for the end of the first frame
+    common::run_frames(&mut chip8, 2, 10);
+    assert!(!chip8.get_display().contains(&true));
+}
+
+#[test]
+fn other_roms_keep_the_standard_display() {
+    let chip8 = common::machine(&[0x12, 0x00], Quirks::CHIP8);
+    assert_eq!(chip8.display_height(), 32);
+}
+
+#[test]
+fn save_state_keeps_the_resolution() {
+    let rom = two_page_rom(false);
+    let mut chip8 = common::machine(&rom, Quirks::CHIP8);
+    common::run_frames(&mut chip8, 1, 10);
+
+    // States only load with the ROM they were taken with
+    let mut restored = common::machine(&rom, Quirks::CHIP8);
+    restored.load_state(&chip8.save_state()).unwrap();
+    assert_eq!(restored.display_height(), 64);
+    assert_eq!(restored.get_display(), chip8.get_display());
+}
@@ -0,0 +1,48 @@
+//! Halting on, skipping and warning about opcodes outside the instruction
+//! set
+
+mod common;
+
+use chip_8_rs::{Chip8Error, Quirks, UnknownOpcodes};
+
+/// An unknown opcode, then V1 is set to show execution carried on
+const GARBAGE: &[u8] = &[0xFF, 0xFF, 0x61, 0x01, 0x12, 0x04];
+
+#[test]
+fn halts_by_default() {
+    let mut chip8 = common::machine(GARBAGE, Quirks::default());
+    assert!(matches!(
+        common::run_frame(&mut chip8),
+        Err(Chip8Error::UnknownOpcode {
+            address: 0x200,
+            opcode: 0xFFFF
+        })
+    ));
+}
+
+#[test]
+fn ignore_skips_them() {
+    let mut chip8 = common::machine(GARBAGE, Quirks::default());
+    chip8.set_unknown_opcodes(UnknownOpcodes::Ignore);
+    common::run_frame(&mut chip8).unwrap();
+    assert_eq!(chip8.registers()[1], 1);
+    assert!(chip8.take_warnings().is_empty());
+}
+
+#[test]
+fn warn_keeps_where_they_were() {
+    let mut chip8 = common::machine(GARBAGE, Quirks::default());
+    chip8.set_unknown_opcodes(UnknownOpcodes::Warn);
+    common::run_frame(&mut chip8).unwrap();
+    assert_eq!(chip8.registers()[1], 1);
+
+    let warnings = chip8.take_warnings();
+    assert!(matches!(
+        warnings[..],
+        [Chip8Error::UnknownOpcode {
+            address: 0x200,
+            opcode: 0xFFFF
+        }]
+    ));
+    assert!(chip8.take_warnings().is_empty());
+}
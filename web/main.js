@@ -0,0 +1,131 @@
+// Build the bindings into web/pkg first, see src/wasm.rs, then serve this
+// directory over HTTP
+import init, { WebChip8 } from "./pkg/chip_8_rs.js";
+
+const INSTRUCTIONS_PER_FRAME = 10;
+const FRAME_MS = 1000 / 60;
+const FOREGROUND = [0x33, 0xff, 0x33];
+const BACKGROUND = [0x00, 0x11, 0x00];
+
+// Physical key codes, indexed by CHIP-8 key
+const LAYOUT = [
+  "KeyX", "Digit1", "Digit2", "Digit3",
+  "KeyQ", "KeyW", "KeyE", "KeyA",
+  "KeyS", "KeyD", "KeyZ", "KeyC",
+  "Digit4", "KeyR", "KeyF", "KeyV",
+];
+
+await init();
+
+const canvas = document.getElementById("display");
+const context = canvas.getContext("2d");
+const chip8 = new WebChip8();
+let running = false;
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+  const file = event.target.files[0];
+  if (!file) {
+    return;
+  }
+  try {
+    chip8.load(new Uint8Array(await file.arrayBuffer()));
+    running = true;
+  } catch (e) {
+    alert(`Failed to load ROM: ${e}`);
+  }
+});
+
+for (const [type, handler] of [["keydown", "key_down"], ["keyup", "key_up"]]) {
+  document.addEventListener(type, (event) => {
+    const key = LAYOUT.indexOf(event.code);
+    if (key !== -1) {
+      chip8[handler](key);
+      event.preventDefault();
+    }
+  });
+}
+
+// The touch keypad, laid out like the COSMAC VIP's. Pointer events cover
+// touch as well as the mouse, and a captured pointer is released when the
+// finger is lifted anywhere
+const keypad = document.getElementById("keypad");
+for (const key of [0x1, 0x2, 0x3, 0xc, 0x4, 0x5, 0x6, 0xd, 0x7, 0x8, 0x9, 0xe, 0xa, 0x0, 0xb, 0xf]) {
+  const button = document.createElement("button");
+  button.textContent = key.toString(16).toUpperCase();
+  button.addEventListener("pointerdown", (event) => {
+    button.setPointerCapture(event.pointerId);
+    button.classList.add("held");
+    chip8.key_down(key);
+    event.preventDefault();
+  });
+  for (const type of ["pointerup", "pointercancel"]) {
+    button.addEventListener(type, () => {
+      button.classList.remove("held");
+      chip8.key_up(key);
+    });
+  }
+  keypad.append(button);
+}
+
+// A square wave that's switched on and off with the sound timer. Browsers
+// only allow audio after user interaction, so it's created lazily
+let beep = null;
+function setTone(active) {
+  if (!beep) {
+    if (!active) {
+      return;
+    }
+    const audio = new AudioContext();
+    const oscillator = audio.createOscillator();
+    const gain = audio.createGain();
+    oscillator.type = "square";
+    oscillator.frequency.value = 440;
+    gain.gain.value = 0;
+    oscillator.connect(gain).connect(audio.destination);
+    oscillator.start();
+    beep = gain.gain;
+  }
+  beep.value = active ? 0.15 : 0;
+}
+
+function draw() {
+  const width = chip8.width();
+  const height = chip8.height();
+  if (canvas.width !== width || canvas.height !== height) {
+    canvas.width = width;
+    canvas.height = height;
+  }
+
+  const image = context.createImageData(width, height);
+  chip8.pixels().forEach((on, i) => {
+    image.data.set(on ? FOREGROUND : BACKGROUND, i * 4);
+    image.data[i * 4 + 3] = 0xff;
+  });
+  context.putImageData(image, 0, 0);
+}
+
+// requestAnimationFrame runs at the monitor's refresh rate, so frames are
+// run whenever enough time has built up for the next 60Hz one
+let last = performance.now();
+let pending = 0;
+function tick(now) {
+  pending = Math.min(pending + now - last, FRAME_MS * 4);
+  last = now;
+
+  if (running) {
+    try {
+      while (pending >= FRAME_MS) {
+        chip8.run_frame(INSTRUCTIONS_PER_FRAME);
+        pending -= FRAME_MS;
+      }
+    } catch (e) {
+      running = false;
+      console.error(`Emulation stopped: ${e}`);
+    }
+    draw();
+    setTone(running && chip8.sound_active());
+  }
+
+  requestAnimationFrame(tick);
+}
+requestAnimationFrame(tick);